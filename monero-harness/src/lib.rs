@@ -19,6 +19,10 @@
 //! create and fund accounts, and start a continuous mining task mining blocks
 //! every BLOCK_TIME_SECS seconds.
 //!
+//! The continuous mining task is optional: Tests that need control over when
+//! blocks are produced can skip `start_miner` and mine blocks on demand with
+//! `generate_blocks` instead.
+//!
 //! Also provides standalone JSON RPC clients for monerod and monero-wallet-rpc.
 pub mod image;
 
@@ -38,6 +42,12 @@ const BLOCK_TIME_SECS: u64 = 1;
 /// Poll interval when checking if the wallet has synced with monerod.
 const WAIT_WALLET_SYNC_MILLIS: u64 = 1000;
 
+/// Poll interval when waiting for monerod to reach a block height.
+const WAIT_BLOCK_HEIGHT_MILLIS: u64 = 100;
+
+/// The number of confirmations after which received outputs can be spent.
+const UNLOCK_BLOCKS: u32 = 10;
+
 #[derive(Clone, Debug)]
 pub struct Monero {
     monerod: Monerod,
@@ -152,6 +162,50 @@ impl<'c> Monero {
         Ok(())
     }
 
+    /// Transfers exactly `amount` piconero from the miner wallet to `address`,
+    /// mines enough blocks for the transfer to unlock and returns the hash of
+    /// the transfer transaction.
+    ///
+    /// Waits for the miner wallet and every wallet of the setup that owns
+    /// `address` to sync with the new height before returning.
+    pub async fn fund_address(&self, address: &str, amount: u64) -> Result<String> {
+        let miner_wallet = self.wallet("miner")?;
+        miner_wallet.refresh().await?;
+
+        let unlocked_balance = miner_wallet.client().get_balance(0).await?.unlocked_balance;
+        if unlocked_balance < amount {
+            bail!(
+                "Miner wallet has {} unlocked piconero which is not enough to fund {} with {}, mine more blocks first",
+                unlocked_balance,
+                address,
+                amount
+            );
+        }
+
+        let transfer = miner_wallet
+            .transfer(address, amount)
+            .await
+            .with_context(|| format!("Failed to fund {} with {}", address, amount))?;
+        tracing::info!(
+            "Funded {} with {} in transaction {}",
+            address,
+            amount,
+            transfer.tx_hash
+        );
+
+        self.generate_blocks(UNLOCK_BLOCKS).await?;
+
+        let block_height = self.monerod.client().get_block_count().await?.count;
+        for wallet in &self.wallets {
+            if wallet.address().await?.address == address {
+                wallet.refresh().await?;
+                wallet.wait_for_wallet_height(block_height).await?;
+            }
+        }
+
+        Ok(transfer.tx_hash)
+    }
+
     pub async fn start_miner(&self) -> Result<()> {
         let miner_wallet = self.wallet("miner")?;
         let miner_address = miner_wallet.address().await?.address;
@@ -169,6 +223,28 @@ impl<'c> Monero {
         Ok(())
     }
 
+    /// Mines `amount` blocks to the miner wallet and waits for the miner wallet
+    /// to catch up with the new height.
+    ///
+    /// Meant for setups that don't run the continuous mining task started by
+    /// `start_miner`.
+    pub async fn generate_blocks(&self, amount: u32) -> Result<()> {
+        let miner_wallet = self.wallet("miner")?;
+        let miner_address = miner_wallet.address().await?.address;
+        let monerod = &self.monerod;
+
+        let res = monerod
+            .client()
+            .generateblocks(amount, miner_address)
+            .await?;
+        tracing::debug!("Generated {:?} blocks", res.blocks.len());
+
+        let block_height = monerod.client().get_block_count().await?.count;
+        miner_wallet.wait_for_wallet_height(block_height).await?;
+
+        Ok(())
+    }
+
     pub async fn init_and_start_miner(&self) -> Result<()> {
         self.init_miner().await?;
         self.start_miner().await?;
@@ -240,6 +316,31 @@ impl<'c> Monerod {
         let _ = tokio::spawn(mine(monerod, miner_wallet_address.to_string()));
         Ok(())
     }
+
+    /// Waits until monerod's block count reaches `height` and returns the
+    /// block count at that point.
+    ///
+    /// Fails if the height is not reached within `timeout`, e.g. because no
+    /// miner is running.
+    pub async fn wait_for_block_height(&self, height: u32, timeout: Duration) -> Result<u32> {
+        let mut current = self.client().get_block_count().await?.count;
+        let deadline = time::Instant::now() + timeout;
+
+        while current < height {
+            if time::Instant::now() >= deadline {
+                bail!(
+                    "monerod did not reach block height {} within {:?}, current height is {}",
+                    height,
+                    timeout,
+                    current
+                )
+            }
+            time::sleep(Duration::from_millis(WAIT_BLOCK_HEIGHT_MILLIS)).await;
+            current = self.client().get_block_count().await?.count;
+        }
+
+        Ok(current)
+    }
 }
 
 impl<'c> MoneroWalletRpc {
@@ -23,10 +23,62 @@ async fn init_miner_and_mine_to_miner_address() {
     let got_miner_balance = miner_wallet.balance().await.unwrap();
     assert_that!(got_miner_balance).is_greater_than(0);
 
-    time::sleep(Duration::from_millis(1010)).await;
+    // the miner should keep mining blocks on top of the initial 70
+    let block_height = monerod
+        .wait_for_block_height(71, Duration::from_secs(10))
+        .await
+        .unwrap();
+
+    assert_that(&block_height).is_greater_than(70);
+}
+
+#[tokio::test]
+async fn without_miner_blocks_are_only_generated_on_demand() {
+    let _guard = tracing_subscriber::fmt()
+        .with_env_filter("warn,test=debug,monero_harness=debug,monero_rpc=debug")
+        .set_default();
+
+    let tc = Cli::default();
+    let (monero, _monerod_container, _wallet_containers) = Monero::new(&tc, vec![]).await.unwrap();
+
+    monero.init_miner().await.unwrap();
+
+    let monerod = monero.monerod();
+    let initial_height = monerod.client().get_block_count().await.unwrap().count;
+
+    time::sleep(Duration::from_millis(2010)).await;
 
-    // after a bit more than 1 sec another block should have been mined
     let block_height = monerod.client().get_block_count().await.unwrap().count;
+    assert_that(&block_height).is_equal_to(initial_height);
 
-    assert_that(&block_height).is_greater_than(70);
+    monero.generate_blocks(5).await.unwrap();
+
+    let block_height = monerod.client().get_block_count().await.unwrap().count;
+    assert_that(&block_height).is_equal_to(initial_height + 5);
+}
+
+#[tokio::test]
+async fn wait_for_block_height_times_out_without_new_blocks() {
+    let _guard = tracing_subscriber::fmt()
+        .with_env_filter("warn,test=debug,monero_harness=debug,monero_rpc=debug")
+        .set_default();
+
+    let tc = Cli::default();
+    let (monero, _monerod_container, _wallet_containers) = Monero::new(&tc, vec![]).await.unwrap();
+
+    monero.init_miner().await.unwrap();
+
+    let monerod = monero.monerod();
+    let initial_height = monerod.client().get_block_count().await.unwrap().count;
+
+    let reached = monerod
+        .wait_for_block_height(initial_height, Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_that(&reached).is_equal_to(initial_height);
+
+    let result = monerod
+        .wait_for_block_height(initial_height + 1, Duration::from_secs(1))
+        .await;
+    assert!(result.is_err());
 }
@@ -55,6 +55,57 @@ async fn fund_transfer_and_check_tx_key() {
     assert_that!(res.received).is_equal_to(send_to_bob);
 }
 
+#[tokio::test]
+async fn fund_address_returns_hash_of_funding_transaction() {
+    let _guard = tracing_subscriber::fmt()
+        .with_env_filter("warn,test=debug,monero_harness=debug,monero_rpc=debug")
+        .set_default();
+
+    let amount = 2_500_000_000;
+
+    let tc = Cli::default();
+    let (monero, _monerod_container, _wallet_containers) =
+        Monero::new(&tc, vec!["alice"]).await.unwrap();
+    let alice_wallet = monero.wallet("alice").unwrap();
+
+    monero.init_miner().await.unwrap();
+
+    let alice_address = alice_wallet.address().await.unwrap().address;
+    let tx_hash = monero.fund_address(&alice_address, amount).await.unwrap();
+
+    let transactions = monero
+        .monerod()
+        .client()
+        .get_transactions(vec![tx_hash.clone()])
+        .await
+        .unwrap();
+    let funding_tx = transactions
+        .txs
+        .iter()
+        .find(|tx| tx.tx_hash == tx_hash)
+        .expect("funding transaction is known to monerod");
+    assert_that!(funding_tx.in_pool).is_false();
+    assert_that!(alice_wallet.balance().await.unwrap()).is_equal_to(amount);
+}
+
+#[tokio::test]
+async fn fund_address_with_more_than_miner_balance_fails() {
+    let tc = Cli::default();
+    let (monero, _monerod_container, _wallet_containers) =
+        Monero::new(&tc, vec!["alice"]).await.unwrap();
+    let alice_address = monero
+        .wallet("alice")
+        .unwrap()
+        .address()
+        .await
+        .unwrap()
+        .address;
+
+    let result = monero.fund_address(&alice_address, u64::MAX).await;
+
+    assert_that!(result).is_err();
+}
+
 async fn wait_for_wallet_to_catch_up(wallet: &MoneroWalletRpc, expected_balance: u64) {
     let max_retry = 15;
     let mut retry = 0;
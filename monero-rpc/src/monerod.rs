@@ -12,6 +12,7 @@ pub trait MonerodRpc {
     async fn get_block_header_by_height(&self, height: u32) -> BlockHeader;
     async fn get_block_count(&self) -> BlockCount;
     async fn get_block(&self, height: u32) -> GetBlockResponse;
+    async fn get_info(&self) -> GetInfo;
 }
 
 #[jsonrpc_client::implement(MonerodRpc)]
@@ -21,6 +22,7 @@ pub struct Client {
     base_url: reqwest::Url,
     get_o_indexes_bin_url: reqwest::Url,
     get_outs_bin_url: reqwest::Url,
+    get_transactions_url: reqwest::Url,
 }
 
 impl Client {
@@ -29,19 +31,31 @@ impl Client {
         Self::new("127.0.0.1".to_owned(), port)
     }
 
-    fn new(host: String, port: u16) -> Result<Self> {
+    /// New monerod RPC client for the daemon at `host` and `port`.
+    pub fn new(host: String, port: u16) -> Result<Self> {
+        Self::from_base_url(
+            format!("http://{}:{}", host, port)
+                .parse()
+                .context("url is well formed")?,
+        )
+    }
+
+    /// New monerod RPC client for the daemon at `base_url`, e.g.
+    /// `https://node.example.com:18089`.
+    pub fn from_base_url(base_url: reqwest::Url) -> Result<Self> {
         Ok(Self {
             inner: reqwest::ClientBuilder::new()
                 .connection_verbose(true)
                 .build()?,
-            base_url: format!("http://{}:{}/json_rpc", host, port)
-                .parse()
+            base_url: base_url.join("json_rpc").context("url is well formed")?,
+            get_o_indexes_bin_url: base_url
+                .join("get_o_indexes.bin")
                 .context("url is well formed")?,
-            get_o_indexes_bin_url: format!("http://{}:{}/get_o_indexes.bin", host, port)
-                .parse()
+            get_outs_bin_url: base_url
+                .join("get_outs.bin")
                 .context("url is well formed")?,
-            get_outs_bin_url: format!("http://{}:{}/get_outs.bin", host, port)
-                .parse()
+            get_transactions_url: base_url
+                .join("get_transactions")
                 .context("url is well formed")?,
         })
     }
@@ -58,6 +72,22 @@ impl Client {
             .await
     }
 
+    /// Looks up the given transactions, which may still be in the pool.
+    pub async fn get_transactions(&self, txs_hashes: Vec<String>) -> Result<GetTransactions> {
+        let response = self
+            .inner
+            .post(self.get_transactions_url.clone())
+            .json(&GetTransactionsPayload { txs_hashes })
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Request failed with status code {}", response.status())
+        }
+
+        Ok(response.json().await?)
+    }
+
     async fn binary_request<Req, Res>(&self, url: reqwest::Url, request: Req) -> Result<Res>
     where
         Req: Serialize,
@@ -91,6 +121,25 @@ pub struct BlockCount {
     pub count: u32,
 }
 
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct GetInfo {
+    pub height: u64,
+    /// The height of the network as far as the daemon knows. Can be `0` if the
+    /// daemon is not syncing.
+    pub target_height: u64,
+    #[serde(default)]
+    pub synchronized: bool,
+    #[serde(default)]
+    pub busy_syncing: bool,
+    /// The network flags are all `false` if the daemon runs in regtest mode.
+    #[serde(default)]
+    pub mainnet: bool,
+    #[serde(default)]
+    pub stagenet: bool,
+    #[serde(default)]
+    pub testnet: bool,
+}
+
 // We should be able to use monero-rs for this but it does not include all
 // the fields.
 #[derive(Clone, Debug, Deserialize)]
@@ -127,6 +176,30 @@ struct GetOIndexesPayload {
     txid: Hash,
 }
 
+#[derive(Clone, Debug, Serialize)]
+struct GetTransactionsPayload {
+    txs_hashes: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetTransactions {
+    pub status: String,
+    #[serde(default)]
+    pub txs: Vec<TransactionEntry>,
+    /// The hashes of the requested transactions the daemon does not know.
+    #[serde(default)]
+    pub missed_tx: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransactionEntry {
+    pub tx_hash: String,
+    pub in_pool: bool,
+    /// Only set if the transaction is not in the pool.
+    #[serde(default)]
+    pub block_height: u64,
+}
+
 #[derive(Clone, Debug, Serialize)]
 struct GetOutsPayload {
     outputs: Vec<GetOutputsOut>,
@@ -257,3 +330,64 @@ mod byte_array {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_get_info() {
+        let response = r#"{
+          "alt_blocks_count": 0,
+          "busy_syncing": true,
+          "height": 2410144,
+          "mainnet": false,
+          "stagenet": true,
+          "status": "OK",
+          "synchronized": false,
+          "target_height": 2510144,
+          "testnet": false,
+          "untrusted": false
+        }"#;
+
+        let info: GetInfo = serde_json::from_str(response).unwrap();
+
+        assert_eq!(info.height, 2410144);
+        assert_eq!(info.target_height, 2510144);
+        assert!(!info.synchronized);
+        assert!(info.busy_syncing);
+        assert!(info.stagenet);
+        assert!(!info.mainnet);
+        assert!(!info.testnet);
+    }
+
+    #[test]
+    fn can_deserialize_get_transactions() {
+        let response = r#"{
+          "credits": 0,
+          "missed_tx": ["c1d8cfa87d445c1915a59d67be3e93ba8a29018640cf69b465f07b1840a8f8c8"],
+          "status": "OK",
+          "top_hash": "",
+          "txs": [{
+            "as_hex": "",
+            "block_height": 2410100,
+            "block_timestamp": 1624000000,
+            "double_spend_seen": false,
+            "in_pool": false,
+            "output_indices": [],
+            "prunable_as_hex": "",
+            "prunable_hash": "",
+            "pruned_as_hex": "",
+            "tx_hash": "d6b3d2a8b0b6a4c3f1e2d3c4b5a697887766554433221100ffeeddccbbaa9988"
+          }],
+          "untrusted": false
+        }"#;
+
+        let transactions: GetTransactions = serde_json::from_str(response).unwrap();
+
+        assert_eq!(transactions.txs.len(), 1);
+        assert_eq!(transactions.txs[0].block_height, 2410100);
+        assert!(!transactions.txs[0].in_pool);
+        assert_eq!(transactions.missed_tx.len(), 1);
+    }
+}
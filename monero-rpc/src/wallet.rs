@@ -6,6 +6,7 @@ use serde::{Deserialize, Deserializer, Serialize};
 pub trait MoneroWalletRpc {
     async fn get_address(&self, account_index: u32) -> GetAddress;
     async fn get_balance(&self, account_index: u32) -> GetBalance;
+    async fn create_address(&self, account_index: u32, label: String) -> CreateAddress;
     async fn create_account(&self, label: String) -> CreateAccount;
     async fn get_accounts(&self, tag: String) -> GetAccounts;
     async fn open_wallet(&self, filename: String) -> WalletOpened;
@@ -31,8 +32,15 @@ pub trait MoneroWalletRpc {
         autosave_current: bool,
     ) -> GenerateFromKeys;
     async fn refresh(&self) -> Refreshed;
-    async fn sweep_all(&self, address: String) -> SweepAll;
+    async fn sweep_all(
+        &self,
+        address: String,
+        do_not_relay: bool,
+        get_tx_metadata: bool,
+    ) -> SweepAll;
+    async fn relay_tx(&self, hex: String) -> RelayTx;
     async fn get_version(&self) -> Version;
+    async fn set_daemon(&self, address: String) -> DaemonSet;
 }
 
 #[jsonrpc_client::implement(MoneroWalletRpc)]
@@ -81,15 +89,47 @@ impl Client {
 #[derive(Deserialize, Debug, Clone)]
 pub struct GetAddress {
     pub address: String,
+    /// All subaddresses of the account, including the primary address.
+    #[serde(default)]
+    pub addresses: Vec<Subaddress>,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone)]
+pub struct Subaddress {
+    pub address: String,
+    pub address_index: u32,
+    #[serde(default)]
+    pub label: String,
+    pub used: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct GetBalance {
     pub balance: u64,
     pub blocks_to_unlock: u32,
     pub multisig_import_needed: bool,
     pub time_to_unlock: u32,
     pub unlocked_balance: u64,
+    /// The balances of the subaddresses that received any funds.
+    #[serde(default)]
+    pub per_subaddress: Vec<SubaddressBalance>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubaddressBalance {
+    pub address_index: u32,
+    pub address: String,
+    pub balance: u64,
+    pub unlocked_balance: u64,
+    #[serde(default)]
+    pub label: String,
+    pub num_unspent_outputs: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreateAddress {
+    pub address: String,
+    pub address_index: u32,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -184,14 +224,23 @@ pub struct Refreshed {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct SweepAll {
-    amount_list: Vec<u64>,
-    fee_list: Vec<u64>,
+    pub amount_list: Vec<u64>,
+    pub fee_list: Vec<u64>,
     multisig_txset: String,
     pub tx_hash_list: Vec<String>,
+    /// Only returned if requested with `get_tx_metadata`, allows relaying
+    /// transactions created with `do_not_relay` later on.
+    #[serde(default)]
+    pub tx_metadata_list: Vec<String>,
     unsigned_txset: String,
     weight_list: Vec<u32>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct RelayTx {
+    pub tx_hash: String,
+}
+
 #[derive(Debug, Copy, Clone, Deserialize)]
 pub struct Version {
     pub version: u32,
@@ -200,6 +249,7 @@ pub struct Version {
 pub type WalletCreated = Empty;
 pub type WalletClosed = Empty;
 pub type WalletOpened = Empty;
+pub type DaemonSet = Empty;
 
 /// Zero-sized struct to allow serde to deserialize an empty JSON object.
 ///
@@ -247,6 +297,79 @@ mod tests {
         let _: Response<SweepAll> = serde_json::from_str(&response).unwrap();
     }
 
+    #[test]
+    fn can_deserialize_sweep_all_response_with_tx_metadata() {
+        let response = r#"{
+          "amount_list": [29921410000],
+          "fee_list": [78590000],
+          "multisig_txset": "",
+          "tx_hash_list": ["c1d8cfa87d445c1915a59d67be3e93ba8a29018640cf69b465f07b1840a8f8c8"],
+          "tx_metadata_list": ["0100000000"],
+          "unsigned_txset": "",
+          "weight_list": [1448]
+        }"#;
+
+        let sweep_all = serde_json::from_str::<SweepAll>(&response).unwrap();
+
+        assert_eq!(sweep_all.fee_list, vec![78590000]);
+        assert_eq!(sweep_all.tx_metadata_list, vec!["0100000000".to_owned()]);
+    }
+
+    #[test]
+    fn can_deserialize_get_address_with_subaddresses() {
+        let response = r#"{
+          "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+          "addresses": [{
+            "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+            "address_index": 0,
+            "label": "Primary account",
+            "used": true
+          },{
+            "address": "7BnERTpvL5MbCLtj5n9No7J5oE5hHiB3tVCK5cjSvCsYWD2WRJLFuWeKTLiXo5QJqt2ZwUaLy2Vh1Ad51K7FNgqcHgjW85o",
+            "address_index": 1,
+            "label": "exchange",
+            "used": false
+          }]
+        }"#;
+
+        let addresses = serde_json::from_str::<GetAddress>(&response)
+            .unwrap()
+            .addresses;
+
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[1].address_index, 1);
+        assert_eq!(addresses[1].label, "exchange");
+    }
+
+    #[test]
+    fn can_deserialize_get_balance_with_per_subaddress() {
+        let response = r#"{
+          "balance": 157443303037455077,
+          "blocks_to_unlock": 0,
+          "multisig_import_needed": false,
+          "per_subaddress": [{
+            "address": "7BnERTpvL5MbCLtj5n9No7J5oE5hHiB3tVCK5cjSvCsYWD2WRJLFuWeKTLiXo5QJqt2ZwUaLy2Vh1Ad51K7FNgqcHgjW85o",
+            "address_index": 1,
+            "balance": 1000000000000,
+            "blocks_to_unlock": 0,
+            "label": "exchange",
+            "num_unspent_outputs": 1,
+            "time_to_unlock": 0,
+            "unlocked_balance": 0
+          }],
+          "time_to_unlock": 0,
+          "unlocked_balance": 157360317826255077
+        }"#;
+
+        let per_subaddress = serde_json::from_str::<GetBalance>(&response)
+            .unwrap()
+            .per_subaddress;
+
+        assert_eq!(per_subaddress.len(), 1);
+        assert_eq!(per_subaddress[0].balance, 1_000_000_000_000);
+        assert_eq!(per_subaddress[0].label, "exchange");
+    }
+
     #[test]
     fn can_deserialize_create_wallet() {
         let response = r#"{
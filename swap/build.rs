@@ -5,5 +5,9 @@ fn main() -> Result<()> {
     let mut config = Config::default();
     *config.git_mut().semver_kind_mut() = SemverKind::Lightweight;
 
-    vergen(config)
+    vergen(config)?;
+
+    tonic_build::compile_protos("proto/admin.proto")?;
+
+    Ok(())
 }
@@ -0,0 +1,8 @@
+CREATE TABLE if NOT EXISTS monero_lock_proofs
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    tx_hash     TEXT                NOT NULL,
+    tx_key      TEXT                NOT NULL,
+    address     TEXT                NOT NULL,
+    amount      INTEGER             NOT NULL
+);
\ No newline at end of file
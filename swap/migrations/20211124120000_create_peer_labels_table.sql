@@ -0,0 +1,5 @@
+CREATE TABLE if NOT EXISTS peer_labels
+(
+    peer_id     TEXT    PRIMARY KEY NOT NULL,
+    label       TEXT                NOT NULL
+);
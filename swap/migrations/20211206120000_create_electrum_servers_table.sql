@@ -0,0 +1,5 @@
+CREATE TABLE if NOT EXISTS electrum_servers
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    rpc_url     TEXT                NOT NULL
+);
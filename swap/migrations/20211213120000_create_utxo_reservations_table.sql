@@ -0,0 +1,5 @@
+CREATE TABLE if NOT EXISTS utxo_reservations
+(
+    outpoint    TEXT    PRIMARY KEY NOT NULL,
+    swap_id     TEXT                NOT NULL
+);
@@ -0,0 +1,8 @@
+CREATE TABLE if NOT EXISTS fee_strategies
+(
+    swap_id                 TEXT    PRIMARY KEY NOT NULL,
+    lock_target_block       INTEGER             NOT NULL,
+    cancel_target_block     INTEGER             NOT NULL,
+    refund_target_block     INTEGER             NOT NULL,
+    redeem_target_block     INTEGER             NOT NULL
+);
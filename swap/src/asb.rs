@@ -1,21 +1,42 @@
+pub mod admin;
+pub mod backup;
 pub mod command;
 pub mod config;
 mod event_loop;
+pub mod history;
+pub mod identity;
+mod liquidity;
+pub mod maker_params;
 mod network;
 mod rate;
+mod reachability;
 mod recovery;
+mod restart;
+pub mod run_lock;
+pub mod storage;
+pub mod telemetry;
 pub mod tracing;
+mod withdraw;
 
-pub use event_loop::{EventLoop, EventLoopHandle, FixedRate, KrakenRate, LatestRate};
+pub use event_loop::{
+    EventLoop, EventLoopHandle, FixedRate, KrakenRate, LatestRate, ManualRate, NoFixedRate,
+    RateProvider, RateProviderError, SwapResumer, SwapSelection,
+};
+pub use liquidity::{check_liquidity, monitor_liquidity, Liquidity};
+pub use maker_params::{MakerParams, MakerParamsUpdater, MakerParamsUpdates, MaxBuyCeiling};
 pub use network::behaviour::{Behaviour, OutEvent};
 pub use network::transport;
 pub use rate::Rate;
+pub use reachability::{check_reachability, Reachability};
 pub use recovery::cancel::cancel;
 pub use recovery::punish::punish;
 pub use recovery::redeem::{redeem, Finality};
 pub use recovery::refund::refund;
 pub use recovery::safely_abort::safely_abort;
+pub use recovery::sweep::{refunded_xmr_keys, sweep_refunded_xmr};
 pub use recovery::{cancel, refund};
+pub use restart::run_with_restarts;
+pub use withdraw::withdraw_btc;
 
 #[cfg(test)]
 pub use network::rendezous;
@@ -0,0 +1,475 @@
+//! The admin interface of the ASB, a gRPC service defined in
+//! `proto/admin.proto` and served by `asb start --grpc-listen`.
+//!
+//! The service shares the handlers of the `history`, `cancel`, `refund` and
+//! `withdraw-btc` subcommands. Unlike the subcommands it runs next to the
+//! swaps, so it refuses to cancel or refund a swap that is running.
+//!
+//! Reading the status and the history is open to everyone who can reach the
+//! listen address. Cancel, refund and withdraw require the token that is
+//! stored in the data directory, see [`AdminToken`].
+
+use crate::asb::{cancel, history, refund, withdraw_btc};
+use crate::bitcoin::{self, Address, Amount};
+use crate::protocol::alice::swap::is_complete;
+use crate::protocol::alice::AliceState;
+use crate::protocol::Database;
+use crate::{env, monero};
+use anyhow::{Context, Result};
+use futures::Stream;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+pub mod proto {
+    #![allow(clippy::all, clippy::unwrap_used, rust_2018_idioms)]
+
+    tonic::include_proto!("admin");
+}
+
+const TOKEN_FILE_NAME: &str = "admin-token";
+
+/// How often a watched swap is checked for a new state.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serves the admin interface on `address` until it fails.
+pub async fn serve(address: SocketAddr, admin: Admin) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(proto::admin_server::AdminServer::new(admin))
+        .serve(address)
+        .await
+        .with_context(|| format!("Failed to serve the admin interface on {}", address))
+}
+
+pub struct Admin {
+    db: Arc<dyn Database + Send + Sync>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    monero_wallet: Arc<monero::Wallet>,
+    env_config: env::Config,
+    running_swaps: RunningSwaps,
+    token: AdminToken,
+}
+
+impl Admin {
+    pub fn new(
+        db: Arc<dyn Database + Send + Sync>,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        monero_wallet: Arc<monero::Wallet>,
+        env_config: env::Config,
+        running_swaps: RunningSwaps,
+        token: AdminToken,
+    ) -> Self {
+        Self {
+            db,
+            bitcoin_wallet,
+            monero_wallet,
+            env_config,
+            running_swaps,
+            token,
+        }
+    }
+
+    /// Marks the swap as running for as long as an admin method works on it,
+    /// fails if the ASB or another admin method is running it.
+    fn claim(&self, swap_id: Uuid) -> Result<RunningSwap, Status> {
+        self.running_swaps.try_insert(swap_id).ok_or_else(|| {
+            Status::failed_precondition(format!(
+                "Swap {} is running, it can only be changed once it stopped",
+                swap_id
+            ))
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl proto::admin_server::Admin for Admin {
+    async fn status(
+        &self,
+        request: Request<proto::SwapRequest>,
+    ) -> Result<Response<proto::SwapStatus>, Status> {
+        let swap_id = parse_swap_id(&request.get_ref().swap_id)?;
+        let state = latest_state(self.db.as_ref(), swap_id).await?;
+
+        Ok(Response::new(swap_status(swap_id, &state)))
+    }
+
+    type WatchStatusStream = StatusUpdates;
+
+    async fn watch_status(
+        &self,
+        request: Request<proto::SwapRequest>,
+    ) -> Result<Response<Self::WatchStatusStream>, Status> {
+        let swap_id = parse_swap_id(&request.get_ref().swap_id)?;
+        let mut state = latest_state(self.db.as_ref(), swap_id).await?;
+
+        let (sender, receiver) = mpsc::channel(1);
+        let db = self.db.clone();
+
+        tokio::spawn(async move {
+            loop {
+                if sender.send(Ok(swap_status(swap_id, &state))).await.is_err() {
+                    return;
+                }
+                if is_complete(&state) {
+                    return;
+                }
+
+                state = loop {
+                    tokio::time::sleep(WATCH_INTERVAL).await;
+
+                    match latest_state(db.as_ref(), swap_id).await {
+                        Ok(latest) if latest != state => break latest,
+                        Ok(_) => {}
+                        Err(status) => {
+                            let _ = sender.send(Err(status)).await;
+                            return;
+                        }
+                    }
+                };
+            }
+        });
+
+        Ok(Response::new(StatusUpdates(receiver)))
+    }
+
+    async fn history(
+        &self,
+        request: Request<proto::HistoryRequest>,
+    ) -> Result<Response<proto::HistoryResponse>, Status> {
+        let request = request.get_ref();
+        let since = parse_timestamp(request.since)?;
+        let until = parse_timestamp(request.until)?;
+
+        let swaps = history::entries(self.db.as_ref(), since, until)
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(|entry| proto::HistoryEntry {
+                swap_id: entry.swap_id.to_string(),
+                peer: entry.peer.unwrap_or_default(),
+                started_at: entry
+                    .started_at
+                    .map(|started_at| started_at.unix_timestamp())
+                    .unwrap_or_default(),
+                state: entry.state.to_string(),
+            })
+            .collect();
+
+        Ok(Response::new(proto::HistoryResponse { swaps }))
+    }
+
+    async fn cancel(
+        &self,
+        request: Request<proto::SwapRequest>,
+    ) -> Result<Response<proto::CancelResponse>, Status> {
+        self.token.authorize(&request)?;
+        let swap_id = parse_swap_id(&request.get_ref().swap_id)?;
+        let _running = self.claim(swap_id)?;
+
+        let (txid, state) = cancel(swap_id, self.bitcoin_wallet.clone(), self.db.clone())
+            .await
+            .map_err(internal)?;
+
+        tracing::info!(%swap_id, %txid, "Published cancel transaction on request of the admin interface");
+
+        Ok(Response::new(proto::CancelResponse {
+            txid: txid.to_string(),
+            status: Some(swap_status(swap_id, &state)),
+        }))
+    }
+
+    async fn refund(
+        &self,
+        request: Request<proto::SwapRequest>,
+    ) -> Result<Response<proto::SwapStatus>, Status> {
+        self.token.authorize(&request)?;
+        let swap_id = parse_swap_id(&request.get_ref().swap_id)?;
+        let _running = self.claim(swap_id)?;
+
+        let state = refund(
+            swap_id,
+            self.bitcoin_wallet.clone(),
+            self.monero_wallet.clone(),
+            self.db.clone(),
+        )
+        .await
+        .map_err(internal)?;
+
+        tracing::info!(%swap_id, "Refunded Monero on request of the admin interface");
+
+        Ok(Response::new(swap_status(swap_id, &state)))
+    }
+
+    async fn withdraw_btc(
+        &self,
+        request: Request<proto::WithdrawBtcRequest>,
+    ) -> Result<Response<proto::WithdrawBtcResponse>, Status> {
+        self.token.authorize(&request)?;
+        let request = request.get_ref();
+
+        let address = request
+            .address
+            .parse::<Address>()
+            .map_err(|error| Status::invalid_argument(format!("Invalid address: {}", error)))?;
+        if address.network != self.env_config.bitcoin_network {
+            return Err(Status::invalid_argument(format!(
+                "Address is on {}, expected an address on {}",
+                address.network, self.env_config.bitcoin_network
+            )));
+        }
+        let amount = match request.amount_sat {
+            0 => None,
+            amount => Some(Amount::from_sat(amount)),
+        };
+
+        let (signed_tx, net_amount) = withdraw_btc(&self.bitcoin_wallet, address, amount)
+            .await
+            .map_err(internal)?;
+        let (txid, _) = self
+            .bitcoin_wallet
+            .broadcast(signed_tx, "withdraw")
+            .await
+            .map_err(internal)?;
+
+        tracing::info!(%txid, %net_amount, "Withdrew Bitcoin on request of the admin interface");
+
+        Ok(Response::new(proto::WithdrawBtcResponse {
+            txid: txid.to_string(),
+            amount_sat: net_amount.as_sat(),
+        }))
+    }
+}
+
+/// The states of a watched swap, see [`proto::admin_server::Admin::watch_status`].
+pub struct StatusUpdates(mpsc::Receiver<Result<proto::SwapStatus, Status>>);
+
+impl Stream for StatusUpdates {
+    type Item = Result<proto::SwapStatus, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+/// The swaps that are running, either by the ASB or by a method of the admin
+/// interface.
+#[derive(Debug, Clone, Default)]
+pub struct RunningSwaps {
+    swaps: Arc<Mutex<HashMap<Uuid, usize>>>,
+}
+
+impl RunningSwaps {
+    /// Marks the swap as running until the returned guard is dropped.
+    pub fn insert(&self, swap_id: Uuid) -> RunningSwap {
+        *self.lock().entry(swap_id).or_default() += 1;
+
+        RunningSwap {
+            swaps: self.clone(),
+            swap_id,
+        }
+    }
+
+    /// Like [`RunningSwaps::insert`], but only if the swap is not running
+    /// already.
+    fn try_insert(&self, swap_id: Uuid) -> Option<RunningSwap> {
+        let mut swaps = self.lock();
+        if swaps.contains_key(&swap_id) {
+            return None;
+        }
+        swaps.insert(swap_id, 1);
+
+        Some(RunningSwap {
+            swaps: self.clone(),
+            swap_id,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, usize>> {
+        self.swaps
+            .lock()
+            .expect("running swaps are never changed while panicking")
+    }
+}
+
+/// Marks a swap as running until dropped.
+#[derive(Debug)]
+pub struct RunningSwap {
+    swaps: RunningSwaps,
+    swap_id: Uuid,
+}
+
+impl Drop for RunningSwap {
+    fn drop(&mut self) {
+        let mut swaps = self.swaps.lock();
+
+        if let Some(count) = swaps.get_mut(&self.swap_id) {
+            *count -= 1;
+            if *count == 0 {
+                swaps.remove(&self.swap_id);
+            }
+        }
+    }
+}
+
+/// The token that authorizes the methods of the admin interface that change
+/// swaps or spend funds.
+///
+/// It is generated on first use and stored in the data directory, so only
+/// users who can read the data directory can use these methods. Clients send
+/// it as `authorization: Bearer <token>`.
+#[derive(Clone)]
+pub struct AdminToken(String);
+
+impl AdminToken {
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(TOKEN_FILE_NAME)
+    }
+
+    /// Reads the token from the data directory, generating it if there is
+    /// none yet.
+    pub fn load_or_generate(data_dir: &Path) -> Result<Self> {
+        let path = Self::path(data_dir);
+
+        if path.exists() {
+            let token = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read admin token from {}", path.display()))?;
+
+            return Ok(Self(token.trim().to_owned()));
+        }
+
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+
+        fs::write(&path, &token)
+            .with_context(|| format!("Failed to write admin token to {}", path.display()))?;
+        crate::fs::ensure_file_is_private(&path)
+            .context("Failed to restrict permissions of admin token")?;
+
+        Ok(Self(token))
+    }
+
+    fn authorize<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let expected = format!("Bearer {}", self.0);
+
+        let authorized = request
+            .metadata()
+            .get("authorization")
+            .map_or(false, |value| {
+                constant_time_eq(value.as_bytes(), expected.as_bytes())
+            });
+
+        if !authorized {
+            return Err(Status::unauthenticated(
+                "This method requires the admin token of the data directory",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares without returning early, so the time it takes does not tell how
+/// much of a guessed token is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+async fn latest_state(
+    db: &(dyn Database + Send + Sync),
+    swap_id: Uuid,
+) -> Result<AliceState, Status> {
+    let state = db
+        .get_state(swap_id)
+        .await
+        .map_err(|error| Status::not_found(format!("{:#}", error)))?;
+
+    state
+        .try_into()
+        .map_err(|error| internal(anyhow::Error::new(error)))
+}
+
+fn swap_status(swap_id: Uuid, state: &AliceState) -> proto::SwapStatus {
+    proto::SwapStatus {
+        swap_id: swap_id.to_string(),
+        state: state.to_string(),
+        complete: is_complete(state),
+    }
+}
+
+fn parse_swap_id(swap_id: &str) -> Result<Uuid, Status> {
+    Uuid::parse_str(swap_id)
+        .map_err(|error| Status::invalid_argument(format!("Invalid swap id: {}", error)))
+}
+
+/// Parses a Unix timestamp in seconds, 0 meaning none.
+fn parse_timestamp(timestamp: i64) -> Result<Option<OffsetDateTime>, Status> {
+    if timestamp == 0 {
+        return Ok(None);
+    }
+
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .map(Some)
+        .map_err(|error| Status::invalid_argument(format!("Invalid timestamp: {}", error)))
+}
+
+fn internal(error: anyhow::Error) -> Status {
+    Status::internal(format!("{:#}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn running_swap_cannot_be_claimed_until_it_stopped() {
+        let running_swaps = RunningSwaps::default();
+        let swap_id = Uuid::new_v4();
+
+        let running = running_swaps.insert(swap_id);
+        assert!(running_swaps.try_insert(swap_id).is_none());
+
+        drop(running);
+        let claimed = running_swaps.try_insert(swap_id);
+        assert!(claimed.is_some());
+        assert!(running_swaps.try_insert(swap_id).is_none());
+    }
+
+    #[test]
+    fn token_is_generated_once_and_required() {
+        let data_dir = tempdir().unwrap();
+        let token = AdminToken::load_or_generate(data_dir.path()).unwrap();
+        let loaded = AdminToken::load_or_generate(data_dir.path()).unwrap();
+        assert_eq!(loaded.0, token.0);
+
+        let mut request = Request::new(());
+        assert!(token.authorize(&request).is_err());
+
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer wrong".parse().unwrap());
+        assert!(token.authorize(&request).is_err());
+
+        request.metadata_mut().insert(
+            "authorization",
+            format!("Bearer {}", token.0).parse().unwrap(),
+        );
+        assert!(token.authorize(&request).is_ok());
+    }
+}
@@ -0,0 +1,170 @@
+//! Exports a swap from the database into a JSON backup and imports it again,
+//! e.g. to recover from a lost database.
+//!
+//! A backup contains every state the swap went through together with the peer
+//! id of the buyer, which is all the ASB needs to resume the swap.
+
+use crate::database::Swap;
+use crate::protocol::alice::AliceState;
+use crate::protocol::{Database, State};
+use anyhow::{bail, Context, Result};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SwapBackup {
+    pub swap_id: Uuid,
+    /// The buyer of the swap, unknown for swaps that failed before the swap
+    /// setup completed.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub peer_id: Option<PeerId>,
+    /// The states of the swap from the oldest to the latest.
+    pub states: Vec<Swap>,
+}
+
+impl SwapBackup {
+    /// Parses a backup and checks that it holds at least one state and only
+    /// states of the ASB.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let backup: SwapBackup =
+            serde_json::from_str(json).context("Failed to parse the swap backup")?;
+
+        if backup.states.is_empty() {
+            bail!("The backup of swap {} contains no state", backup.swap_id);
+        }
+        for state in &backup.states {
+            state.clone().try_into_alice().with_context(|| {
+                format!("The backup of swap {} is not from an ASB", backup.swap_id)
+            })?;
+        }
+
+        Ok(backup)
+    }
+
+    /// The state the swap resumes from after the import.
+    pub fn latest_state(&self) -> Option<AliceState> {
+        self.states
+            .last()
+            .cloned()
+            .and_then(|state| state.try_into_alice().ok())
+            .map(AliceState::from)
+    }
+}
+
+pub async fn export_swap(swap_id: Uuid, db: Arc<dyn Database>) -> Result<SwapBackup> {
+    let states = db.get_states(swap_id).await?;
+    if states.is_empty() {
+        bail!("Swap {} does not exist", swap_id);
+    }
+
+    Ok(SwapBackup {
+        swap_id,
+        peer_id: db.get_peer_id(swap_id).await.ok(),
+        states: states.into_iter().map(Swap::from).collect(),
+    })
+}
+
+/// Inserts the states of the backup into the database so the swap can be
+/// resumed.
+///
+/// Fails if the swap exists already unless `force` is given. Nothing is
+/// deleted then, the imported states are recorded after the existing ones
+/// and the peer id is replaced.
+pub async fn import_swap(backup: SwapBackup, db: Arc<dyn Database>, force: bool) -> Result<()> {
+    let swap_id = backup.swap_id;
+
+    let exists = db.get_state(swap_id).await.is_ok();
+    if exists && !force {
+        bail!(
+            "Swap {} exists already, pass --force to import it anyway",
+            swap_id
+        );
+    }
+
+    if let Some(peer_id) = backup.peer_id {
+        match db.get_peer_id(swap_id).await {
+            Ok(_) => db.update_peer_id(swap_id, peer_id).await?,
+            Err(_) => db.insert_peer_id(swap_id, peer_id).await?,
+        }
+    }
+
+    for state in backup.states {
+        db.insert_latest_state(swap_id, State::from(state)).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::open_db;
+    use std::convert::TryFrom;
+    use tempfile::tempdir;
+
+    fn backup(swap_id: Uuid) -> SwapBackup {
+        SwapBackup {
+            swap_id,
+            peer_id: Some(PeerId::random()),
+            states: vec![
+                Swap::from(State::from(AliceState::SafelyAborted)),
+                Swap::from(State::from(AliceState::BtcRedeemed)),
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn exported_swap_is_imported_into_empty_database() {
+        let swap_id = Uuid::new_v4();
+        let backup = backup(swap_id);
+        let json = serde_json::to_string(&backup).unwrap();
+        let data_dir = tempdir().unwrap();
+        let db = open_db(data_dir.path().join("sqlite")).await.unwrap();
+
+        import_swap(SwapBackup::from_json(&json).unwrap(), db.clone(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(export_swap(swap_id, db.clone()).await.unwrap(), backup);
+        assert_eq!(
+            AliceState::try_from(db.get_state(swap_id).await.unwrap()).unwrap(),
+            AliceState::BtcRedeemed
+        );
+    }
+
+    #[tokio::test]
+    async fn existing_swap_is_only_overwritten_with_force() {
+        let swap_id = Uuid::new_v4();
+        let data_dir = tempdir().unwrap();
+        let db = open_db(data_dir.path().join("sqlite")).await.unwrap();
+        db.insert_latest_state(swap_id, AliceState::SafelyAborted.into())
+            .await
+            .unwrap();
+
+        assert!(import_swap(backup(swap_id), db.clone(), false)
+            .await
+            .is_err());
+
+        import_swap(backup(swap_id), db.clone(), true)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            AliceState::try_from(db.get_state(swap_id).await.unwrap()).unwrap(),
+            AliceState::BtcRedeemed
+        );
+    }
+
+    #[test]
+    fn backup_without_states_is_rejected() {
+        let json = format!(r#"{{"swap_id":"{}","states":[]}}"#, Uuid::new_v4());
+
+        assert!(SwapBackup::from_json(&json).is_err());
+        assert!(SwapBackup::from_json("not json").is_err());
+    }
+}
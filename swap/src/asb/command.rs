@@ -2,12 +2,19 @@ use crate::asb::config::GetDefaults;
 use crate::bitcoin::Amount;
 use crate::env;
 use crate::env::GetConfig;
-use anyhow::{bail, Result};
+use crate::monero::{PrivateViewKey, WalletKeys};
+use anyhow::{bail, Context, Result};
 use bitcoin::Address;
+use libp2p::PeerId;
+use monero_rpc::wallet::BlockHeight;
 use serde::Serialize;
 use std::ffi::OsString;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 use structopt::StructOpt;
+use time::format_description::well_known::Rfc3339;
+use time::{format_description, Date, OffsetDateTime};
 use uuid::Uuid;
 
 pub fn parse_args<I, T>(raw_args: I) -> Result<Arguments>
@@ -25,23 +32,101 @@ where
     let command: RawCommand = args.cmd;
 
     let arguments = match command {
-        RawCommand::Start { resume_only } => Arguments {
+        RawCommand::Start {
+            resume_only,
+            grpc_listen,
+        } => Arguments {
             testnet,
             json,
             disable_timestamp,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
-            cmd: Command::Start { resume_only },
+            cmd: Command::Start {
+                resume_only,
+                grpc_listen,
+            },
+        },
+        RawCommand::History { since, until, wide } => {
+            if let (Some(since), Some(until)) = (since, until) {
+                if since >= until {
+                    bail!("The start of the time range must be before its end")
+                }
+            }
+
+            Arguments {
+                testnet,
+                json,
+                disable_timestamp,
+                config_path: config_path(config, testnet)?,
+                env_config: env_config(testnet),
+                cmd: Command::History { since, until, wide },
+            }
+        }
+        RawCommand::LabelPeer { peer_id, label } => {
+            if label.trim().is_empty() {
+                bail!("The label of a peer must not be empty")
+            }
+
+            Arguments {
+                testnet,
+                json,
+                disable_timestamp,
+                config_path: config_path(config, testnet)?,
+                env_config: env_config(testnet),
+                cmd: Command::LabelPeer { peer_id, label },
+            }
+        }
+        RawCommand::Peers => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Peers,
+        },
+        RawCommand::Prune {
+            older_than_days,
+            dry_run,
+        } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Prune {
+                older_than: Duration::from_secs(older_than_days * 24 * 60 * 60),
+                dry_run,
+            },
+        },
+        RawCommand::CompactDatabase => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::CompactDatabase,
+        },
+        RawCommand::Export { swap_id } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Export { swap_id },
         },
-        RawCommand::History => Arguments {
+        RawCommand::Import { file, force } => Arguments {
             testnet,
             json,
             disable_timestamp,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
-            cmd: Command::History,
+            cmd: Command::Import { file, force },
         },
-        RawCommand::WithdrawBtc { amount, address } => Arguments {
+        RawCommand::WithdrawBtc {
+            amount,
+            address,
+            dry_run,
+        } => Arguments {
             testnet,
             json,
             disable_timestamp,
@@ -50,6 +135,7 @@ where
             cmd: Command::WithdrawBtc {
                 amount,
                 address: bitcoin_address(address, testnet)?,
+                dry_run,
             },
         },
         RawCommand::Balance => Arguments {
@@ -60,7 +146,23 @@ where
             env_config: env_config(testnet),
             cmd: Command::Balance,
         },
-        RawCommand::Config => Arguments {
+        RawCommand::CreateMoneroSubaddress { label } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::CreateMoneroSubaddress { label },
+        },
+        RawCommand::MoneroSubaddresses => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::MoneroSubaddresses,
+        },
+        RawCommand::Config { cmd: None } => Arguments {
             testnet,
             json,
             disable_timestamp,
@@ -68,6 +170,26 @@ where
             env_config: env_config(testnet),
             cmd: Command::Config,
         },
+        RawCommand::Config {
+            cmd: Some(ConfigCommand::Validate),
+        } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::ValidateConfig,
+        },
+        RawCommand::Config {
+            cmd: Some(ConfigCommand::Show),
+        } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::ShowConfig,
+        },
         RawCommand::ExportBitcoinWallet => Arguments {
             testnet,
             json,
@@ -76,6 +198,56 @@ where
             env_config: env_config(testnet),
             cmd: Command::ExportBitcoinWallet,
         },
+        RawCommand::SellerAddresses => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::SellerAddresses,
+        },
+        RawCommand::PeerId => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::PeerId,
+        },
+        RawCommand::CheckReachability => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::CheckReachability,
+        },
+        RawCommand::RotateIdentity => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::RotateIdentity,
+        },
+        RawCommand::IdentityStatus => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::IdentityStatus,
+        },
+        RawCommand::ShowXmrProof {
+            params: RecoverCommandParams { swap_id },
+        } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::ShowXmrProof { swap_id },
+        },
         RawCommand::ManualRecovery(ManualRecovery::Redeem {
             redeem_params: RecoverCommandParams { swap_id },
             do_not_await_finality,
@@ -129,6 +301,50 @@ where
             env_config: env_config(testnet),
             cmd: Command::SafelyAbort { swap_id },
         },
+        RawCommand::ManualRecovery(ManualRecovery::SweepRefundedXmr {
+            sweep_params: RecoverCommandParams { swap_id },
+            address,
+        }) => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::SweepRefundedXmr {
+                swap_id,
+                address: monero_address(address, testnet)?,
+            },
+        },
+        RawCommand::ManualRecovery(ManualRecovery::RestoreMoneroWallet {
+            wallet_name,
+            swap_id,
+            spend_key,
+            view_key,
+            restore_height,
+        }) => {
+            let from = match (swap_id, spend_key, view_key, restore_height) {
+                (Some(swap_id), None, None, None) => RestoreFrom::Swap(swap_id),
+                (None, Some(spend_key), Some(view_key), Some(height)) => {
+                    RestoreFrom::Keys(WalletKeys {
+                        spend_key,
+                        view_key,
+                        restore_height: BlockHeight { height },
+                    })
+                }
+                _ => bail!(
+                    "Either --swap-id or all of --spend-key, --view-key and --restore-height are required"
+                ),
+            };
+
+            Arguments {
+                testnet,
+                json,
+                disable_timestamp,
+                config_path: config_path(config, testnet)?,
+                env_config: env_config(testnet),
+                cmd: Command::RestoreMoneroWallet { wallet_name, from },
+            }
+        }
     };
 
     Ok(arguments)
@@ -151,6 +367,23 @@ fn bitcoin_address(address: Address, is_testnet: bool) -> Result<Address> {
     Ok(address)
 }
 
+fn monero_address(address: monero::Address, is_testnet: bool) -> Result<monero::Address> {
+    let network = if is_testnet {
+        monero::Network::Stagenet
+    } else {
+        monero::Network::Mainnet
+    };
+
+    if address.network != network {
+        bail!(MoneroAddressNetworkMismatch {
+            expected: network,
+            actual: address.network
+        });
+    }
+
+    Ok(address)
+}
+
 fn config_path(config: Option<PathBuf>, is_testnet: bool) -> Result<PathBuf> {
     let config_path = if let Some(config_path) = config {
         config_path
@@ -171,6 +404,26 @@ fn env_config(is_testnet: bool) -> env::Config {
     }
 }
 
+fn parse_since(s: &str) -> Result<OffsetDateTime> {
+    parse_time(s, |date| date)
+}
+
+/// A date without a time includes the whole day, i.e. is the midnight after it.
+fn parse_until(s: &str) -> Result<OffsetDateTime> {
+    parse_time(s, |date| date.next_day().unwrap_or(date))
+}
+
+fn parse_time(s: &str, day_to_midnight: impl Fn(Date) -> Date) -> Result<OffsetDateTime> {
+    if let Ok(time) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(time);
+    }
+
+    let date = Date::parse(s, &format_description::parse("[year]-[month]-[day]")?)
+        .with_context(|| format!("Invalid time {}, expected RFC3339 or YYYY-MM-DD", s))?;
+
+    Ok(day_to_midnight(date).midnight().assume_utc())
+}
+
 #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Serialize)]
 #[error("Invalid Bitcoin address provided, expected address on network {expected:?}  but address provided is on {actual:?}")]
 pub struct BitcoinAddressNetworkMismatch {
@@ -180,6 +433,15 @@ pub struct BitcoinAddressNetworkMismatch {
     actual: bitcoin::Network,
 }
 
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Serialize)]
+#[error("Invalid Monero address provided, expected address on network {expected:?} but address provided is on {actual:?}")]
+pub struct MoneroAddressNetworkMismatch {
+    #[serde(with = "crate::monero::network")]
+    expected: monero::Network,
+    #[serde(with = "crate::monero::network")]
+    actual: monero::Network,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Arguments {
     pub testnet: bool,
@@ -194,14 +456,45 @@ pub struct Arguments {
 pub enum Command {
     Start {
         resume_only: bool,
+        /// Serve the admin interface on this address, see [`crate::asb::admin`].
+        grpc_listen: Option<SocketAddr>,
+    },
+    /// Only swaps that started in `[since, until)` are listed.
+    History {
+        since: Option<OffsetDateTime>,
+        until: Option<OffsetDateTime>,
+        wide: bool,
+    },
+    LabelPeer {
+        peer_id: PeerId,
+        label: String,
+    },
+    Peers,
+    Prune {
+        older_than: Duration,
+        dry_run: bool,
+    },
+    CompactDatabase,
+    Export {
+        swap_id: Uuid,
+    },
+    Import {
+        file: PathBuf,
+        force: bool,
     },
-    History,
     Config,
+    ValidateConfig,
+    ShowConfig,
     WithdrawBtc {
         amount: Option<Amount>,
         address: Address,
+        dry_run: bool,
     },
     Balance,
+    CreateMoneroSubaddress {
+        label: String,
+    },
+    MoneroSubaddresses,
     Redeem {
         swap_id: Uuid,
         do_not_await_finality: bool,
@@ -218,7 +511,31 @@ pub enum Command {
     SafelyAbort {
         swap_id: Uuid,
     },
+    SweepRefundedXmr {
+        swap_id: Uuid,
+        address: monero::Address,
+    },
+    RestoreMoneroWallet {
+        wallet_name: String,
+        from: RestoreFrom,
+    },
     ExportBitcoinWallet,
+    SellerAddresses,
+    PeerId,
+    CheckReachability,
+    RotateIdentity,
+    IdentityStatus,
+    ShowXmrProof {
+        swap_id: Uuid,
+    },
+}
+
+/// Where the keys of a restored Monero wallet come from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestoreFrom {
+    /// The keys of the refunded Monero of a swap in the database.
+    Swap(Uuid),
+    Keys(WalletKeys),
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -267,11 +584,105 @@ pub enum RawCommand {
             help = "For maintenance only. When set, no new swap requests will be accepted, but existing unfinished swaps will be resumed."
         )]
         resume_only: bool,
+
+        #[structopt(
+            long = "grpc-listen",
+            help = "Serve the gRPC admin interface on this address, e.g. 127.0.0.1:9944. Changing swaps and withdrawing requires the token in the admin-token file of the data directory."
+        )]
+        grpc_listen: Option<SocketAddr>,
+    },
+    #[structopt(about = "Prints swap-id, peer, start time and the state of each swap ever made.")]
+    History {
+        #[structopt(
+            long = "since",
+            help = "Only list swaps that started at or after this time, either RFC3339 or YYYY-MM-DD (midnight UTC)",
+            parse(try_from_str = parse_since)
+        )]
+        since: Option<OffsetDateTime>,
+
+        #[structopt(
+            long = "until",
+            help = "Only list swaps that started before this time, either RFC3339 or YYYY-MM-DD (including the whole day in UTC)",
+            parse(try_from_str = parse_until)
+        )]
+        until: Option<OffsetDateTime>,
+
+        #[structopt(
+            long = "wide",
+            help = "Print the full table even if it is wider than the terminal. Long columns are cut to fit the terminal otherwise, output that is not printed to a terminal is never cut."
+        )]
+        wide: bool,
+    },
+    #[structopt(
+        about = "Labels a peer, e.g. a repeat counterparty. The history and peers commands show the label instead of the peer id."
+    )]
+    LabelPeer {
+        #[structopt(long = "peer-id", help = "The peer id of the counterparty")]
+        peer_id: PeerId,
+
+        #[structopt(
+            long = "label",
+            help = "The label, replaces a previous label of the peer"
+        )]
+        label: String,
+    },
+    #[structopt(
+        about = "Prints every peer we swapped with or labeled, with its label and number of swaps."
+    )]
+    Peers,
+    #[structopt(
+        about = "Deletes finished swaps that did not change their state for the given number of days from the database. Swaps that are still in progress are never deleted."
+    )]
+    Prune {
+        #[structopt(
+            long = "older-than",
+            help = "Only delete swaps that finished more than this many days ago",
+            default_value = "30"
+        )]
+        older_than_days: u64,
+
+        #[structopt(
+            long = "dry-run",
+            help = "List the swaps that would be deleted without deleting them"
+        )]
+        dry_run: bool,
+    },
+    #[structopt(
+        about = "Rewrites the swap database to return the space of deleted swaps to the file system. Stop the ASB before running this."
+    )]
+    CompactDatabase,
+    #[structopt(
+        about = "Prints a JSON backup of a swap with all of its states, which can be imported again with the import subcommand."
+    )]
+    Export {
+        #[structopt(
+            long = "swap-id",
+            help = "The swap id can be retrieved using the history subcommand"
+        )]
+        swap_id: Uuid,
+    },
+    #[structopt(
+        about = "Imports a swap from a JSON backup created by the export subcommand, e.g. to resume it after the database was lost."
+    )]
+    Import {
+        #[structopt(
+            long = "file",
+            help = "The JSON backup of the swap",
+            parse(from_os_str)
+        )]
+        file: PathBuf,
+
+        #[structopt(
+            long = "force",
+            help = "Import the swap even if it exists already, its latest state is replaced by the imported one"
+        )]
+        force: bool,
     },
-    #[structopt(about = "Prints swap-id and the state of each swap ever made.")]
-    History,
     #[structopt(about = "Prints the current config")]
-    Config,
+    Config {
+        #[structopt(subcommand)]
+        cmd: Option<ConfigCommand>,
+    },
     #[structopt(about = "Allows withdrawing BTC from the internal Bitcoin wallet.")]
     WithdrawBtc {
         #[structopt(
@@ -281,13 +692,56 @@ pub enum RawCommand {
         amount: Option<Amount>,
         #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
         address: Address,
+        #[structopt(
+            long = "dry-run",
+            help = "Build and sign the transaction and print its fee, fee rate, size and the amount sent to the address, without broadcasting it."
+        )]
+        dry_run: bool,
     },
     #[structopt(
         about = "Prints the Bitcoin and Monero balance. Requires the monero-wallet-rpc to be running."
     )]
     Balance,
+    #[structopt(
+        about = "Creates a labeled subaddress in the Monero wallet, e.g. to tell deposits from different funding sources apart."
+    )]
+    CreateMoneroSubaddress {
+        #[structopt(long = "label", help = "The label of the new subaddress")]
+        label: String,
+    },
+    #[structopt(
+        about = "Prints the subaddresses of the Monero wallet with their labels and balances."
+    )]
+    MoneroSubaddresses,
     #[structopt(about = "Print the internal bitcoin wallet descriptor.")]
     ExportBitcoinWallet,
+    #[structopt(
+        about = "Prints the addresses under which takers can reach this ASB, in full and in the compact format accepted by the CLI's --seller option."
+    )]
+    SellerAddresses,
+    #[structopt(
+        about = "Prints the libp2p peer id of this ASB, derived from its seed. Does not connect to any network or wallet."
+    )]
+    PeerId,
+    #[structopt(
+        about = "Dials the onion addresses of this ASB through Tor to check that takers can reach it. The ASB has to be running."
+    )]
+    CheckReachability,
+    #[structopt(
+        about = "Switches new swaps to a new peer id and onion address derived from the seed. The old ones stay reachable until the swaps that were in-flight at the time of the rotation finished. Takes effect when the ASB is started the next time."
+    )]
+    RotateIdentity,
+    #[structopt(
+        about = "Prints the current and the retiring peer id and onion address, and the swaps that keep the old ones from being retired."
+    )]
+    IdentityStatus,
+    #[structopt(
+        about = "Prints the hash, key and destination of the transaction that locked the XMR of a swap, which allows anyone to verify that the agreed amount was locked to the address shared with the taker."
+    )]
+    ShowXmrProof {
+        #[structopt(flatten)]
+        params: RecoverCommandParams,
+    },
     #[structopt(about = "Contains sub-commands for recovering a swap manually.")]
     ManualRecovery(ManualRecovery),
 }
@@ -336,6 +790,64 @@ pub enum ManualRecovery {
         )]
         swap_id: Uuid,
     },
+    #[structopt(
+        about = "Sweeps the Monero refunded to us from the wallet of the swap to the given address. The wallet is recreated from the swap's keys if necessary, running this again after a successful sweep is a no-op."
+    )]
+    SweepRefundedXmr {
+        #[structopt(flatten)]
+        sweep_params: RecoverCommandParams,
+
+        #[structopt(
+            long = "address",
+            help = "The Monero address to sweep the refunded Monero to"
+        )]
+        address: monero::Address,
+    },
+    #[structopt(
+        about = "Generates a Monero wallet in the wallet directory of the monero-wallet-rpc from the keys of the Monero refunded to us in a swap or from the given spend and view key. The wallet is not swept, so it can be opened with any Monero wallet afterwards."
+    )]
+    RestoreMoneroWallet {
+        #[structopt(
+            long = "wallet-name",
+            help = "The file name of the new wallet, must not exist yet"
+        )]
+        wallet_name: String,
+
+        #[structopt(
+            long = "swap-id",
+            help = "Use the keys of the Monero refunded to us in this swap, which requires that Bob published his Bitcoin refund transaction",
+            conflicts_with_all = &["spend_key", "view_key", "restore_height"]
+        )]
+        swap_id: Option<Uuid>,
+
+        #[structopt(
+            long = "spend-key",
+            help = "The private spend key as 64 hex characters, e.g. the sum s_a + s_b of a swap",
+            parse(try_from_str = crate::monero::private_key_from_hex)
+        )]
+        spend_key: Option<monero::PrivateKey>,
+
+        #[structopt(long = "view-key", help = "The private view key as 64 hex characters")]
+        view_key: Option<PrivateViewKey>,
+
+        #[structopt(
+            long = "restore-height",
+            help = "The block height to scan from, the Monero of a swap cannot be older than its lock transaction"
+        )]
+        restore_height: Option<u32>,
+    },
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum ConfigCommand {
+    #[structopt(
+        about = "Checks the config file without starting the ASB and prints every error and warning. Exits with an error if the config file has any errors."
+    )]
+    Validate,
+    #[structopt(
+        about = "Prints the configuration the ASB runs with as JSON: the config file with its defaults and the swap parameters after applying its overrides. Passwords are redacted."
+    )]
+    Show,
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -355,6 +867,8 @@ mod tests {
     const BINARY_NAME: &str = "asb";
     const BITCOIN_MAINNET_ADDRESS: &str = "1KFHE7w8BhaENAswwryaoccDb6qcT6DbYY";
     const BITCOIN_TESTNET_ADDRESS: &str = "tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a";
+    const MONERO_STAGENET_ADDRESS: &str = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a";
+    const MONERO_MAINNET_ADDRESS: &str = "44Ato7HveWidJYUAVw5QffEcEtSH1DwzSP3FPPkHxNAS4LX9CqgucphTisH978FLHE34YNEx7FcbBfQLQUU8m3NUC4VqsRa";
     const SWAP_ID: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
 
     #[test]
@@ -369,12 +883,30 @@ mod tests {
             disable_timestamp: false,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                grpc_listen: None,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_start_command_with_grpc_listen_mapping() {
+        let raw_ars = vec![BINARY_NAME, "start", "--grpc-listen", "127.0.0.1:9944"];
+
+        let args = parse_args(raw_ars).unwrap();
+
+        assert_eq!(
+            args.cmd,
+            Command::Start {
+                resume_only: false,
+                grpc_listen: Some("127.0.0.1:9944".parse().unwrap()),
+            }
+        );
+    }
+
     #[test]
     fn ensure_history_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
@@ -387,39 +919,433 @@ mod tests {
             disable_timestamp: false,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::History,
+            cmd: Command::History {
+                since: None,
+                until: None,
+                wide: false,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_history_with_dates_then_whole_days_are_included() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "history",
+            "--since",
+            "2021-11-01",
+            "--until",
+            "2021-11-30",
+        ];
+
+        let args = parse_args(raw_ars).unwrap();
+
+        assert_eq!(
+            args.cmd,
+            Command::History {
+                since: Some(OffsetDateTime::parse("2021-11-01T00:00:00Z", &Rfc3339).unwrap()),
+                until: Some(OffsetDateTime::parse("2021-12-01T00:00:00Z", &Rfc3339).unwrap()),
+                wide: false,
+            }
+        );
+    }
+
+    #[test]
+    fn given_history_with_rfc3339_time_then_time_is_kept() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "history",
+            "--since",
+            "2021-11-01T12:30:00+02:00",
+        ];
+
+        let args = parse_args(raw_ars).unwrap();
+
+        assert_eq!(
+            args.cmd,
+            Command::History {
+                since: Some(OffsetDateTime::parse("2021-11-01T10:30:00Z", &Rfc3339).unwrap()),
+                until: None,
+                wide: false,
+            }
+        );
+    }
+
+    #[test]
+    fn given_history_with_wide_then_wide_set() {
+        let raw_ars = vec![BINARY_NAME, "history", "--wide"];
+
+        let args = parse_args(raw_ars).unwrap();
+
+        assert_eq!(
+            args.cmd,
+            Command::History {
+                since: None,
+                until: None,
+                wide: true,
+            }
+        );
+    }
+
+    #[test]
+    fn given_history_with_empty_time_range_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "history",
+            "--since",
+            "2021-12-01",
+            "--until",
+            "2021-11-01",
+        ];
+
+        assert!(parse_args(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_history_with_invalid_time_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "history", "--since", "01.11.2021"];
+
+        assert!(parse_args(raw_ars).is_err());
+    }
+
+    #[test]
+    fn ensure_prune_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "prune", "--older-than", "7", "--dry-run"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Prune {
+                older_than: Duration::from_secs(7 * 24 * 60 * 60),
+                dry_run: true,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_balance_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "balance"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Balance,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_create_monero_subaddress_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "create-monero-subaddress",
+            "--label",
+            "exchange",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::CreateMoneroSubaddress {
+                label: "exchange".to_owned(),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_monero_subaddresses_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "monero-subaddresses"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::MoneroSubaddresses,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_seller_addresses_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "seller-addresses"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::SellerAddresses,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_label_peer_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+        let peer_id = PeerId::random();
+
+        let raw_ars = vec![
+            BINARY_NAME.to_owned(),
+            "label-peer".to_owned(),
+            "--peer-id".to_owned(),
+            peer_id.to_string(),
+            "--label".to_owned(),
+            "exchange-X".to_owned(),
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::LabelPeer {
+                peer_id,
+                label: "exchange-X".to_owned(),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_empty_peer_label_then_error() {
+        let peer_id = PeerId::random().to_string();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "label-peer",
+            "--peer-id",
+            peer_id.as_str(),
+            "--label",
+            " ",
+        ];
+
+        assert!(parse_args(raw_ars).is_err());
+    }
+
+    #[test]
+    fn ensure_peers_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "peers"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Peers,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_peer_id_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "peer-id"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::PeerId,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_config_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "config"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Config,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_config_validate_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "config", "validate"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::ValidateConfig,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_config_show_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "config", "show"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::ShowConfig,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_check_reachability_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "check-reachability"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::CheckReachability,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_show_xmr_proof_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "show-xmr-proof", "--swap-id", SWAP_ID];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::ShowXmrProof {
+                swap_id: Uuid::parse_str(SWAP_ID).unwrap(),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_withdraw_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+        let raw_ars = vec![
+            BINARY_NAME,
+            "withdraw-btc",
+            "--address",
+            BITCOIN_MAINNET_ADDRESS,
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::WithdrawBtc {
+                amount: None,
+                address: Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(),
+                dry_run: false,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
     }
 
     #[test]
-    fn ensure_balance_command_mapping_mainnet() {
+    fn ensure_cancel_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
         let mainnet_env_config = env::Mainnet::get_config();
 
-        let raw_ars = vec![BINARY_NAME, "balance"];
+        let raw_ars = vec![
+            BINARY_NAME,
+            "manual-recovery",
+            "cancel",
+            "--swap-id",
+            SWAP_ID,
+        ];
         let expected_args = Arguments {
             testnet: false,
             json: false,
             disable_timestamp: false,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Balance,
+            cmd: Command::Cancel {
+                swap_id: Uuid::parse_str(SWAP_ID).unwrap(),
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
     }
 
     #[test]
-    fn ensure_withdraw_command_mapping_mainnet() {
+    fn ensure_refund_command_mappin_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
         let mainnet_env_config = env::Mainnet::get_config();
+
         let raw_ars = vec![
             BINARY_NAME,
-            "withdraw-btc",
-            "--address",
-            BITCOIN_MAINNET_ADDRESS,
+            "manual-recovery",
+            "refund",
+            "--swap-id",
+            SWAP_ID,
         ];
         let expected_args = Arguments {
             testnet: false,
@@ -427,9 +1353,8 @@ mod tests {
             disable_timestamp: false,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::WithdrawBtc {
-                amount: None,
-                address: Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(),
+            cmd: Command::Refund {
+                swap_id: Uuid::parse_str(SWAP_ID).unwrap(),
             },
         };
         let args = parse_args(raw_ars).unwrap();
@@ -437,16 +1362,18 @@ mod tests {
     }
 
     #[test]
-    fn ensure_cancel_command_mapping_mainnet() {
+    fn ensure_sweep_refunded_xmr_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
         let mainnet_env_config = env::Mainnet::get_config();
 
         let raw_ars = vec![
             BINARY_NAME,
             "manual-recovery",
-            "cancel",
+            "sweep-refunded-xmr",
             "--swap-id",
             SWAP_ID,
+            "--address",
+            MONERO_MAINNET_ADDRESS,
         ];
         let expected_args = Arguments {
             testnet: false,
@@ -454,8 +1381,9 @@ mod tests {
             disable_timestamp: false,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Cancel {
+            cmd: Command::SweepRefundedXmr {
                 swap_id: Uuid::parse_str(SWAP_ID).unwrap(),
+                address: monero::Address::from_str(MONERO_MAINNET_ADDRESS).unwrap(),
             },
         };
         let args = parse_args(raw_ars).unwrap();
@@ -463,16 +1391,24 @@ mod tests {
     }
 
     #[test]
-    fn ensure_refund_command_mappin_mainnet() {
+    fn ensure_restore_monero_wallet_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
         let mainnet_env_config = env::Mainnet::get_config();
+        let spend_key = format!("01{}", "00".repeat(31));
+        let view_key = format!("02{}", "00".repeat(31));
 
         let raw_ars = vec![
             BINARY_NAME,
             "manual-recovery",
-            "refund",
-            "--swap-id",
-            SWAP_ID,
+            "restore-monero-wallet",
+            "--wallet-name",
+            "recovered",
+            "--spend-key",
+            &spend_key,
+            "--view-key",
+            &view_key,
+            "--restore-height",
+            "2500000",
         ];
         let expected_args = Arguments {
             testnet: false,
@@ -480,14 +1416,78 @@ mod tests {
             disable_timestamp: false,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Refund {
-                swap_id: Uuid::parse_str(SWAP_ID).unwrap(),
+            cmd: Command::RestoreMoneroWallet {
+                wallet_name: "recovered".to_owned(),
+                from: RestoreFrom::Keys(WalletKeys {
+                    spend_key: crate::monero::private_key_from_hex(&spend_key).unwrap(),
+                    view_key: PrivateViewKey::from_str(&view_key).unwrap(),
+                    restore_height: BlockHeight { height: 2_500_000 },
+                }),
             },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn given_restore_monero_wallet_with_swap_id_then_keys_from_swap() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "manual-recovery",
+            "restore-monero-wallet",
+            "--wallet-name",
+            "recovered",
+            "--swap-id",
+            SWAP_ID,
+        ];
+
+        let args = parse_args(raw_ars).unwrap();
+
+        assert_eq!(
+            args.cmd,
+            Command::RestoreMoneroWallet {
+                wallet_name: "recovered".to_owned(),
+                from: RestoreFrom::Swap(Uuid::parse_str(SWAP_ID).unwrap()),
+            }
+        );
+    }
+
+    #[test]
+    fn given_restore_monero_wallet_without_view_key_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "manual-recovery",
+            "restore-monero-wallet",
+            "--wallet-name",
+            "recovered",
+            "--spend-key",
+            "0100000000000000000000000000000000000000000000000000000000000000",
+            "--restore-height",
+            "2500000",
+        ];
+
+        assert!(parse_args(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_restore_monero_wallet_with_invalid_key_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "manual-recovery",
+            "restore-monero-wallet",
+            "--wallet-name",
+            "recovered",
+            "--spend-key",
+            "00",
+            "--view-key",
+            "00",
+            "--restore-height",
+            "2500000",
+        ];
+
+        assert!(parse_args(raw_ars).is_err());
+    }
+
     #[test]
     fn ensure_punish_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
@@ -552,7 +1552,10 @@ mod tests {
             disable_timestamp: false,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                grpc_listen: None,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -570,7 +1573,71 @@ mod tests {
             disable_timestamp: false,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
-            cmd: Command::History,
+            cmd: Command::History {
+                since: None,
+                until: None,
+                wide: false,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_prune_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "--testnet", "prune"];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::Prune {
+                older_than: Duration::from_secs(30 * 24 * 60 * 60),
+                dry_run: false,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_compact_database_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "compact-database"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::CompactDatabase,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_import_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "import", "--file", "backup.json", "--force"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Import {
+                file: PathBuf::from("backup.json"),
+                force: true,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -594,6 +1661,96 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_seller_addresses_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "--testnet", "seller-addresses"];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::SellerAddresses,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_peer_id_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "--testnet", "peer-id"];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::PeerId,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_rotate_identity_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "rotate-identity"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::RotateIdentity,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_identity_status_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "--testnet", "identity-status"];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::IdentityStatus,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_check_reachability_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "--testnet", "check-reachability"];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::CheckReachability,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
     #[test]
     fn ensure_withdraw_command_mapping_testnet() {
         let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
@@ -615,6 +1772,36 @@ mod tests {
             cmd: Command::WithdrawBtc {
                 amount: None,
                 address: Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(),
+                dry_run: false,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_withdraw_dry_run_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "withdraw-btc",
+            "--address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--dry-run",
+        ];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::WithdrawBtc {
+                amount: None,
+                address: Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(),
+                dry_run: true,
             },
         };
         let args = parse_args(raw_ars).unwrap();
@@ -674,6 +1861,36 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_sweep_refunded_xmr_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "manual-recovery",
+            "sweep-refunded-xmr",
+            "--swap-id",
+            SWAP_ID,
+            "--address",
+            MONERO_STAGENET_ADDRESS,
+        ];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::SweepRefundedXmr {
+                swap_id: Uuid::parse_str(SWAP_ID).unwrap(),
+                address: monero::Address::from_str(MONERO_STAGENET_ADDRESS).unwrap(),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
     #[test]
     fn ensure_punish_command_mapping_testnet() {
         let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
@@ -740,7 +1957,10 @@ mod tests {
             disable_timestamp: true,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                grpc_listen: None,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -785,4 +2005,23 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn given_monero_address_network_mismatch_then_error() {
+        let error = monero_address(
+            monero::Address::from_str(MONERO_MAINNET_ADDRESS).unwrap(),
+            true,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error
+                .downcast_ref::<MoneroAddressNetworkMismatch>()
+                .unwrap(),
+            &MoneroAddressNetworkMismatch {
+                expected: monero::Network::Stagenet,
+                actual: monero::Network::Mainnet
+            }
+        );
+    }
 }
@@ -0,0 +1,678 @@
+use crate::asb::config::{Config, PriceSource};
+use crate::asb::MakerParams;
+use crate::env;
+use crate::libp2p_ext::MultiAddrExt;
+use crate::monero::daemon::Daemon;
+use libp2p::core::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The ASB would fail to start or misbehave with this setting.
+    Error,
+    /// The ASB starts, but the setting is likely a mistake.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// The setting the finding is about, e.g. `network.listen`.
+    pub field: String,
+    pub message: String,
+}
+
+/// The result of validating a config file without starting the ASB.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /// A report with a single error, e.g. because the config file could not be
+    /// parsed at all.
+    pub fn failed(field: &str, message: impl Into<String>) -> Self {
+        let mut report = Report::default();
+        report.error(field, message);
+
+        report
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors() > 0
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    }
+
+    fn error(&mut self, field: &str, message: impl Into<String>) {
+        self.push(Severity::Error, field, message);
+    }
+
+    fn warning(&mut self, field: &str, message: impl Into<String>) {
+        self.push(Severity::Warning, field, message);
+    }
+
+    fn push(&mut self, severity: Severity, field: &str, message: impl Into<String>) {
+        self.findings.push(Finding {
+            severity,
+            field: field.to_owned(),
+            message: message.into(),
+        });
+    }
+}
+
+/// Checks the invariants of `config` that parsing does not enforce.
+///
+/// Nothing is started or connected to, the only side effect is a probe file
+/// that is created and removed again to check that the data directory is
+/// writable.
+pub fn validate(config: &Config, env_config: &env::Config) -> Report {
+    let mut report = Report::default();
+
+    validate_data_dir(&mut report, &config.data.dir);
+    validate_network(&mut report, config);
+    validate_bitcoin(&mut report, config, env_config);
+    validate_monero(&mut report, config, env_config);
+
+    if config.tor.control_port == config.tor.socks5_port {
+        report.error(
+            "tor",
+            format!(
+                "control_port and socks5_port must differ, both are {}",
+                config.tor.socks5_port
+            ),
+        );
+    }
+
+    validate_maker(&mut report, config);
+    validate_telemetry(&mut report, config);
+
+    report
+}
+
+fn validate_data_dir(report: &mut Report, dir: &Path) {
+    const FIELD: &str = "data.dir";
+
+    if dir.as_os_str().is_empty() {
+        report.error(FIELD, "The data directory must not be empty");
+        return;
+    }
+
+    if dir.exists() && !dir.is_dir() {
+        report.error(FIELD, format!("{} is not a directory", dir.display()));
+        return;
+    }
+
+    // The ASB creates missing directories on startup, so the closest existing
+    // ancestor has to be writable.
+    let existing = dir
+        .ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .or_else(|| Some(Path::new(".")).filter(|_| dir.is_relative()));
+    let existing = match existing {
+        Some(existing) => existing,
+        None => {
+            report.error(
+                FIELD,
+                format!("No parent directory of {} exists", dir.display()),
+            );
+            return;
+        }
+    };
+
+    if existing != dir {
+        report.warning(
+            FIELD,
+            format!(
+                "{} does not exist yet and will be created on startup",
+                dir.display()
+            ),
+        );
+    }
+
+    let probe = existing.join(".asb-config-validate");
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+        }
+        Err(error) => report.error(
+            FIELD,
+            format!("{} is not writable: {}", existing.display(), error),
+        ),
+    }
+}
+
+fn validate_network(report: &mut Report, config: &Config) {
+    let network = &config.network;
+
+    if network.listen.is_empty() {
+        report.error(
+            "network.listen",
+            "At least one listen address is required to accept swaps",
+        );
+    }
+
+    for address in &network.listen {
+        if !address
+            .iter()
+            .any(|protocol| matches!(protocol, Protocol::Tcp(_)))
+        {
+            report.error(
+                "network.listen",
+                format!(
+                    "{} is not a TCP address, the ASB only listens on TCP",
+                    address
+                ),
+            );
+        }
+    }
+
+    if let Some(rendezvous_point) = &network.rendezvous_point {
+        if rendezvous_point.extract_peer_id().is_none() {
+            report.error(
+                "network.rendezvous_point",
+                format!(
+                    "{} must end with the peer id of the rendezvous point, i.e. /p2p/<peer-id>",
+                    rendezvous_point
+                ),
+            );
+        }
+    }
+
+    for address in &network.external_addresses {
+        if let Some(reason) = unreachable_reason(address) {
+            report.warning(
+                "network.external_addresses",
+                format!("{} is advertised to takers but {}", address, reason),
+            );
+        }
+    }
+
+    if network.listen_backlog == Some(0) {
+        report.error(
+            "network.listen_backlog",
+            "The listen backlog must be greater than 0",
+        );
+    }
+}
+
+/// Why takers most likely cannot reach an advertised `address`.
+fn unreachable_reason(address: &Multiaddr) -> Option<&'static str> {
+    address.iter().find_map(|protocol| match protocol {
+        Protocol::Ip4(ip) if ip.is_unspecified() => Some("is an unspecified address"),
+        Protocol::Ip4(ip) if ip.is_loopback() => Some("is a loopback address"),
+        Protocol::Ip4(ip) if ip.is_private() || ip.is_link_local() => Some("is a private address"),
+        Protocol::Ip6(ip) if ip.is_unspecified() => Some("is an unspecified address"),
+        Protocol::Ip6(ip) if ip.is_loopback() => Some("is a loopback address"),
+        _ => None,
+    })
+}
+
+fn validate_bitcoin(report: &mut Report, config: &Config, env_config: &env::Config) {
+    let bitcoin = &config.bitcoin;
+
+    if bitcoin.network != env_config.bitcoin_network {
+        report.error(
+            "bitcoin.network",
+            format!(
+                "Expected {:?} but the config file has {:?}",
+                env_config.bitcoin_network, bitcoin.network
+            ),
+        );
+    }
+
+    match &bitcoin.bitcoind {
+        None => validate_url(
+            report,
+            "bitcoin.electrum_rpc_url",
+            &bitcoin.electrum_rpc_url,
+            &["tcp", "ssl"],
+        ),
+        Some(bitcoind) => {
+            validate_url(
+                report,
+                "bitcoin.bitcoind.rpc_url",
+                &bitcoind.rpc_url,
+                &["http", "https"],
+            );
+
+            if let Some(cookie_file) = &bitcoind.cookie_file {
+                if !cookie_file.is_file() {
+                    report.warning(
+                        "bitcoin.bitcoind.cookie_file",
+                        format!(
+                            "{} does not exist, bitcoind writes it once it is running",
+                            cookie_file.display()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    if let Err(error) = bitcoin.backend("asb") {
+        report.error("bitcoin.bitcoind", format!("{:#}", error));
+    }
+
+    if bitcoin.target_block == 0 {
+        report.error(
+            "bitcoin.target_block",
+            "The target block must be at least 1",
+        );
+    }
+
+    if bitcoin.finality_confirmations == Some(0) {
+        report.error(
+            "bitcoin.finality_confirmations",
+            "At least 1 confirmation is required for finality",
+        );
+    }
+
+    if let Some(fee_rate) = bitcoin.fallback_fee_sat_per_vb {
+        if fee_rate.is_nan() || fee_rate <= 0.0 {
+            report.error(
+                "bitcoin.fallback_fee_sat_per_vb",
+                format!(
+                    "The fallback fee rate must be greater than 0, got {}",
+                    fee_rate
+                ),
+            );
+        }
+    }
+
+    if let Err(error) = bitcoin.confirmation_quorum() {
+        report.error("bitcoin.electrum_quorum", format!("{:#}", error));
+    }
+    for (index, url) in bitcoin.electrum_quorum_urls.iter().enumerate() {
+        validate_url(
+            report,
+            &format!("bitcoin.electrum_quorum_urls[{}]", index),
+            url,
+            &["tcp", "ssl"],
+        );
+    }
+
+    if let Err(error) = bitcoin.fee_bump_schedule() {
+        report.error("bitcoin.fee_bump_after_blocks", format!("{:#}", error));
+    }
+
+    if let Some(external_signer) = &bitcoin.external_signer {
+        validate_url(
+            report,
+            "bitcoin.external_signer.url",
+            &external_signer.url,
+            &["http", "https"],
+        );
+    }
+}
+
+fn validate_monero(report: &mut Report, config: &Config, env_config: &env::Config) {
+    let monero = &config.monero;
+
+    if monero.network != env_config.monero_network {
+        report.error(
+            "monero.network",
+            format!(
+                "Expected {:?} but the config file has {:?}",
+                env_config.monero_network, monero.network
+            ),
+        );
+    }
+
+    validate_url(
+        report,
+        "monero.wallet_rpc_url",
+        &monero.wallet_rpc_url,
+        &["http", "https"],
+    );
+
+    for url in &monero.swap_wallet_rpc_urls {
+        validate_url(
+            report,
+            "monero.swap_wallet_rpc_urls",
+            url,
+            &["http", "https"],
+        );
+
+        if url == &monero.wallet_rpc_url {
+            report.error(
+                "monero.swap_wallet_rpc_urls",
+                format!("{} is the monero-wallet-rpc of the main wallet", url),
+            );
+        }
+    }
+
+    if monero.finality_confirmations == Some(0) {
+        report.error(
+            "monero.finality_confirmations",
+            "At least 1 confirmation is required for finality",
+        );
+    }
+
+    if monero.daemons.is_empty() {
+        return;
+    }
+
+    for daemon in &monero.daemons {
+        if let Err(error) = daemon
+            .address
+            .parse()
+            .and_then(|address| Daemon::new(address, monero.network))
+        {
+            report.error("monero.daemons", format!("{:#}", error));
+        }
+    }
+
+    let primaries = monero
+        .daemons
+        .iter()
+        .filter(|daemon| daemon.primary)
+        .count();
+    if primaries != 1 {
+        report.error(
+            "monero.daemons",
+            format!(
+                "Exactly one daemon has to be the primary, got {}",
+                primaries
+            ),
+        );
+    }
+}
+
+fn validate_maker(report: &mut Report, config: &Config) {
+    let maker = &config.maker;
+
+    if let Err(error) = MakerParams::from(maker).validate() {
+        report.error("maker", format!("{:#}", error));
+    }
+
+    if let Err(error) = maker.validate_price_source() {
+        report.error("maker.fixed_rate_btc", format!("{:#}", error));
+    }
+
+    match maker.price_source {
+        PriceSource::Kraken => validate_url(
+            report,
+            "maker.price_ticker_ws_url",
+            &maker.price_ticker_ws_url,
+            &["ws", "wss"],
+        ),
+        PriceSource::Fixed => {}
+    }
+
+    if maker.min_buy_btc == bitcoin::Amount::ZERO {
+        report.warning(
+            "maker.min_buy_btc",
+            "A minimum of 0 accepts swaps that are not worth their fees",
+        );
+    }
+
+    if maker.swap_restart_attempts.unwrap_or(0) > 0 && maker.swap_restart_backoff_secs == Some(0) {
+        report.warning(
+            "maker.swap_restart_backoff_secs",
+            "Restarting failed swaps without a backoff retries them in a tight loop",
+        );
+    }
+}
+
+fn validate_telemetry(report: &mut Report, config: &Config) {
+    let telemetry = match &config.telemetry {
+        Some(telemetry) => telemetry,
+        None => return,
+    };
+
+    validate_url(
+        report,
+        "telemetry.collector_url",
+        &telemetry.collector_url,
+        &["http", "https"],
+    );
+
+    if telemetry.interval_secs == Some(0) {
+        report.error(
+            "telemetry.interval_secs",
+            "The interval between telemetry reports must not be 0",
+        );
+    }
+}
+
+fn validate_url(report: &mut Report, field: &str, url: &Url, schemes: &[&str]) {
+    if !schemes.contains(&url.scheme()) {
+        report.error(
+            field,
+            format!(
+                "{} has scheme {} but one of {} is required",
+                url,
+                url.scheme(),
+                schemes.join(", ")
+            ),
+        );
+    }
+
+    if url.host_str().map_or(true, str::is_empty) {
+        report.error(field, format!("{} has no host", url));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::config::{
+        Bitcoin, Data, GetDefaults, Maker, Monero, MoneroDaemon, Network, Telemetry,
+    };
+    use crate::env::{GetConfig, Mainnet};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+    use tempfile::tempdir;
+
+    #[test]
+    fn default_config_is_valid() {
+        let data_dir = tempdir().unwrap();
+
+        let report = validate(&mainnet_config(data_dir.path()), &Mainnet::get_config());
+
+        assert_eq!(report, Report::default());
+    }
+
+    #[test]
+    fn violated_invariants_are_reported_as_errors() {
+        let data_dir = tempdir().unwrap();
+        let mut config = mainnet_config(data_dir.path());
+        config.network.listen = vec![];
+        config.bitcoin.electrum_rpc_url = Url::parse("http://blockstream.info:700").unwrap();
+        config.maker.min_buy_btc = bitcoin::Amount::from_btc(1.0).unwrap();
+
+        let report = validate(&config, &Mainnet::get_config());
+
+        let fields = report
+            .findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+            .map(|finding| finding.field.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec!["network.listen", "bitcoin.electrum_rpc_url", "maker"]
+        );
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn telemetry_requires_http_collector_and_interval() {
+        let data_dir = tempdir().unwrap();
+        let mut config = mainnet_config(data_dir.path());
+        config.telemetry = Some(Telemetry {
+            enabled: true,
+            collector_url: Url::parse("ftp://stats.example.com").unwrap(),
+            interval_secs: Some(0),
+        });
+
+        let report = validate(&config, &Mainnet::get_config());
+
+        let fields = report
+            .findings
+            .iter()
+            .map(|finding| finding.field.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec!["telemetry.collector_url", "telemetry.interval_secs"]
+        );
+    }
+
+    #[test]
+    fn unreachable_external_address_is_a_warning() {
+        let data_dir = tempdir().unwrap();
+        let mut config = mainnet_config(data_dir.path());
+        config.network.external_addresses =
+            vec![Multiaddr::from_str("/ip4/192.168.1.10/tcp/9939").unwrap()];
+
+        let report = validate(&config, &Mainnet::get_config());
+
+        assert_eq!(report.warnings(), 1);
+        assert!(!report.has_errors());
+    }
+
+    #[test]
+    fn daemon_pool_without_primary_is_an_error() {
+        let data_dir = tempdir().unwrap();
+        let mut config = mainnet_config(data_dir.path());
+        config.monero.daemons = vec![
+            MoneroDaemon {
+                address: "node.melo.tools:18081".to_owned(),
+                primary: false,
+            },
+            MoneroDaemon {
+                address: "xmr-node.cakewallet.com:18081".to_owned(),
+                primary: false,
+            },
+        ];
+
+        let report = validate(&config, &Mainnet::get_config());
+
+        assert_eq!(report.errors(), 1);
+        assert_eq!(report.findings[0].field, "monero.daemons");
+    }
+
+    #[test]
+    fn main_wallet_rpc_as_swap_wallet_rpc_is_an_error() {
+        let data_dir = tempdir().unwrap();
+        let mut config = mainnet_config(data_dir.path());
+        config.monero.swap_wallet_rpc_urls = vec![
+            Url::parse("http://127.0.0.1:18084/json_rpc").unwrap(),
+            config.monero.wallet_rpc_url.clone(),
+        ];
+
+        let report = validate(&config, &Mainnet::get_config());
+
+        assert_eq!(report.errors(), 1);
+        assert_eq!(report.findings[0].field, "monero.swap_wallet_rpc_urls");
+    }
+
+    #[test]
+    fn electrum_quorum_above_configured_servers_is_an_error() {
+        let data_dir = tempdir().unwrap();
+        let mut config = mainnet_config(data_dir.path());
+        config.bitcoin.electrum_quorum_urls =
+            vec![Url::parse("ssl://electrum.emzy.de:50002").unwrap()];
+        config.bitcoin.electrum_quorum = Some(3);
+
+        let report = validate(&config, &Mainnet::get_config());
+
+        assert_eq!(report.errors(), 1);
+        assert_eq!(report.findings[0].field, "bitcoin.electrum_quorum");
+    }
+
+    fn mainnet_config(data_dir: &Path) -> Config {
+        let defaults = Mainnet::getConfigFileDefaults().unwrap();
+
+        Config {
+            data: Data {
+                dir: data_dir.to_path_buf(),
+                max_size_mb: None,
+            },
+            network: Network {
+                listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                rendezvous_point: None,
+                external_addresses: vec![],
+                listen_backlog: None,
+            },
+            bitcoin: Bitcoin {
+                electrum_rpc_url: defaults.electrum_rpc_url,
+                target_block: defaults.bitcoin_confirmation_target,
+                finality_confirmations: None,
+                electrum_reconnect_attempts: None,
+                electrum_connect_timeout_secs: None,
+                fallback_fee_sat_per_vb: None,
+                change_address_type: None,
+                status_cache_ttl_secs: None,
+                electrum_quorum_urls: vec![],
+                electrum_quorum: None,
+                network: bitcoin::Network::Bitcoin,
+                fee_bump_after_blocks: None,
+                max_fee_btc: None,
+                external_signer: None,
+                bitcoind: None,
+            },
+            monero: Monero {
+                wallet_rpc_url: defaults.monero_wallet_rpc_url,
+                finality_confirmations: None,
+                network: monero::Network::Mainnet,
+                refresh_retries: None,
+                refresh_backoff_secs: None,
+                account_index: None,
+                daemons: vec![],
+                log_lock_confirmations: None,
+                swap_wallet_rpc_urls: vec![],
+            },
+            tor: Default::default(),
+            maker: Maker {
+                min_buy_btc: bitcoin::Amount::from_btc(0.002).unwrap(),
+                max_buy_btc: bitcoin::Amount::from_btc(0.02).unwrap(),
+                ask_spread: Decimal::from_str("0.02").unwrap(),
+                price_ticker_ws_url: defaults.price_ticker_ws_url,
+                price_source: PriceSource::Kraken,
+                fixed_rate_btc: None,
+                redeem_fee_margin: None,
+                counterparty_silence_timeout_secs: None,
+                swap_restart_attempts: None,
+                swap_restart_backoff_secs: None,
+                punish_grace_blocks: None,
+                lower_max_buy_to_balance: None,
+            },
+            telemetry: None,
+        }
+    }
+}
@@ -1,11 +1,12 @@
-use crate::asb::{Behaviour, OutEvent, Rate};
-use crate::network::quote::BidQuote;
+use crate::asb::{Behaviour, MakerParamsUpdates, OutEvent, Rate};
+use crate::clock::SystemClock;
+use crate::network::quote::{self, BidQuote};
 use crate::network::swap_setup::alice::WalletSnapshot;
 use crate::network::transfer_proof;
 use crate::protocol::alice::{AliceState, State3, Swap};
 use crate::protocol::{Database, State};
 use crate::{bitcoin, env, kraken, monero};
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use futures::future;
 use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
@@ -17,9 +18,28 @@ use std::collections::HashMap;
 use std::convert::{Infallible, TryInto};
 use std::fmt::Debug;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
+/// The unfinished swaps an [`EventLoop`] resumes on startup.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwapSelection {
+    All,
+    Only(Vec<Uuid>),
+    Except(Vec<Uuid>),
+}
+
+impl SwapSelection {
+    fn contains(&self, swap_id: Uuid) -> bool {
+        match self {
+            SwapSelection::All => true,
+            SwapSelection::Only(swaps) => swaps.contains(&swap_id),
+            SwapSelection::Except(swaps) => !swaps.contains(&swap_id),
+        }
+    }
+}
+
 /// A future that resolves to a tuple of `PeerId`, `transfer_proof::Request` and
 /// `Responder`.
 ///
@@ -41,11 +61,16 @@ where
     monero_wallet: Arc<monero::Wallet>,
     db: Arc<dyn Database + Send + Sync>,
     latest_rate: LR,
-    min_buy: bitcoin::Amount,
-    max_buy: bitcoin::Amount,
+    maker_params: MakerParamsUpdates,
 
     swap_sender: mpsc::Sender<Swap>,
 
+    /// Requests to resume a swap that stopped, see [`SwapResumer`].
+    resume_swap: MpscChannels<(Uuid, oneshot::Sender<Result<Swap>>)>,
+
+    /// The unfinished swaps that are resumed on startup.
+    resumed_swaps: SwapSelection,
+
     /// Stores incoming [`EncryptedSignature`]s per swap.
     recv_encrypted_signature: HashMap<Uuid, bmrng::RequestSender<bitcoin::EncryptedSignature, ()>>,
     inflight_encrypted_signatures: FuturesUnordered<BoxFuture<'static, ResponseChannel<()>>>,
@@ -73,8 +98,7 @@ where
         monero_wallet: Arc<monero::Wallet>,
         db: Arc<dyn Database + Send + Sync>,
         latest_rate: LR,
-        min_buy: bitcoin::Amount,
-        max_buy: bitcoin::Amount,
+        maker_params: MakerParamsUpdates,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
 
@@ -86,8 +110,9 @@ where
             db,
             latest_rate,
             swap_sender: swap_channel.sender,
-            min_buy,
-            max_buy,
+            resume_swap: Default::default(),
+            resumed_swaps: SwapSelection::All,
+            maker_params,
             recv_encrypted_signature: Default::default(),
             inflight_encrypted_signatures: Default::default(),
             send_transfer_proof: Default::default(),
@@ -97,10 +122,24 @@ where
         Ok((event_loop, swap_channel.receiver))
     }
 
+    /// Only resumes the selected unfinished swaps on startup instead of all of
+    /// them, e.g. to split the swaps between the event loops of two network
+    /// identities.
+    pub fn with_resumed_swaps(mut self, swaps: SwapSelection) -> Self {
+        self.resumed_swaps = swaps;
+        self
+    }
+
     pub fn peer_id(&self) -> PeerId {
         *Swarm::local_peer_id(&self.swarm)
     }
 
+    pub fn swap_resumer(&self) -> SwapResumer {
+        SwapResumer {
+            sender: self.resume_swap.sender.clone(),
+        }
+    }
+
     pub async fn run(mut self) {
         // ensure that these streams are NEVER empty, otherwise it will
         // terminate forever.
@@ -118,7 +157,9 @@ where
 
         let unfinished_swaps = swaps
             .into_iter()
-            .filter(|(_swap_id, state)| !state.swap_finished())
+            .filter(|(swap_id, state)| {
+                !state.swap_finished() && self.resumed_swaps.contains(*swap_id)
+            })
             .collect::<Vec<(Uuid, State)>>();
 
         for (swap_id, state) in unfinished_swaps {
@@ -138,6 +179,7 @@ where
                 monero_wallet: self.monero_wallet.clone(),
                 env_config: self.env_config,
                 db: self.db.clone(),
+                clock: Arc::new(SystemClock),
                 state: state.try_into().expect("Alice state loaded from db"),
                 swap_id,
             };
@@ -182,7 +224,7 @@ where
                             tracing::warn!(%peer, "Ignoring spot price request: {}", error);
                         }
                         SwarmEvent::Behaviour(OutEvent::QuoteRequested { channel, peer }) => {
-                            let quote = match self.make_quote(self.min_buy, self.max_buy).await {
+                            let quote = match self.make_quote().await {
                                 Ok(quote) => quote,
                                 Err(error) => {
                                     tracing::warn!(%peer, "Failed to make quote: {:#}", error);
@@ -310,15 +352,15 @@ where
                 Some(response_channel) = self.inflight_encrypted_signatures.next() => {
                     let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
                 }
+                Some((swap_id, responder)) = self.resume_swap.receiver.recv() => {
+                    let _ = responder.send(self.resume(swap_id).await);
+                }
             }
         }
     }
 
-    async fn make_quote(
-        &mut self,
-        min_buy: bitcoin::Amount,
-        max_buy: bitcoin::Amount,
-    ) -> Result<BidQuote> {
+    async fn make_quote(&mut self) -> Result<BidQuote> {
+        let maker_params = self.maker_params.latest();
         let rate = self
             .latest_rate
             .latest_rate()
@@ -326,8 +368,9 @@ where
 
         Ok(BidQuote {
             price: rate.ask().context("Failed to compute asking price")?,
-            min_quantity: min_buy,
-            max_quantity: max_buy,
+            min_quantity: maker_params.min_buy,
+            max_quantity: maker_params.max_buy,
+            quote_expiry: Some(quote::quote_expiry(unix_timestamp()?)),
         })
     }
 
@@ -349,6 +392,7 @@ where
             monero_wallet: self.monero_wallet.clone(),
             env_config: self.env_config,
             db: self.db.clone(),
+            clock: Arc::new(SystemClock),
             state: initial_state,
             swap_id,
         };
@@ -358,6 +402,13 @@ where
         // swaps save peer id so we can resume
         match self.db.insert_peer_id(swap_id, bob_peer_id).await {
             Ok(_) => {
+                // The redeem fee was estimated for the current target block,
+                // escalating it after a restart starts from there as well.
+                let fee_strategy = bitcoin::FeeStrategy::new(self.bitcoin_wallet.target_block());
+                if let Err(error) = self.db.insert_fee_strategy(swap_id, fee_strategy).await {
+                    tracing::warn!(%swap_id, "Unable to save fee strategy in database: {}", error);
+                }
+
                 if let Err(error) = self.swap_sender.send(swap).await {
                     tracing::warn!(%swap_id, "Failed to start swap: {}", error);
                 }
@@ -368,6 +419,31 @@ where
         }
     }
 
+    /// Loads the latest state of the swap from the database and sets up a new
+    /// [`EventLoopHandle`] for it, which replaces the handle of the stopped
+    /// swap.
+    async fn resume(&mut self, swap_id: Uuid) -> Result<Swap> {
+        let state = self.db.get_state(swap_id).await?;
+        let peer_id = self.db.get_peer_id(swap_id).await?;
+
+        if state.swap_finished() {
+            bail!("Swap {} is already finished", swap_id);
+        }
+
+        let handle = self.new_handle(peer_id, swap_id);
+
+        Ok(Swap {
+            event_loop_handle: handle,
+            bitcoin_wallet: self.bitcoin_wallet.clone(),
+            monero_wallet: self.monero_wallet.clone(),
+            env_config: self.env_config,
+            db: self.db.clone(),
+            clock: Arc::new(SystemClock),
+            state: state.try_into()?,
+            swap_id,
+        })
+    }
+
     /// Create a new [`EventLoopHandle`] that is scoped for communication with
     /// the given peer.
     fn new_handle(&mut self, peer: PeerId, swap_id: Uuid) -> EventLoopHandle {
@@ -435,18 +511,18 @@ impl LatestRate for FixedRate {
     }
 }
 
-/// Produces [`Rate`]s based on [`PriceUpdate`]s from kraken and a configured
-/// spread.
+/// Produces [`Rate`]s based on [`PriceUpdate`]s from kraken and the currently
+/// configured spread.
 #[derive(Debug, Clone)]
 pub struct KrakenRate {
-    ask_spread: Decimal,
+    maker_params: MakerParamsUpdates,
     price_updates: kraken::PriceUpdates,
 }
 
 impl KrakenRate {
-    pub fn new(ask_spread: Decimal, price_updates: kraken::PriceUpdates) -> Self {
+    pub fn new(maker_params: MakerParamsUpdates, price_updates: kraken::PriceUpdates) -> Self {
         Self {
-            ask_spread,
+            maker_params,
             price_updates,
         }
     }
@@ -457,7 +533,64 @@ impl LatestRate for KrakenRate {
 
     fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
         let update = self.price_updates.latest_update()?;
-        let rate = Rate::new(update.ask, self.ask_spread);
+        let rate = Rate::new(update.ask, self.maker_params.latest().ask_spread);
+
+        Ok(rate)
+    }
+}
+
+/// Produces [`Rate`]s based on the fixed rate and spread in the currently
+/// configured maker parameters, without consulting any price feed.
+#[derive(Debug, Clone)]
+pub struct ManualRate {
+    maker_params: MakerParamsUpdates,
+}
+
+impl ManualRate {
+    pub fn new(maker_params: MakerParamsUpdates) -> Self {
+        Self { maker_params }
+    }
+}
+
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+#[error("No fixed rate configured")]
+pub struct NoFixedRate;
+
+impl LatestRate for ManualRate {
+    type Error = NoFixedRate;
+
+    fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
+        let maker_params = self.maker_params.latest();
+        let ask = maker_params.fixed_rate.ok_or(NoFixedRate)?;
+
+        Ok(Rate::new(ask, maker_params.ask_spread))
+    }
+}
+
+/// The [`LatestRate`] of the configured
+/// [`PriceSource`](crate::asb::config::PriceSource).
+#[derive(Debug, Clone)]
+pub enum RateProvider {
+    Kraken(KrakenRate),
+    Manual(ManualRate),
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum RateProviderError {
+    #[error(transparent)]
+    Kraken(#[from] kraken::Error),
+    #[error(transparent)]
+    Manual(#[from] NoFixedRate),
+}
+
+impl LatestRate for RateProvider {
+    type Error = RateProviderError;
+
+    fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
+        let rate = match self {
+            RateProvider::Kraken(rate) => rate.latest_rate()?,
+            RateProvider::Manual(rate) => rate.latest_rate()?,
+        };
 
         Ok(rate)
     }
@@ -497,6 +630,28 @@ impl EventLoopHandle {
     }
 }
 
+/// Resumes swaps of the [`EventLoop`] that stopped, e.g. because they failed.
+#[derive(Debug, Clone)]
+pub struct SwapResumer {
+    sender: mpsc::Sender<(Uuid, oneshot::Sender<Result<Swap>>)>,
+}
+
+impl SwapResumer {
+    /// Returns the swap in its latest saved state with a new
+    /// [`EventLoopHandle`], the handle of the stopped swap must not be used
+    /// anymore.
+    pub async fn resume(&self, swap_id: Uuid) -> Result<Swap> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.sender
+            .send((swap_id, sender))
+            .await
+            .map_err(|_| anyhow!("Event loop is not running"))?;
+
+        receiver.await.context("Event loop stopped")?
+    }
+}
+
 #[allow(missing_debug_implementations)]
 struct MpscChannels<T> {
     sender: mpsc::Sender<T>,
@@ -509,3 +664,63 @@ impl<T> Default for MpscChannels<T> {
         MpscChannels { sender, receiver }
     }
 }
+
+fn unix_timestamp() -> Result<u64> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System time is before the Unix epoch")?;
+
+    Ok(timestamp.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::{maker_params, MakerParams};
+
+    fn params(fixed_rate: Option<u64>) -> MakerParams {
+        MakerParams {
+            min_buy: bitcoin::Amount::ZERO,
+            max_buy: bitcoin::Amount::from_sat(u64::MAX),
+            ask_spread: Decimal::new(2, 2),
+            fixed_rate: fixed_rate.map(bitcoin::Amount::from_sat),
+            redeem_fee_margin: Decimal::from(0u64),
+        }
+    }
+
+    #[test]
+    fn manual_rate_applies_spread_to_adjusted_fixed_rate() {
+        let (updater, updates) = maker_params::channel(params(Some(1_000_000)));
+        let mut rate = ManualRate::new(updates);
+
+        let ask = rate.latest_rate().unwrap().ask().unwrap();
+        assert_eq!(ask, bitcoin::Amount::from_sat(1_020_000));
+
+        updater.update(params(Some(2_000_000))).unwrap();
+
+        let ask = rate.latest_rate().unwrap().ask().unwrap();
+        assert_eq!(ask, bitcoin::Amount::from_sat(2_040_000));
+    }
+
+    #[test]
+    fn manual_rate_without_fixed_rate_fails() {
+        let mut rate = ManualRate::new(MakerParamsUpdates::fixed(params(None)));
+
+        assert!(rate.latest_rate().is_err());
+    }
+
+    #[test]
+    fn swap_selections_split_the_swaps() {
+        let old_swap = Uuid::new_v4();
+        let new_swap = Uuid::new_v4();
+
+        let old_identity = SwapSelection::Only(vec![old_swap]);
+        let new_identity = SwapSelection::Except(vec![old_swap]);
+
+        assert!(old_identity.contains(old_swap));
+        assert!(!old_identity.contains(new_swap));
+        assert!(!new_identity.contains(old_swap));
+        assert!(new_identity.contains(new_swap));
+        assert!(SwapSelection::All.contains(old_swap));
+    }
+}
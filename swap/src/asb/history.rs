@@ -0,0 +1,222 @@
+//! Lists the swap history and renders it for the width of the terminal.
+//!
+//! Swap ids, peer ids and states are long enough for the table to wrap on
+//! narrow terminals. The widest columns are cut with an ellipsis as far as
+//! needed, and if even that does not fit every swap is printed as a block of
+//! `key: value` lines instead. Without a width, e.g. if the output is piped,
+//! nothing is shortened.
+
+use crate::protocol::alice::AliceState;
+use crate::protocol::Database;
+use anyhow::Result;
+use comfy_table::Table;
+use std::convert::TryInto;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const ELLIPSIS: char = '…';
+
+/// A swap of the history.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub swap_id: Uuid,
+    /// The label of the peer, or its peer id if it has none. `None` if the
+    /// peer is unknown.
+    pub peer: Option<String>,
+    pub started_at: Option<OffsetDateTime>,
+    pub state: AliceState,
+}
+
+/// The swaps that started in `[since, until)`.
+///
+/// Swaps without a known start time are always listed, so that a time range
+/// never hides them.
+pub async fn entries(
+    db: &(dyn Database + Send + Sync),
+    since: Option<OffsetDateTime>,
+    until: Option<OffsetDateTime>,
+) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+
+    for (swap_id, state) in db.all().await? {
+        let started_at = db.get_started_at(swap_id).await?;
+        if let Some(started_at) = started_at {
+            if since.map_or(false, |since| started_at < since)
+                || until.map_or(false, |until| started_at >= until)
+            {
+                continue;
+            }
+        }
+
+        let state: AliceState = state.try_into()?;
+        let peer = match db.get_peer_id(swap_id).await {
+            Ok(peer_id) => Some(
+                db.get_peer_label(peer_id)
+                    .await?
+                    .unwrap_or_else(|| peer_id.to_string()),
+            ),
+            Err(_) => None,
+        };
+
+        entries.push(Entry {
+            swap_id,
+            peer,
+            started_at,
+            state,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Columns are not cut below this many characters, including the ellipsis.
+const MIN_COLUMN_WIDTH: usize = 8;
+
+/// Renders the rows as a table that fits into `width` characters, or as
+/// `key: value` blocks if it cannot fit. Renders the full table if no
+/// `width` is given.
+pub fn render(header: &[&str], rows: &[Vec<String>], width: Option<usize>) -> String {
+    let width = match width {
+        Some(width) => width,
+        None => return table(header, rows),
+    };
+
+    let widths = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain(Some(header[column].chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let min_widths = header
+        .iter()
+        .zip(&widths)
+        .map(|(name, width)| (*width).min(MIN_COLUMN_WIDTH.max(name.chars().count())))
+        .collect::<Vec<_>>();
+
+    match shrink(widths, &min_widths, width) {
+        Some(widths) => {
+            let rows = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .zip(&widths)
+                        .map(|(cell, width)| truncate(cell, *width))
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+
+            table(header, &rows)
+        }
+        None => vertical(header, rows),
+    }
+}
+
+fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut table = Table::new();
+
+    table.set_header(header.to_vec());
+    for row in rows {
+        table.add_row(row.clone());
+    }
+
+    table.to_string()
+}
+
+/// The width of a table with bordered columns of the given widths, each
+/// padded by a space on both sides.
+fn table_width(widths: &[usize]) -> usize {
+    widths.iter().map(|width| width + 3).sum::<usize>() + 1
+}
+
+/// Cuts the widest column by one character until the table fits into
+/// `available`, fails if all columns reached their minimum width.
+fn shrink(mut widths: Vec<usize>, min_widths: &[usize], available: usize) -> Option<Vec<usize>> {
+    while table_width(&widths) > available {
+        let (column, _) = widths
+            .iter()
+            .enumerate()
+            .filter(|(column, width)| **width > min_widths[*column])
+            .max_by_key(|(_, width)| **width)?;
+
+        widths[column] -= 1;
+    }
+
+    Some(widths)
+}
+
+fn truncate(cell: &str, width: usize) -> String {
+    if cell.chars().count() <= width {
+        return cell.to_owned();
+    }
+
+    cell.chars()
+        .take(width.saturating_sub(1))
+        .chain(Some(ELLIPSIS))
+        .collect()
+}
+
+fn vertical(header: &[&str], rows: &[Vec<String>]) -> String {
+    rows.iter()
+        .map(|row| {
+            header
+                .iter()
+                .zip(row)
+                .map(|(name, cell)| format!("{}: {}", name, cell))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: [&str; 2] = ["SWAP ID", "STATE"];
+
+    fn rows() -> Vec<Vec<String>> {
+        vec![vec![
+            "ea030832-3be9-454f-bb98-5ea9a788406b".to_owned(),
+            "btc is redeemed".to_owned(),
+        ]]
+    }
+
+    fn max_line_width(rendered: &str) -> usize {
+        rendered
+            .lines()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn table_that_fits_is_not_shortened() {
+        let full = render(&HEADER, &rows(), None);
+
+        assert_eq!(render(&HEADER, &rows(), Some(200)), full);
+        assert!(full.contains("ea030832-3be9-454f-bb98-5ea9a788406b"));
+    }
+
+    #[test]
+    fn widest_column_is_cut_with_ellipsis_to_fit() {
+        let rendered = render(&HEADER, &rows(), Some(40));
+
+        assert!(max_line_width(&rendered) <= 40);
+        assert!(rendered.contains("ea030832-3be9-454…"));
+        assert!(rendered.contains("btc is redeemed"));
+    }
+
+    #[test]
+    fn too_narrow_terminal_gets_key_value_blocks() {
+        let rendered = render(&HEADER, &rows(), Some(20));
+
+        assert_eq!(
+            rendered,
+            "SWAP ID: ea030832-3be9-454f-bb98-5ea9a788406b\nSTATE: btc is redeemed"
+        );
+    }
+}
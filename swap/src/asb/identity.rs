@@ -0,0 +1,217 @@
+//! The network identity of the ASB, i.e. its libp2p peer id and the onion
+//! address of its hidden services, and the rotation of it.
+//!
+//! Both are derived from the seed for a generation that starts at 0 and is
+//! raised by every rotation. Takers that started a swap before a rotation
+//! resume it with the peer id and onion address of the old generation, which
+//! is why the old generation stays active until all of those swaps finished.
+
+use crate::protocol::Database;
+use crate::seed::Seed;
+use anyhow::{bail, Context, Result};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use torut::onion::TorSecretKeyV3;
+use uuid::Uuid;
+
+const FILE_NAME: &str = "identity.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Identity {
+    /// The generation that new swaps are started with.
+    pub generation: u32,
+    /// The previous generation, as long as it is still needed.
+    pub retiring: Option<Retiring>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Retiring {
+    pub generation: u32,
+    /// The swaps that were in-flight when the identity was rotated.
+    pub swaps: Vec<Uuid>,
+}
+
+impl Identity {
+    /// Reads the identity from the data directory, a missing file means the ASB
+    /// never rotated its identity.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(FILE_NAME);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read identity file {}", path.display()))?;
+
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse identity file {}", path.display()))
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        let path = data_dir.join(FILE_NAME);
+        let contents = toml::to_string(self)?;
+
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write identity file {}", path.display()))
+    }
+
+    /// Switches new swaps to the next generation. The current generation is
+    /// retired once the given in-flight swaps finished.
+    ///
+    /// Fails if the previous rotation is not complete yet, i.e. if any of the
+    /// given `pending` swaps of the retiring generation did not finish.
+    pub fn rotate(&mut self, in_flight: Vec<Uuid>, pending: &[Uuid]) -> Result<()> {
+        if let Some(retiring) = &self.retiring {
+            if !pending.is_empty() {
+                bail!(
+                    "Generation {} of the identity still has {} unfinished swap(s), wait for them to finish before rotating the identity again",
+                    retiring.generation,
+                    pending.len()
+                );
+            }
+        }
+
+        let generation = self
+            .generation
+            .checked_add(1)
+            .context("Identity cannot be rotated any further")?;
+
+        self.retiring = if in_flight.is_empty() {
+            None
+        } else {
+            Some(Retiring {
+                generation: self.generation,
+                swaps: in_flight,
+            })
+        };
+        self.generation = generation;
+
+        Ok(())
+    }
+
+    /// Drops the retiring generation, fails if any of its swaps is `pending`.
+    pub fn retire(&mut self, pending: &[Uuid]) -> Result<()> {
+        if !pending.is_empty() {
+            bail!(
+                "Cannot retire the old identity while {} of its swap(s) did not finish",
+                pending.len()
+            );
+        }
+
+        self.retiring = None;
+
+        Ok(())
+    }
+
+    pub fn peer_id(&self, seed: &Seed) -> PeerId {
+        PeerId::from(seed.derive_libp2p_identity_at(self.generation).public())
+    }
+
+    pub fn tor_key(&self, seed: &Seed) -> TorSecretKeyV3 {
+        seed.derive_torv3_key_at(self.generation)
+    }
+}
+
+impl Retiring {
+    pub fn peer_id(&self, seed: &Seed) -> PeerId {
+        PeerId::from(seed.derive_libp2p_identity_at(self.generation).public())
+    }
+
+    pub fn tor_key(&self, seed: &Seed) -> TorSecretKeyV3 {
+        seed.derive_torv3_key_at(self.generation)
+    }
+
+    /// The swaps of this generation that did not finish yet. Swaps that are
+    /// no longer in the database are considered finished.
+    pub async fn pending_swaps(&self, db: &dyn Database) -> Result<Vec<Uuid>> {
+        let unfinished = in_flight_swaps(db).await?;
+
+        Ok(self
+            .swaps
+            .iter()
+            .copied()
+            .filter(|swap_id| unfinished.contains(swap_id))
+            .collect())
+    }
+}
+
+/// The swaps in the database that did not finish yet.
+pub async fn in_flight_swaps(db: &dyn Database) -> Result<Vec<Uuid>> {
+    Ok(db
+        .all()
+        .await?
+        .into_iter()
+        .filter(|(_, state)| !state.swap_finished())
+        .map(|(swap_id, _)| swap_id)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn missing_file_is_the_first_generation() {
+        let data_dir = tempdir().unwrap();
+
+        let identity = Identity::load(data_dir.path()).unwrap();
+
+        assert_eq!(identity, Identity::default());
+        assert_eq!(identity.generation, 0);
+    }
+
+    #[test]
+    fn rotation_retires_current_generation_with_in_flight_swaps() {
+        let data_dir = tempdir().unwrap();
+        let swap_id = Uuid::new_v4();
+        let mut identity = Identity::default();
+
+        identity.rotate(vec![swap_id], &[]).unwrap();
+        identity.save(data_dir.path()).unwrap();
+
+        assert_eq!(
+            Identity::load(data_dir.path()).unwrap(),
+            Identity {
+                generation: 1,
+                retiring: Some(Retiring {
+                    generation: 0,
+                    swaps: vec![swap_id],
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn rotation_without_in_flight_swaps_retires_immediately() {
+        let mut identity = Identity::default();
+
+        identity.rotate(vec![], &[]).unwrap();
+
+        assert_eq!(
+            identity,
+            Identity {
+                generation: 1,
+                retiring: None,
+            }
+        );
+    }
+
+    #[test]
+    fn cannot_rotate_or_retire_while_old_swaps_are_pending() {
+        let swap_id = Uuid::new_v4();
+        let mut identity = Identity::default();
+        identity.rotate(vec![swap_id], &[]).unwrap();
+
+        assert!(identity.rotate(vec![], &[swap_id]).is_err());
+        assert!(identity.retire(&[swap_id]).is_err());
+
+        identity.retire(&[]).unwrap();
+        assert_eq!(identity.retiring, None);
+    }
+}
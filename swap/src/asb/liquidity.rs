@@ -0,0 +1,198 @@
+//! Checks that the Monero balance of the ASB can back the maximum buy amount it
+//! advertises.
+//!
+//! Only the XMR side needs liquidity, the BTC we receive pays for our own
+//! transactions. Swaps that did not lock their XMR yet will still spend from
+//! the unlocked balance, which is why their amounts are reserved.
+
+use crate::asb::{LatestRate, MakerParamsUpdates, MaxBuyCeiling, Rate};
+use crate::protocol::alice::AliceState;
+use crate::protocol::Database;
+use crate::{bitcoin, monero};
+use anyhow::{Context, Result};
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the balance is compared with the advertised maximum.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The XMR that the ASB can sell in a new swap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Liquidity {
+    /// The balance of the Monero wallet that is not locked.
+    pub unlocked: monero::Amount,
+    /// The XMR that in-flight swaps still have to lock.
+    pub reserved_for_swaps: monero::Amount,
+}
+
+impl Liquidity {
+    pub async fn capture(
+        monero_wallet: &monero::Wallet,
+        db: &(dyn Database + Send + Sync),
+    ) -> Result<Self> {
+        let unlocked = monero_wallet
+            .get_unlocked_balance()
+            .await
+            .context("Failed to get unlocked Monero balance")?;
+
+        let reserved_for_swaps = db
+            .all()
+            .await?
+            .into_iter()
+            .filter_map(|(_, state)| {
+                let state: AliceState = state.try_into().ok()?;
+                xmr_to_lock(&state)
+            })
+            .fold(monero::Amount::ZERO, |total, amount| total + amount);
+
+        Ok(Self {
+            unlocked,
+            reserved_for_swaps,
+        })
+    }
+
+    /// The XMR left for a new swap after the reserved amounts and the fee of
+    /// its lock transaction.
+    pub fn available(&self) -> monero::Amount {
+        let available = self
+            .unlocked
+            .as_piconero()
+            .saturating_sub(self.reserved_for_swaps.as_piconero())
+            .saturating_sub(monero::MONERO_FEE.as_piconero());
+
+        monero::Amount::from_piconero(available)
+    }
+
+    /// The largest BTC amount that the available XMR can back at `rate`.
+    pub fn max_buy(&self, rate: &Rate) -> Result<bitcoin::Amount> {
+        rate.sell_price(self.available())
+    }
+}
+
+/// The XMR a swap still has to lock, if it did not lock it yet.
+fn xmr_to_lock(state: &AliceState) -> Option<monero::Amount> {
+    match state {
+        AliceState::Started { state3 }
+        | AliceState::BtcLockTransactionSeen { state3 }
+        | AliceState::BtcLocked { state3 } => Some(state3.lock_xmr_transfer_request().amount),
+        _ => None,
+    }
+}
+
+/// Compares the configured maximum buy amount with the one the current
+/// balance can back, warns if it cannot and lowers the advertised maximum if a
+/// `ceiling` is given.
+pub async fn check_liquidity<LR>(
+    monero_wallet: &monero::Wallet,
+    db: &(dyn Database + Send + Sync),
+    latest_rate: &mut LR,
+    maker_params: &MakerParamsUpdates,
+    ceiling: Option<&MaxBuyCeiling>,
+) -> Result<()>
+where
+    LR: LatestRate,
+{
+    let liquidity = Liquidity::capture(monero_wallet, db).await?;
+    let rate = latest_rate
+        .latest_rate()
+        .context("Failed to get latest rate")?;
+
+    let max_buy = maker_params.configured().max_buy;
+    let backed_max_buy = liquidity.max_buy(&rate)?;
+
+    if backed_max_buy < max_buy {
+        tracing::warn!(
+            %max_buy,
+            %backed_max_buy,
+            unlocked_balance = %liquidity.unlocked,
+            reserved_for_swaps = %liquidity.reserved_for_swaps,
+            lowering_max_buy = ceiling.is_some(),
+            "The Monero balance cannot back the advertised maximum buy amount, swaps above the backed amount will fail"
+        );
+
+        if let Some(ceiling) = ceiling {
+            ceiling.set(Some(backed_max_buy))?;
+        }
+    } else if let Some(ceiling) = ceiling {
+        ceiling.set(None)?;
+    }
+
+    Ok(())
+}
+
+/// Runs [`check_liquidity`] every [`CHECK_INTERVAL`], forever.
+pub async fn monitor_liquidity<LR>(
+    monero_wallet: Arc<monero::Wallet>,
+    db: Arc<dyn Database + Send + Sync>,
+    mut latest_rate: LR,
+    maker_params: MakerParamsUpdates,
+    ceiling: Option<MaxBuyCeiling>,
+) where
+    LR: LatestRate + Send,
+{
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(error) = check_liquidity(
+            &monero_wallet,
+            db.as_ref(),
+            &mut latest_rate,
+            &maker_params,
+            ceiling.as_ref(),
+        )
+        .await
+        {
+            tracing::warn!("Failed to check the liquidity of the ASB: {:#}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn xmr(amount: f64) -> monero::Amount {
+        monero::Amount::from_monero(amount).unwrap()
+    }
+
+    #[test]
+    fn reserved_xmr_and_lock_fee_are_not_available() {
+        let liquidity = Liquidity {
+            unlocked: xmr(10.0),
+            reserved_for_swaps: xmr(4.0),
+        };
+
+        assert_eq!(liquidity.available(), xmr(6.0) - monero::MONERO_FEE);
+    }
+
+    #[test]
+    fn nothing_is_available_if_swaps_reserve_more_than_unlocked() {
+        let liquidity = Liquidity {
+            unlocked: xmr(1.0),
+            reserved_for_swaps: xmr(4.0),
+        };
+
+        assert_eq!(liquidity.available(), monero::Amount::ZERO);
+    }
+
+    #[test]
+    fn max_buy_is_price_of_available_xmr() {
+        let liquidity = Liquidity {
+            unlocked: xmr(2.0) + monero::MONERO_FEE,
+            reserved_for_swaps: monero::Amount::ZERO,
+        };
+        let rate = Rate::new(
+            bitcoin::Amount::from_btc(0.005).unwrap(),
+            Decimal::from(0u64),
+        );
+
+        assert_eq!(
+            liquidity.max_buy(&rate).unwrap(),
+            bitcoin::Amount::from_btc(0.01).unwrap()
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use crate::asb::config::Maker;
+use crate::bitcoin;
+use anyhow::{bail, Context, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// The parameters the ASB uses to quote and accept swaps.
+///
+/// These parameters can be adjusted while the ASB is running. Adjustments only
+/// apply to quotes and swap setups that happen afterwards. Swaps that are
+/// already in-flight keep the amounts agreed upon during swap setup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MakerParams {
+    pub min_buy: bitcoin::Amount,
+    pub max_buy: bitcoin::Amount,
+    pub ask_spread: Decimal,
+    /// The price of 1 XMR if the ASB does not use a price feed, see
+    /// [`crate::asb::ManualRate`].
+    pub fixed_rate: Option<bitcoin::Amount>,
+    /// How much more than the estimated fee of our redeem transaction a swap
+    /// has to lock, e.g. `1` requires twice the redeem fee. This protects us
+    /// from swaps that are not worth redeeming.
+    pub redeem_fee_margin: Decimal,
+}
+
+/// The default [`MakerParams::redeem_fee_margin`].
+pub const DEFAULT_REDEEM_FEE_MARGIN: u64 = 1;
+
+impl MakerParams {
+    /// The smallest amount a swap has to lock to cover `redeem_fee` plus the
+    /// configured margin.
+    pub fn min_lock_amount(&self, redeem_fee: bitcoin::Amount) -> bitcoin::Amount {
+        let factor = Decimal::from(1u64) + self.redeem_fee_margin;
+        let sats = Decimal::from(redeem_fee.as_sat()) * factor;
+        let sats = sats.ceil().to_u64().unwrap_or(u64::MAX);
+
+        bitcoin::Amount::from_sat(sats)
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.min_buy > self.max_buy {
+            bail!(
+                "Minimum buy amount {} must not be greater than maximum buy amount {}",
+                self.min_buy,
+                self.max_buy
+            );
+        }
+
+        if self.ask_spread < Decimal::from(0u64) || self.ask_spread > Decimal::from(1u64) {
+            bail!(
+                "Invalid spread {}. The spread must be in the interval [0..1]",
+                self.ask_spread
+            );
+        }
+
+        if self.redeem_fee_margin < Decimal::from(0u64) {
+            bail!(
+                "Invalid redeem fee margin {}. The margin must not be negative",
+                self.redeem_fee_margin
+            );
+        }
+
+        if self.fixed_rate == Some(bitcoin::Amount::ZERO) {
+            bail!("Fixed rate must be greater than 0");
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&Maker> for MakerParams {
+    fn from(maker: &Maker) -> Self {
+        Self {
+            min_buy: maker.min_buy_btc,
+            max_buy: maker.max_buy_btc,
+            ask_spread: maker.ask_spread,
+            fixed_rate: maker.fixed_rate_btc,
+            redeem_fee_margin: maker
+                .redeem_fee_margin
+                .unwrap_or_else(|| Decimal::from(DEFAULT_REDEEM_FEE_MARGIN)),
+        }
+    }
+}
+
+/// Creates a channel for adjusting the [`MakerParams`] at runtime.
+pub fn channel(initial: MakerParams) -> (MakerParamsUpdater, MakerParamsUpdates) {
+    let (sender, receiver) = watch::channel(initial);
+    let (ceiling_sender, ceiling_receiver) = watch::channel(None);
+
+    (
+        MakerParamsUpdater {
+            inner: sender,
+            max_buy_ceiling: Arc::new(ceiling_sender),
+        },
+        MakerParamsUpdates {
+            inner: receiver,
+            max_buy_ceiling: ceiling_receiver,
+        },
+    )
+}
+
+#[derive(Debug)]
+pub struct MakerParamsUpdater {
+    inner: watch::Sender<MakerParams>,
+    max_buy_ceiling: Arc<watch::Sender<Option<bitcoin::Amount>>>,
+}
+
+impl MakerParamsUpdater {
+    /// Publishes new maker parameters to all subscribers.
+    ///
+    /// Every adjustment is logged so operators have an audit trail of the
+    /// parameters used over time.
+    pub fn update(&self, new: MakerParams) -> Result<()> {
+        new.validate()?;
+
+        let old = *self.inner.borrow();
+
+        if old == new {
+            tracing::debug!("Maker parameters unchanged");
+            return Ok(());
+        }
+
+        self.inner
+            .send(new)
+            .context("Failed to publish maker parameters because all subscribers are gone")?;
+
+        tracing::info!(
+            old_min_buy = %old.min_buy,
+            new_min_buy = %new.min_buy,
+            old_max_buy = %old.max_buy,
+            new_max_buy = %new.max_buy,
+            old_ask_spread = %old.ask_spread,
+            new_ask_spread = %new.ask_spread,
+            old_fixed_rate = ?old.fixed_rate,
+            new_fixed_rate = ?new.fixed_rate,
+            old_redeem_fee_margin = %old.redeem_fee_margin,
+            new_redeem_fee_margin = %new.redeem_fee_margin,
+            "Adjusted maker parameters"
+        );
+
+        Ok(())
+    }
+
+    /// A handle to lower the maximum buy amount below the configured one
+    /// without changing the configured parameters.
+    pub fn max_buy_ceiling(&self) -> MaxBuyCeiling {
+        MaxBuyCeiling {
+            inner: self.max_buy_ceiling.clone(),
+        }
+    }
+}
+
+/// Caps the [`MakerParams::max_buy`] that subscribers see, e.g. while the
+/// balance of the ASB cannot back the configured maximum.
+///
+/// Adjusting the configured parameters keeps the ceiling in place.
+#[derive(Debug, Clone)]
+pub struct MaxBuyCeiling {
+    inner: Arc<watch::Sender<Option<bitcoin::Amount>>>,
+}
+
+impl MaxBuyCeiling {
+    /// Sets the ceiling, `None` lifts it.
+    pub fn set(&self, ceiling: Option<bitcoin::Amount>) -> Result<()> {
+        if *self.inner.borrow() == ceiling {
+            return Ok(());
+        }
+
+        self.inner
+            .send(ceiling)
+            .context("Failed to publish maximum buy ceiling because all subscribers are gone")?;
+
+        match ceiling {
+            Some(ceiling) => tracing::info!(%ceiling, "Lowered the maximum buy amount"),
+            None => tracing::info!("Restored the configured maximum buy amount"),
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MakerParamsUpdates {
+    inner: watch::Receiver<MakerParams>,
+    max_buy_ceiling: watch::Receiver<Option<bitcoin::Amount>>,
+}
+
+impl MakerParamsUpdates {
+    /// Maker parameters that never change.
+    pub fn fixed(params: MakerParams) -> Self {
+        let (_, receiver) = watch::channel(params);
+        let (_, max_buy_ceiling) = watch::channel(None);
+
+        Self {
+            inner: receiver,
+            max_buy_ceiling,
+        }
+    }
+
+    /// The parameters to quote and accept swaps with, i.e. the configured
+    /// ones with the [`MaxBuyCeiling`] applied.
+    pub fn latest(&self) -> MakerParams {
+        let mut params = self.configured();
+
+        if let Some(ceiling) = *self.max_buy_ceiling.borrow() {
+            params.max_buy = params.max_buy.min(ceiling);
+        }
+
+        params
+    }
+
+    /// The parameters as configured, ignoring the [`MaxBuyCeiling`].
+    pub fn configured(&self) -> MakerParams {
+        *self.inner.borrow()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(min_buy: u64, max_buy: u64, ask_spread: i64) -> MakerParams {
+        MakerParams {
+            min_buy: bitcoin::Amount::from_sat(min_buy),
+            max_buy: bitcoin::Amount::from_sat(max_buy),
+            ask_spread: Decimal::new(ask_spread, 2),
+            fixed_rate: None,
+            redeem_fee_margin: Decimal::from(DEFAULT_REDEEM_FEE_MARGIN),
+        }
+    }
+
+    #[test]
+    fn subscribers_observe_adjusted_params() {
+        let (updater, updates) = channel(params(1_000, 10_000, 2));
+        let cloned = updates.clone();
+
+        updater.update(params(1_000, 20_000, 5)).unwrap();
+
+        assert_eq!(updates.latest(), params(1_000, 20_000, 5));
+        assert_eq!(cloned.latest(), params(1_000, 20_000, 5));
+    }
+
+    #[test]
+    fn ceiling_caps_max_buy_until_lifted() {
+        let (updater, updates) = channel(params(1_000, 10_000, 2));
+        let ceiling = updater.max_buy_ceiling();
+
+        ceiling.set(Some(bitcoin::Amount::from_sat(5_000))).unwrap();
+        assert_eq!(updates.latest(), params(1_000, 5_000, 2));
+        assert_eq!(updates.configured(), params(1_000, 10_000, 2));
+
+        updater.update(params(1_000, 20_000, 5)).unwrap();
+        assert_eq!(updates.latest(), params(1_000, 5_000, 5));
+
+        ceiling.set(None).unwrap();
+        assert_eq!(updates.latest(), params(1_000, 20_000, 5));
+    }
+
+    #[test]
+    fn ceiling_above_max_buy_has_no_effect() {
+        let (updater, updates) = channel(params(1_000, 10_000, 2));
+
+        updater
+            .max_buy_ceiling()
+            .set(Some(bitcoin::Amount::from_sat(50_000)))
+            .unwrap();
+
+        assert_eq!(updates.latest(), params(1_000, 10_000, 2));
+    }
+
+    #[test]
+    fn rejects_min_buy_greater_than_max_buy() {
+        let (updater, updates) = channel(params(1_000, 10_000, 2));
+
+        let result = updater.update(params(20_000, 10_000, 2));
+
+        assert!(result.is_err());
+        assert_eq!(updates.latest(), params(1_000, 10_000, 2));
+    }
+
+    #[test]
+    fn rejects_spread_outside_of_unit_interval() {
+        let (updater, updates) = channel(params(1_000, 10_000, 2));
+
+        let result = updater.update(params(1_000, 10_000, 150));
+
+        assert!(result.is_err());
+        assert_eq!(updates.latest(), params(1_000, 10_000, 2));
+    }
+
+    #[test]
+    fn min_lock_amount_covers_redeem_fee_with_margin() {
+        let redeem_fee = bitcoin::Amount::from_sat(1_001);
+
+        assert_eq!(
+            params(1_000, 10_000, 2).min_lock_amount(redeem_fee),
+            bitcoin::Amount::from_sat(2_002)
+        );
+
+        let params = MakerParams {
+            redeem_fee_margin: Decimal::new(5, 1),
+            ..params(1_000, 10_000, 2)
+        };
+        assert_eq!(
+            params.min_lock_amount(redeem_fee),
+            bitcoin::Amount::from_sat(1_502)
+        );
+    }
+
+    #[test]
+    fn rejects_negative_redeem_fee_margin() {
+        let (updater, updates) = channel(params(1_000, 10_000, 2));
+
+        let result = updater.update(MakerParams {
+            redeem_fee_margin: Decimal::new(-1, 0),
+            ..params(1_000, 10_000, 2)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(updates.latest(), params(1_000, 10_000, 2));
+    }
+
+    #[test]
+    fn rejects_fixed_rate_of_zero() {
+        let (updater, updates) = channel(params(1_000, 10_000, 2));
+
+        let result = updater.update(MakerParams {
+            fixed_rate: Some(bitcoin::Amount::ZERO),
+            ..params(1_000, 10_000, 2)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(updates.latest(), params(1_000, 10_000, 2));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::asb::event_loop::LatestRate;
+use crate::asb::MakerParamsUpdates;
 use crate::env;
 use crate::network::quote::BidQuote;
 use crate::network::rendezvous::XmrBtcNamespace;
@@ -30,8 +31,16 @@ pub mod transport {
     use super::*;
 
     /// Creates the libp2p transport for the ASB.
-    pub fn new(identity: &identity::Keypair) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
-        let tcp = TokioTcpConfig::new().nodelay(true);
+    ///
+    /// Without a `listen_backlog` the default of the tcp transport is used.
+    pub fn new(
+        identity: &identity::Keypair,
+        listen_backlog: Option<u32>,
+    ) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+        let mut tcp = TokioTcpConfig::new().nodelay(true);
+        if let Some(backlog) = listen_backlog {
+            tcp = tcp.listen_backlog(backlog);
+        }
         let tcp_with_dns = TokioDnsConfig::system(tcp)?;
         let websocket_with_dns = WsConfig::new(tcp_with_dns.clone());
 
@@ -123,8 +132,7 @@ pub mod behaviour {
         LR: LatestRate + Send + 'static,
     {
         pub fn new(
-            min_buy: bitcoin::Amount,
-            max_buy: bitcoin::Amount,
+            maker_params: MakerParamsUpdates,
             latest_rate: LR,
             resume_only: bool,
             env_config: env::Config,
@@ -142,16 +150,17 @@ pub mod behaviour {
                         )
                     },
                 )),
-                quote: quote::asb(),
+                quote: quote::asb(quote::DEFAULT_TIMEOUT),
                 swap_setup: alice::Behaviour::new(
-                    min_buy,
-                    max_buy,
+                    maker_params,
                     env_config,
                     latest_rate,
                     resume_only,
                 ),
-                transfer_proof: transfer_proof::alice(),
-                encrypted_signature: encrypted_signature::alice(),
+                transfer_proof: transfer_proof::alice(transfer_proof::DEFAULT_TIMEOUT),
+                encrypted_signature: encrypted_signature::alice(
+                    encrypted_signature::DEFAULT_TIMEOUT,
+                ),
                 ping: Ping::new(PingConfig::new().with_keep_alive(true)),
             }
         }
@@ -47,6 +47,25 @@ impl Rate {
         Self::quote(self.ask()?, quote)
     }
 
+    /// Calculate the BTC amount we ask for selling the given XMR amount, which
+    /// is the inverse of [`Rate::sell_quote`].
+    pub fn sell_price(&self, xmr: monero::Amount) -> Result<bitcoin::Amount> {
+        let rate_in_sats = Decimal::from(self.ask()?.as_sat());
+        let xmr_in_xmr = xmr
+            .as_piconero_decimal()
+            .checked_div(Decimal::from(monero::Amount::ONE_XMR.as_piconero()))
+            .context("Division overflow")?;
+
+        let sats = rate_in_sats
+            .checked_mul(xmr_in_xmr)
+            .context("Multiplication overflow")?
+            .floor()
+            .to_u64()
+            .context("Failed to fit satoshi amount into a u64")?;
+
+        Ok(bitcoin::Amount::from_sat(sats))
+    }
+
     fn quote(rate: bitcoin::Amount, quote: bitcoin::Amount) -> Result<monero::Amount> {
         // quote (btc) = rate * base (xmr)
         // base = quote / rate
@@ -98,6 +117,22 @@ mod tests {
         assert_eq!(xmr_amount, monero::Amount::from_monero(1000.0).unwrap())
     }
 
+    #[test]
+    fn sell_price_is_inverse_of_sell_quote() {
+        let asking_price = bitcoin::Amount::from_btc(0.002_500).unwrap();
+        let rate = Rate::new(asking_price, TWO_PERCENT);
+
+        let btc_amount = rate
+            .sell_price(monero::Amount::from_monero(1000.0).unwrap())
+            .unwrap();
+
+        assert_eq!(btc_amount, bitcoin::Amount::from_btc(2.55).unwrap());
+        assert_eq!(
+            rate.sell_quote(btc_amount).unwrap(),
+            monero::Amount::from_monero(1000.0).unwrap()
+        );
+    }
+
     #[test]
     fn applies_spread_to_asking_price() {
         let asking_price = bitcoin::Amount::from_sat(100);
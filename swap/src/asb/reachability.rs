@@ -0,0 +1,113 @@
+use crate::network::swarm;
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use libp2p::ping::{Ping, PingConfig, PingEvent, PingSuccess};
+use libp2p::swarm::SwarmEvent;
+use libp2p::{identity, Multiaddr, PeerId};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reachability {
+    Reachable {
+        /// Time it took to establish the connection through Tor.
+        connect_time: Duration,
+        /// Round-trip time of a ping over the established connection.
+        rtt: Duration,
+    },
+    Unreachable {
+        reason: String,
+    },
+}
+
+impl fmt::Display for Reachability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reachability::Reachable { connect_time, rtt } => write!(
+                f,
+                "reachable (connected after {} ms, round-trip time {} ms)",
+                connect_time.as_millis(),
+                rtt.as_millis()
+            ),
+            Reachability::Unreachable { reason } => write!(f, "unreachable ({})", reason),
+        }
+    }
+}
+
+/// Dials `address` through Tor with a throwaway identity and pings the peer
+/// behind it.
+///
+/// This only succeeds if the hidden service is registered and forwards to the
+/// port the ASB with the given `peer_id` is listening on, i.e. if takers can
+/// reach the ASB under this address.
+pub async fn check_reachability(
+    address: Multiaddr,
+    peer_id: PeerId,
+    tor_socks5_port: u16,
+    timeout: Duration,
+) -> Result<Reachability> {
+    let behaviour = Ping::new(PingConfig::new().with_keep_alive(true));
+    let mut swarm = swarm::cli(
+        identity::Keypair::generate_ed25519(),
+        tor_socks5_port,
+        behaviour,
+    )
+    .await?;
+
+    let started = Instant::now();
+    swarm
+        .dial_addr(address.clone())
+        .with_context(|| format!("Failed to dial {}", address))?;
+
+    let probe = async {
+        let mut connect_time = None;
+
+        loop {
+            match swarm.select_next_some().await {
+                SwarmEvent::ConnectionEstablished {
+                    peer_id: connected_peer_id,
+                    ..
+                } => {
+                    if connected_peer_id != peer_id {
+                        return Reachability::Unreachable {
+                            reason: format!(
+                                "connected to peer {} instead of this ASB ({})",
+                                connected_peer_id, peer_id
+                            ),
+                        };
+                    }
+
+                    connect_time = Some(started.elapsed());
+                }
+                SwarmEvent::Behaviour(PingEvent {
+                    result: Ok(PingSuccess::Ping { rtt }),
+                    ..
+                }) => {
+                    if let Some(connect_time) = connect_time {
+                        return Reachability::Reachable { connect_time, rtt };
+                    }
+                }
+                SwarmEvent::Behaviour(PingEvent { result: Err(e), .. }) => {
+                    return Reachability::Unreachable {
+                        reason: format!("ping failed: {}", e),
+                    };
+                }
+                SwarmEvent::UnknownPeerUnreachableAddr { error, .. } => {
+                    return Reachability::Unreachable {
+                        reason: error.to_string(),
+                    };
+                }
+                _ => {}
+            }
+        }
+    };
+
+    let reachability = match tokio::time::timeout(timeout, probe).await {
+        Ok(reachability) => reachability,
+        Err(_) => Reachability::Unreachable {
+            reason: format!("no response within {} seconds", timeout.as_secs()),
+        },
+    };
+
+    Ok(reachability)
+}
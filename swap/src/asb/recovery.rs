@@ -3,3 +3,4 @@ pub mod punish;
 pub mod redeem;
 pub mod refund;
 pub mod safely_abort;
+pub mod sweep;
@@ -1,15 +1,16 @@
 use crate::bitcoin::{self, Txid};
+use crate::database::UnexpectedState;
 use crate::protocol::alice::AliceState;
 use crate::protocol::Database;
-use anyhow::{bail, Result};
+use anyhow::Result;
 use std::convert::TryInto;
 use std::sync::Arc;
 use uuid::Uuid;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("Cannot punish swap because it is in state {0} which is not punishable")]
-    SwapNotPunishable(AliceState),
+    #[error("Cannot punish swap because it is not punishable: {0}")]
+    SwapNotPunishable(UnexpectedState),
 }
 
 pub async fn punish(
@@ -17,30 +18,11 @@ pub async fn punish(
     bitcoin_wallet: Arc<bitcoin::Wallet>,
     db: Arc<dyn Database>,
 ) -> Result<(Txid, AliceState)> {
-    let state = db.get_state(swap_id).await?.try_into()?;
+    let state: AliceState = db.get_state(swap_id).await?.try_into()?;
 
-    let state3 = match state {
-        // Punish potentially possible (no knowledge of cancel transaction)
-        AliceState::BtcLockTransactionSeen { state3 }
-        | AliceState::BtcLocked { state3, .. }
-        | AliceState::XmrLockTransactionSent {state3, ..}
-        | AliceState::XmrLocked {state3, ..}
-        | AliceState::XmrLockTransferProofSent {state3, ..}
-        | AliceState::EncSigLearned {state3, ..}
-        | AliceState::CancelTimelockExpired {state3, ..}
-        // Punish possible due to cancel transaction already being published
-        | AliceState::BtcCancelled {state3, ..}
-        | AliceState::BtcPunishable {state3, ..} => { state3 }
-        // The state machine is in a state where punish is theoretically impossible but we try and punish anyway as this is what the user wants
-        AliceState::BtcRedeemTransactionPublished { state3 }
-        | AliceState::BtcRefunded { state3,.. }
-        | AliceState::Started { state3 }  => { state3 }
-        // Alice already in final state
-        | AliceState::BtcRedeemed
-        | AliceState::XmrRefunded
-        | AliceState::BtcPunished
-        | AliceState::SafelyAborted => bail!(Error::SwapNotPunishable(state)),
-    };
+    // Punish is attempted in every state that is not final, even where it is
+    // theoretically impossible, as this is what the user wants
+    let state3 = state.try_into_state3().map_err(Error::SwapNotPunishable)?;
 
     tracing::info!(%swap_id, "Trying to manually punish swap");
 
@@ -1,4 +1,5 @@
 use crate::bitcoin::{self};
+use crate::database::UnexpectedState;
 use crate::monero;
 use crate::protocol::alice::AliceState;
 use crate::protocol::Database;
@@ -19,17 +20,17 @@ pub enum Error {
     // state
     #[error("Swap is in state {0} where no XMR was locked. Try aborting instead.")]
     NoXmrLocked(AliceState),
-    #[error("Swap is in state {0} which is not refundable")]
-    SwapNotRefundable(AliceState),
+    #[error("Swap is not refundable: {0}")]
+    SwapNotRefundable(UnexpectedState),
 }
 
 pub async fn refund(
     swap_id: Uuid,
     bitcoin_wallet: Arc<bitcoin::Wallet>,
     monero_wallet: Arc<monero::Wallet>,
-    db: Arc<dyn Database>,
+    db: Arc<dyn Database + Send + Sync>,
 ) -> Result<AliceState> {
-    let state = db.get_state(swap_id).await?.try_into()?;
+    let state: AliceState = db.get_state(swap_id).await?.try_into()?;
 
     let (monero_wallet_restore_blockheight, transfer_proof, state3) = match state {
         // In case no XMR has been locked, move to Safely Aborted
@@ -37,26 +38,11 @@ pub async fn refund(
         | AliceState::BtcLockTransactionSeen { .. }
         | AliceState::BtcLocked { .. } => bail!(Error::NoXmrLocked(state)),
 
-        // Refund potentially possible (no knowledge of cancel transaction)
-        AliceState::XmrLockTransactionSent { monero_wallet_restore_blockheight, transfer_proof, state3, }
-        | AliceState::XmrLocked { monero_wallet_restore_blockheight, transfer_proof, state3 }
-        | AliceState::XmrLockTransferProofSent { monero_wallet_restore_blockheight, transfer_proof, state3 }
-        | AliceState::EncSigLearned { monero_wallet_restore_blockheight, transfer_proof, state3, .. }
-        | AliceState::CancelTimelockExpired { monero_wallet_restore_blockheight, transfer_proof, state3 }
-
-        // Refund possible due to cancel transaction already being published
-        | AliceState::BtcCancelled { monero_wallet_restore_blockheight, transfer_proof, state3 }
-        | AliceState::BtcRefunded { monero_wallet_restore_blockheight, transfer_proof, state3, .. }
-        | AliceState::BtcPunishable { monero_wallet_restore_blockheight, transfer_proof, state3, .. } => {
-            (monero_wallet_restore_blockheight, transfer_proof, state3)
-        }
-
-        // Alice already in final state
-        AliceState::BtcRedeemTransactionPublished { .. }
-        | AliceState::BtcRedeemed
-        | AliceState::XmrRefunded
-        | AliceState::BtcPunished
-        | AliceState::SafelyAborted => bail!(Error::SwapNotRefundable(state)),
+        // Refund is possible in every state the XMR is locked in, but not once
+        // the swap finished or the redeem transaction was published
+        state => state
+            .try_into_xmr_locked()
+            .map_err(Error::SwapNotRefundable)?,
     };
 
     tracing::info!(%swap_id, "Trying to manually refund swap");
@@ -0,0 +1,135 @@
+use crate::bitcoin::{self};
+use crate::monero::{self, TxHash, WalletKeys};
+use crate::protocol::alice::AliceState;
+use crate::protocol::Database;
+use anyhow::{bail, Result};
+use libp2p::PeerId;
+use std::convert::TryInto;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Counterparty {0} did not refund the BTC yet, so the Monero cannot be swept.")]
+    RefundTransactionNotPublishedYet(PeerId),
+    #[error("Swap is in state {0} where there is no refunded Monero to sweep")]
+    NothingToSweep(AliceState),
+}
+
+/// Sweeps the refunded Monero of a swap from the wallet named after the swap
+/// to `destination`.
+///
+/// If the wallet does not exist (anymore) it is recreated from `s_a + s_b`
+/// and the view key of the swap. Sweeping a wallet that was already swept is
+/// a no-op, so this can be retried until it succeeds.
+pub async fn sweep_refunded_xmr(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    monero_wallet: Arc<monero::Wallet>,
+    db: Arc<dyn Database>,
+    destination: monero::Address,
+) -> Result<Vec<TxHash>> {
+    let keys = refunded_xmr_keys(swap_id, bitcoin_wallet.as_ref(), db.as_ref()).await?;
+
+    tracing::info!(%swap_id, %destination, "Trying to sweep refunded Monero");
+
+    let tx_hashes = monero_wallet
+        .sweep_from(swap_id.to_string(), keys, destination)
+        .await?;
+
+    let state = AliceState::XmrRefunded;
+    db.insert_latest_state(swap_id, state.into()).await?;
+
+    Ok(tx_hashes)
+}
+
+/// The keys of the wallet that holds the refunded Monero of a swap, i.e.
+/// `s_a + s_b` and the view key of the swap.
+///
+/// `s_b` is extracted from Bob's refund transaction if it was not learned yet.
+/// Returns `None` if the keys are no longer stored because the wallet was
+/// already generated.
+pub async fn refunded_xmr_keys(
+    swap_id: Uuid,
+    bitcoin_wallet: &bitcoin::Wallet,
+    db: &dyn Database,
+) -> Result<Option<WalletKeys>> {
+    let state = db.get_state(swap_id).await?.try_into()?;
+
+    let keys = match state {
+        AliceState::BtcRefunded {
+            monero_wallet_restore_blockheight,
+            spend_key,
+            state3,
+            ..
+        } => Some(WalletKeys {
+            spend_key,
+            view_key: state3.v,
+            restore_height: monero_wallet_restore_blockheight,
+        }),
+
+        AliceState::XmrLockTransactionSent {
+            monero_wallet_restore_blockheight,
+            state3,
+            ..
+        }
+        | AliceState::XmrLocked {
+            monero_wallet_restore_blockheight,
+            state3,
+            ..
+        }
+        | AliceState::XmrLockTransferProofSent {
+            monero_wallet_restore_blockheight,
+            state3,
+            ..
+        }
+        | AliceState::EncSigLearned {
+            monero_wallet_restore_blockheight,
+            state3,
+            ..
+        }
+        | AliceState::CancelTimelockExpired {
+            monero_wallet_restore_blockheight,
+            state3,
+            ..
+        }
+        | AliceState::BtcCancelled {
+            monero_wallet_restore_blockheight,
+            state3,
+            ..
+        }
+        | AliceState::BtcPunishable {
+            monero_wallet_restore_blockheight,
+            state3,
+            ..
+        } => {
+            let published_refund_tx = match state3.fetch_tx_refund(bitcoin_wallet).await {
+                Ok(published_refund_tx) => published_refund_tx,
+                Err(_) => {
+                    let bob_peer_id = db.get_peer_id(swap_id).await?;
+                    bail!(Error::RefundTransactionNotPublishedYet(bob_peer_id));
+                }
+            };
+
+            Some(WalletKeys {
+                spend_key: state3.extract_monero_private_key(published_refund_tx)?,
+                view_key: state3.v,
+                restore_height: monero_wallet_restore_blockheight,
+            })
+        }
+
+        // The keys are no longer stored, but the wallet was generated before
+        // the swap got into this state.
+        AliceState::XmrRefunded => None,
+
+        AliceState::Started { .. }
+        | AliceState::BtcLockTransactionSeen { .. }
+        | AliceState::BtcLocked { .. }
+        | AliceState::BtcRedeemTransactionPublished { .. }
+        | AliceState::BtcRedeemed
+        | AliceState::BtcPunished
+        | AliceState::SafelyAborted => bail!(Error::NothingToSweep(state)),
+    };
+
+    Ok(keys)
+}
@@ -0,0 +1,191 @@
+use crate::asb::{LatestRate, SwapResumer};
+use crate::bitcoin::ElectrumUnreachable;
+use crate::protocol::alice::{self, AliceState, Swap};
+use anyhow::{bail, Context, Result};
+use bdk::electrum_client;
+use futures::FutureExt;
+use std::any::Any;
+use std::cmp::min;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+/// The backoff between two restarts does not grow beyond this.
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// Runs the swap and restarts it from its last saved state if it failed
+/// because of a transient error, see [`is_transient`].
+///
+/// The swap is restarted up to
+/// [`alice_swap_restart_attempts`](crate::env::Config) times with a backoff
+/// that doubles with every restart, up to [`MAX_BACKOFF`]. A panicking swap is
+/// not restarted, but only fails the swap instead of the task running it.
+pub async fn run_with_restarts<LR>(
+    mut swap: Swap,
+    rate_service: LR,
+    resumer: SwapResumer,
+) -> Result<AliceState>
+where
+    LR: LatestRate + Clone,
+{
+    let swap_id = swap.swap_id;
+    let max_restarts = swap.env_config.alice_swap_restart_attempts;
+    let mut backoff = swap.env_config.alice_swap_restart_backoff;
+    let mut restarts = 0;
+    let clock = swap.clock.clone();
+
+    loop {
+        let result = AssertUnwindSafe(alice::run(swap, rate_service.clone()))
+            .catch_unwind()
+            .await;
+
+        let error = match result {
+            Ok(Ok(state)) => return Ok(state),
+            Ok(Err(error)) => error,
+            Err(panic) => bail!("Swap panicked: {}", panic_message(&*panic)),
+        };
+
+        if restarts >= max_restarts || !is_transient(&error) {
+            return Err(error);
+        }
+
+        restarts += 1;
+
+        tracing::warn!(
+            %swap_id,
+            %restarts,
+            %max_restarts,
+            "Swap failed because of a transient error, restarting it in {} s: {:#}",
+            backoff.as_secs(),
+            error
+        );
+
+        clock.sleep(backoff).await;
+        backoff = next_backoff(backoff);
+
+        swap = resumer
+            .resume(swap_id)
+            .await
+            .context("Failed to restart swap")?;
+        swap.clock = clock.clone();
+    }
+}
+
+fn next_backoff(backoff: Duration) -> Duration {
+    min(backoff * 2, MAX_BACKOFF)
+}
+
+/// Whether the swap failed because some infrastructure could not be reached,
+/// which may succeed once it is restarted.
+///
+/// Only failed connections and timeouts count. Any other error, e.g. of the
+/// protocol itself or a response that could not be parsed, would fail again.
+fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.is::<ElectrumUnreachable>()
+            || cause
+                .downcast_ref::<io::Error>()
+                .map_or(false, is_connection_error)
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .map_or(false, |error| error.is_connect() || error.is_timeout())
+            || cause
+                .downcast_ref::<electrum_client::Error>()
+                .map_or(false, is_electrum_connection_error)
+            || cause
+                .downcast_ref::<bdk::Error>()
+                .map_or(false, |error| match error {
+                    bdk::Error::Electrum(error) => is_electrum_connection_error(error),
+                    _ => false,
+                })
+            || cause
+                .downcast_ref::<monero_rpc::jsonrpc::Error<reqwest::Error>>()
+                .map_or(false, crate::monero::wallet::is_transient)
+    })
+}
+
+fn is_connection_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::TimedOut
+    )
+}
+
+fn is_electrum_connection_error(error: &electrum_client::Error) -> bool {
+    match error {
+        electrum_client::Error::IOError(error) => is_connection_error(error),
+        electrum_client::Error::SharedIOError(error) => is_connection_error(error),
+        electrum_client::Error::AllAttemptsErrored(errors) => {
+            errors.iter().all(is_electrum_connection_error)
+        }
+        _ => false,
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown cause"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn unreachable_infrastructure_is_transient() {
+        let error = anyhow!(ElectrumUnreachable { attempts: 30 }).context("Failed to lock BTC");
+
+        assert!(is_transient(&error));
+    }
+
+    #[test]
+    fn protocol_errors_are_not_transient() {
+        let error = anyhow!("Invalid encrypted signature").context("Failed to redeem BTC");
+
+        assert!(!is_transient(&error));
+    }
+
+    #[test]
+    fn failed_connections_are_transient() {
+        let error = anyhow!(io::Error::from(io::ErrorKind::ConnectionRefused))
+            .context("Failed to connect to Electrum server");
+
+        assert!(is_transient(&error));
+    }
+
+    #[test]
+    fn other_io_errors_are_not_transient() {
+        let error = anyhow!(io::Error::from(io::ErrorKind::InvalidData))
+            .context("Failed to decode message");
+
+        assert!(!is_transient(&error));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(
+            next_backoff(Duration::from_secs(30)),
+            Duration::from_secs(60)
+        );
+        assert_eq!(next_backoff(Duration::from_secs(20 * 60)), MAX_BACKOFF);
+        assert_eq!(next_backoff(MAX_BACKOFF), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn panic_message_is_extracted() {
+        let panic = std::panic::catch_unwind(|| panic!("boom")).unwrap_err();
+
+        assert_eq!(panic_message(&*panic), "boom");
+    }
+}
@@ -0,0 +1,64 @@
+//! Tells whether the ASB is running, for commands that must not run next to
+//! it.
+//!
+//! A running ASB holds an exclusive lock on a small sqlite database in its data
+//! directory. The operating system releases the lock when the process exits,
+//! also if it is killed, so no stale lock is left behind.
+
+use anyhow::{Context, Result};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, SqliteConnection};
+use std::path::Path;
+use std::time::Duration;
+
+const FILE_NAME: &str = "asb.lock";
+
+/// The lock of a data directory, released when dropped.
+pub struct RunLock {
+    _connection: SqliteConnection,
+}
+
+impl RunLock {
+    /// Fails if another process holds the lock of `data_dir`, i.e. if an ASB
+    /// with this data directory is running.
+    pub async fn acquire(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(FILE_NAME);
+
+        let mut connection = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true)
+            .busy_timeout(Duration::ZERO)
+            .connect()
+            .await
+            .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+        sqlx::query("BEGIN EXCLUSIVE")
+            .execute(&mut connection)
+            .await
+            .with_context(|| {
+                format!(
+                    "Another process holds the lock file {}, is the ASB running?",
+                    path.display()
+                )
+            })?;
+
+        Ok(Self {
+            _connection: connection,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn lock_cannot_be_acquired_twice() {
+        let data_dir = tempdir().unwrap();
+
+        let _lock = RunLock::acquire(data_dir.path()).await.unwrap();
+
+        assert!(RunLock::acquire(data_dir.path()).await.is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Reports how much disk space the databases in the data directory of the ASB
+//! take.
+//!
+//! The swap database is a sqlite file that does not shrink when swaps are
+//! pruned, the freed pages are only returned to the file system by the
+//! `compact-database` command. The sled database of the Bitcoin wallet is
+//! managed by bdk and only reported here.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often the disk usage is reported.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const BYTES_PER_MB: u64 = 1_000_000;
+
+/// The size of the databases in the data directory in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StorageUsage {
+    /// The sqlite database of the swaps including its journal files.
+    pub swap_database: u64,
+    /// The sled databases of the Bitcoin wallets.
+    pub bitcoin_wallet: u64,
+}
+
+impl StorageUsage {
+    /// Measures the databases in `data_dir`, missing ones take no space.
+    pub fn measure(data_dir: &Path) -> Result<Self> {
+        let swap_database = ["sqlite", "sqlite-wal", "sqlite-shm"]
+            .iter()
+            .map(|name| size_of(&data_dir.join(name)))
+            .sum::<Result<u64>>()?;
+        let bitcoin_wallet = ["wallet", "wallet-external-signer"]
+            .iter()
+            .map(|name| size_of(&data_dir.join(name)))
+            .sum::<Result<u64>>()?;
+
+        Ok(Self {
+            swap_database,
+            bitcoin_wallet,
+        })
+    }
+
+    pub fn total(&self) -> u64 {
+        self.swap_database + self.bitcoin_wallet
+    }
+}
+
+/// The size of the file or of all files below the directory at `path`.
+fn size_of(path: &Path) -> Result<u64> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to read {}", path.display()))
+        }
+    };
+
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(path).with_context(|| format!("Failed to read {}", path.display()))? {
+        size += size_of(&entry?.path())?;
+    }
+
+    Ok(size)
+}
+
+/// Logs the disk usage of the databases, and warns if they take more than
+/// `max_size_mb`.
+pub fn check_storage(data_dir: &Path, max_size_mb: Option<u64>) -> Result<StorageUsage> {
+    let usage = StorageUsage::measure(data_dir)?;
+
+    tracing::info!(
+        swap_database_mb = usage.swap_database / BYTES_PER_MB,
+        bitcoin_wallet_mb = usage.bitcoin_wallet / BYTES_PER_MB,
+        "Disk usage of the databases"
+    );
+
+    if let Some(max_size_mb) = max_size_mb {
+        if usage.total() > max_size_mb * BYTES_PER_MB {
+            tracing::warn!(
+                total_mb = usage.total() / BYTES_PER_MB,
+                %max_size_mb,
+                "The databases take more disk space than configured, consider the `prune` command followed by `compact-database` while the ASB is stopped"
+            );
+        }
+    }
+
+    Ok(usage)
+}
+
+/// Runs [`check_storage`] every [`CHECK_INTERVAL`], forever.
+///
+/// The file system is walked on a blocking thread so a slow disk does not
+/// hold up the swaps.
+pub async fn monitor_storage(data_dir: PathBuf, max_size_mb: Option<u64>) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let data_dir = data_dir.clone();
+        let result =
+            tokio::task::spawn_blocking(move || check_storage(&data_dir, max_size_mb)).await;
+
+        match result {
+            Ok(Ok(_)) => {}
+            Ok(Err(error)) => tracing::warn!("Failed to measure disk usage: {:#}", error),
+            Err(error) => tracing::warn!("Failed to measure disk usage: {:#}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn measures_sqlite_files_and_wallet_directories() {
+        let data_dir = tempdir().unwrap();
+        let path = data_dir.path();
+        fs::write(path.join("sqlite"), vec![0; 100]).unwrap();
+        fs::write(path.join("sqlite-wal"), vec![0; 10]).unwrap();
+        fs::create_dir_all(path.join("wallet").join("tree")).unwrap();
+        fs::write(path.join("wallet").join("conf"), vec![0; 5]).unwrap();
+        fs::write(path.join("wallet").join("tree").join("db"), vec![0; 20]).unwrap();
+        fs::write(path.join("seed.pem"), vec![0; 1000]).unwrap();
+
+        let usage = StorageUsage::measure(path).unwrap();
+
+        assert_eq!(
+            usage,
+            StorageUsage {
+                swap_database: 110,
+                bitcoin_wallet: 25,
+            }
+        );
+        assert_eq!(usage.total(), 135);
+    }
+}
@@ -0,0 +1,264 @@
+//! Opt-in reports of anonymized, aggregate swap statistics to a collector.
+//!
+//! Telemetry is off unless `telemetry.enabled` is set in the config file. The
+//! [`Report`] is everything that is sent: the number of swaps that succeeded
+//! or failed within the last period and their median duration in whole
+//! minutes. No amounts, peer ids, swap ids, addresses or exact timestamps ever
+//! leave the ASB, they do not even enter the report.
+//!
+//! Reports are sent through Tor if it is running, otherwise the collector
+//! learns the IP address of the ASB.
+
+use crate::asb::config::Telemetry;
+use crate::protocol::alice::AliceState;
+use crate::protocol::Database;
+use anyhow::{Context, Result};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use std::convert::{TryFrom, TryInto};
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use url::Url;
+
+/// How often a report is sent if `telemetry.interval_secs` is not set.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The statistics of the swaps that finished within the last period.
+///
+/// This is serialized as JSON and is the only data sent to the collector.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Report {
+    /// The length of the period the report covers, in seconds.
+    pub period_secs: u64,
+    /// Swaps in which we redeemed the BTC.
+    pub successful_swaps: u64,
+    /// Swaps that ended with a refund, a punishment or an abort.
+    pub failed_swaps: u64,
+    /// The median time between the first and the last state of the finished
+    /// swaps, rounded down to whole minutes. Not known without any finished
+    /// swap.
+    pub median_duration_mins: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Success,
+    Failure,
+}
+
+impl Report {
+    /// Aggregates the swaps that finished within `period` before `now`.
+    pub async fn collect(
+        db: &(dyn Database + Send + Sync),
+        period: Duration,
+        now: OffsetDateTime,
+    ) -> Result<Self> {
+        let mut finished = Vec::new();
+
+        for (swap_id, state) in db.all().await? {
+            let outcome = match state.try_into().ok().and_then(outcome) {
+                Some(outcome) => outcome,
+                None => continue,
+            };
+
+            let finished_at = db.get_entered_at(swap_id).await?;
+            if !within(now, finished_at, period) {
+                continue;
+            }
+
+            let duration = db
+                .get_started_at(swap_id)
+                .await?
+                .and_then(|started_at| duration_between(started_at, finished_at));
+
+            finished.push((outcome, duration));
+        }
+
+        Ok(Self::aggregate(period, finished))
+    }
+
+    fn aggregate(period: Duration, finished: Vec<(Outcome, Option<Duration>)>) -> Self {
+        let successful_swaps = finished
+            .iter()
+            .filter(|(outcome, _)| *outcome == Outcome::Success)
+            .count();
+        let failed_swaps = finished.len() - successful_swaps;
+
+        let mut durations = finished
+            .into_iter()
+            .filter_map(|(_, duration)| duration)
+            .collect::<Vec<_>>();
+        durations.sort();
+
+        Self {
+            period_secs: period.as_secs(),
+            successful_swaps: successful_swaps as u64,
+            failed_swaps: failed_swaps as u64,
+            median_duration_mins: median(&durations).map(|median| median.as_secs() / 60),
+        }
+    }
+}
+
+fn outcome(state: AliceState) -> Option<Outcome> {
+    match state {
+        AliceState::BtcRedeemed => Some(Outcome::Success),
+        AliceState::XmrRefunded | AliceState::BtcPunished | AliceState::SafelyAborted => {
+            Some(Outcome::Failure)
+        }
+        _ => None,
+    }
+}
+
+fn within(now: OffsetDateTime, time: OffsetDateTime, period: Duration) -> bool {
+    match duration_between(time, now) {
+        Some(age) => age < period,
+        // Timestamps in the future are within any period.
+        None => true,
+    }
+}
+
+fn duration_between(from: OffsetDateTime, to: OffsetDateTime) -> Option<Duration> {
+    let secs = u64::try_from((to - from).whole_seconds()).ok()?;
+
+    Some(Duration::from_secs(secs))
+}
+
+/// The median of sorted `durations`, the lower one of the two middle values
+/// for an even number.
+fn median(durations: &[Duration]) -> Option<Duration> {
+    if durations.is_empty() {
+        return None;
+    }
+
+    Some(durations[(durations.len() - 1) / 2])
+}
+
+/// Posts the report as JSON to the collector.
+pub async fn send(client: &reqwest::Client, collector_url: &Url, report: &Report) -> Result<()> {
+    client
+        .post(collector_url.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(report)?)
+        .send()
+        .await
+        .context("Failed to send telemetry report")?
+        .error_for_status()
+        .context("Collector rejected telemetry report")?;
+
+    Ok(())
+}
+
+/// Sends a [`Report`] to the collector once per interval, forever. Does nothing
+/// unless telemetry is enabled.
+///
+/// If `tor_socks5_port` is given, the reports are sent through Tor.
+pub async fn report_periodically(
+    config: Telemetry,
+    db: Arc<dyn Database + Send + Sync>,
+    tor_socks5_port: Option<u16>,
+) -> Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let period = config
+        .interval_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_INTERVAL);
+
+    let mut client = reqwest::Client::builder();
+    if let Some(port) = tor_socks5_port {
+        client = client.proxy(reqwest::Proxy::all(format!(
+            "socks5h://127.0.0.1:{}",
+            port
+        ))?);
+    }
+    let client = client.build()?;
+
+    tracing::info!(collector_url = %config.collector_url, through_tor = %tor_socks5_port.is_some(), "Sending anonymized swap statistics");
+
+    let mut interval = tokio::time::interval(period);
+
+    // The first tick completes right away, reports only cover full periods.
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let report = match Report::collect(db.as_ref(), period, OffsetDateTime::now_utc()).await {
+            Ok(report) => report,
+            Err(error) => {
+                tracing::warn!("Failed to collect telemetry report: {:#}", error);
+                continue;
+            }
+        };
+
+        match send(&client, &config.collector_url, &report).await {
+            Ok(()) => tracing::debug!(?report, "Sent telemetry report"),
+            Err(error) => tracing::warn!("{:#}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn mins(mins: u64) -> Option<Duration> {
+        Some(Duration::from_secs(mins * 60))
+    }
+
+    #[test]
+    fn report_counts_outcomes_and_takes_median_duration() {
+        let report = Report::aggregate(
+            DAY,
+            vec![
+                (Outcome::Success, mins(30)),
+                (Outcome::Failure, mins(300)),
+                (Outcome::Success, mins(20)),
+                (Outcome::Success, None),
+            ],
+        );
+
+        assert_eq!(
+            report,
+            Report {
+                period_secs: DAY.as_secs(),
+                successful_swaps: 3,
+                failed_swaps: 1,
+                median_duration_mins: Some(30),
+            }
+        );
+    }
+
+    #[test]
+    fn report_without_swaps_has_no_median() {
+        let report = Report::aggregate(DAY, vec![]);
+
+        assert_eq!(report.successful_swaps, 0);
+        assert_eq!(report.failed_swaps, 0);
+        assert_eq!(report.median_duration_mins, None);
+    }
+
+    #[test]
+    fn report_only_serializes_aggregates() {
+        let report = Report::aggregate(DAY, vec![(Outcome::Success, mins(42))]);
+
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"period_secs":86400,"successful_swaps":1,"failed_swaps":0,"median_duration_mins":42}"#
+        );
+    }
+
+    #[test]
+    fn only_swaps_of_the_last_period_are_within_it() {
+        let now = OffsetDateTime::now_utc();
+
+        assert!(within(now, now - time::Duration::hours(23), DAY));
+        assert!(!within(now, now - time::Duration::hours(25), DAY));
+        assert!(within(now, now + time::Duration::hours(1), DAY));
+    }
+}
@@ -0,0 +1,36 @@
+use crate::bitcoin::{self, Address, Amount, Transaction};
+use anyhow::Result;
+
+/// Builds and signs a transaction of the internal wallet that pays `amount`
+/// to `address`, or all of the balance if no amount is given.
+///
+/// Returns the transaction without publishing it, together with the amount
+/// that reaches `address` after fees.
+pub async fn withdraw_btc(
+    bitcoin_wallet: &bitcoin::Wallet,
+    address: Address,
+    amount: Option<Amount>,
+) -> Result<(Transaction, Amount)> {
+    let script_pubkey = address.script_pubkey();
+
+    let amount = match amount {
+        Some(amount) => amount,
+        None => bitcoin_wallet.max_giveable(script_pubkey.len()).await?,
+    };
+
+    let psbt = bitcoin_wallet
+        .send_to_address(address, amount, None)
+        .await?;
+    let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+
+    let net_amount = Amount::from_sat(
+        signed_tx
+            .output
+            .iter()
+            .filter(|output| output.script_pubkey == script_pubkey)
+            .map(|output| output.value)
+            .sum(),
+    );
+
+    Ok((signed_tx, net_amount))
+}
@@ -14,33 +14,52 @@
 
 use anyhow::{bail, Context, Result};
 use comfy_table::Table;
+use futures::StreamExt;
 use libp2p::core::multiaddr::Protocol;
 use libp2p::core::Multiaddr;
-use libp2p::swarm::AddressScore;
+use libp2p::swarm::{AddressScore, NetworkBehaviour, SwarmEvent};
 use libp2p::Swarm;
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::env;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use structopt::clap;
 use structopt::clap::ErrorKind;
-use swap::asb::command::{parse_args, Arguments, Command};
+use swap::asb::admin::{Admin, AdminToken, RunningSwaps};
+use swap::asb::command::{parse_args, Arguments, Command, RestoreFrom};
 use swap::asb::config::{
-    initial_setup, query_user_for_initial_config, read_config, Config, ConfigNotInitialized,
+    initial_setup, query_user_for_initial_config, read_config, validate, Config,
+    ConfigNotInitialized, EffectiveConfig, PriceSource, Report,
 };
-use swap::asb::{cancel, punish, redeem, refund, safely_abort, EventLoop, Finality, KrakenRate};
-use swap::database::open_db;
+use swap::asb::identity::{in_flight_swaps, Identity};
+use swap::asb::run_lock::RunLock;
+use swap::asb::{admin, backup, history, storage, telemetry};
+use swap::asb::{
+    cancel, maker_params, punish, redeem, refund, refunded_xmr_keys, safely_abort,
+    sweep_refunded_xmr, withdraw_btc, EventLoop, Finality, KrakenRate, MakerParams, ManualRate,
+    RateProvider, SwapResumer, SwapSelection,
+};
+use swap::database::{open_db, prunable_swaps};
+use swap::monero::daemon::{DaemonPool, MoneroDaemonAddress};
 use swap::monero::Amount;
 use swap::network::rendezvous::XmrBtcNamespace;
-use swap::network::swarm;
-use swap::protocol::alice::{run, AliceState};
+use swap::network::{compact_address, swarm};
+use swap::protocol::alice::{AliceState, Swap};
+use swap::protocol::summary::SwapSummary;
 use swap::seed::Seed;
 use swap::tor::AuthenticatedClient;
 use swap::{asb, bitcoin, kraken, monero, tor};
+use tokio::sync::mpsc;
+use torut::onion::TorSecretKeyV3;
 use tracing_subscriber::filter::LevelFilter;
 
 const DEFAULT_WALLET_NAME: &str = "asb-wallet";
 
+/// Establishing a circuit to a hidden service can take a while.
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(120);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let Arguments {
@@ -48,7 +67,7 @@ async fn main() -> Result<()> {
         json,
         disable_timestamp,
         config_path,
-        env_config,
+        env_config: _,
         cmd,
     } = match parse_args(env::args_os()) {
         Ok(args) => args,
@@ -70,13 +89,56 @@ async fn main() -> Result<()> {
 
     asb::tracing::init(LevelFilter::DEBUG, json, !disable_timestamp).expect("initialize tracing");
 
+    if let Command::ValidateConfig = cmd {
+        let report = match read_config(config_path.clone()) {
+            Ok(Ok(config)) => validate(&config, &swap::env::new(testnet, &config)),
+            Ok(Err(ConfigNotInitialized {})) => Report::failed(
+                "config",
+                format!("There is no config file at {}", config_path.display()),
+            ),
+            Err(error) => Report::failed("config", format!("{:#}", error)),
+        };
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else if !report.findings.is_empty() {
+            let mut table = Table::new();
+
+            table.set_header(vec!["SEVERITY", "FIELD", "MESSAGE"]);
+
+            for finding in &report.findings {
+                table.add_row(vec![
+                    finding.severity.to_string(),
+                    finding.field.clone(),
+                    finding.message.clone(),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+
+        if report.has_errors() {
+            bail!(
+                "The config file at {} has {} error(s) and {} warning(s)",
+                config_path.display(),
+                report.errors(),
+                report.warnings()
+            );
+        }
+
+        tracing::info!(path = %config_path.display(), warnings = %report.warnings(), "The config file is valid");
+
+        return Ok(());
+    }
+
     let config = match read_config(config_path.clone())? {
         Ok(config) => config,
         Err(ConfigNotInitialized {}) => {
             initial_setup(config_path.clone(), query_user_for_initial_config(testnet)?)?;
-            read_config(config_path)?.expect("after initial setup config can be read")
+            read_config(config_path.clone())?.expect("after initial setup config can be read")
         }
     };
+    let env_config = swap::env::new(testnet, &config);
 
     if config.monero.network != env_config.monero_network {
         bail!(format!(
@@ -91,14 +153,43 @@ async fn main() -> Result<()> {
         ));
     }
 
+    swap::fs::ensure_directory_is_private(&config.data.dir)
+        .context("Failed to restrict permissions of data directory")?;
+
     let db = open_db(config.data.dir.join("sqlite")).await?;
 
     let seed =
         Seed::from_file_or_generate(&config.data.dir).expect("Could not retrieve/initialize seed");
 
     match cmd {
-        Command::Start { resume_only } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+        Command::Start {
+            resume_only,
+            grpc_listen,
+        } => {
+            let _run_lock = RunLock::acquire(&config.data.dir).await?;
+            let mut identity = Identity::load(&config.data.dir)?;
+
+            // The old identity is only kept running for the takers of the swaps
+            // that were in-flight when it was rotated.
+            let retiring = match identity.retiring.clone() {
+                Some(retiring) => {
+                    let pending = retiring.pending_swaps(db.as_ref()).await?;
+
+                    if pending.is_empty() {
+                        identity.retire(&pending)?;
+                        identity.save(&config.data.dir)?;
+
+                        tracing::info!(peer_id = %retiring.peer_id(&seed), "Retired the old identity because all of its swaps finished");
+
+                        None
+                    } else {
+                        Some((retiring, pending))
+                    }
+                }
+                None => None,
+            };
+
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, true).await?;
 
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
 
@@ -116,33 +207,47 @@ async fn main() -> Result<()> {
                 tracing::info!(%monero_balance, "Initialized Monero wallet");
             }
 
-            let kraken_price_updates = kraken::connect(config.maker.price_ticker_ws_url.clone())?;
+            config.maker.validate_price_source()?;
 
-            // setup Tor hidden services
-            let tor_client =
-                tor::Client::new(config.tor.socks5_port).with_control_port(config.tor.control_port);
-            let _ac = match tor_client.assert_tor_running().await {
-                Ok(_) => {
-                    tracing::info!("Setting up Tor hidden service");
-                    let ac =
-                        register_tor_services(config.network.clone().listen, tor_client, &seed)
-                            .await?;
-                    Some(ac)
+            let (maker_params_updater, maker_params) =
+                maker_params::channel(MakerParams::from(&config.maker));
+            let max_buy_ceiling = config
+                .maker
+                .lower_max_buy_to_balance
+                .unwrap_or(false)
+                .then(|| maker_params_updater.max_buy_ceiling());
+
+            #[cfg(unix)]
+            tokio::spawn(adjust_maker_params_on_sighup(
+                config_path,
+                maker_params_updater,
+            ));
+            #[cfg(not(unix))]
+            let _ = maker_params_updater;
+
+            let latest_rate = match config.maker.price_source {
+                PriceSource::Kraken => {
+                    let kraken_price_updates =
+                        kraken::connect(config.maker.price_ticker_ws_url.clone())?;
+
+                    RateProvider::Kraken(KrakenRate::new(
+                        maker_params.clone(),
+                        kraken_price_updates,
+                    ))
                 }
-                Err(_) => {
-                    tracing::warn!("Tor not found. Running on clear net");
-                    None
+                PriceSource::Fixed => {
+                    tracing::info!("Quoting the configured fixed rate instead of a market price");
+
+                    RateProvider::Manual(ManualRate::new(maker_params.clone()))
                 }
             };
-
-            let kraken_rate = KrakenRate::new(config.maker.ask_spread, kraken_price_updates);
             let mut swarm = swarm::asb(
-                &seed,
-                config.maker.min_buy_btc,
-                config.maker.max_buy_btc,
-                kraken_rate.clone(),
+                seed.derive_libp2p_identity_at(identity.generation),
+                maker_params.clone(),
+                latest_rate.clone(),
                 resume_only,
                 env_config,
+                config.network.listen_backlog,
                 config.network.rendezvous_point.map(|rendezvous_point| {
                     (
                         rendezvous_point,
@@ -155,14 +260,77 @@ async fn main() -> Result<()> {
                 }),
             )?;
 
-            for listen in config.network.listen.clone() {
-                Swarm::listen_on(&mut swarm, listen.clone())
-                    .with_context(|| format!("Failed to listen on network interface {}", listen))?;
-            }
+            let listening = listen_on_all(&mut swarm, config.network.listen.clone())?;
 
             tracing::info!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
 
+            for address in &listening {
+                tracing::info!(%address, "Listening on address");
+            }
+
+            let mut hidden_services = vec![(
+                identity.tor_key(&seed),
+                hidden_service_ports(&listening, |port| port),
+            )];
+
+            // The old identity cannot share the ports of the new one, it listens
+            // on the same interfaces with a port of its own instead.
+            let retiring_swarm = match &retiring {
+                Some((retiring, pending)) => {
+                    let mut retiring_swarm = swarm::asb(
+                        seed.derive_libp2p_identity_at(retiring.generation),
+                        maker_params.clone(),
+                        latest_rate.clone(),
+                        true,
+                        env_config,
+                        config.network.listen_backlog,
+                        None,
+                    )?;
+                    let port = listen_on_new_port(&mut retiring_swarm, &listening).await?;
+
+                    for external_address in &config.network.external_addresses {
+                        let external_address = with_tcp_port(external_address, port);
+
+                        tracing::info!(address = %external_address, "Advertising external address of the old identity");
+                        let _ = Swarm::add_external_address(
+                            &mut retiring_swarm,
+                            external_address,
+                            AddressScore::Infinite,
+                        );
+                    }
+
+                    tracing::info!(peer_id = %retiring_swarm.local_peer_id(), %port, pending_swaps = %pending.len(), "Keeping the old identity until its swaps finished");
+
+                    hidden_services.push((
+                        retiring.tor_key(&seed),
+                        hidden_service_ports(&listening, |_| port),
+                    ));
+
+                    Some((retiring_swarm, pending.clone()))
+                }
+                None => None,
+            };
+
+            // setup Tor hidden services for the addresses we actually listen on
+            let tor_client =
+                tor::Client::new(config.tor.socks5_port).with_control_port(config.tor.control_port);
+            let _ac = match tor_client.assert_tor_running().await {
+                Ok(_) => {
+                    tracing::info!("Setting up Tor hidden service");
+                    let ac = register_tor_services(tor_client, hidden_services).await?;
+                    Some(ac)
+                }
+                Err(_) => {
+                    if retiring_swarm.is_some() {
+                        tracing::warn!("Tor not found, the old identity is only reachable through its onion address");
+                    }
+                    tracing::warn!("Tor not found. Running on clear net");
+                    None
+                }
+            };
+
             for external_address in config.network.external_addresses {
+                tracing::info!(address = %external_address, "Advertising external address");
                 let _ = Swarm::add_external_address(
                     &mut swarm,
                     external_address,
@@ -170,74 +338,259 @@ async fn main() -> Result<()> {
                 );
             }
 
-            let (event_loop, mut swap_receiver) = EventLoop::new(
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let monero_wallet = Arc::new(monero_wallet);
+
+            if let Some(telemetry) = config.telemetry.clone() {
+                let tor_socks5_port = tor::Client::new(config.tor.socks5_port)
+                    .assert_tor_running()
+                    .await
+                    .ok()
+                    .map(|_| config.tor.socks5_port);
+
+                let db = db.clone();
+                tokio::spawn(async move {
+                    if let Err(error) =
+                        telemetry::report_periodically(telemetry, db, tor_socks5_port).await
+                    {
+                        tracing::warn!("Stopped sending telemetry reports: {:#}", error);
+                    }
+                });
+            }
+
+            tokio::spawn(storage::monitor_storage(
+                config.data.dir.clone(),
+                config.data.max_size_mb,
+            ));
+
+            tokio::spawn(asb::monitor_liquidity(
+                monero_wallet.clone(),
+                db.clone(),
+                latest_rate.clone(),
+                maker_params.clone(),
+                max_buy_ceiling,
+            ));
+
+            let running_swaps = RunningSwaps::default();
+
+            if let Some(address) = grpc_listen {
+                let token = AdminToken::load_or_generate(&config.data.dir)?;
+                let admin = Admin::new(
+                    db.clone(),
+                    bitcoin_wallet.clone(),
+                    monero_wallet.clone(),
+                    env_config,
+                    running_swaps.clone(),
+                    token,
+                );
+
+                tracing::info!(%address, token = %AdminToken::path(&config.data.dir).display(), "Serving the admin interface");
+                tokio::spawn(async move {
+                    if let Err(error) = admin::serve(address, admin).await {
+                        tracing::error!("Stopped serving the admin interface: {:#}", error);
+                    }
+                });
+            }
+
+            if let Some((retiring_swarm, pending)) = retiring_swarm {
+                let (retiring_event_loop, swap_receiver) = EventLoop::new(
+                    retiring_swarm,
+                    env_config,
+                    bitcoin_wallet.clone(),
+                    monero_wallet.clone(),
+                    db.clone(),
+                    latest_rate.clone(),
+                    maker_params.clone(),
+                )?;
+                let retiring_event_loop =
+                    retiring_event_loop.with_resumed_swaps(SwapSelection::Only(pending));
+
+                spawn_swaps(
+                    swap_receiver,
+                    latest_rate.clone(),
+                    retiring_event_loop.swap_resumer(),
+                    running_swaps.clone(),
+                );
+                tokio::spawn(retiring_event_loop.run());
+            }
+
+            let retiring_swaps = retiring
+                .map(|(retiring, _)| retiring.swaps)
+                .unwrap_or_default();
+
+            let (event_loop, swap_receiver) = EventLoop::new(
                 swarm,
                 env_config,
-                Arc::new(bitcoin_wallet),
-                Arc::new(monero_wallet),
+                bitcoin_wallet,
+                monero_wallet,
                 db,
-                kraken_rate.clone(),
-                config.maker.min_buy_btc,
-                config.maker.max_buy_btc,
+                latest_rate.clone(),
+                maker_params,
             )
             .unwrap();
+            let event_loop = event_loop.with_resumed_swaps(SwapSelection::Except(retiring_swaps));
 
-            tokio::spawn(async move {
-                while let Some(swap) = swap_receiver.recv().await {
-                    let rate = kraken_rate.clone();
-                    tokio::spawn(async move {
-                        let swap_id = swap.swap_id;
-                        match run(swap, rate).await {
-                            Ok(state) => {
-                                tracing::debug!(%swap_id, final_state=%state, "Swap completed")
-                            }
-                            Err(error) => {
-                                tracing::error!(%swap_id, "Swap failed: {:#}", error)
-                            }
-                        }
-                    });
-                }
-            });
+            spawn_swaps(
+                swap_receiver,
+                latest_rate,
+                event_loop.swap_resumer(),
+                running_swaps,
+            );
 
             event_loop.run().await;
         }
-        Command::History => {
+        Command::History { since, until, wide } => {
+            let rows = history::entries(db.as_ref(), since, until)
+                .await?
+                .into_iter()
+                .map(|entry| {
+                    vec![
+                        entry.swap_id.to_string(),
+                        entry.peer.unwrap_or_else(|| "-".to_owned()),
+                        entry
+                            .started_at
+                            .map(|started_at| started_at.to_string())
+                            .unwrap_or_else(|| "unknown time".to_owned()),
+                        entry.state.to_string(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+
+            let width = if wide || !atty::is(atty::Stream::Stdout) {
+                None
+            } else {
+                Table::new().get_table_width().map(usize::from)
+            };
+
+            println!(
+                "{}",
+                history::render(&["SWAP ID", "PEER", "STARTED", "STATE"], &rows, width)
+            );
+        }
+        Command::LabelPeer { peer_id, label } => {
+            db.insert_peer_label(peer_id, label.clone()).await?;
+
+            tracing::info!(%peer_id, %label, "Labeled peer");
+        }
+        Command::Peers => {
+            let mut swaps_per_peer = BTreeMap::<String, (Option<String>, usize)>::new();
+
+            for (peer_id, label) in db.all_peer_labels().await? {
+                swaps_per_peer.insert(peer_id.to_string(), (Some(label), 0));
+            }
+
+            for (swap_id, _) in db.all().await? {
+                if let Ok(peer_id) = db.get_peer_id(swap_id).await {
+                    swaps_per_peer.entry(peer_id.to_string()).or_default().1 += 1;
+                }
+            }
+
+            let mut table = Table::new();
+
+            table.set_header(vec!["PEER ID", "LABEL", "SWAPS"]);
+
+            for (peer_id, (label, swaps)) in swaps_per_peer {
+                table.add_row(vec![peer_id, label.unwrap_or_default(), swaps.to_string()]);
+            }
+
+            println!("{}", table);
+        }
+        Command::Prune {
+            older_than,
+            dry_run,
+        } => {
             let mut table = Table::new();
 
-            table.set_header(vec!["SWAP ID", "STATE"]);
+            table.set_header(vec!["SWAP ID", "STATE", "LAST UPDATED"]);
 
-            for (swap_id, state) in db.all().await? {
+            for (swap_id, state, entered_at) in prunable_swaps(db.as_ref(), older_than).await? {
                 let state: AliceState = state.try_into()?;
-                table.add_row(vec![swap_id.to_string(), state.to_string()]);
+
+                if !dry_run {
+                    db.delete(swap_id).await?;
+                }
+
+                table.add_row(vec![
+                    swap_id.to_string(),
+                    state.to_string(),
+                    entered_at.to_string(),
+                ]);
             }
 
+            if dry_run {
+                println!("Would delete the following swaps:");
+            } else {
+                println!("Deleted the following swaps:");
+            }
             println!("{}", table);
         }
+        Command::CompactDatabase => {
+            let before = storage::StorageUsage::measure(&config.data.dir)?;
+
+            db.compact().await?;
+
+            let after = storage::StorageUsage::measure(&config.data.dir)?;
+
+            println!(
+                "Compacted the swap database from {} to {} bytes",
+                before.swap_database, after.swap_database
+            );
+        }
+        Command::Export { swap_id } => {
+            let backup = backup::export_swap(swap_id, db).await?;
+
+            println!("{}", serde_json::to_string_pretty(&backup)?);
+        }
+        Command::Import { file, force } => {
+            let json = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let backup = backup::SwapBackup::from_json(&json)?;
+            let swap_id = backup.swap_id;
+            let state = backup.latest_state();
+
+            backup::import_swap(backup, db, force).await?;
+
+            if let Some(state) = state {
+                tracing::info!(%swap_id, %state, "Imported swap, it is resumed when the ASB starts");
+            }
+        }
         Command::Config => {
             let config_json = serde_json::to_string_pretty(&config)?;
             println!("{}", config_json);
         }
-        Command::WithdrawBtc { amount, address } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+        Command::ValidateConfig => unreachable!("The config is validated before it is read"),
+        Command::ShowConfig => {
+            let effective_config = EffectiveConfig::new(config_path, &config, testnet);
+            println!("{}", serde_json::to_string_pretty(&effective_config)?);
+        }
+        Command::WithdrawBtc {
+            amount,
+            address,
+            dry_run,
+        } => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, true).await?;
 
-            let amount = match amount {
-                Some(amount) => amount,
-                None => {
-                    bitcoin_wallet
-                        .max_giveable(address.script_pubkey().len())
-                        .await?
-                }
-            };
+            let (signed_tx, net_amount) =
+                withdraw_btc(&bitcoin_wallet, address.clone(), amount).await?;
 
-            let psbt = bitcoin_wallet
-                .send_to_address(address, amount, None)
-                .await?;
-            let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+            if dry_run {
+                let fee = bitcoin_wallet.transaction_fee_details(&signed_tx).await?;
 
-            bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+                tracing::info!(
+                    txid = %signed_tx.txid(),
+                    %address,
+                    %net_amount,
+                    fee = %fee.fee,
+                    fee_rate_sat_per_vb = %format!("{:.2}", fee.fee_rate()),
+                    vsize = %fee.vsize,
+                    "Withdraw transaction was not broadcast because of --dry-run"
+                );
+            } else {
+                bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+            }
         }
         Command::Balance => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, true).await?;
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
 
             let bitcoin_balance = bitcoin_wallet.balance().await?;
@@ -248,15 +601,47 @@ async fn main() -> Result<()> {
                 %monero_balance,
                 "Current balance");
         }
+        Command::CreateMoneroSubaddress { label } => {
+            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+
+            let subaddress = monero_wallet.create_subaddress(label).await?;
+
+            println!("{}", subaddress.address);
+        }
+        Command::MoneroSubaddresses => {
+            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+
+            let mut table = Table::new();
+
+            table.set_header(vec![
+                "INDEX",
+                "LABEL",
+                "ADDRESS",
+                "BALANCE",
+                "UNLOCKED BALANCE",
+            ]);
+
+            for subaddress in monero_wallet.subaddresses().await? {
+                table.add_row(vec![
+                    subaddress.index.to_string(),
+                    subaddress.label,
+                    subaddress.address.to_string(),
+                    subaddress.balance.to_string(),
+                    subaddress.unlocked_balance.to_string(),
+                ]);
+            }
+
+            println!("{}", table);
+        }
         Command::Cancel { swap_id } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, true).await?;
 
             let (txid, _) = cancel(swap_id, Arc::new(bitcoin_wallet), db).await?;
 
             tracing::info!("Cancel transaction successfully published with id {}", txid);
         }
         Command::Refund { swap_id } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, true).await?;
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
 
             refund(
@@ -270,7 +655,7 @@ async fn main() -> Result<()> {
             tracing::info!("Monero successfully refunded");
         }
         Command::Punish { swap_id } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, true).await?;
 
             let (txid, _) = punish(swap_id, Arc::new(bitcoin_wallet), db).await?;
 
@@ -281,11 +666,58 @@ async fn main() -> Result<()> {
 
             tracing::info!("Swap safely aborted");
         }
+        Command::SweepRefundedXmr { swap_id, address } => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, true).await?;
+            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+
+            let tx_hashes = sweep_refunded_xmr(
+                swap_id,
+                Arc::new(bitcoin_wallet),
+                Arc::new(monero_wallet),
+                db,
+                address,
+            )
+            .await?;
+
+            for tx_hash in tx_hashes {
+                tracing::info!(%address, "Refunded Monero swept in transaction {}", tx_hash.0);
+            }
+        }
+        Command::RestoreMoneroWallet { wallet_name, from } => {
+            let keys = match from {
+                RestoreFrom::Keys(keys) => keys,
+                RestoreFrom::Swap(swap_id) => {
+                    let bitcoin_wallet =
+                        init_bitcoin_wallet(&config, &seed, env_config, true).await?;
+
+                    refunded_xmr_keys(swap_id, &bitcoin_wallet, db.as_ref())
+                        .await?
+                        .with_context(|| {
+                            format!(
+                                "The keys of swap {} are no longer stored, its Monero wallet is named after the swap",
+                                swap_id
+                            )
+                        })?
+                }
+            };
+            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+
+            let address = monero_wallet
+                .generate_from_keys(wallet_name.clone(), keys)
+                .await?;
+
+            tracing::info!(
+                monero_wallet_name = %wallet_name,
+                %address,
+                restore_height = %keys.restore_height.height,
+                "Generated Monero wallet from keys in the wallet directory of the monero-wallet-rpc"
+            );
+        }
         Command::Redeem {
             swap_id,
             do_not_await_finality,
         } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, true).await?;
 
             let (txid, _) = redeem(
                 swap_id,
@@ -298,34 +730,271 @@ async fn main() -> Result<()> {
             tracing::info!("Redeem transaction successfully published with id {}", txid);
         }
         Command::ExportBitcoinWallet => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config, false).await?;
             let wallet_export = bitcoin_wallet.wallet_export("asb").await?;
             println!("{}", wallet_export.to_string())
         }
+        Command::SellerAddresses => {
+            let identity = Identity::load(&config.data.dir)?;
+            let peer_id = identity.peer_id(&seed);
+
+            let mut table = Table::new();
+
+            table.set_header(vec!["ADDRESS", "COMPACT ADDRESS"]);
+
+            for address in seller_addresses(&config, &identity.tor_key(&seed))? {
+                let address = address.with(Protocol::P2p(peer_id.into()));
+                let compact_address = compact_address::encode(&address);
+
+                table.add_row(vec![address.to_string(), compact_address]);
+            }
+
+            println!("{}", table);
+        }
+        Command::PeerId => {
+            let peer_id = Identity::load(&config.data.dir)?.peer_id(&seed);
+
+            println!("{}", peer_id);
+        }
+        Command::RotateIdentity => {
+            // A running ASB would keep listening with the rotated identity and
+            // start new swaps with it until it is restarted
+            let _run_lock = RunLock::acquire(&config.data.dir)
+                .await
+                .context("Stop the ASB before rotating its identity")?;
+            let mut identity = Identity::load(&config.data.dir)?;
+
+            let pending = match &identity.retiring {
+                Some(retiring) => retiring.pending_swaps(db.as_ref()).await?,
+                None => Vec::new(),
+            };
+            let in_flight = in_flight_swaps(db.as_ref()).await?;
+            let in_flight_count = in_flight.len();
+
+            identity.rotate(in_flight, &pending)?;
+            identity.save(&config.data.dir)?;
+
+            tracing::info!(
+                generation = %identity.generation,
+                peer_id = %identity.peer_id(&seed),
+                onion_address = %onion_address(&identity.tor_key(&seed)),
+                in_flight_swaps = %in_flight_count,
+                "Rotated the identity, restart the ASB to use it"
+            );
+        }
+        Command::IdentityStatus => {
+            let identity = Identity::load(&config.data.dir)?;
+
+            let mut table = Table::new();
+
+            table.set_header(vec!["GENERATION", "STATUS", "PEER ID", "ONION ADDRESS"]);
+            table.add_row(vec![
+                identity.generation.to_string(),
+                "active".to_owned(),
+                identity.peer_id(&seed).to_string(),
+                onion_address(&identity.tor_key(&seed)),
+            ]);
+
+            let pending = match &identity.retiring {
+                Some(retiring) => {
+                    table.add_row(vec![
+                        retiring.generation.to_string(),
+                        "retiring".to_owned(),
+                        retiring.peer_id(&seed).to_string(),
+                        onion_address(&retiring.tor_key(&seed)),
+                    ]);
+
+                    Some(retiring.pending_swaps(db.as_ref()).await?)
+                }
+                None => None,
+            };
+
+            println!("{}", table);
+
+            match pending {
+                None => println!("There is no old identity to retire."),
+                Some(pending) if pending.is_empty() => println!(
+                    "All swaps of the old identity finished, it is safe to retire it. It is retired the next time the ASB starts."
+                ),
+                Some(pending) => {
+                    println!(
+                        "The old identity cannot be retired yet, {} swap(s) still use it:",
+                        pending.len()
+                    );
+
+                    for swap_id in pending {
+                        println!("{}", swap_id);
+                    }
+                }
+            }
+        }
+        Command::ShowXmrProof { swap_id } => {
+            let proof = db.get_monero_lock_proof(swap_id).await?;
+
+            let mut table = Table::new();
+
+            table.set_header(vec!["FIELD", "VALUE"]);
+            table.add_row(vec!["Swap ID".to_owned(), swap_id.to_string()]);
+            table.add_row(vec![
+                "Transaction hash".to_owned(),
+                proof.transfer_proof.tx_hash().to_string(),
+            ]);
+            table.add_row(vec![
+                "Transaction key".to_owned(),
+                proof.transfer_proof.tx_key().to_string(),
+            ]);
+            table.add_row(vec!["Address".to_owned(), proof.address.to_string()]);
+            table.add_row(vec!["Amount".to_owned(), proof.amount.to_string()]);
+
+            println!("{}", table);
+            println!(
+                "Verify with: monero-wallet-cli check_tx_key {} {} {}",
+                proof.transfer_proof.tx_hash(),
+                proof.transfer_proof.tx_key(),
+                proof.address
+            );
+        }
+        Command::CheckReachability => {
+            tor::Client::new(config.tor.socks5_port)
+                .assert_tor_running()
+                .await
+                .context("Tor is required to check the reachability of this ASB")?;
+
+            let identity = Identity::load(&config.data.dir)?;
+            let peer_id = identity.peer_id(&seed);
+
+            let mut table = Table::new();
+
+            table.set_header(vec!["ADDRESS", "STATUS"]);
+
+            let mut all_reachable = true;
+
+            for address in seller_addresses(&config, &identity.tor_key(&seed))? {
+                tracing::info!(%address, "Checking reachability");
+
+                let reachability = asb::check_reachability(
+                    address.clone().with(Protocol::P2p(peer_id.into())),
+                    peer_id,
+                    config.tor.socks5_port,
+                    REACHABILITY_TIMEOUT,
+                )
+                .await?;
+
+                all_reachable &= matches!(reachability, asb::Reachability::Reachable { .. });
+                table.add_row(vec![address.to_string(), reachability.to_string()]);
+            }
+
+            println!("{}", table);
+
+            if !all_reachable {
+                bail!("Some addresses of this ASB are not reachable, make sure the ASB is running and the hidden service forwards to the ports the ASB listens on");
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Re-reads the maker section of the config file whenever the ASB receives
+/// `SIGHUP` and publishes the new parameters to the running event loop.
+#[cfg(unix)]
+async fn adjust_maker_params_on_sighup(
+    config_path: std::path::PathBuf,
+    updater: asb::MakerParamsUpdater,
+) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(error) => {
+            tracing::warn!(
+                "Failed to listen for SIGHUP, maker parameters cannot be adjusted at runtime: {:#}",
+                error
+            );
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        tracing::info!(path = %config_path.display(), "Received SIGHUP, re-reading maker parameters from config file");
+
+        let config = match read_config(config_path.clone()) {
+            Ok(Ok(config)) => config,
+            Ok(Err(ConfigNotInitialized {})) => {
+                tracing::warn!(
+                    "Config file does not exist anymore, keeping current maker parameters"
+                );
+                continue;
+            }
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to read config file, keeping current maker parameters: {:#}",
+                    error
+                );
+                continue;
+            }
+        };
+
+        if let Err(error) = config.maker.validate_price_source() {
+            tracing::warn!("Keeping current maker parameters: {:#}", error);
+            continue;
+        }
+
+        if let Err(error) = updater.update(MakerParams::from(&config.maker)) {
+            tracing::warn!("Failed to adjust maker parameters: {:#}", error);
+        }
+    }
+}
+
 async fn init_bitcoin_wallet(
     config: &Config,
     seed: &Seed,
     env_config: swap::env::Config,
+    sync: bool,
 ) -> Result<bitcoin::Wallet> {
     tracing::debug!("Opening Bitcoin wallet");
-    let wallet_dir = config.data.dir.join("wallet");
 
-    let wallet = bitcoin::Wallet::new(
-        config.bitcoin.electrum_rpc_url.clone(),
-        &wallet_dir,
-        seed.derive_extended_private_key(env_config.bitcoin_network)?,
-        env_config,
-        config.bitcoin.target_block,
+    let wallet = match &config.bitcoin.external_signer {
+        None => {
+            bitcoin::Wallet::new(
+                config.bitcoin.backend("asb")?,
+                &config.data.dir.join("wallet"),
+                seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                env_config,
+                config.bitcoin.target_block,
+            )
+            .await
+        }
+        Some(external_signer) => {
+            tracing::info!(url = %external_signer.url, "Signing Bitcoin transactions with external signer");
+
+            // The watch-only wallet has different descriptors than the one
+            // derived from the seed and thus needs its own database.
+            bitcoin::Wallet::new_with_external_signer(
+                config.bitcoin.backend("asb-external-signer")?,
+                &config.data.dir.join("wallet-external-signer"),
+                external_signer.account_xpub,
+                external_signer.master_fingerprint,
+                external_signer.url.clone(),
+                env_config,
+                config.bitcoin.target_block,
+            )
+            .await
+        }
+    }
+    .context("Failed to initialize Bitcoin wallet")?
+    .with_fee_bump_schedule(config.bitcoin.fee_bump_schedule()?)
+    .with_confirmation_quorum(
+        config.bitcoin.confirmation_quorum()?,
+        env_config.bitcoin_electrum_connect_timeout,
     )
-    .await
-    .context("Failed to initialize Bitcoin wallet")?;
+    .await?;
 
-    wallet.sync().await?;
+    // Commands that only read the wallet's database don't need to wait for a
+    // sync with the Electrum server.
+    if sync {
+        wallet.sync().await?;
+    }
 
     Ok(wallet)
 }
@@ -340,27 +1009,212 @@ async fn init_monero_wallet(
         DEFAULT_WALLET_NAME.to_string(),
         env_config,
     )
+    .await?
+    .with_account_index(config.monero.account_index.unwrap_or_default())
+    .await?
+    .with_swap_wallet_rpcs(config.monero.swap_wallet_rpc_urls.clone())
     .await?;
 
-    Ok(wallet)
+    if config.monero.daemons.is_empty() {
+        return Ok(wallet);
+    }
+
+    let daemons: Vec<(MoneroDaemonAddress, bool)> = config
+        .monero
+        .daemons
+        .iter()
+        .map(|daemon| Ok((daemon.address.parse()?, daemon.primary)))
+        .collect::<Result<_>>()?;
+    let pool = DaemonPool::new(daemons, env_config.monero_network).await?;
+
+    wallet.with_daemon_pool(pool).await
 }
 
-/// Registers a hidden service for each network.
-/// Note: Once ac goes out of scope, the services will be de-registered.
-async fn register_tor_services(
-    networks: Vec<Multiaddr>,
-    tor_client: tor::Client,
-    seed: &Seed,
-) -> Result<AuthenticatedClient> {
-    let mut ac = tor_client.into_authenticated_client().await?;
+/// The addresses under which takers can reach this ASB, i.e. the configured
+/// external addresses and the onion addresses of the hidden services for the
+/// tcp ports we listen on.
+fn seller_addresses(config: &Config, tor_key: &TorSecretKeyV3) -> Result<Vec<Multiaddr>> {
+    let onion_address = tor_key
+        .public()
+        .get_onion_address()
+        .get_address_without_dot_onion();
+
+    let onion_addresses = config
+        .network
+        .listen
+        .iter()
+        .flat_map(|listen| listen.iter())
+        .filter_map(|protocol| match protocol {
+            Protocol::Tcp(port) => Some(port),
+            _ => None,
+        })
+        .map(|port| {
+            format!("/onion3/{}:{}", onion_address, port)
+                .parse::<Multiaddr>()
+                .context("Failed to construct onion address")
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    let hidden_services_details = networks
+    Ok(config
+        .network
+        .external_addresses
+        .iter()
+        .cloned()
+        .chain(onion_addresses)
+        .collect())
+}
+
+/// Listens on as many of the given addresses as possible and returns the ones
+/// we listen on.
+///
+/// Only fails if we cannot listen on any of the addresses, so that a single
+/// unavailable interface does not prevent the ASB from starting.
+fn listen_on_all<B>(swarm: &mut Swarm<B>, addresses: Vec<Multiaddr>) -> Result<Vec<Multiaddr>>
+where
+    B: NetworkBehaviour,
+{
+    let mut listening = Vec::new();
+
+    for address in addresses {
+        match swarm.listen_on(address.clone()) {
+            Ok(_) => listening.push(address),
+            Err(error) => {
+                tracing::warn!(%address, "Failed to listen on network interface: {}", error)
+            }
+        }
+    }
+
+    if listening.is_empty() {
+        bail!("Failed to listen on any of the configured network interfaces");
+    }
+
+    Ok(listening)
+}
+
+/// Listens on the interfaces of `networks` with a tcp port chosen by the
+/// operating system for the first one, and returns that port.
+///
+/// The port is only known once the swarm reports the listener, the other
+/// networks are listened on with the same port if it is free there as well.
+async fn listen_on_new_port<B>(swarm: &mut Swarm<B>, networks: &[Multiaddr]) -> Result<u16>
+where
+    B: NetworkBehaviour,
+{
+    let (first, others) = networks
+        .split_first()
+        .context("No network interface to listen on")?;
+    let listener = swarm.listen_on(with_tcp_port(first, 0))?;
+
+    let port = loop {
+        match swarm.select_next_some().await {
+            SwarmEvent::NewListenAddr {
+                listener_id,
+                address,
+            } if listener_id == listener => {
+                if let Some(port) = tcp_port(&address) {
+                    break port;
+                }
+            }
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                reason,
+                ..
+            } if listener_id == listener => {
+                bail!("Failed to listen on {}: {:?}", first, reason)
+            }
+            _ => {}
+        }
+    };
+
+    for network in others {
+        let address = with_tcp_port(network, port);
+
+        if let Err(error) = swarm.listen_on(address.clone()) {
+            tracing::warn!(%address, "Failed to listen on network interface: {}", error)
+        }
+    }
+
+    Ok(port)
+}
+
+fn with_tcp_port(address: &Multiaddr, port: u16) -> Multiaddr {
+    address
+        .iter()
+        .map(|protocol| match protocol {
+            Protocol::Tcp(_) => Protocol::Tcp(port),
+            protocol => protocol,
+        })
+        .collect()
+}
+
+fn tcp_port(address: &Multiaddr) -> Option<u16> {
+    address.iter().find_map(|protocol| match protocol {
+        Protocol::Tcp(port) => Some(port),
+        _ => None,
+    })
+}
+
+/// Runs each swap received from an event loop until it finished, restarting
+/// it on failure.
+fn spawn_swaps(
+    mut swap_receiver: mpsc::Receiver<Swap>,
+    latest_rate: RateProvider,
+    swap_resumer: SwapResumer,
+    running_swaps: RunningSwaps,
+) {
+    tokio::spawn(async move {
+        while let Some(swap) = swap_receiver.recv().await {
+            let rate = latest_rate.clone();
+            let swap_resumer = swap_resumer.clone();
+            let running = running_swaps.insert(swap.swap_id);
+            tokio::spawn(async move {
+                let _running = running;
+                let swap_id = swap.swap_id;
+                let db = swap.db.clone();
+                match asb::run_with_restarts(swap, rate, swap_resumer).await {
+                    Ok(state) => match SwapSummary::of_alice_swap(swap_id, db.as_ref()).await {
+                        Ok(summary) => {
+                            tracing::info!(
+                                %swap_id,
+                                outcome = %summary.outcome,
+                                btc = ?summary.btc,
+                                xmr = ?summary.xmr,
+                                duration_secs = ?summary.duration.map(|duration| duration.as_secs()),
+                                "Swap completed"
+                            )
+                        }
+                        Err(error) => {
+                            tracing::warn!(
+                                %swap_id,
+                                final_state = %state,
+                                "Swap completed, but failed to summarize it: {:#}",
+                                error
+                            )
+                        }
+                    },
+                    Err(error) => {
+                        tracing::error!(%swap_id, "Swap failed: {:#}", error)
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// The onion port and the local target of a hidden service for each tcp port
+/// of the given networks. `target_port` maps the tcp port to the local port
+/// that the hidden service forwards to.
+fn hidden_service_ports(
+    networks: &[Multiaddr],
+    target_port: impl Fn(u16) -> u16,
+) -> Vec<(u16, SocketAddr)> {
+    networks
         .iter()
         .flat_map(|network| {
             network.iter().map(|protocol| match protocol {
                 Protocol::Tcp(port) => Some((
                     port,
-                    SocketAddr::new(IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), port),
+                    SocketAddr::new(IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), target_port(port)),
                 )),
                 _ => {
                     // We only care for Tcp for now.
@@ -369,21 +1223,40 @@ async fn register_tor_services(
             })
         })
         .flatten()
-        .collect::<Vec<_>>();
+        .collect()
+}
+
+fn onion_address(tor_key: &TorSecretKeyV3) -> String {
+    format!(
+        "{}.onion",
+        tor_key
+            .public()
+            .get_onion_address()
+            .get_address_without_dot_onion()
+    )
+}
 
-    let key = seed.derive_torv3_key();
+/// Registers a hidden service with each key for its ports.
+/// Note: Once ac goes out of scope, the services will be de-registered.
+async fn register_tor_services(
+    tor_client: tor::Client,
+    hidden_services: Vec<(TorSecretKeyV3, Vec<(u16, SocketAddr)>)>,
+) -> Result<AuthenticatedClient> {
+    let mut ac = tor_client.into_authenticated_client().await?;
 
-    ac.add_services(&hidden_services_details, &key).await?;
+    for (key, ports) in hidden_services {
+        ac.add_services(&ports, &key).await?;
 
-    let onion_address = key
-        .public()
-        .get_onion_address()
-        .get_address_without_dot_onion();
+        let onion_address = key
+            .public()
+            .get_onion_address()
+            .get_address_without_dot_onion();
 
-    hidden_services_details.iter().for_each(|(port, _)| {
-        let onion_address = format!("/onion3/{}:{}", onion_address, port);
-        tracing::info!(%onion_address, "Successfully created hidden service");
-    });
+        ports.iter().for_each(|(port, _)| {
+            let onion_address = format!("/onion3/{}:{}", onion_address, port);
+            tracing::info!(%onion_address, "Successfully created hidden service");
+        });
+    }
 
     Ok(ac)
 }
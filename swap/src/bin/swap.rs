@@ -14,30 +14,40 @@
 
 use anyhow::{bail, Context, Result};
 use comfy_table::Table;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
+use libp2p::PeerId;
 use qrcode::render::unicode;
 use qrcode::QrCode;
 use std::cmp::min;
 use std::convert::TryInto;
 use std::env;
 use std::future::Future;
+use std::iter;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use swap::bitcoin::TxLock;
 use swap::cli::command::{parse_args_and_apply_defaults, Arguments, Command, ParseResult};
-use swap::cli::{list_sellers, EventLoop, SellerStatus};
-use swap::database::open_db;
+use swap::cli::{list_sellers, EventLoop, QuoteRefresh, RateBounds, SellerStatus, SellersCache};
+use swap::database::{open_db, prunable_swaps};
 use swap::env::Config;
 use swap::libp2p_ext::MultiAddrExt;
+use swap::monero::daemon::{Daemons, MoneroDaemonAddress};
 use swap::network::quote::BidQuote;
 use swap::network::swarm;
 use swap::protocol::bob;
 use swap::protocol::bob::{BobState, Swap};
+use swap::protocol::summary::SwapSummary;
+use swap::protocol::{fee_strategy, Database};
 use swap::seed::Seed;
-use swap::{bitcoin, cli, monero};
+use swap::{bitcoin, cli, monero, tor};
 use url::Url;
 use uuid::Uuid;
 
+/// How long `doctor` waits for the seller to answer a quote request.
+const SELLER_REACHABILITY_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let Arguments {
@@ -58,36 +68,87 @@ async fn main() -> Result<()> {
         Command::BuyXmr {
             seller,
             bitcoin_electrum_rpc_url,
-            bitcoin_target_block,
+            bitcoin_target_block: _,
             bitcoin_change_address,
+            bitcoin_coin_selection,
+            bitcoin_utxos,
+            bitcoin_fee_bump_schedule,
+            bitcoin_fee_strategy,
+            bitcoin_confirmation_quorum,
             monero_receive_address,
             monero_daemon_address,
+            monero_daemon_fallback_addresses,
+            rate_bounds,
+            quote_refresh,
+            seller_whitelist,
+            own_peer_ids,
             tor_socks5_port,
+            tor_control_port,
+            tor_bootstrap_timeout,
+            refund_on_failure,
+            pin_electrum_server,
         } => {
             let swap_id = Uuid::new_v4();
 
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            if monero_receive_address.network != env_config.monero_network {
+                tracing::warn!(
+                    receive_address_network = ?monero_receive_address.network,
+                    swap_network = ?env_config.monero_network,
+                    "UNSAFE: The Monero receive address is on another network than the swap because of --allow-different-monero-network. The Monero is lost if the address cannot receive it, only use this for testing"
+                );
+            }
             let db = open_db(data_dir.join("sqlite")).await?;
             let seed = Seed::from_file_or_generate(data_dir.as_path())
                 .context("Failed to read in seed file")?;
-
+            let seller_peer_id = seller
+                .extract_peer_id()
+                .context("Seller address must contain peer ID")?;
+            cli::ensure_not_self(
+                seller_peer_id,
+                PeerId::from(seed.derive_libp2p_identity().public()),
+                &own_peer_ids,
+            )?;
+
+            // The fees of the lock transaction and of the amount that can be
+            // swapped at most are estimated for the target block of the wallet.
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_url.clone(),
                 &seed,
                 data_dir.clone(),
                 env_config,
-                bitcoin_target_block,
+                bitcoin_fee_strategy.lock,
+                true,
+            )
+            .await?
+            .with_coin_selection(bitcoin_coin_selection)
+            .with_utxos(bitcoin_utxos)
+            .with_fee_bump_schedule(bitcoin_fee_bump_schedule)
+            .with_confirmation_quorum(
+                bitcoin_confirmation_quorum,
+                env_config.bitcoin_electrum_connect_timeout,
+            )
+            .await?;
+            let (monero_wallet, _process) = init_monero_wallet(
+                data_dir,
+                monero_daemon_address,
+                monero_daemon_fallback_addresses,
+                env_config,
             )
             .await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
-            let seller_peer_id = seller
-                .extract_peer_id()
-                .context("Seller address must contain peer ID")?;
+            if !seller_whitelist.is_trusted(seller_peer_id, &seller) {
+                bail!("Seller address {} is not whitelisted", seller);
+            }
             db.insert_address(seller_peer_id, seller.clone()).await?;
 
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
+            let behaviour = cli::Behaviour::new(
+                seller_peer_id,
+                env_config,
+                bitcoin_wallet.clone(),
+                rate_bounds,
+            );
+            wait_for_tor(tor_socks5_port, tor_control_port, tor_bootstrap_timeout).await?;
             let mut swarm =
                 swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
             swarm.behaviour_mut().add_address(seller_peer_id, seller);
@@ -99,26 +160,47 @@ async fn main() -> Result<()> {
             let event_loop = tokio::spawn(event_loop.run());
 
             let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
-            let (amount, fees) = determine_btc_to_swap(
+            let estimate_lock_fee = |amount| bitcoin_wallet.estimate_fee(TxLock::weight(), amount);
+            let quote_requested_at = Instant::now();
+            let (amount, fees, bid_quote) = determine_btc_to_swap(
                 json,
                 event_loop_handle.request_quote(),
+                rate_bounds,
                 bitcoin_wallet.new_address(),
                 || bitcoin_wallet.balance(),
                 max_givable,
+                estimate_lock_fee,
                 || bitcoin_wallet.sync(),
             )
             .await?;
+            let amount = cli::refresh_quote(
+                quote_refresh,
+                bid_quote,
+                quote_requested_at.elapsed(),
+                cli::unix_timestamp()?,
+                amount,
+                rate_bounds,
+                event_loop_handle.request_quote(),
+                |previous, refreshed| confirm_price_change(json, previous, refreshed),
+            )
+            .await?;
 
             tracing::info!(%amount, %fees,  "Determined swap amount");
 
             db.insert_peer_id(swap_id, seller_peer_id).await?;
             db.insert_monero_address(swap_id, monero_receive_address)
                 .await?;
+            db.insert_fee_strategy(swap_id, bitcoin_fee_strategy)
+                .await?;
+            if pin_electrum_server {
+                db.insert_electrum_server(swap_id, bitcoin_electrum_rpc_url)
+                    .await?;
+            }
 
             let swap = Swap::new(
-                db,
+                db.clone(),
                 swap_id,
-                bitcoin_wallet,
+                bitcoin_wallet.clone(),
                 Arc::new(monero_wallet),
                 env_config,
                 event_loop_handle,
@@ -127,26 +209,83 @@ async fn main() -> Result<()> {
                 amount,
             );
 
-            tokio::select! {
+            let result = tokio::select! {
                 result = event_loop => {
                     result
-                        .context("EventLoop panicked")?;
+                        .context("EventLoop panicked")
+                        .map(|_| None)
                 },
                 result = bob::run(swap) => {
-                    result.context("Failed to complete swap")?;
+                    result.context("Failed to complete swap").map(Some)
+                }
+            };
+
+            if !matches!(result, Ok(Some(_))) {
+                release_reserved_utxos(db.as_ref(), &bitcoin_wallet, swap_id).await;
+            }
+
+            match result {
+                Ok(Some(_)) => print_summary(swap_id, db.as_ref(), json).await?,
+                Ok(None) => {}
+                Err(error) => {
+                    if !refund_on_failure {
+                        return Err(error);
+                    }
+
+                    tracing::error!("{:#}", error);
+
+                    match cli::refund_on_failure(
+                        swap_id,
+                        bitcoin_wallet,
+                        db,
+                        env_config.bitcoin_avg_block_time,
+                        env_config.bitcoin_cancel_reorg_policy,
+                    )
+                    .await?
+                    {
+                        Some(_) => {
+                            tracing::info!(%swap_id, "Refunded the Bitcoin of the failed swap")
+                        }
+                        None => return Err(error),
+                    }
                 }
             }
         }
-        Command::History => {
+        Command::History {
+            bitcoin_electrum_rpc_url,
+            bitcoin_target_block,
+        } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            swap::fs::ensure_directory_is_private(&data_dir)
+                .context("Failed to restrict permissions of data directory")?;
 
             let db = open_db(data_dir.join("sqlite")).await?;
             let swaps = db.all().await?;
 
             if json {
+                let seed = Seed::from_file_or_generate(data_dir.as_path())
+                    .context("Failed to read in seed file")?;
+                let bitcoin_wallet = init_bitcoin_wallet(
+                    bitcoin_electrum_rpc_url,
+                    &seed,
+                    data_dir,
+                    env_config,
+                    bitcoin_target_block,
+                    false,
+                )
+                .await?;
+
                 for (swap_id, state) in swaps {
                     let state: BobState = state.try_into()?;
-                    tracing::info!(swap_id=%swap_id.to_string(), state=%state.to_string(), "Read swap state from database");
+
+                    match state.expired_timelocks(&bitcoin_wallet).await? {
+                        Some(expired_timelocks) => {
+                            tracing::info!(swap_id=%swap_id.to_string(), state=%state.to_string(), %expired_timelocks, "Read swap state from database")
+                        }
+                        None => {
+                            tracing::info!(swap_id=%swap_id.to_string(), state=%state.to_string(), "Read swap state from database")
+                        }
+                    }
                 }
             } else {
                 let mut table = Table::new();
@@ -161,6 +300,27 @@ async fn main() -> Result<()> {
                 println!("{}", table);
             }
         }
+        Command::Prune {
+            older_than,
+            dry_run,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            let db = open_db(data_dir.join("sqlite")).await?;
+            let swaps = prunable_swaps(db.as_ref(), older_than).await?;
+
+            for (swap_id, state, entered_at) in swaps {
+                let state: BobState = state.try_into()?;
+
+                if dry_run {
+                    tracing::info!(%swap_id, %state, %entered_at, "Would delete swap");
+                    continue;
+                }
+
+                db.delete(swap_id).await?;
+                tracing::info!(%swap_id, %state, %entered_at, "Deleted swap");
+            }
+        }
         Command::Config => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
 
@@ -186,6 +346,7 @@ async fn main() -> Result<()> {
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                true,
             )
             .await?;
 
@@ -219,6 +380,7 @@ async fn main() -> Result<()> {
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                true,
             )
             .await?;
 
@@ -232,30 +394,64 @@ async fn main() -> Result<()> {
             swap_id,
             bitcoin_electrum_rpc_url,
             bitcoin_target_block,
+            bitcoin_fee_bump_schedule,
+            bitcoin_confirmation_quorum,
             monero_daemon_address,
+            monero_daemon_fallback_addresses,
+            seller_whitelist,
+            quote_tolerance,
+            clock_skew_tolerance,
             tor_socks5_port,
+            tor_control_port,
+            tor_bootstrap_timeout,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
             let db = open_db(data_dir.join("sqlite")).await?;
             let seed = Seed::from_file_or_generate(data_dir.as_path())
                 .context("Failed to read in seed file")?;
 
-            let bitcoin_wallet = init_bitcoin_wallet(
+            let fee_strategy = fee_strategy(db.as_ref(), swap_id, bitcoin_target_block).await?;
+            let bitcoin_wallet = init_bitcoin_wallet_with_pinned_server(
+                db.get_electrum_server(swap_id).await?,
                 bitcoin_electrum_rpc_url,
                 &seed,
                 data_dir.clone(),
                 env_config,
-                bitcoin_target_block,
+                fee_strategy.lock,
+            )
+            .await?
+            .with_fee_bump_schedule(bitcoin_fee_bump_schedule)
+            .with_confirmation_quorum(
+                bitcoin_confirmation_quorum,
+                env_config.bitcoin_electrum_connect_timeout,
+            )
+            .await?;
+            let (monero_wallet, _process) = init_monero_wallet(
+                data_dir,
+                monero_daemon_address,
+                monero_daemon_fallback_addresses,
+                env_config,
             )
             .await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
 
             let seller_peer_id = db.get_peer_id(swap_id).await?;
-            let seller_addresses = db.get_addresses(seller_peer_id).await?;
+            let seller_addresses = seller_whitelist
+                .dialable_addresses(seller_peer_id, db.get_addresses(seller_peer_id).await?);
+            if !seller_whitelist.is_empty() && seller_addresses.is_empty() {
+                bail!(
+                    "No whitelisted address for seller {}, pass one with --seller-whitelist",
+                    seller_peer_id
+                );
+            }
 
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
+            let behaviour = cli::Behaviour::new(
+                seller_peer_id,
+                env_config,
+                bitcoin_wallet.clone(),
+                RateBounds::default(),
+            );
+            wait_for_tor(tor_socks5_port, tor_control_port, tor_bootstrap_timeout).await?;
             let mut swarm =
                 swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
             let our_peer_id = swarm.local_peer_id();
@@ -272,7 +468,7 @@ async fn main() -> Result<()> {
             let handle = tokio::spawn(event_loop.run());
 
             let monero_receive_address = db.get_monero_address(swap_id).await?;
-            let swap = Swap::from_db(
+            let mut swap = Swap::from_db(
                 db,
                 swap_id,
                 bitcoin_wallet,
@@ -283,12 +479,62 @@ async fn main() -> Result<()> {
             )
             .await?;
 
-            tokio::select! {
+            if cli::resume_needs_quote(&swap.state) {
+                let quote = swap.event_loop_handle.request_quote().await?;
+                cli::check_quote_expiry(&quote, cli::unix_timestamp()?, clock_skew_tolerance)?;
+
+                let refresh = QuoteRefresh {
+                    tolerance: quote_tolerance,
+                    clock_skew_tolerance,
+                    ..QuoteRefresh::default()
+                };
+
+                if let Err(stale) = cli::check_resumed_terms(&swap.state, &quote, refresh) {
+                    // The lock transaction is broadcast before the state is
+                    // updated, once it may be published the negotiated terms
+                    // are binding
+                    let lock_may_be_published = match &swap.state {
+                        BobState::SwapSetupCompleted(state2) => {
+                            !state2.lock_is_unpublished(&swap.bitcoin_wallet).await?
+                        }
+                        _ => false,
+                    };
+
+                    if lock_may_be_published {
+                        tracing::warn!(
+                            "{}. Resuming the swap at the negotiated terms because its Bitcoin lock transaction may have been published",
+                            stale
+                        );
+                    } else {
+                        swap.db
+                            .insert_latest_state(swap_id, BobState::SafelyAborted.into())
+                            .await?;
+                        swap.db.release_utxos(swap_id).await?;
+                        bail!(
+                            "{}. The swap was aborted, no Bitcoin was locked. Start a new swap to trade at the current price",
+                            stale
+                        );
+                    }
+                }
+            }
+
+            let db = swap.db.clone();
+            let bitcoin_wallet = swap.bitcoin_wallet.clone();
+            let result = tokio::select! {
                 event_loop_result = handle => {
-                    event_loop_result?;
+                    event_loop_result.context("EventLoop panicked").map(|_| false)
                 },
-                swap_result = bob::run(swap) => {
-                    swap_result?;
+                result = bob::run(swap) => {
+                    result.map(|_| true)
+                }
+            };
+
+            match result {
+                Ok(true) => print_summary(swap_id, db.as_ref(), json).await?,
+                Ok(false) => release_reserved_utxos(db.as_ref(), &bitcoin_wallet, swap_id).await,
+                Err(error) => {
+                    release_reserved_utxos(db.as_ref(), &bitcoin_wallet, swap_id).await;
+                    return Err(error);
                 }
             }
         }
@@ -308,6 +554,7 @@ async fn main() -> Result<()> {
                 data_dir,
                 env_config,
                 bitcoin_target_block,
+                true,
             )
             .await?;
 
@@ -318,6 +565,37 @@ async fn main() -> Result<()> {
             swap_id,
             bitcoin_electrum_rpc_url,
             bitcoin_target_block,
+            fee_rate,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite")).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_url,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                true,
+            )
+            .await?;
+
+            cli::refund(
+                swap_id,
+                Arc::new(bitcoin_wallet),
+                db,
+                fee_rate,
+                env_config.bitcoin_cancel_reorg_policy,
+            )
+            .await?;
+        }
+        Command::ReplaceSeller {
+            swap_id,
+            seller,
+            bitcoin_electrum_rpc_url,
+            bitcoin_target_block,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
             let db = open_db(data_dir.join("sqlite")).await?;
@@ -330,33 +608,75 @@ async fn main() -> Result<()> {
                 data_dir,
                 env_config,
                 bitcoin_target_block,
+                false,
             )
             .await?;
 
-            cli::refund(swap_id, Arc::new(bitcoin_wallet), db).await?;
+            cli::replace_seller(swap_id, seller, Arc::new(bitcoin_wallet), db).await?;
+            tracing::info!(
+                "Run `swap resume --swap-id {}` to set up the swap with the new seller",
+                swap_id
+            );
         }
         Command::ListSellers {
             rendezvous_point,
             namespace,
             tor_socks5_port,
+            tor_control_port,
+            tor_bootstrap_timeout,
+            max_concurrent_dials,
+            cache_ttl,
+            clock_skew_tolerance,
+            refresh,
         } => {
             let rendezvous_node_peer_id = rendezvous_point
                 .extract_peer_id()
                 .context("Rendezvous node address must contain peer ID")?;
 
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
-                .context("Failed to read in seed file")?;
-            let identity = seed.derive_libp2p_identity();
-
-            let sellers = list_sellers(
-                rendezvous_node_peer_id,
-                rendezvous_point,
-                namespace,
-                tor_socks5_port,
-                identity,
-            )
-            .await?;
+
+            let now = cli::unix_timestamp()?;
+            let mut cache = SellersCache::load(&data_dir);
+            let cached = if refresh {
+                None
+            } else {
+                cache.fresh_sellers(&rendezvous_point, cache_ttl, clock_skew_tolerance, now)
+            };
+
+            let sellers = match cached {
+                Some((sellers, age)) => {
+                    tracing::info!(
+                        age_secs = age.as_secs(),
+                        "Listing the sellers discovered {} seconds ago, pass --refresh to discover them again",
+                        age.as_secs()
+                    );
+
+                    sellers
+                }
+                None => {
+                    let seed = Seed::from_file_or_generate(data_dir.as_path())
+                        .context("Failed to read in seed file")?;
+                    let identity = seed.derive_libp2p_identity();
+
+                    wait_for_tor(tor_socks5_port, tor_control_port, tor_bootstrap_timeout).await?;
+                    let sellers = list_sellers(
+                        rendezvous_node_peer_id,
+                        rendezvous_point.clone(),
+                        namespace,
+                        tor_socks5_port,
+                        identity,
+                        max_concurrent_dials,
+                    )
+                    .await?;
+
+                    cache.insert(rendezvous_point, sellers.clone(), now);
+                    if let Err(error) = cache.save(&data_dir) {
+                        tracing::warn!("Failed to cache the sellers: {:#}", error);
+                    }
+
+                    sellers
+                }
+            };
 
             if json {
                 for seller in sellers {
@@ -433,11 +753,114 @@ async fn main() -> Result<()> {
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                false,
             )
             .await?;
             let wallet_export = bitcoin_wallet.wallet_export("cli").await?;
             tracing::info!(descriptor=%wallet_export.to_string(), "Exported bitcoin wallet");
         }
+        Command::ExportProof {
+            swap_id,
+            bitcoin_electrum_rpc_url,
+            bitcoin_target_block,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite")).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_url,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                true,
+            )
+            .await?;
+
+            let proof = cli::export_proof(
+                swap_id,
+                Arc::new(bitcoin_wallet),
+                db,
+                env_config.monero_network,
+            )
+            .await?;
+
+            println!("{}", serde_json::to_string_pretty(&proof)?);
+        }
+        Command::Doctor {
+            swap_id,
+            bitcoin_electrum_rpc_url,
+            bitcoin_target_block,
+            tor_socks5_port,
+            tor_control_port,
+            tor_bootstrap_timeout,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite")).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = Arc::new(
+                init_bitcoin_wallet(
+                    bitcoin_electrum_rpc_url,
+                    &seed,
+                    data_dir,
+                    env_config,
+                    bitcoin_target_block,
+                    true,
+                )
+                .await?,
+            );
+
+            let seller_peer_id = db.get_peer_id(swap_id).await?;
+            let seller_addresses = db.get_addresses(seller_peer_id).await?;
+
+            let behaviour = cli::Behaviour::new(
+                seller_peer_id,
+                env_config,
+                bitcoin_wallet.clone(),
+                RateBounds::default(),
+            );
+            wait_for_tor(tor_socks5_port, tor_control_port, tor_bootstrap_timeout).await?;
+            let mut swarm =
+                swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
+            for seller_address in seller_addresses {
+                swarm
+                    .behaviour_mut()
+                    .add_address(seller_peer_id, seller_address);
+            }
+
+            let (event_loop, mut event_loop_handle) =
+                EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
+            let event_loop = tokio::spawn(event_loop.run());
+
+            let error = match tokio::time::timeout(
+                SELLER_REACHABILITY_TIMEOUT,
+                event_loop_handle.request_quote(),
+            )
+            .await
+            {
+                Ok(Ok(_)) => None,
+                Ok(Err(error)) => Some(format!("{:#}", error)),
+                Err(_) => Some("Timed out waiting for a quote".to_owned()),
+            };
+            event_loop.abort();
+
+            let seller = cli::SellerReachability {
+                peer_id: seller_peer_id.to_string(),
+                reachable: error.is_none(),
+                error,
+            };
+            let diagnosis = cli::diagnose(swap_id, bitcoin_wallet, db, seller).await?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&diagnosis)?);
+            } else {
+                println!("{}", diagnosis);
+            }
+        }
         Command::MoneroRecovery { swap_id } => {
             let db = open_db(data_dir.join("sqlite")).await?;
 
@@ -485,11 +908,14 @@ async fn init_bitcoin_wallet(
     data_dir: PathBuf,
     env_config: Config,
     bitcoin_target_block: usize,
+    sync: bool,
 ) -> Result<bitcoin::Wallet> {
     let wallet_dir = data_dir.join("wallet");
 
     let wallet = bitcoin::Wallet::new(
-        electrum_rpc_url.clone(),
+        bitcoin::Backend::Electrum {
+            rpc_url: electrum_rpc_url,
+        },
         &wallet_dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
@@ -498,24 +924,83 @@ async fn init_bitcoin_wallet(
     .await
     .context("Failed to initialize Bitcoin wallet")?;
 
-    wallet.sync().await?;
+    // Commands that only read the wallet's database don't need to wait for a
+    // sync with the Electrum server.
+    if sync {
+        wallet.sync().await?;
+    }
 
     Ok(wallet)
 }
 
+/// Initializes the wallet with the Electrum server a swap is pinned to, falling
+/// back to `electrum_rpc_url` if it is not pinned or the pinned server cannot
+/// be reached.
+async fn init_bitcoin_wallet_with_pinned_server(
+    pinned_rpc_url: Option<Url>,
+    electrum_rpc_url: Url,
+    seed: &Seed,
+    data_dir: PathBuf,
+    env_config: Config,
+    bitcoin_target_block: usize,
+) -> Result<bitcoin::Wallet> {
+    if let Some(pinned_rpc_url) = pinned_rpc_url.filter(|pinned| *pinned != electrum_rpc_url) {
+        match init_bitcoin_wallet(
+            pinned_rpc_url.clone(),
+            seed,
+            data_dir.clone(),
+            env_config,
+            bitcoin_target_block,
+            true,
+        )
+        .await
+        {
+            Ok(wallet) => {
+                tracing::info!(electrum_rpc_url = %pinned_rpc_url, "Using the Electrum server the swap is pinned to");
+                return Ok(wallet);
+            }
+            Err(error) => tracing::warn!(
+                pinned_rpc_url = %pinned_rpc_url,
+                fallback_rpc_url = %electrum_rpc_url,
+                "The Electrum server the swap is pinned to cannot be reached, falling back to another server: {:#}",
+                error
+            ),
+        }
+    }
+
+    init_bitcoin_wallet(
+        electrum_rpc_url,
+        seed,
+        data_dir,
+        env_config,
+        bitcoin_target_block,
+        true,
+    )
+    .await
+}
+
 async fn init_monero_wallet(
     data_dir: PathBuf,
-    monero_daemon_address: String,
+    monero_daemon_address: MoneroDaemonAddress,
+    monero_daemon_fallback_addresses: Vec<MoneroDaemonAddress>,
     env_config: Config,
 ) -> Result<(monero::Wallet, monero::WalletRpcProcess)> {
     let network = env_config.monero_network;
 
+    let daemons = Daemons::select(
+        iter::once(monero_daemon_address)
+            .chain(monero_daemon_fallback_addresses)
+            .collect(),
+        network,
+    )
+    .await?;
+
     const MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME: &str = "swap-tool-blockchain-monitoring-wallet";
 
     let monero_wallet_rpc = monero::WalletRpc::new(data_dir.join("monero")).await?;
 
     let monero_wallet_rpc_process = monero_wallet_rpc
-        .run(network, monero_daemon_address.as_str())
+        .run(network, daemons.current().address())
         .await?;
 
     let monero_wallet = monero::Wallet::open_or_create(
@@ -523,11 +1008,67 @@ async fn init_monero_wallet(
         MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME.to_string(),
         env_config,
     )
-    .await?;
+    .await?
+    .with_daemons(daemons);
 
     Ok((monero_wallet, monero_wallet_rpc_process))
 }
 
+/// Waits for Tor to bootstrap if a timeout is given, so that the first dial of
+/// an onion address does not fail because Tor has not built its circuits yet.
+async fn wait_for_tor(
+    tor_socks5_port: u16,
+    tor_control_port: u16,
+    tor_bootstrap_timeout: Option<Duration>,
+) -> Result<()> {
+    match tor_bootstrap_timeout {
+        Some(timeout) => {
+            tor::Client::new(tor_socks5_port)
+                .with_control_port(tor_control_port)
+                .wait_until_bootstrapped(timeout)
+                .await
+        }
+        None => Ok(()),
+    }
+}
+
+/// Prints the final report of a swap, as a single line of JSON if `json` is
+/// given.
+///
+/// The swap already finished, so failing to summarize it is only logged.
+async fn print_summary(swap_id: Uuid, db: &(dyn Database + Send + Sync), json: bool) -> Result<()> {
+    let summary = match SwapSummary::of_bob_swap(swap_id, db).await {
+        Ok(summary) => summary,
+        Err(error) => {
+            tracing::warn!(%swap_id, "Swap completed, but failed to summarize it: {:#}", error);
+            return Ok(());
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string(&summary)?);
+    } else {
+        println!("{}", summary);
+    }
+
+    Ok(())
+}
+
+/// Releases the UTXOs reserved for a swap that stopped before it locked its
+/// Bitcoin, such that other swaps can use them.
+///
+/// The swap already failed or stopped, so failing to release them is only
+/// logged.
+async fn release_reserved_utxos(
+    db: &(dyn Database + Send + Sync),
+    bitcoin_wallet: &bitcoin::Wallet,
+    swap_id: Uuid,
+) {
+    if let Err(error) = bob::release_utxos_unless_locked(db, bitcoin_wallet, swap_id).await {
+        tracing::warn!(%swap_id, "Failed to release the UTXOs reserved for the swap: {:#}", error);
+    }
+}
+
 fn qr_code(value: &impl ToString) -> Result<String> {
     let code = QrCode::new(value.to_string())?;
     let qr_code = code
@@ -538,19 +1079,28 @@ fn qr_code(value: &impl ToString) -> Result<String> {
     Ok(qr_code)
 }
 
-async fn determine_btc_to_swap<FB, TB, FMG, TMG, FS, TS>(
+/// Waits for a deposit if the wallet cannot fund the minimum quantity of the
+/// seller. The user is asked to deposit the missing amount plus the estimated
+/// fee of the lock transaction, `estimate_lock_fee` is given the amount to
+/// lock. Returns the quote the amount was determined with.
+#[allow(clippy::too_many_arguments)]
+async fn determine_btc_to_swap<FB, TB, FMG, TMG, FF, TF, FS, TS>(
     json: bool,
     bid_quote: impl Future<Output = Result<BidQuote>>,
+    rate_bounds: RateBounds,
     get_new_address: impl Future<Output = Result<bitcoin::Address>>,
     balance: FB,
     max_giveable_fn: FMG,
+    estimate_lock_fee: FF,
     sync: FS,
-) -> Result<(bitcoin::Amount, bitcoin::Amount)>
+) -> Result<(bitcoin::Amount, bitcoin::Amount, BidQuote)>
 where
     TB: Future<Output = Result<bitcoin::Amount>>,
     FB: Fn() -> TB,
     TMG: Future<Output = Result<bitcoin::Amount>>,
     FMG: Fn() -> TMG,
+    TF: Future<Output = Result<bitcoin::Amount>>,
+    FF: Fn(bitcoin::Amount) -> TF,
     TS: Future<Output = Result<()>>,
     FS: Fn() -> TS,
 {
@@ -563,6 +1113,8 @@ where
         "Received quote",
     );
 
+    rate_bounds.check(bid_quote.price)?;
+
     let mut max_giveable = max_giveable_fn().await?;
 
     if max_giveable == bitcoin::Amount::ZERO || max_giveable < bid_quote.min_quantity {
@@ -570,13 +1122,25 @@ where
         let minimum_amount = bid_quote.min_quantity;
         let maximum_amount = bid_quote.max_quantity;
 
-        if !json {
-            eprintln!("{}", qr_code(&deposit_address)?);
-        }
-
         loop {
+            // Without a minimum quantity any deposit is enough.
+            let missing_amount = minimum_amount
+                .checked_sub(max_giveable)
+                .unwrap_or(bitcoin::Amount::ZERO);
+            let deposit_amount = if missing_amount == bitcoin::Amount::ZERO {
+                None
+            } else {
+                Some(missing_amount + estimate_lock_fee(minimum_amount).await?)
+            };
+            let deposit_uri = bitcoin::PaymentRequest::new(deposit_address.clone(), deposit_amount);
+
+            if !json {
+                eprintln!("{}", qr_code(&deposit_uri)?);
+            }
+
             tracing::info!(
                 %deposit_address,
+                %deposit_uri,
                 %max_giveable,
                 %minimum_amount,
                 %maximum_amount,
@@ -613,7 +1177,35 @@ where
 
     let btc_swap_amount = min(max_giveable, max_accepted);
 
-    Ok((btc_swap_amount, fees))
+    Ok((btc_swap_amount, fees, bid_quote))
+}
+
+fn confirm_price_change(
+    json: bool,
+    previous: bitcoin::Amount,
+    refreshed: bitcoin::Amount,
+) -> Result<bool> {
+    // The prompt would end up in between the JSON logs or fail without a
+    // terminal to read the answer from
+    if json || !atty::is(atty::Stream::Stdin) || !atty::is(atty::Stream::Stderr) {
+        tracing::warn!(
+            previous_price = %previous,
+            price = %refreshed,
+            "Cannot ask for confirmation of the new price, pass --yes to start swaps whose price changed by more than --quote-tolerance"
+        );
+        return Ok(false);
+    }
+
+    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "The price of 1 XMR changed from {} to {} since the first quote, start the swap anyway?",
+            previous, refreshed
+        ))
+        .default(false)
+        .interact()
+        .context("Failed to ask for confirmation of the new price")?;
+
+    Ok(confirmed)
 }
 
 #[cfg(test)]
@@ -633,15 +1225,17 @@ mod tests {
             Amount::from_btc(0.0009).unwrap(),
         ])));
 
-        let (amount, fees) = determine_btc_to_swap(
+        let (amount, fees, _) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            RateBounds::default(),
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.001)?) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
+            |_| async { Ok(Amount::from_sat(1000)) },
             || async { Ok(()) },
         )
         .await
@@ -654,7 +1248,7 @@ mod tests {
         assert_eq!(
             writer.captured(),
             r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
- INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 deposit_uri=bitcoin:1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
  INFO swap: Received Bitcoin new_balance=0.00100000 BTC max_giveable=0.00090000 BTC
 "
         );
@@ -668,15 +1262,17 @@ mod tests {
             Amount::from_btc(0.1).unwrap(),
         ])));
 
-        let (amount, fees) = determine_btc_to_swap(
+        let (amount, fees, _) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            RateBounds::default(),
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.1001)?) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
+            |_| async { Ok(Amount::from_sat(1000)) },
             || async { Ok(()) },
         )
         .await
@@ -689,7 +1285,7 @@ mod tests {
         assert_eq!(
             writer.captured(),
             r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
- INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 deposit_uri=bitcoin:1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
  INFO swap: Received Bitcoin new_balance=0.10010000 BTC max_giveable=0.10000000 BTC
 "
         );
@@ -703,15 +1299,17 @@ mod tests {
             Amount::from_btc(99.9).unwrap(),
         ])));
 
-        let (amount, fees) = determine_btc_to_swap(
+        let (amount, fees, _) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            RateBounds::default(),
             async { panic!("should not request new address when initial balance  is > 0") },
             || async { Ok(Amount::from_btc(0.005)?) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
+            |_| async { Ok(Amount::from_sat(1000)) },
             || async { Ok(()) },
         )
         .await
@@ -736,15 +1334,17 @@ mod tests {
             Amount::from_btc(99.9).unwrap(),
         ])));
 
-        let (amount, fees) = determine_btc_to_swap(
+        let (amount, fees, _) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            RateBounds::default(),
             async { panic!("should not request new address when initial balance is > 0") },
             || async { Ok(Amount::from_btc(0.1001)?) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
+            |_| async { Ok(Amount::from_sat(1000)) },
             || async { Ok(()) },
         )
         .await
@@ -769,15 +1369,17 @@ mod tests {
             Amount::from_btc(0.01).unwrap(),
         ])));
 
-        let (amount, fees) = determine_btc_to_swap(
+        let (amount, fees, _) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_min(0.01)) },
+            RateBounds::default(),
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.0101)?) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
+            |_| async { Ok(Amount::from_sat(1000)) },
             || async { Ok(()) },
         )
         .await
@@ -790,7 +1392,7 @@ mod tests {
         assert_eq!(
             writer.captured(),
             r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
- INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 deposit_uri=bitcoin:1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6?amount=0.01001000 max_giveable=0.00000000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
  INFO swap: Received Bitcoin new_balance=0.01010000 BTC max_giveable=0.01000000 BTC
 "
         );
@@ -804,15 +1406,17 @@ mod tests {
             Amount::from_btc(0.01).unwrap(),
         ])));
 
-        let (amount, fees) = determine_btc_to_swap(
+        let (amount, fees, _) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_min(0.01)) },
+            RateBounds::default(),
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.0101)?) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
+            |_| async { Ok(Amount::from_sat(1000)) },
             || async { Ok(()) },
         )
         .await
@@ -825,7 +1429,7 @@ mod tests {
         assert_eq!(
             writer.captured(),
             r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
- INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00010000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 deposit_uri=bitcoin:1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6?amount=0.00991000 max_giveable=0.00010000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
  INFO swap: Received Bitcoin new_balance=0.01010000 BTC max_giveable=0.01000000 BTC
 "
         );
@@ -847,12 +1451,14 @@ mod tests {
             determine_btc_to_swap(
                 true,
                 async { Ok(quote_with_min(0.1)) },
+                RateBounds::default(),
                 get_dummy_address(),
                 || async { Ok(Amount::from_btc(0.0101)?) },
                 || async {
                     let mut result = givable.lock().unwrap();
                     result.give()
                 },
+                |_| async { Ok(Amount::from_sat(1000)) },
                 || async { Ok(()) },
             ),
         )
@@ -863,10 +1469,10 @@ mod tests {
         assert_eq!(
             writer.captured(),
             r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
- INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 deposit_uri=bitcoin:1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6?amount=0.10001000 max_giveable=0.00000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
  INFO swap: Received Bitcoin new_balance=0.01010000 BTC max_giveable=0.01000000 BTC
  INFO swap: Deposited amount is less than `min_quantity`
- INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.01000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 deposit_uri=bitcoin:1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6?amount=0.09001000 max_giveable=0.01000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
 "
         );
     }
@@ -892,6 +1498,7 @@ mod tests {
             determine_btc_to_swap(
                 true,
                 async { Ok(quote_with_min(0.1)) },
+                RateBounds::default(),
                 get_dummy_address(),
                 || async { Ok(Amount::from_btc(0.21)?) },
                 || async {
@@ -899,6 +1506,7 @@ mod tests {
 
                     result.give()
                 },
+                |_| async { Ok(Amount::from_sat(1000)) },
                 || async { Ok(()) },
             ),
         )
@@ -909,12 +1517,44 @@ mod tests {
         assert_eq!(
             writer.captured(),
             r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
- INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 deposit_uri=bitcoin:1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6?amount=0.10001000 max_giveable=0.00000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
  INFO swap: Received Bitcoin new_balance=0.21000000 BTC max_giveable=0.20000000 BTC
 "
         );
     }
 
+    #[tokio::test]
+    async fn given_quote_above_max_rate_then_fails_before_waiting_for_deposit() {
+        let writer = capture_logs(LevelFilter::INFO);
+        let rate_bounds = RateBounds {
+            min: None,
+            max: Some(Amount::from_sat(50_000)),
+        };
+
+        let error = determine_btc_to_swap(
+            true,
+            async { Ok(quote_with_max(0.01)) },
+            rate_bounds,
+            get_dummy_address(),
+            || async { Ok(Amount::ZERO) },
+            || async { Ok(Amount::ZERO) },
+            |_| async { Ok(Amount::from_sat(1000)) },
+            || async { Ok(()) },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Seller quoted 0.00100000 BTC per XMR which is above the maximum rate of 0.00050000 BTC per XMR"
+        );
+        assert_eq!(
+            writer.captured(),
+            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+"
+        );
+    }
+
     struct MaxGiveable {
         amounts: Vec<Amount>,
         call_counter: usize,
@@ -942,6 +1582,7 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::from_btc(btc).unwrap(),
             min_quantity: Amount::ZERO,
+            quote_expiry: None,
         }
     }
 
@@ -950,6 +1591,7 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::max_value(),
             min_quantity: Amount::from_btc(btc).unwrap(),
+            quote_expiry: None,
         }
     }
 
@@ -0,0 +1,74 @@
+//! Runs a complete swap between an ASB (Alice) and a CLI (Bob) against regtest
+//! `bitcoind`, `electrs` and `monerod` containers and reports whether it
+//! succeeded.
+//!
+//! Requires docker and the `selftest` feature, run it with `cargo selftest`.
+
+#[path = "../../tests/harness/mod.rs"]
+pub mod harness;
+
+use anyhow::{anyhow, bail, Result};
+use harness::SlowCancelConfig;
+use std::any::Any;
+use std::thread;
+use swap::asb::FixedRate;
+use swap::protocol::{alice, bob};
+use tokio::join;
+
+fn main() {
+    println!(
+        "Running a swap between Alice and Bob against regtest containers, this takes a few minutes"
+    );
+
+    // The harness panics as soon as any part of the swap fails. Running it on
+    // a thread of its own catches that, the containers are removed while the
+    // harness unwinds, or is dropped if the self-test is interrupted.
+    let outcome = thread::spawn(run)
+        .join()
+        .unwrap_or_else(|panic| Err(anyhow!(panic_message(panic))));
+
+    match outcome {
+        Ok(()) => println!("Self-test passed: Alice redeemed the Bitcoin and Bob the Monero"),
+        Err(error) => {
+            eprintln!("Self-test failed: {:#}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    tokio::runtime::Runtime::new()?.block_on(async {
+        tokio::select! {
+            () = happy_path() => Ok(()),
+            _ = tokio::signal::ctrl_c() => bail!("Interrupted"),
+        }
+    })
+}
+
+async fn happy_path() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _) = ctx.bob_swap().await;
+        let bob_swap = tokio::spawn(bob::run(bob_swap));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        let (bob_state, alice_state) = join!(bob_swap, alice_swap);
+
+        ctx.assert_alice_redeemed(alice_state??).await;
+        ctx.assert_bob_redeemed(bob_state??).await;
+
+        Ok(())
+    })
+    .await;
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "The swap panicked".to_string(),
+        },
+    }
+}
@@ -2,24 +2,33 @@ pub mod wallet;
 
 mod cancel;
 mod lock;
+mod payment_request;
 mod punish;
 mod redeem;
 mod refund;
+mod signer;
 mod timelocks;
 
-pub use crate::bitcoin::cancel::{CancelTimelock, PunishTimelock, TxCancel};
-pub use crate::bitcoin::lock::TxLock;
+pub use crate::bitcoin::cancel::{
+    CancelReorgPolicy, CancelTimelock, InvalidTxCancel, PunishTimelock, TxCancel,
+};
+pub use crate::bitcoin::lock::{InvalidTxLock, TxLock};
+pub use crate::bitcoin::payment_request::PaymentRequest;
 pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
 pub use crate::bitcoin::refund::TxRefund;
-pub use crate::bitcoin::timelocks::{BlockHeight, ExpiredTimelocks};
+pub use crate::bitcoin::signer::{ExternalSigner, InProcessSigner, Signer};
+pub use crate::bitcoin::timelocks::{approximate_duration, BlockHeight, ExpiredTimelocks};
 pub use ::bitcoin::util::amount::Amount;
 pub use ::bitcoin::util::psbt::PartiallySignedTransaction;
-pub use ::bitcoin::{Address, Network, Transaction, Txid};
+pub use ::bitcoin::{Address, Network, OutPoint, Transaction, Txid};
 pub use ecdsa_fun::adaptor::EncryptedSignature;
 pub use ecdsa_fun::fun::Scalar;
 pub use ecdsa_fun::Signature;
-pub use wallet::Wallet;
+pub use wallet::{
+    AddressType, Backend, CoinSelection, ConfirmationQuorum, ElectrumUnreachable, FeeBumpSchedule,
+    FeeStrategy, TxFee, Wallet,
+};
 
 #[cfg(test)]
 pub use wallet::WalletBuilder;
@@ -32,12 +41,14 @@ use anyhow::{bail, Context, Result};
 use bdk::miniscript::descriptor::Wsh;
 use bdk::miniscript::{Descriptor, Segwitv0};
 use ecdsa_fun::adaptor::{Adaptor, HashTranscript};
+use ecdsa_fun::fun::marker::{Mark, NonZero};
 use ecdsa_fun::fun::Point;
 use ecdsa_fun::nonce::Deterministic;
 use ecdsa_fun::ECDSA;
 use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use std::convert::TryInto;
 use std::str::FromStr;
 
 #[derive(Serialize, Deserialize)]
@@ -78,6 +89,25 @@ impl SecretKey {
         self.inner.to_bytes()
     }
 
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let bytes = hex::decode(hex).context("Failed to decode secret key from hex")?;
+        let bytes: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .with_context(|| format!("Expected 32 bytes for secret key, got {}", bytes.len()))?;
+
+        let scalar = Scalar::from_bytes(bytes)
+            .context("Secret key overflows the curve order")?
+            .mark::<NonZero>()
+            .context("Secret key must not be zero")?;
+
+        Ok(Self::from(scalar))
+    }
+
     pub fn sign(&self, digest: SigHash) -> Signature {
         let ecdsa = ECDSA::<Deterministic<Sha256>>::default();
 
@@ -116,6 +146,75 @@ impl PublicKey {
     pub fn random() -> Self {
         Self(Point::random(&mut rand::thread_rng()))
     }
+
+    /// Hex encoding of the compressed public key.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.to_bytes())
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let bytes = hex::decode(hex).context("Failed to decode public key from hex")?;
+        let bytes: [u8; 33] = bytes
+            .as_slice()
+            .try_into()
+            .with_context(|| format!("Expected 33 bytes for public key, got {}", bytes.len()))?;
+
+        let point = Point::from_bytes(bytes).context("Bytes are not a valid public key")?;
+
+        Ok(Self(point))
+    }
+}
+
+/// Serde helper to (de)serialize a [`PublicKey`] as hex string.
+///
+/// Use with `#[serde(with = "crate::bitcoin::public_key_hex")]`.
+pub mod public_key_hex {
+    use super::PublicKey;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(x: &PublicKey, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_str(&x.to_hex())
+    }
+
+    pub fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<PublicKey, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+
+        PublicKey::from_hex(&hex).map_err(|err| de::Error::custom(format!("{:#}", err)))
+    }
+}
+
+/// Serde helper to (de)serialize a [`SecretKey`] as hex string.
+///
+/// Use with `#[serde(with = "crate::bitcoin::secret_key_hex")]`.
+pub mod secret_key_hex {
+    use super::SecretKey;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(x: &SecretKey, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_str(&x.to_hex())
+    }
+
+    pub fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<SecretKey, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+
+        SecretKey::from_hex(&hex).map_err(|err| de::Error::custom(format!("{:#}", err)))
+    }
 }
 
 impl From<PublicKey> for Point {
@@ -209,7 +308,8 @@ pub struct InvalidEncryptedSignature;
 pub fn build_shared_output_descriptor(A: Point, B: Point) -> Descriptor<bitcoin::PublicKey> {
     const MINISCRIPT_TEMPLATE: &str = "c:and_v(v:pk(A),pk_k(B))";
 
-    // NOTE: This shouldn't be a source of error, but maybe it is
+    // The hex of the keys is lower case, so it cannot collide with the upper case
+    // placeholders of the template. The resulting script is pinned by the tests.
     let A = ToHex::to_hex(&secp256k1::PublicKey::from(A));
     let B = ToHex::to_hex(&secp256k1::PublicKey::from(B));
 
@@ -239,15 +339,33 @@ pub fn current_epoch(
     tx_lock_status: ScriptStatus,
     tx_cancel_status: ScriptStatus,
 ) -> ExpiredTimelocks {
-    if tx_cancel_status.is_confirmed_with(punish_timelock) {
-        return ExpiredTimelocks::Punish;
-    }
+    let expired_timelocks = if tx_cancel_status.is_confirmed_with(punish_timelock) {
+        ExpiredTimelocks::Punish
+    } else if tx_lock_status.is_confirmed_with(cancel_timelock) {
+        ExpiredTimelocks::Cancel
+    } else {
+        ExpiredTimelocks::None
+    };
 
-    if tx_lock_status.is_confirmed_with(cancel_timelock) {
-        return ExpiredTimelocks::Cancel;
-    }
+    tracing::debug!(%expired_timelocks, "Checked for expired Bitcoin timelocks");
+
+    expired_timelocks
+}
+
+/// The number of blocks that have to be mined until the cancel timelock
+/// expires, given the status of the lock transaction.
+pub fn blocks_until_cancel_timelock_expires(
+    cancel_timelock: CancelTimelock,
+    tx_lock_status: ScriptStatus,
+) -> u32 {
+    let cancel_timelock = u32::from(cancel_timelock);
 
-    ExpiredTimelocks::None
+    match tx_lock_status {
+        ScriptStatus::Confirmed(confirmed) => {
+            cancel_timelock.saturating_sub(confirmed.confirmations())
+        }
+        _ => cancel_timelock,
+    }
 }
 
 /// Bitcoin error codes: https://github.com/bitcoin/bitcoin/blob/97d3500601c1d28642347d014a6de1e38f53ae4e/src/rpc/protocol.h#L23
@@ -326,6 +444,34 @@ mod tests {
     use rand::rngs::OsRng;
     use uuid::Uuid;
 
+    #[test]
+    fn unconfirmed_lock_has_full_cancel_timelock_left() {
+        let blocks_left =
+            blocks_until_cancel_timelock_expires(CancelTimelock::new(10), ScriptStatus::InMempool);
+
+        assert_eq!(blocks_left, 10);
+    }
+
+    #[test]
+    fn confirmed_lock_reduces_blocks_until_cancel_timelock_expires() {
+        let blocks_left = blocks_until_cancel_timelock_expires(
+            CancelTimelock::new(10),
+            ScriptStatus::from_confirmations(4),
+        );
+
+        assert_eq!(blocks_left, 6);
+    }
+
+    #[test]
+    fn no_blocks_left_once_cancel_timelock_expired() {
+        let blocks_left = blocks_until_cancel_timelock_expires(
+            CancelTimelock::new(10),
+            ScriptStatus::from_confirmations(12),
+        );
+
+        assert_eq!(blocks_left, 0);
+    }
+
     #[test]
     fn lock_confirmations_le_to_cancel_timelock_no_timelock_expired() {
         let tx_lock_status = ScriptStatus::from_confirmations(4);
@@ -421,7 +567,7 @@ mod tests {
         let alice_message1 = alice_state1.next_message();
 
         let bob_state1 = bob_state0
-            .receive(&bob_wallet, alice_message1)
+            .receive(&bob_wallet, alice_message1, &[])
             .await
             .unwrap();
         let bob_message2 = bob_state1.next_message();
@@ -468,4 +614,133 @@ mod tests {
             transaction
         )
     }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct HexKeys {
+        #[serde(with = "public_key_hex")]
+        public: PublicKey,
+        #[serde(with = "secret_key_hex")]
+        secret: SecretKey,
+    }
+
+    #[test]
+    fn public_key_hex_roundtrip() {
+        let public_key = SecretKey::new_random(&mut OsRng).public();
+
+        let hex = public_key.to_hex();
+
+        assert_eq!(hex.len(), 66);
+        assert_eq!(PublicKey::from_hex(&hex).unwrap(), public_key);
+    }
+
+    #[test]
+    fn secret_key_hex_roundtrip() {
+        let secret_key = SecretKey::new_random(&mut OsRng);
+
+        let hex = secret_key.to_hex();
+
+        assert_eq!(hex.len(), 64);
+        assert_eq!(SecretKey::from_hex(&hex).unwrap(), secret_key);
+    }
+
+    #[test]
+    fn serde_keys_as_hex_json_roundtrip() {
+        let secret = SecretKey::new_random(&mut OsRng);
+        let keys = HexKeys {
+            public: secret.public(),
+            secret,
+        };
+
+        let encoded = serde_json::to_string(&keys).unwrap();
+        let decoded: HexKeys = serde_json::from_str(&encoded).unwrap();
+
+        assert!(encoded.contains(&keys.public.to_hex()));
+        assert!(encoded.contains(&keys.secret.to_hex()));
+        assert_eq!(keys, decoded);
+    }
+
+    #[test]
+    fn serde_keys_as_hex_toml_roundtrip() {
+        let secret = SecretKey::new_random(&mut OsRng);
+        let keys = HexKeys {
+            public: secret.public(),
+            secret,
+        };
+
+        let encoded = toml::to_string(&keys).unwrap();
+        let decoded: HexKeys = toml::from_str(&encoded).unwrap();
+
+        assert_eq!(keys, decoded);
+    }
+
+    #[test]
+    fn malformed_hex_keys_are_rejected() {
+        assert!(PublicKey::from_hex("not hex").is_err());
+        assert!(PublicKey::from_hex("02abcd").is_err());
+        assert!(PublicKey::from_hex(&format!("05{}", "00".repeat(32))).is_err());
+
+        assert!(SecretKey::from_hex("not hex").is_err());
+        assert!(SecretKey::from_hex("abcd").is_err());
+        assert!(SecretKey::from_hex(&"00".repeat(32)).is_err());
+        assert!(SecretKey::from_hex(&"ff".repeat(32)).is_err());
+
+        let result = serde_json::from_str::<HexKeys>(r#"{"public":"zz","secret":"zz"}"#);
+        assert!(result.is_err());
+    }
+
+    /// The generator and twice the generator, so the expected descriptor and
+    /// address can be computed independently.
+    const A_HEX: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const B_HEX: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    #[test]
+    fn shared_output_descriptor_of_known_keys() {
+        let A = PublicKey::from_hex(A_HEX).unwrap().0;
+        let B = PublicKey::from_hex(B_HEX).unwrap().0;
+
+        let descriptor = build_shared_output_descriptor(A, B);
+
+        let expected = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "wsh(c:and_v(v:pk({}),pk_k({})))",
+            A_HEX, B_HEX
+        ))
+        .unwrap();
+        assert_eq!(descriptor, expected);
+        assert_eq!(
+            descriptor.explicit_script().to_hex(),
+            format!("21{}ad21{}ac", A_HEX, B_HEX)
+        );
+        assert_eq!(
+            descriptor.address(Network::Bitcoin).unwrap().to_string(),
+            "bc1qgm9necwzx6nmu8u50pgmhrtzzj6vvvcd328sc787nryefcszf8hs7nywgh"
+        );
+        assert_eq!(
+            descriptor.address(Network::Regtest).unwrap().to_string(),
+            "bcrt1qgm9necwzx6nmu8u50pgmhrtzzj6vvvcd328sc787nryefcszf8hsyzc88z"
+        );
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn shared_output_descriptor_roundtrips_and_commits_to_both_keys(a in crate::proptest::ecdsa_fun::point(), b in crate::proptest::ecdsa_fun::point()) {
+            proptest::prop_assume!(a != b);
+
+            let descriptor = build_shared_output_descriptor(a, b);
+            let parsed = Descriptor::<bitcoin::PublicKey>::from_str(&descriptor.to_string()).unwrap();
+            let address = descriptor.address(Network::Bitcoin).unwrap();
+
+            assert_eq!(parsed, descriptor);
+            assert_eq!(parsed.address(Network::Bitcoin).unwrap(), address);
+            assert_eq!(address, Address::p2wsh(&descriptor.explicit_script(), Network::Bitcoin));
+            assert_eq!(
+                descriptor.explicit_script().to_hex(),
+                format!(
+                    "21{}ad21{}ac",
+                    secp256k1::PublicKey::from(a).to_hex(),
+                    secp256k1::PublicKey::from(b).to_hex()
+                )
+            );
+            assert_ne!(build_shared_output_descriptor(b, a).address(Network::Bitcoin).unwrap(), address);
+        }
+    }
 }
@@ -1,11 +1,12 @@
 use crate::bitcoin;
-use crate::bitcoin::wallet::Watchable;
+use crate::bitcoin::lock::SEQUENCE_LOCKTIME_DISABLE_FLAG;
+use crate::bitcoin::wallet::{ScriptStatus, Watchable};
 use crate::bitcoin::{
     build_shared_output_descriptor, Address, Amount, BlockHeight, PublicKey, Transaction, TxLock,
 };
 use ::bitcoin::util::bip143::SigHashCache;
 use ::bitcoin::{OutPoint, Script, SigHash, SigHashType, TxIn, TxOut, Txid};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bdk::miniscript::{Descriptor, DescriptorTrait};
 use ecdsa_fun::Signature;
 use serde::{Deserialize, Serialize};
@@ -28,6 +29,12 @@ impl CancelTimelock {
     }
 }
 
+impl From<CancelTimelock> for u32 {
+    fn from(cancel_timelock: CancelTimelock) -> Self {
+        cancel_timelock.0
+    }
+}
+
 impl Add<CancelTimelock> for BlockHeight {
     type Output = BlockHeight;
 
@@ -68,6 +75,12 @@ impl PunishTimelock {
     }
 }
 
+impl From<PunishTimelock> for u32 {
+    fn from(punish_timelock: PunishTimelock) -> Self {
+        punish_timelock.0
+    }
+}
+
 impl Add<PunishTimelock> for BlockHeight {
     type Output = BlockHeight;
 
@@ -88,6 +101,36 @@ impl PartialEq<PunishTimelock> for u32 {
     }
 }
 
+/// If this bit of a sequence number is set the relative timelock is expressed
+/// in units of 512 seconds instead of blocks, see BIP68.
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+
+/// The relative timelock is encoded in this many low bits of the sequence
+/// number.
+const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_FFFF;
+
+/// Reasons why a cancel transaction would not enforce the cancel timelock the
+/// way the swap protocol assumes.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum InvalidTxCancel {
+    #[error("Cancel fee of {fee} is not less than the locked amount of {lock_amount}")]
+    FeeExceedsLockAmount { fee: Amount, lock_amount: Amount },
+    #[error("Cancel timelock of {0} cannot be expressed as a relative timelock in blocks")]
+    InvalidCancelTimelock(CancelTimelock),
+    #[error("Cancel transaction has version {0}, expected 2")]
+    UnsupportedVersion(i32),
+    #[error("Cancel transaction has an absolute locktime of {0}")]
+    AbsoluteLockTime(u32),
+    #[error("Cancel transaction has {0} inputs, expected a single input spending the lock output")]
+    UnexpectedInputs(usize),
+    #[error("Cancel transaction input has sequence number {actual:#x}, expected {expected:#x}")]
+    UnexpectedSequence { expected: u32, actual: u32 },
+    #[error("Cancel transaction has {0} outputs, expected one")]
+    UnexpectedOutputs(usize),
+    #[error("Cancel transaction output does not pay the cancel amount to the shared output of both parties")]
+    UnexpectedOutput,
+}
+
 #[derive(Debug)]
 pub struct TxCancel {
     inner: Transaction,
@@ -140,6 +183,39 @@ impl TxCancel {
         }
     }
 
+    /// Like [`TxCancel::new`] but fails if the resulting transaction would
+    /// not enforce `cancel_timelock` on the lock output.
+    ///
+    /// This should be used whenever the parameters stem from the
+    /// counterparty.
+    pub fn new_checked(
+        tx_lock: &TxLock,
+        cancel_timelock: CancelTimelock,
+        A: PublicKey,
+        B: PublicKey,
+        spending_fee: Amount,
+    ) -> Result<Self, InvalidTxCancel> {
+        let lock_amount = tx_lock.lock_amount();
+        if spending_fee >= lock_amount {
+            return Err(InvalidTxCancel::FeeExceedsLockAmount {
+                fee: spending_fee,
+                lock_amount,
+            });
+        }
+
+        // Derived from the parameters instead of the built transaction, so the
+        // check does not depend on `TxCancel::new` getting the output right
+        let expected_output = TxOut {
+            value: lock_amount.as_sat() - spending_fee.as_sat(),
+            script_pubkey: build_shared_output_descriptor(A.0, B.0).script_pubkey(),
+        };
+
+        let tx_cancel = Self::new(tx_lock, cancel_timelock, A, B, spending_fee);
+        check_structure(&tx_cancel.inner, tx_lock, cancel_timelock, &expected_output)?;
+
+        Ok(tx_cancel)
+    }
+
     pub fn txid(&self) -> Txid {
         self.inner.txid()
     }
@@ -252,6 +328,103 @@ impl TxCancel {
     }
 }
 
+/// Checks that `transaction` enforces `cancel_timelock` on the lock output and
+/// pays only the `expected_output`.
+fn check_structure(
+    transaction: &Transaction,
+    tx_lock: &TxLock,
+    cancel_timelock: CancelTimelock,
+    expected_output: &TxOut,
+) -> Result<(), InvalidTxCancel> {
+    let sequence_flags = SEQUENCE_LOCKTIME_DISABLE_FLAG | SEQUENCE_LOCKTIME_TYPE_FLAG;
+    if cancel_timelock.0 & sequence_flags != 0 || cancel_timelock.0 > SEQUENCE_LOCKTIME_MASK {
+        return Err(InvalidTxCancel::InvalidCancelTimelock(cancel_timelock));
+    }
+
+    // BIP68 is only enforced for transactions of version 2 and up
+    if transaction.version != 2 {
+        return Err(InvalidTxCancel::UnsupportedVersion(transaction.version));
+    }
+
+    if transaction.lock_time != 0 {
+        return Err(InvalidTxCancel::AbsoluteLockTime(transaction.lock_time));
+    }
+
+    let input = match transaction.input.as_slice() {
+        [input] if input.previous_output == tx_lock.as_outpoint() => input,
+        inputs => return Err(InvalidTxCancel::UnexpectedInputs(inputs.len())),
+    };
+
+    if input.sequence != cancel_timelock.0 {
+        return Err(InvalidTxCancel::UnexpectedSequence {
+            expected: cancel_timelock.0,
+            actual: input.sequence,
+        });
+    }
+
+    match transaction.output.as_slice() {
+        [output] if output == expected_output => Ok(()),
+        [_] => Err(InvalidTxCancel::UnexpectedOutput),
+        outputs => Err(InvalidTxCancel::UnexpectedOutputs(outputs.len())),
+    }
+}
+
+/// What to do if the cancel transaction is unknown to the blockchain and the
+/// mempool right before the refund spending it is published.
+///
+/// A cancel transaction that was published already only disappears if a reorg
+/// removed the block that confirmed it and it was dropped from the mempool
+/// afterwards. A refund published then references a parent that no longer
+/// exists and is never confirmed.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    strum::Display,
+    strum::EnumString,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CancelReorgPolicy {
+    /// Publish the cancel transaction again before the refund.
+    Rebroadcast,
+    /// Do not publish the refund, so the swap can be cancelled and refunded
+    /// manually once the chain settled.
+    Abort,
+}
+
+impl Default for CancelReorgPolicy {
+    fn default() -> Self {
+        CancelReorgPolicy::Rebroadcast
+    }
+}
+
+impl CancelReorgPolicy {
+    /// Whether the cancel transaction with the given status has to be
+    /// published again before the refund, fails if the policy does not allow
+    /// it.
+    ///
+    /// A status that could not be determined is treated like an unseen
+    /// transaction, publishing a transaction that is known already is
+    /// harmless.
+    pub fn rebroadcast_tx_cancel(self, status: ScriptStatus) -> Result<bool> {
+        if status.has_been_seen() {
+            return Ok(false);
+        }
+
+        match self {
+            CancelReorgPolicy::Rebroadcast => Ok(true),
+            CancelReorgPolicy::Abort => bail!(
+                "The cancel transaction is {}, likely because a reorg removed it. Not publishing the refund, run `swap cancel` and `swap refund` again once the chain settled",
+                status
+            ),
+        }
+    }
+}
+
 impl Watchable for TxCancel {
     fn id(&self) -> Txid {
         self.txid()
@@ -261,3 +434,157 @@ impl Watchable for TxCancel {
         self.output_descriptor.script_pubkey()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::WalletBuilder;
+
+    const CANCEL_TIMELOCK: CancelTimelock = CancelTimelock::new(12);
+
+    #[test]
+    fn published_cancel_transaction_is_not_rebroadcast() {
+        for policy in [CancelReorgPolicy::Rebroadcast, CancelReorgPolicy::Abort] {
+            assert!(!policy
+                .rebroadcast_tx_cancel(ScriptStatus::InMempool)
+                .unwrap());
+            assert!(!policy
+                .rebroadcast_tx_cancel(ScriptStatus::from_confirmations(3))
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn reorged_cancel_transaction_is_rebroadcast_or_aborts_as_configured() {
+        assert!(CancelReorgPolicy::Rebroadcast
+            .rebroadcast_tx_cancel(ScriptStatus::Unseen)
+            .unwrap());
+        assert!(CancelReorgPolicy::Abort
+            .rebroadcast_tx_cancel(ScriptStatus::Unseen)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn given_valid_parameters_then_cancel_transaction_is_accepted() {
+        let (tx_lock, A, B) = tx_lock().await;
+
+        let result =
+            TxCancel::new_checked(&tx_lock, CANCEL_TIMELOCK, A, B, Amount::from_sat(1_000));
+
+        result.expect("cancel transaction to be valid");
+    }
+
+    #[tokio::test]
+    async fn given_fee_exceeding_lock_amount_then_fails() {
+        let (tx_lock, A, B) = tx_lock().await;
+        let lock_amount = tx_lock.lock_amount();
+        let fee = lock_amount;
+
+        let error = TxCancel::new_checked(&tx_lock, CANCEL_TIMELOCK, A, B, fee).unwrap_err();
+
+        assert_eq!(
+            error,
+            InvalidTxCancel::FeeExceedsLockAmount { fee, lock_amount }
+        );
+    }
+
+    #[tokio::test]
+    async fn given_cancel_timelock_that_is_not_block_based_then_fails() {
+        let (tx_lock, A, B) = tx_lock().await;
+
+        for &timelock in &[
+            SEQUENCE_LOCKTIME_MASK + 1,
+            SEQUENCE_LOCKTIME_TYPE_FLAG | 12,
+            SEQUENCE_LOCKTIME_DISABLE_FLAG | 12,
+        ] {
+            let timelock = CancelTimelock::new(timelock);
+
+            let error = TxCancel::new_checked(&tx_lock, timelock, A, B, Amount::from_sat(1_000))
+                .unwrap_err();
+
+            assert_eq!(error, InvalidTxCancel::InvalidCancelTimelock(timelock));
+        }
+    }
+
+    #[tokio::test]
+    async fn given_malformed_cancel_transaction_then_fails() {
+        let (tx_lock, A, B) = tx_lock().await;
+        let valid = TxCancel::new(&tx_lock, CANCEL_TIMELOCK, A, B, Amount::from_sat(1_000)).inner;
+        let output = TxOut {
+            value: tx_lock.lock_amount().as_sat() - 1_000,
+            script_pubkey: build_shared_output_descriptor(A.0, B.0).script_pubkey(),
+        };
+
+        let mut transaction = valid.clone();
+        transaction.version = 1;
+        assert_eq!(
+            check_structure(&transaction, &tx_lock, CANCEL_TIMELOCK, &output),
+            Err(InvalidTxCancel::UnsupportedVersion(1))
+        );
+
+        let mut transaction = valid.clone();
+        transaction.lock_time = 700_000;
+        assert_eq!(
+            check_structure(&transaction, &tx_lock, CANCEL_TIMELOCK, &output),
+            Err(InvalidTxCancel::AbsoluteLockTime(700_000))
+        );
+
+        let mut transaction = valid.clone();
+        transaction.input[0].previous_output = OutPoint::default();
+        assert_eq!(
+            check_structure(&transaction, &tx_lock, CANCEL_TIMELOCK, &output),
+            Err(InvalidTxCancel::UnexpectedInputs(1))
+        );
+
+        let mut transaction = valid.clone();
+        transaction.input.push(transaction.input[0].clone());
+        assert_eq!(
+            check_structure(&transaction, &tx_lock, CANCEL_TIMELOCK, &output),
+            Err(InvalidTxCancel::UnexpectedInputs(2))
+        );
+
+        let mut transaction = valid.clone();
+        transaction.input[0].sequence = 0xFFFF_FFFF;
+        assert_eq!(
+            check_structure(&transaction, &tx_lock, CANCEL_TIMELOCK, &output),
+            Err(InvalidTxCancel::UnexpectedSequence {
+                expected: 12,
+                actual: 0xFFFF_FFFF
+            })
+        );
+
+        let mut transaction = valid.clone();
+        transaction.output.push(transaction.output[0].clone());
+        assert_eq!(
+            check_structure(&transaction, &tx_lock, CANCEL_TIMELOCK, &output),
+            Err(InvalidTxCancel::UnexpectedOutputs(2))
+        );
+
+        let mut transaction = valid.clone();
+        transaction.output[0].value -= 1;
+        assert_eq!(
+            check_structure(&transaction, &tx_lock, CANCEL_TIMELOCK, &output),
+            Err(InvalidTxCancel::UnexpectedOutput)
+        );
+
+        let mut transaction = valid;
+        transaction.output[0].script_pubkey =
+            build_shared_output_descriptor(B.0, PublicKey::random().0).script_pubkey();
+        assert_eq!(
+            check_structure(&transaction, &tx_lock, CANCEL_TIMELOCK, &output),
+            Err(InvalidTxCancel::UnexpectedOutput)
+        );
+    }
+
+    async fn tx_lock() -> (TxLock, PublicKey, PublicKey) {
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let wallet = WalletBuilder::new(50_000).build();
+        let change = wallet.new_address().await.unwrap();
+
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B, change, &[])
+            .await
+            .unwrap();
+
+        (tx_lock, A, B)
+    }
+}
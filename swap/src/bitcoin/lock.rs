@@ -12,6 +12,28 @@ use serde::{Deserialize, Serialize};
 
 const SCRIPT_SIZE: usize = 34;
 
+/// Values of `lock_time` from this threshold on are interpreted as a UNIX
+/// timestamp instead of a block height.
+const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
+/// If this bit of an input's sequence number is set the input does not have a
+/// relative timelock, see [BIP68](https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki).
+pub(in crate::bitcoin) const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+
+const SEQUENCE_FINAL: u32 = 0xFFFF_FFFF;
+
+/// Reasons for rejecting a lock transaction whose structure could delay it
+/// beyond what the timelocks of the swap account for.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum InvalidTxLock {
+    #[error("Lock transaction has version {0}, expected 1 or 2")]
+    UnsupportedVersion(i32),
+    #[error("Input {input} of the lock transaction has sequence number {sequence:#x} which enables a relative timelock")]
+    RelativeTimelock { input: usize, sequence: u32 },
+    #[error("Lock transaction is timelocked until the UNIX timestamp {0}")]
+    TimestampLockTime(u32),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TxLock {
     inner: PartiallySignedTransaction,
@@ -19,12 +41,15 @@ pub struct TxLock {
 }
 
 impl TxLock {
+    /// Funds the lock output from the wallet without spending any of the
+    /// `unspendable` UTXOs.
     pub async fn new<B, D, C>(
         wallet: &Wallet<B, D, C>,
         amount: Amount,
         A: PublicKey,
         B: PublicKey,
         change: bitcoin::Address,
+        unspendable: &[OutPoint],
     ) -> Result<Self>
     where
         C: EstimateFeeRate,
@@ -36,7 +61,7 @@ impl TxLock {
             .expect("can derive address from descriptor");
 
         let psbt = wallet
-            .send_to_address(address, amount, Some(change))
+            .send_to_address_avoiding(address, amount, Some(change), unspendable)
             .await?;
 
         Ok(Self {
@@ -56,6 +81,8 @@ impl TxLock {
         B: PublicKey,
         btc: Amount,
     ) -> Result<Self> {
+        check_structure(&psbt.global.unsigned_tx)?;
+
         let shared_output_candidate = match psbt.global.unsigned_tx.output.as_slice() {
             [shared_output_candidate, _] if shared_output_candidate.value == btc.as_sat() => {
                 shared_output_candidate
@@ -103,6 +130,17 @@ impl TxLock {
         self.inner.clone().extract_tx().txid()
     }
 
+    /// The UTXOs of the wallet that fund the lock output.
+    pub fn inputs(&self) -> Vec<OutPoint> {
+        self.inner
+            .global
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect()
+    }
+
     pub fn as_outpoint(&self) -> OutPoint {
         // This is fine because a transaction that has that many outputs is not
         // realistic
@@ -115,6 +153,12 @@ impl TxLock {
         SCRIPT_SIZE
     }
 
+    /// The weight of a lock transaction that spends a single native segwit
+    /// output without change, e.g. the deposit into an empty wallet.
+    pub fn weight() -> usize {
+        486
+    }
+
     pub fn script_pubkey(&self) -> Script {
         self.output_descriptor.script_pubkey()
     }
@@ -163,6 +207,40 @@ impl TxLock {
     }
 }
 
+/// Checks the fields the timelock logic of the swap depends on.
+///
+/// The outputs are checked separately because that requires knowing the
+/// parameters of the swap.
+fn check_structure(transaction: &Transaction) -> Result<(), InvalidTxLock> {
+    if !(1..=2).contains(&transaction.version) {
+        return Err(InvalidTxLock::UnsupportedVersion(transaction.version));
+    }
+
+    // Relative timelocks are only enforced for transactions of version 2 and up
+    if transaction.version >= 2 {
+        if let Some((input, sequence)) = transaction
+            .input
+            .iter()
+            .map(|input| input.sequence)
+            .enumerate()
+            .find(|(_, sequence)| sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0)
+        {
+            return Err(InvalidTxLock::RelativeTimelock { input, sequence });
+        }
+    }
+
+    // The locktime is only enforced if at least one input is not final
+    let lock_time_enforced = transaction
+        .input
+        .iter()
+        .any(|input| input.sequence != SEQUENCE_FINAL);
+    if lock_time_enforced && transaction.lock_time >= LOCK_TIME_THRESHOLD {
+        return Err(InvalidTxLock::TimestampLockTime(transaction.lock_time));
+    }
+
+    Ok(())
+}
+
 impl From<TxLock> for PartiallySignedTransaction {
     fn from(from: TxLock) -> Self {
         from.inner
@@ -241,6 +319,76 @@ mod tests {
         result.expect_err("PSBT to be invalid");
     }
 
+    #[tokio::test]
+    async fn given_lock_transaction_with_unsupported_version_when_reconstructing_then_fails() {
+        let (A, B) = alice_and_bob();
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+
+        let mut psbt = bob_make_psbt(A, B, &wallet, agreed_amount).await;
+        psbt.global.unsigned_tx.version = 3;
+        let error = TxLock::from_psbt(psbt, A, B, agreed_amount).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<InvalidTxLock>().unwrap(),
+            &InvalidTxLock::UnsupportedVersion(3)
+        );
+    }
+
+    #[tokio::test]
+    async fn given_lock_transaction_with_relative_timelock_when_reconstructing_then_fails() {
+        let (A, B) = alice_and_bob();
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+
+        let mut psbt = bob_make_psbt(A, B, &wallet, agreed_amount).await;
+        psbt.global.unsigned_tx.version = 2;
+        psbt.global.unsigned_tx.input[0].sequence = 144;
+        let error = TxLock::from_psbt(psbt, A, B, agreed_amount).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<InvalidTxLock>().unwrap(),
+            &InvalidTxLock::RelativeTimelock {
+                input: 0,
+                sequence: 144
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn given_lock_transaction_with_timestamp_locktime_when_reconstructing_then_fails() {
+        let (A, B) = alice_and_bob();
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+
+        let mut psbt = bob_make_psbt(A, B, &wallet, agreed_amount).await;
+        psbt.global.unsigned_tx.lock_time = LOCK_TIME_THRESHOLD;
+        psbt.global.unsigned_tx.input[0].sequence = SEQUENCE_FINAL - 1;
+        let error = TxLock::from_psbt(psbt, A, B, agreed_amount).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<InvalidTxLock>().unwrap(),
+            &InvalidTxLock::TimestampLockTime(LOCK_TIME_THRESHOLD)
+        );
+    }
+
+    #[test]
+    fn timestamp_locktime_is_ignored_if_all_inputs_are_final() {
+        let transaction = Transaction {
+            version: 2,
+            lock_time: LOCK_TIME_THRESHOLD,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Default::default(),
+                sequence: SEQUENCE_FINAL,
+                witness: Vec::new(),
+            }],
+            output: vec![],
+        };
+
+        assert_eq!(check_structure(&transaction), Ok(()));
+    }
+
     proptest::proptest! {
         #[test]
         fn estimated_tx_lock_script_size_never_changes(a in crate::proptest::ecdsa_fun::point(), b in crate::proptest::ecdsa_fun::point()) {
@@ -262,7 +410,7 @@ mod tests {
         amount: Amount,
     ) -> PartiallySignedTransaction {
         let change = wallet.new_address().await.unwrap();
-        TxLock::new(&wallet, amount, A, B, change)
+        TxLock::new(&wallet, amount, A, B, change, &[])
             .await
             .unwrap()
             .into()
@@ -0,0 +1,51 @@
+use crate::bitcoin::{Address, Amount};
+use ::bitcoin::Denomination;
+use std::fmt;
+
+/// A request to pay to an address, displayed as a [BIP21](https://github.com/bitcoin/bips/blob/master/bip-0021.mediawiki)
+/// URI that wallets can open or scan, e.g.
+/// `bitcoin:bc1q...?amount=0.01000000`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentRequest {
+    pub address: Address,
+    /// Without an amount the wallet lets the user choose one.
+    pub amount: Option<Amount>,
+}
+
+impl PaymentRequest {
+    pub fn new(address: Address, amount: Option<Amount>) -> Self {
+        Self { address, amount }
+    }
+}
+
+impl fmt::Display for PaymentRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bitcoin:{}", self.address)?;
+
+        if let Some(amount) = self.amount {
+            write!(f, "?amount={}", amount.to_string_in(Denomination::Bitcoin))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn displays_bip21_uri_with_amount_in_btc() {
+        let address = Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq").unwrap();
+
+        assert_eq!(
+            PaymentRequest::new(address.clone(), Some(Amount::from_sat(1_001_000))).to_string(),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=0.01001000"
+        );
+        assert_eq!(
+            PaymentRequest::new(address, None).to_string(),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use crate::bitcoin::wallet::Watchable;
 use crate::bitcoin::{
-    verify_encsig, verify_sig, Address, Amount, EmptyWitnessStack, EncryptedSignature, NoInputs,
-    NotThreeWitnesses, PublicKey, SecretKey, TooManyInputs, Transaction, TxLock,
+    verify_encsig, verify_sig, Address, Amount, EmptyWitnessStack, EncryptedSignature, PublicKey,
+    SecretKey, Transaction, TxLock,
 };
 use ::bitcoin::util::bip143::SigHashCache;
 use ::bitcoin::{SigHash, SigHashType, Txid};
@@ -97,39 +97,41 @@ impl TxRedeem {
         Ok(self.inner)
     }
 
+    /// Extracts the signature of `B` from a transaction that spends the lock
+    /// output.
+    ///
+    /// The candidate does not have to match the transaction we constructed,
+    /// e.g. Alice may have signed it with another sighash type. A signature
+    /// of `B` on the redeem digest is all we need.
     pub fn extract_signature_by_key(
         &self,
         candidate_transaction: Transaction,
         B: PublicKey,
     ) -> Result<Signature> {
-        let input = match candidate_transaction.input.as_slice() {
-            [input] => input,
-            [] => bail!(NoInputs),
-            [inputs @ ..] => bail!(TooManyInputs(inputs.len())),
-        };
+        let lock_outpoint = self.inner.input[0].previous_output;
+
+        let input = candidate_transaction
+            .input
+            .iter()
+            .find(|input| input.previous_output == lock_outpoint)
+            .context("Transaction does not spend the lock output")?;
+
+        if input.witness.is_empty() {
+            bail!(EmptyWitnessStack)
+        }
 
-        let sigs = match input
+        let sig = input
             .witness
             .iter()
-            .map(|vec| vec.as_slice())
-            .collect::<Vec<_>>()
-            .as_slice()
-        {
-            [sig_1, sig_2, _script] => [sig_1, sig_2]
-                .iter()
-                .map(|sig| {
-                    bitcoin::secp256k1::Signature::from_der(&sig[..sig.len() - 1])
-                        .map(Signature::from)
-                })
-                .collect::<std::result::Result<Vec<_>, _>>(),
-            [] => bail!(EmptyWitnessStack),
-            [witnesses @ ..] => bail!(NotThreeWitnesses(witnesses.len())),
-        }?;
-
-        let sig = sigs
-            .into_iter()
-            .find(|sig| verify_sig(&B, &self.digest(), &sig).is_ok())
-            .context("Neither signature on witness stack verifies against B")?;
+            .filter_map(|element| {
+                // Strip the sighash type
+                let (_, der) = element.split_last()?;
+                let sig = bitcoin::secp256k1::Signature::from_der(der).ok()?;
+
+                Some(Signature::from(sig))
+            })
+            .find(|sig| verify_sig(&B, &self.digest(), sig).is_ok())
+            .context("No signature on witness stack verifies against B")?;
 
         Ok(sig)
     }
@@ -153,3 +155,152 @@ impl Watchable for TxRedeem {
         self.watch_script.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{recover, WalletBuilder};
+    use ::bitcoin::OutPoint;
+    use rand::rngs::OsRng;
+
+    #[tokio::test]
+    async fn extracts_signature_from_expected_redeem_transaction() {
+        let setup = Setup::new().await;
+
+        let sig = setup
+            .tx_redeem
+            .extract_signature_by_key(setup.redeem_transaction.clone(), setup.b.public())
+            .unwrap();
+
+        setup.assert_recovers_s_a(sig);
+    }
+
+    #[tokio::test]
+    async fn extracts_signature_from_redeem_transaction_signed_with_other_sighash_type() {
+        let setup = Setup::new().await;
+        let variant =
+            setup.redeem_transaction_signed_by_alice_with(SigHashType::AllPlusAnyoneCanPay);
+        assert_ne!(variant.wtxid(), setup.redeem_transaction.wtxid());
+
+        let sig = setup
+            .tx_redeem
+            .extract_signature_by_key(variant, setup.b.public())
+            .unwrap();
+
+        setup.assert_recovers_s_a(sig);
+    }
+
+    #[tokio::test]
+    async fn fails_to_extract_signature_if_lock_output_is_not_spent() {
+        let setup = Setup::new().await;
+        let mut variant = setup.redeem_transaction.clone();
+        variant.input[0].previous_output = OutPoint::default();
+
+        let result = setup
+            .tx_redeem
+            .extract_signature_by_key(variant, setup.b.public());
+
+        assert!(result.is_err());
+    }
+
+    struct Setup {
+        tx_lock: TxLock,
+        tx_redeem: TxRedeem,
+        redeem_transaction: Transaction,
+        a: SecretKey,
+        b: SecretKey,
+        s_a: Scalar,
+        S_a: PublicKey,
+        encsig: EncryptedSignature,
+    }
+
+    impl Setup {
+        async fn new() -> Self {
+            let wallet = WalletBuilder::new(50_000).build();
+            let a = SecretKey::new_random(&mut OsRng);
+            let b = SecretKey::new_random(&mut OsRng);
+            let s_a = Scalar::random(&mut OsRng);
+            let S_a = PublicKey::from(s_a.clone());
+
+            let change = wallet.new_address().await.unwrap();
+            let tx_lock = TxLock::new(
+                &wallet,
+                Amount::from_sat(10_000),
+                a.public(),
+                b.public(),
+                change,
+                &[],
+            )
+            .await
+            .unwrap();
+            let redeem_address = wallet.new_address().await.unwrap();
+            let tx_redeem = TxRedeem::new(&tx_lock, &redeem_address, Amount::from_sat(1_000));
+
+            let encsig = b.encsign(S_a, tx_redeem.digest());
+            let redeem_transaction = tx_redeem
+                .clone()
+                .complete(encsig.clone(), a.clone(), s_a.clone(), b.public())
+                .unwrap();
+
+            Self {
+                tx_lock,
+                tx_redeem,
+                redeem_transaction,
+                a,
+                b,
+                s_a,
+                S_a,
+                encsig,
+            }
+        }
+
+        /// The redeem transaction with a valid signature of Alice over the
+        /// digest of `sighash_type`, while Bob's signature still covers all of
+        /// the transaction.
+        fn redeem_transaction_signed_by_alice_with(
+            &self,
+            sighash_type: SigHashType,
+        ) -> Transaction {
+            let mut transaction = self.tx_redeem.inner();
+            let digest = SigHashCache::new(&transaction).signature_hash(
+                0,
+                &self.tx_lock.output_descriptor.script_code(),
+                self.tx_lock.lock_amount().as_sat(),
+                sighash_type,
+            );
+
+            let sig_a = self.a.sign(digest);
+            let adaptor = Adaptor::<HashTranscript<Sha256>, Deterministic<Sha256>>::default();
+            let sig_b = adaptor.decrypt_signature(&self.s_a, self.encsig.clone());
+
+            let mut satisfier = HashMap::with_capacity(2);
+            satisfier.insert(
+                ::bitcoin::PublicKey {
+                    compressed: true,
+                    key: self.a.public.into(),
+                },
+                (sig_a.into(), sighash_type),
+            );
+            satisfier.insert(
+                ::bitcoin::PublicKey {
+                    compressed: true,
+                    key: self.b.public().0.into(),
+                },
+                (sig_b.into(), SigHashType::All),
+            );
+
+            self.tx_lock
+                .output_descriptor
+                .satisfy(&mut transaction.input[0], satisfier)
+                .unwrap();
+
+            transaction
+        }
+
+        fn assert_recovers_s_a(&self, sig: Signature) {
+            let s_a = recover(self.S_a, sig, self.encsig.clone()).unwrap();
+
+            assert_eq!(s_a.public(), self.S_a);
+        }
+    }
+}
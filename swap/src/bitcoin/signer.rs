@@ -0,0 +1,197 @@
+//! Signing of the transactions that spend from the internal Bitcoin wallet.
+//!
+//! By default the wallet signs with the keys derived from the seed, i.e. in
+//! the same process. Alternatively the wallet can be watch-only and hand its
+//! transactions to an [`ExternalSigner`], which keeps the spending key out of
+//! the swap process (e.g. in an HSM).
+//!
+//! Only the keys of the wallet itself are covered: The keys that are generated
+//! for every swap are needed to produce adaptor signatures and never hold
+//! funds beyond the duration of a swap.
+
+use ::bitcoin::consensus::encode;
+use ::bitcoin::util::psbt::PartiallySignedTransaction;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use bdk::database::BatchDatabase;
+use bdk::SignOptions;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use url::Url;
+
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Signs all inputs of `psbt` that spend from the wallet.
+    ///
+    /// The returned PSBT may or may not be finalized.
+    async fn sign(&self, psbt: PartiallySignedTransaction) -> Result<PartiallySignedTransaction>;
+}
+
+/// Signs with the private keys of the wallet's own descriptors.
+pub struct InProcessSigner<B, D> {
+    wallet: Arc<Mutex<bdk::Wallet<B, D>>>,
+}
+
+impl<B, D> InProcessSigner<B, D> {
+    pub fn new(wallet: Arc<Mutex<bdk::Wallet<B, D>>>) -> Self {
+        Self { wallet }
+    }
+}
+
+#[async_trait]
+impl<B, D> Signer for InProcessSigner<B, D>
+where
+    B: Send,
+    D: BatchDatabase + Send,
+{
+    async fn sign(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+    ) -> Result<PartiallySignedTransaction> {
+        self.wallet
+            .lock()
+            .await
+            .sign(&mut psbt, SignOptions::default())?;
+
+        Ok(psbt)
+    }
+}
+
+/// How long the external signer has to respond, including the time it takes
+/// to have the transaction confirmed on the signing device.
+const EXTERNAL_SIGNER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Sends transactions to an external signing service.
+///
+/// The service is expected to accept a `POST` request with a JSON body of the
+/// form `{"psbt": "<base64>"}` and to respond with the signed PSBT in the same
+/// form. The signed PSBT has to spend and pay exactly like the one that was
+/// sent.
+#[derive(Debug, Clone)]
+pub struct ExternalSigner {
+    url: Url,
+    client: reqwest::Client,
+}
+
+impl ExternalSigner {
+    pub fn new(url: Url) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(EXTERNAL_SIGNER_TIMEOUT)
+            .build()
+            .context("Failed to build HTTP client for external signer")?;
+
+        Ok(Self { url, client })
+    }
+}
+
+#[async_trait]
+impl Signer for ExternalSigner {
+    async fn sign(&self, psbt: PartiallySignedTransaction) -> Result<PartiallySignedTransaction> {
+        let txid = psbt.global.unsigned_tx.txid();
+
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(&PsbtMessage::from(&psbt))
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach external signer at {}", self.url))?
+            .error_for_status()
+            .with_context(|| format!("External signer refused to sign transaction {}", txid))?
+            .json::<PsbtMessage>()
+            .await
+            .context("Failed to deserialize response of external signer")?;
+
+        let signed = response.psbt()?;
+        ensure_same_transaction(&psbt, &signed)?;
+
+        tracing::debug!(%txid, "Transaction signed by external signer");
+
+        Ok(signed)
+    }
+}
+
+/// Fails if `signed` is not a signed version of the transaction of `sent`,
+/// signatures only commit to what the signer was shown and not to what it
+/// hands back.
+fn ensure_same_transaction(
+    sent: &PartiallySignedTransaction,
+    signed: &PartiallySignedTransaction,
+) -> Result<()> {
+    if signed.global.unsigned_tx != sent.global.unsigned_tx {
+        bail!(
+            "External signer returned transaction {} instead of {}",
+            signed.global.unsigned_tx.txid(),
+            sent.global.unsigned_tx.txid()
+        )
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PsbtMessage {
+    /// The consensus encoding of the PSBT, encoded as base64.
+    psbt: String,
+}
+
+impl PsbtMessage {
+    fn psbt(&self) -> Result<PartiallySignedTransaction> {
+        let bytes = base64::decode(&self.psbt).context("PSBT is not valid base64")?;
+        let psbt = encode::deserialize(&bytes).context("Failed to deserialize PSBT")?;
+
+        Ok(psbt)
+    }
+}
+
+impl From<&PartiallySignedTransaction> for PsbtMessage {
+    fn from(psbt: &PartiallySignedTransaction) -> Self {
+        Self {
+            psbt: base64::encode(encode::serialize(psbt)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::WalletBuilder;
+    use crate::bitcoin::Amount;
+
+    #[tokio::test]
+    async fn psbt_message_roundtrip() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let address = wallet.new_address().await.unwrap();
+        let psbt = wallet
+            .send_to_address(address, Amount::from_sat(10_000), None)
+            .await
+            .unwrap();
+
+        let message = serde_json::to_string(&PsbtMessage::from(&psbt)).unwrap();
+        let roundtripped = serde_json::from_str::<PsbtMessage>(&message)
+            .unwrap()
+            .psbt()
+            .unwrap();
+
+        assert_eq!(roundtripped, psbt);
+    }
+
+    #[tokio::test]
+    async fn rejects_signed_psbt_of_other_transaction() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let address = wallet.new_address().await.unwrap();
+        let sent = wallet
+            .send_to_address(address.clone(), Amount::from_sat(10_000), None)
+            .await
+            .unwrap();
+        let other = wallet
+            .send_to_address(address, Amount::from_sat(20_000), None)
+            .await
+            .unwrap();
+
+        assert!(ensure_same_transaction(&sent, &sent).is_ok());
+        assert!(ensure_same_transaction(&sent, &other).is_err());
+    }
+}
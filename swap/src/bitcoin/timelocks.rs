@@ -3,6 +3,7 @@ use bdk::electrum_client::HeaderNotification;
 use serde::{Deserialize, Serialize};
 use std::convert::{TryFrom, TryInto};
 use std::ops::Add;
+use std::time::Duration;
 
 /// Represent a block height, or block number, expressed in absolute block
 /// count. E.g. The transaction was included in block #655123, 655123 block
@@ -11,6 +12,12 @@ use std::ops::Add;
 #[serde(transparent)]
 pub struct BlockHeight(u32);
 
+impl From<u32> for BlockHeight {
+    fn from(height: u32) -> Self {
+        Self(height)
+    }
+}
+
 impl From<BlockHeight> for u32 {
     fn from(height: BlockHeight) -> Self {
         height.0
@@ -37,9 +44,57 @@ impl Add<u32> for BlockHeight {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// The approximate time it takes to mine `blocks` blocks, rounded down to
+/// full minutes, e.g. `~12h 0m`.
+pub fn approximate_duration(blocks: u32, avg_block_time: Duration) -> String {
+    let minutes = avg_block_time.as_secs() * u64::from(blocks) / 60;
+
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("~{}m", minutes),
+        (hours, minutes) => format!("~{}h {}m", hours, minutes),
+    }
+}
+
+/// Which phase of the timelock state machine a swap is in.
+///
+/// Serialized in snake case, matching its `Display` representation in
+/// structured (JSON) logs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
 pub enum ExpiredTimelocks {
     None,
     Cancel,
     Punish,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approximate_duration_of_blocks() {
+        let ten_minutes = Duration::from_secs(600);
+
+        assert_eq!(approximate_duration(72, ten_minutes), "~12h 0m");
+        assert_eq!(approximate_duration(3, ten_minutes), "~30m");
+        assert_eq!(approximate_duration(9, ten_minutes), "~1h 30m");
+        assert_eq!(approximate_duration(5, Duration::from_secs(1)), "~0m");
+    }
+
+    #[test]
+    fn expired_timelocks_serde_roundtrip() {
+        for &(expired_timelocks, json) in &[
+            (ExpiredTimelocks::None, r#""none""#),
+            (ExpiredTimelocks::Cancel, r#""cancel""#),
+            (ExpiredTimelocks::Punish, r#""punish""#),
+        ] {
+            let serialized = serde_json::to_string(&expired_timelocks).unwrap();
+            let deserialized = serde_json::from_str::<ExpiredTimelocks>(&serialized).unwrap();
+
+            assert_eq!(serialized, json);
+            assert_eq!(deserialized, expired_timelocks);
+            assert_eq!(expired_timelocks.to_string(), json.trim_matches('"'));
+        }
+    }
+}
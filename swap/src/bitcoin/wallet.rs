@@ -1,24 +1,35 @@
+use crate::bitcoin::signer::{ExternalSigner, InProcessSigner, Signer};
 use crate::bitcoin::timelocks::BlockHeight;
 use crate::bitcoin::{Address, Amount, Transaction};
 use crate::env;
+use ::bitcoin::blockdata::constants::genesis_block;
+use ::bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint};
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
-use ::bitcoin::Txid;
+use ::bitcoin::{BlockHash, OutPoint, Txid};
 use anyhow::{bail, Context, Result};
-use bdk::blockchain::{noop_progress, Blockchain, ElectrumBlockchain};
+use bdk::blockchain::any::AnyBlockchain;
+use bdk::blockchain::rpc::{Auth, RpcBlockchain, RpcConfig};
+use bdk::blockchain::{noop_progress, Blockchain, ConfigurableBlockchain, ElectrumBlockchain};
+use bdk::core_rpc::{self, RpcApi};
 use bdk::database::BatchDatabase;
-use bdk::descriptor::Segwitv0;
+use bdk::descriptor::{IntoWalletDescriptor, Segwitv0};
 use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
 use bdk::keys::DerivableKey;
+use bdk::wallet::coin_selection::{
+    BranchAndBoundCoinSelection, CoinSelectionAlgorithm, LargestFirstCoinSelection,
+};
 use bdk::wallet::export::WalletExport;
+use bdk::wallet::tx_builder::{CreateTx, TxBuilder};
 use bdk::wallet::AddressIndex;
 use bdk::{FeeRate, KeychainKind, SignOptions};
 use bitcoin::{Network, Script};
+use futures::future;
 use reqwest::Url;
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use std::collections::{BTreeMap, HashMap};
-use std::convert::TryFrom;
+use std::convert::{Infallible, TryFrom};
 use std::fmt;
 use std::path::Path;
 use std::sync::Arc;
@@ -27,58 +38,430 @@ use tokio::sync::{watch, Mutex};
 
 const SLED_TREE_NAME: &str = "default_tree";
 
+/// The derivation path `m/84'/<coin>'/<account>'/<change>` of the addresses of
+/// a BIP84 account.
+fn bip84_path(network: Network, account: u32, keychain: KeychainKind) -> Result<DerivationPath> {
+    let coin_type = match network {
+        Network::Bitcoin => 0,
+        _ => 1,
+    };
+    let change = match keychain {
+        KeychainKind::External => 0,
+        KeychainKind::Internal => 1,
+    };
+
+    let path = vec![
+        ChildNumber::from_hardened_idx(84)?,
+        ChildNumber::from_hardened_idx(coin_type)?,
+        ChildNumber::from_hardened_idx(account)
+            .with_context(|| format!("Invalid Bitcoin account {}", account))?,
+        ChildNumber::from_normal_idx(change)?,
+    ];
+
+    Ok(DerivationPath::from(path))
+}
+
+/// Every account keeps its addresses and transactions in its own tree of the
+/// wallet database, account 0 in the tree used before there were accounts.
+fn sled_tree_name(account: u32) -> String {
+    match account {
+        0 => SLED_TREE_NAME.to_owned(),
+        account => format!("{}_account_{}", SLED_TREE_NAME, account),
+    }
+}
+
 /// Assuming we add a spread of 3% we don't want to pay more than 3% of the
 /// amount for tx fees.
 const MAX_RELATIVE_TX_FEE: Decimal = dec!(0.03);
 const MAX_ABSOLUTE_TX_FEE: Decimal = dec!(100_000);
 const DUST_AMOUNT: u64 = 546;
 
-pub struct Wallet<B = ElectrumBlockchain, D = bdk::sled::Tree, C = Client> {
+/// The cost of a change output in vbytes: Creating the P2WPKH output and
+/// spending it again later on.
+const COST_OF_CHANGE: u64 = 31 + 68;
+
+/// How the wallet selects the UTXOs that fund a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum CoinSelection {
+    /// Spend the largest UTXOs first, which results in as few inputs as
+    /// possible.
+    LargestFirst,
+    /// Search for a combination of UTXOs that does not need a change output
+    /// and fall back to a random selection otherwise.
+    BranchAndBound,
+    /// Like [`CoinSelection::BranchAndBound`], but rather pay up to the cost
+    /// of a change output as additional fee than create one.
+    AvoidChange,
+}
+
+impl Default for CoinSelection {
+    fn default() -> Self {
+        CoinSelection::BranchAndBound
+    }
+}
+
+/// The type of the addresses the wallet derives for its change outputs.
+///
+/// This does not affect the outputs of the swap transactions themselves, e.g.
+/// the lock output, whose scripts are fixed by the protocol.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    strum::Display,
+    strum::EnumString,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AddressType {
+    /// Native segwit v0 addresses derived according to BIP84.
+    P2wpkh,
+}
+
+impl Default for AddressType {
+    fn default() -> Self {
+        AddressType::P2wpkh
+    }
+}
+
+/// How the wallet escalates the fee of its own unconfirmed transactions.
+///
+/// A transaction that is still unconfirmed after `bump_after_blocks` blocks is
+/// bumped towards the fee rate for a target block half as far away as the
+/// previous one, by paying for it with a child transaction spending our output
+/// of it (child-pays-for-parent). Every further escalation replaces the
+/// previous child. Escalation stops once the transaction is confirmed or
+/// bumping further would exceed `max_fee`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeBumpSchedule {
+    pub bump_after_blocks: u32,
+    /// The maximum fee paid by the transaction and its child together.
+    pub max_fee: Amount,
+}
+
+/// The target blocks the fees of the Bitcoin transactions of a swap are
+/// estimated for.
+///
+/// It is chosen when the swap is created and stored with it, so resuming or
+/// recovering the swap later estimates the same fees even if the target block
+/// configured then is a different one. Each party only uses the targets of
+/// the transactions whose fees it chooses: the CLI those of the lock, cancel
+/// and refund transactions, the ASB that of the redeem transaction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeStrategy {
+    pub lock: usize,
+    pub cancel: usize,
+    pub refund: usize,
+    pub redeem: usize,
+}
+
+impl FeeStrategy {
+    /// Estimates the fees of all transactions for the same target block.
+    pub fn new(target_block: usize) -> Self {
+        Self {
+            lock: target_block,
+            cancel: target_block,
+            refund: target_block,
+            redeem: target_block,
+        }
+    }
+}
+
+/// Every escalation raises the fee rate by at least this factor, even if the
+/// estimate for the next target block did not change.
+const FEE_BUMP_MIN_INCREASE: f32 = 1.25;
+
+const FEE_BUMP_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Further Electrum servers that have to agree that a transaction is
+/// confirmed before the wallet treats it as such.
+///
+/// The status of a transaction that the node of the wallet reports as
+/// confirmed is also requested from every server, the status reported by at
+/// least `size` of all of them, counting the node of the wallet, is used. A
+/// single server can thereby neither make a transaction appear confirmed nor
+/// deeper than it is. Unreachable servers count as having not seen the
+/// transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmationQuorum {
+    servers: Vec<Url>,
+    size: usize,
+}
+
+impl ConfirmationQuorum {
+    /// Requires `size` servers out of the node of the wallet and `servers` to
+    /// agree, a size of 1 trusts the node of the wallet alone.
+    pub fn new(servers: Vec<Url>, size: usize) -> Result<Self> {
+        if size == 0 || size > servers.len() + 1 {
+            bail!(
+                "The quorum of Electrum servers must be between 1 and {}, the number of configured servers, got {}",
+                servers.len() + 1,
+                size
+            );
+        }
+
+        Ok(Self { servers, size })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Default for ConfirmationQuorum {
+    fn default() -> Self {
+        Self {
+            servers: Vec::new(),
+            size: 1,
+        }
+    }
+}
+
+/// The error code bitcoind returns if it does not know a transaction.
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+/// Where the wallet gets its view of the blockchain from.
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Electrum {
+        rpc_url: Url,
+    },
+    /// The RPC interface of a bitcoind full node, which has to run with
+    /// `txindex=1` because the transactions of a swap are looked up by id.
+    ///
+    /// The addresses of the wallet are imported into the watch-only bitcoind
+    /// wallet `wallet_name`, which is created if it does not exist yet.
+    Bitcoind {
+        rpc_url: Url,
+        auth: Auth,
+        wallet_name: String,
+    },
+}
+
+pub struct Wallet<B = AnyBlockchain, D = bdk::sled::Tree, C = Client> {
     client: Arc<Mutex<C>>,
     wallet: Arc<Mutex<bdk::Wallet<B, D>>>,
+    signer: Arc<dyn Signer>,
     finality_confirmations: u32,
+    electrum_reconnect_attempts: u32,
     network: Network,
     target_block: usize,
+    coin_selection: CoinSelection,
+    /// The UTXOs that fund our transactions, if empty they are selected
+    /// according to `coin_selection`.
+    utxos: Vec<OutPoint>,
+    fee_bump_schedule: Option<FeeBumpSchedule>,
 }
 
 impl Wallet {
     pub async fn new(
-        electrum_rpc_url: Url,
+        backend: Backend,
         wallet_dir: &Path,
         key: impl DerivableKey<Segwitv0> + Clone,
         env_config: env::Config,
         target_block: usize,
     ) -> Result<Self> {
-        let config = bdk::electrum_client::ConfigBuilder::default()
-            .retry(5)
-            .build();
-        let client = bdk::electrum_client::Client::from_config(electrum_rpc_url.as_str(), config)
-            .context("Failed to initialize Electrum RPC client")?;
+        let network = env_config.bitcoin_network;
+
+        match env_config.bitcoin_account {
+            // Keeps the descriptors of the wallets that were created before
+            // accounts could be selected, the database refuses others.
+            0 => {
+                Self::with_descriptors(
+                    backend,
+                    wallet_dir,
+                    bdk::template::Bip84(key.clone(), KeychainKind::External),
+                    bdk::template::Bip84(key, KeychainKind::Internal),
+                    None,
+                    env_config,
+                    target_block,
+                )
+                .await
+            }
+            account => {
+                Self::with_descriptors(
+                    backend,
+                    wallet_dir,
+                    bdk::template::P2Wpkh((
+                        key.clone(),
+                        bip84_path(network, account, KeychainKind::External)?,
+                    )),
+                    bdk::template::P2Wpkh((
+                        key,
+                        bip84_path(network, account, KeychainKind::Internal)?,
+                    )),
+                    None,
+                    env_config,
+                    target_block,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Creates a watch-only wallet for the BIP84 account `account_xpub` whose
+    /// transactions are signed by the [`ExternalSigner`] at `signer_url`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new_with_external_signer(
+        backend: Backend,
+        wallet_dir: &Path,
+        account_xpub: ExtendedPubKey,
+        master_fingerprint: Fingerprint,
+        signer_url: Url,
+        env_config: env::Config,
+        target_block: usize,
+    ) -> Result<Self> {
+        Self::with_descriptors(
+            backend,
+            wallet_dir,
+            bdk::template::Bip84Public(account_xpub, master_fingerprint, KeychainKind::External),
+            bdk::template::Bip84Public(account_xpub, master_fingerprint, KeychainKind::Internal),
+            Some(Arc::new(ExternalSigner::new(signer_url)?)),
+            env_config,
+            target_block,
+        )
+        .await
+    }
 
-        let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+    /// Only treat transactions as confirmed if the given quorum of Electrum
+    /// servers agrees, see [`ConfirmationQuorum`].
+    ///
+    /// The servers are connected to with the same `connect_timeout` as the
+    /// node of the wallet and have to be on the same network.
+    pub async fn with_confirmation_quorum(
+        self,
+        quorum: ConfirmationQuorum,
+        connect_timeout: Duration,
+    ) -> Result<Self> {
+        let mut servers = Vec::with_capacity(quorum.servers.len());
+        for rpc_url in quorum.servers {
+            let electrum = connect_electrum(&rpc_url, connect_timeout)?;
+            let genesis = electrum
+                .block_header(0)
+                .context("Failed to get genesis block header from Electrum server")?;
+            ensure_same_network(self.network, genesis.block_hash(), "Electrum server")?;
+
+            servers.push(QuorumServer { rpc_url, electrum });
+        }
+
+        if quorum.size > 1 {
+            tracing::info!(
+                quorum = quorum.size,
+                servers = servers.len() + 1,
+                "Transactions are only treated as confirmed if enough Electrum servers agree"
+            );
+        }
+
+        {
+            let mut client = self.client.lock().await;
+            client.quorum_servers = servers;
+            client.quorum_size = quorum.size;
+        }
+
+        Ok(self)
+    }
+
+    /// Signs with the private keys of the descriptors unless a `signer` is
+    /// given.
+    async fn with_descriptors<E>(
+        backend: Backend,
+        wallet_dir: &Path,
+        descriptor: E,
+        change_descriptor: E,
+        signer: Option<Arc<dyn Signer>>,
+        env_config: env::Config,
+        target_block: usize,
+    ) -> Result<Self>
+    where
+        E: IntoWalletDescriptor,
+    {
+        let (blockchain, node, rpc_url) = match backend {
+            Backend::Electrum { rpc_url } => {
+                let timeout = env_config.bitcoin_electrum_connect_timeout;
+                let client = connect_electrum(&rpc_url, timeout)?;
+                let electrum = connect_electrum(&rpc_url, timeout)?;
+
+                (
+                    AnyBlockchain::from(ElectrumBlockchain::from(client)),
+                    Node::Electrum(electrum),
+                    rpc_url,
+                )
+            }
+            Backend::Bitcoind {
+                rpc_url,
+                auth,
+                wallet_name,
+            } => {
+                let blockchain = RpcBlockchain::from_config(&RpcConfig {
+                    url: rpc_url.to_string(),
+                    auth: auth.clone(),
+                    network: env_config.bitcoin_network,
+                    wallet_name,
+                    skip_blocks: None,
+                })
+                .context("Failed to initialize bitcoind RPC client")?;
+
+                let bitcoind = core_rpc::Client::new(rpc_url.as_str(), auth.into())
+                    .context("Failed to initialize bitcoind RPC client")?;
+                ensure_txindex(&bitcoind)?;
+
+                (
+                    AnyBlockchain::from(blockchain),
+                    Node::Bitcoind(bitcoind),
+                    rpc_url,
+                )
+            }
+        };
+
+        let db =
+            bdk::sled::open(wallet_dir)?.open_tree(sled_tree_name(env_config.bitcoin_account))?;
 
         let wallet = bdk::Wallet::new(
-            bdk::template::Bip84(key.clone(), KeychainKind::External),
-            Some(bdk::template::Bip84(key, KeychainKind::Internal)),
+            descriptor,
+            Some(change_descriptor),
             env_config.bitcoin_network,
             db,
-            ElectrumBlockchain::from(client),
+            blockchain,
         )?;
 
-        let electrum = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
-
         let network = wallet.network();
 
+        ensure_same_network(network, node.genesis_block_hash()?, node.name())?;
+
+        let fallback_fee_rate = match env_config.bitcoin_fallback_fee_rate {
+            Some(sat_per_vb) if !(sat_per_vb > 0.0 && sat_per_vb.is_finite()) => {
+                bail!("Fallback fee rate must be > 0 sat/vB, got {}", sat_per_vb)
+            }
+            fallback => fallback.map(FeeRate::from_sat_per_vb),
+        };
+
+        let wallet = Arc::new(Mutex::new(wallet));
+        let signer: Arc<dyn Signer> = match signer {
+            Some(signer) => signer,
+            None => Arc::new(InProcessSigner::new(wallet.clone())),
+        };
+
         Ok(Self {
             client: Arc::new(Mutex::new(Client::new(
-                electrum,
+                node,
+                rpc_url,
                 env_config.bitcoin_sync_interval(),
+                env_config.bitcoin_status_cache_ttl,
+                fallback_fee_rate,
             )?)),
-            wallet: Arc::new(Mutex::new(wallet)),
+            wallet,
+            signer,
             finality_confirmations: env_config.bitcoin_finality_confirmations,
+            electrum_reconnect_attempts: env_config.bitcoin_electrum_reconnect_attempts,
             network,
             target_block,
+            coin_selection: CoinSelection::default(),
+            utxos: Vec::new(),
+            fee_bump_schedule: None,
         })
     }
 
@@ -87,6 +470,10 @@ impl Wallet {
     ///
     /// Returns the transaction ID and a future for when the transaction meets
     /// the configured finality confirmations.
+    ///
+    /// A transaction that is already known to the network is not published
+    /// again, so that a swap can repeat a transition that was interrupted
+    /// after publishing its transaction.
     pub async fn broadcast(
         &self,
         transaction: Transaction,
@@ -99,6 +486,14 @@ impl Wallet {
             .subscribe_to((txid, transaction.output[0].script_pubkey.clone()))
             .await;
 
+        if let Ok(Some(_)) = self.get_tx(txid).await {
+            tracing::info!(%txid, %kind, "Bitcoin transaction was already published");
+
+            return Ok((txid, subscription));
+        }
+
+        let fee = self.transaction_fee_details(&transaction).await;
+
         self.wallet
             .lock()
             .await
@@ -107,7 +502,20 @@ impl Wallet {
                 format!("Failed to broadcast Bitcoin {} transaction {}", kind, txid)
             })?;
 
-        tracing::info!(%txid, %kind, "Published Bitcoin transaction");
+        match fee {
+            Ok(fee) => tracing::info!(
+                %txid,
+                %kind,
+                fee = %fee.fee,
+                fee_rate = %format!("{:.2} sat/vB", fee.fee_rate()),
+                vsize = %fee.vsize,
+                "Published Bitcoin transaction"
+            ),
+            Err(e) => {
+                tracing::info!(%txid, %kind, "Published Bitcoin transaction");
+                tracing::debug!(%txid, "Failed to determine fee of transaction: {:#}", e);
+            }
+        }
 
         Ok((txid, subscription))
     }
@@ -118,6 +526,170 @@ impl Wallet {
             .with_context(|| format!("Could not get raw tx with id: {}", txid))
     }
 
+    /// Returns all transactions known to the Electrum server that spend
+    /// `outpoint`, including ones we did not construct ourselves.
+    ///
+    /// `script` has to be the script of the spent output because Electrum only
+    /// indexes transactions by script. bitcoind has no such index, so this is
+    /// not supported with a bitcoind backend.
+    pub async fn spending_transactions(
+        &self,
+        outpoint: OutPoint,
+        script: &Script,
+    ) -> Result<Vec<Transaction>> {
+        let history = match &self.client.lock().await.node {
+            Node::Electrum(electrum) => electrum
+                .script_get_history(script)
+                .context("Failed to get script history")?,
+            Node::Bitcoind(_) => {
+                bail!("Looking up the transactions spending an output requires an Electrum server")
+            }
+        };
+
+        let mut spending_transactions = Vec::new();
+
+        for entry in history {
+            if entry.tx_hash == outpoint.txid {
+                continue;
+            }
+
+            let transaction = self.get_raw_transaction(entry.tx_hash).await?;
+
+            if transaction
+                .input
+                .iter()
+                .any(|input| input.previous_output == outpoint)
+            {
+                spending_transactions.push(transaction);
+            }
+        }
+
+        Ok(spending_transactions)
+    }
+
+    /// Whether [`Wallet::spending_transactions`] is supported by the backend.
+    pub async fn can_look_up_spending_transactions(&self) -> bool {
+        matches!(self.client.lock().await.node, Node::Electrum(_))
+    }
+
+    /// How often the wallet updates the status of the scripts it watches.
+    pub async fn sync_interval(&self) -> Duration {
+        self.client.lock().await.sync_interval
+    }
+
+    pub async fn latest_block_height(&self) -> Result<BlockHeight> {
+        let mut client = self.client.lock().await;
+        client.update_state()?;
+
+        Ok(client.latest_block_height)
+    }
+
+    /// Bumps the fee of our unconfirmed transaction `txid` according to the
+    /// configured [`FeeBumpSchedule`], escalating from `target_block`.
+    ///
+    /// Never resolves, even once the transaction is confirmed or its fee cannot
+    /// be bumped any further, so it can be raced against the futures waiting
+    /// for the outcome of the transaction.
+    pub async fn escalate_fee(&self, txid: Txid, kind: &str, target_block: usize) -> Infallible {
+        if let Some(schedule) = self.fee_bump_schedule {
+            if let Err(error) = self
+                .escalate_fee_until_confirmed(txid, kind, schedule, target_block)
+                .await
+            {
+                tracing::warn!(%txid, "Stopped bumping the fee of Bitcoin {} transaction: {:#}", kind, error);
+            }
+        }
+
+        future::pending().await
+    }
+
+    async fn escalate_fee_until_confirmed(
+        &self,
+        txid: Txid,
+        kind: &str,
+        schedule: FeeBumpSchedule,
+        initial_target_block: usize,
+    ) -> Result<()> {
+        let mut bump_at = self.latest_block_height().await? + schedule.bump_after_blocks;
+        let mut escalation = 0;
+        let mut fee_rate = None;
+        let mut child = None;
+
+        loop {
+            tokio::time::sleep(FEE_BUMP_POLL_INTERVAL).await;
+
+            if self.latest_block_height().await? < bump_at {
+                continue;
+            }
+            bump_at = bump_at + schedule.bump_after_blocks;
+
+            let parent = self.get_raw_transaction(txid).await?;
+            let script = parent.output[0].script_pubkey.clone();
+            if self.status_of_script(&(txid, script)).await?.is_confirmed() {
+                return Ok(());
+            }
+
+            escalation += 1;
+            let target_block = escalated_target_block(initial_target_block, escalation);
+            let new_fee_rate =
+                escalated_fee_rate(fee_rate, self.estimate_fee_rate(target_block).await?);
+
+            // The parent and any previous child have to be known to the wallet
+            self.sync().await?;
+
+            // The wallet only knows the fee of transactions it funded, not
+            // e.g. of the redeem transaction that spends the lock output
+            let parent_fee = self.transaction_fee_details(&parent).await?.fee;
+            let min_relay_fee = self.client.lock().await.min_relay_fee()?;
+            let child_fee = match child_pays_for_parent_fee(
+                parent.get_weight(),
+                parent_fee,
+                new_fee_rate,
+                min_relay_fee,
+            )? {
+                Some(child_fee) => child_fee,
+                None => {
+                    fee_rate = Some(new_fee_rate);
+                    continue;
+                }
+            };
+
+            let total_fee = parent_fee + child_fee;
+            if total_fee > schedule.max_fee {
+                tracing::warn!(
+                    %txid,
+                    fee = %total_fee,
+                    max_fee = %schedule.max_fee,
+                    "Not bumping the fee of Bitcoin {} transaction any further, the maximum fee would be exceeded",
+                    kind
+                );
+                return Ok(());
+            }
+
+            let psbt = match child {
+                None => self.build_child_pays_for_parent(&parent, child_fee).await?,
+                Some(child) => self.bump_fee_of_child(child, child_fee).await?,
+            };
+            let signed_child = self.sign_and_finalize(psbt).await?;
+            let (child_txid, _) = self
+                .broadcast(signed_child, &format!("{}-cpfp", kind))
+                .await?;
+
+            tracing::info!(
+                %txid,
+                %child_txid,
+                %target_block,
+                fee_rate = %new_fee_rate.as_sat_vb(),
+                fee = %total_fee,
+                "Bumped fee of unconfirmed Bitcoin {} transaction",
+                kind
+            );
+
+            child = Some(child_txid);
+            fee_rate = Some(new_fee_rate);
+        }
+    }
+
     pub async fn status_of_script<T>(&self, tx: &T) -> Result<ScriptStatus>
     where
         T: Watchable,
@@ -125,6 +697,16 @@ impl Wallet {
         self.client.lock().await.status_of_script(tx)
     }
 
+    /// The status of the transaction right now, for decisions that must not
+    /// rely on the status of the last sync, which a wallet that was just
+    /// created has not done yet.
+    pub async fn current_status_of_script<T>(&self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable,
+    {
+        self.client.lock().await.current_status_of_script(tx)
+    }
+
     pub async fn subscribe_to(&self, tx: impl Watchable + Send + 'static) -> Subscription {
         let txid = tx.id();
         let script = tx.script();
@@ -137,15 +719,27 @@ impl Wallet {
             .entry((txid, script.clone()))
             .or_insert_with(|| {
                 let (sender, receiver) = watch::channel(ScriptStatus::Unseen);
+                let (reconnect_attempts_sender, reconnect_attempts) = watch::channel(0);
                 let client = self.client.clone();
 
                 tokio::spawn(async move {
                     let mut last_status = None;
+                    let mut last_reconnect_attempts = 0;
 
                     loop {
                         tokio::time::sleep(Duration::from_secs(5)).await;
 
-                        let new_status = match client.lock().await.status_of_script(&tx) {
+                        let (result, reconnect_attempts) = {
+                            let mut client = client.lock().await;
+                            (client.status_of_script(&tx), client.reconnect_attempts)
+                        };
+
+                        if reconnect_attempts != last_reconnect_attempts {
+                            let _ = reconnect_attempts_sender.send(reconnect_attempts);
+                            last_reconnect_attempts = reconnect_attempts;
+                        }
+
+                        let new_status = match result {
                             Ok(new_status) => new_status,
                             Err(error) => {
                                 tracing::warn!(%txid, "Failed to get status of script: {:#}", error);
@@ -170,6 +764,8 @@ impl Wallet {
 
                 Subscription {
                     receiver,
+                    reconnect_attempts,
+                    max_reconnect_attempts: self.electrum_reconnect_attempts,
                     finality_confirmations: self.finality_confirmations,
                     txid,
                 }
@@ -206,15 +802,38 @@ fn print_status_change(txid: Txid, old: Option<ScriptStatus>, new: ScriptStatus)
     new
 }
 
+/// Waiting for a transaction was given up because the Electrum server could
+/// not be reached.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[error("No Electrum server reachable after {attempts} attempts")]
+pub struct ElectrumUnreachable {
+    pub attempts: u32,
+}
+
 /// Represents a subscription to the status of a given transaction.
 #[derive(Debug, Clone)]
 pub struct Subscription {
     receiver: watch::Receiver<ScriptStatus>,
+    /// The number of consecutive failed attempts of the wallet to reach the
+    /// Electrum server.
+    reconnect_attempts: watch::Receiver<u32>,
+    /// Waiting fails with [`ElectrumUnreachable`] once this many consecutive
+    /// attempts failed during the wait, 0 waits forever.
+    max_reconnect_attempts: u32,
     finality_confirmations: u32,
     txid: Txid,
 }
 
 impl Subscription {
+    /// Keep waiting however long the Electrum server cannot be reached, for
+    /// steps that must not give up, e.g. punishing.
+    pub fn without_reconnect_limit(self) -> Self {
+        Self {
+            max_reconnect_attempts: 0,
+            ..self
+        }
+    }
+
     pub async fn wait_until_final(&self) -> Result<()> {
         let conf_target = self.finality_confirmations;
         let txid = self.txid;
@@ -257,12 +876,31 @@ impl Subscription {
 
     async fn wait_until(&self, mut predicate: impl FnMut(&ScriptStatus) -> bool) -> Result<()> {
         let mut receiver = self.receiver.clone();
+        let mut reconnect_attempts = self.reconnect_attempts.clone();
+        // Attempts that failed before the wait started, e.g. during another
+        // wait, do not count against the limit of this one.
+        let mut failed_before = *reconnect_attempts.borrow();
 
         while !predicate(&receiver.borrow()) {
-            receiver
-                .changed()
-                .await
-                .context("Failed while waiting for next status update")?;
+            let attempts = *reconnect_attempts.borrow();
+            if attempts < failed_before {
+                // The server was reached in between
+                failed_before = 0;
+            }
+
+            let attempts = attempts - failed_before;
+            if self.max_reconnect_attempts > 0 && attempts >= self.max_reconnect_attempts {
+                return Err(ElectrumUnreachable { attempts }.into());
+            }
+
+            tokio::select! {
+                changed = receiver.changed() => {
+                    changed.context("Failed while waiting for next status update")?
+                }
+                changed = reconnect_attempts.changed() => {
+                    changed.context("Failed while waiting for next status update")?
+                }
+            }
         }
 
         Ok(())
@@ -274,15 +912,21 @@ where
     C: EstimateFeeRate,
     D: BatchDatabase,
 {
-    pub async fn sign_and_finalize(
-        &self,
-        mut psbt: PartiallySignedTransaction,
-    ) -> Result<Transaction> {
-        let finalized = self
-            .wallet
-            .lock()
-            .await
-            .sign(&mut psbt, SignOptions::default())?;
+    pub async fn sign_and_finalize(&self, psbt: PartiallySignedTransaction) -> Result<Transaction> {
+        let mut psbt = self.signer.sign(psbt).await?;
+
+        let is_finalized = |psbt: &PartiallySignedTransaction| {
+            psbt.inputs.iter().all(|input| {
+                input.final_script_witness.is_some() || input.final_script_sig.is_some()
+            })
+        };
+
+        let finalized = is_finalized(&psbt)
+            || self
+                .wallet
+                .lock()
+                .await
+                .finalize_psbt(&mut psbt, SignOptions::default())?;
 
         if !finalized {
             bail!("PSBT is not finalized")
@@ -304,6 +948,23 @@ where
         Ok(Amount::from_sat(balance))
     }
 
+    /// Whether every one of the `outpoints` is still an unspent output of
+    /// this wallet. Outputs spent by a transaction the wallet knows about,
+    /// confirmed or not, count as spent, hence the wallet should be synced
+    /// before.
+    pub async fn are_unspent(&self, outpoints: &[OutPoint]) -> Result<bool> {
+        let unspent = self
+            .wallet
+            .lock()
+            .await
+            .list_unspent()
+            .context("Failed to list the unspent outputs of the Bitcoin wallet")?;
+
+        Ok(outpoints
+            .iter()
+            .all(|outpoint| unspent.iter().any(|utxo| utxo.outpoint == *outpoint)))
+    }
+
     pub async fn new_address(&self) -> Result<Address> {
         let address = self
             .wallet
@@ -326,7 +987,7 @@ where
             .find(|tx| tx.txid == txid)
             .context("Could not find tx in bdk wallet when trying to determine fees")?
             .fee
-            .expect("fees are always present with Electrum backend");
+            .context("The fee of the transaction is unknown to the bdk wallet")?;
 
         Ok(Amount::from_sat(fees))
     }
@@ -340,6 +1001,19 @@ where
         address: Address,
         amount: Amount,
         change_override: Option<Address>,
+    ) -> Result<PartiallySignedTransaction> {
+        self.send_to_address_avoiding(address, amount, change_override, &[])
+            .await
+    }
+
+    /// Like [`Wallet::send_to_address`], but never spends the `unspendable`
+    /// UTXOs, e.g. because they fund the lock transaction of another swap.
+    pub async fn send_to_address_avoiding(
+        &self,
+        address: Address,
+        amount: Amount,
+        change_override: Option<Address>,
+        unspendable: &[OutPoint],
     ) -> Result<PartiallySignedTransaction> {
         if self.network != address.network {
             bail!("Cannot build PSBT because network of given address is {} but wallet is on network {}", address.network, self.network);
@@ -356,11 +1030,36 @@ where
         let fee_rate = client.estimate_feerate(self.target_block)?;
         let script = address.script_pubkey();
 
-        let mut tx_builder = wallet.build_tx();
-        tx_builder.add_recipient(script.clone(), amount.as_sat());
-        tx_builder.fee_rate(fee_rate);
-        let (psbt, _details) = tx_builder.finish()?;
-        let mut psbt: PartiallySignedTransaction = psbt;
+        let mut psbt = match self.coin_selection {
+            CoinSelection::LargestFirst => build_psbt(
+                wallet.build_tx().coin_selection(LargestFirstCoinSelection),
+                script.clone(),
+                amount,
+                fee_rate,
+                &self.utxos,
+                unspendable,
+            )?,
+            CoinSelection::BranchAndBound => build_psbt(
+                wallet
+                    .build_tx()
+                    .coin_selection(BranchAndBoundCoinSelection::default()),
+                script.clone(),
+                amount,
+                fee_rate,
+                &self.utxos,
+                unspendable,
+            )?,
+            CoinSelection::AvoidChange => build_psbt(
+                wallet
+                    .build_tx()
+                    .coin_selection(BranchAndBoundCoinSelection::new(COST_OF_CHANGE)),
+                script.clone(),
+                amount,
+                fee_rate,
+                &self.utxos,
+                unspendable,
+            )?,
+        };
 
         match psbt.global.unsigned_tx.output.as_mut_slice() {
             // our primary output is the 2nd one? reverse the vectors
@@ -416,15 +1115,19 @@ where
         let dummy_script = Script::from(vec![0u8; locking_script_size]);
         tx_builder.drain_to(dummy_script);
         tx_builder.fee_rate(fee_rate);
-        tx_builder.drain_wallet();
+        if self.utxos.is_empty() {
+            tx_builder.drain_wallet();
+        } else {
+            add_selected_utxos(&mut tx_builder, &self.utxos)?;
+        }
 
         let response = tx_builder.finish();
         match response {
             Ok((_, details)) => {
-                let max_giveable = details.sent
-                    - details
-                        .fee
-                        .expect("fees are always present with Electrum backend");
+                let fee = details
+                    .fee
+                    .context("The fee of the drain transaction is unknown")?;
+                let max_giveable = details.sent - fee;
                 Ok(Amount::from_sat(max_giveable))
             }
             Err(bdk::Error::InsufficientFunds { .. }) => Ok(Amount::ZERO),
@@ -439,13 +1142,324 @@ where
         &self,
         weight: usize,
         transfer_amount: bitcoin::Amount,
+    ) -> Result<bitcoin::Amount> {
+        self.estimate_fee_within(weight, transfer_amount, self.target_block)
+            .await
+    }
+
+    /// Like [`Wallet::estimate_fee`], but for the given target block instead
+    /// of the target block of this wallet.
+    pub async fn estimate_fee_within(
+        &self,
+        weight: usize,
+        transfer_amount: bitcoin::Amount,
+        target_block: usize,
     ) -> Result<bitcoin::Amount> {
         let client = self.client.lock().await;
-        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let fee_rate = client.estimate_feerate(target_block)?;
         let min_relay_fee = client.min_relay_fee()?;
 
         estimate_fee(weight, transfer_amount, fee_rate, min_relay_fee)
     }
+
+    /// The number of blocks the fees of this wallet are estimated for.
+    pub fn target_block(&self) -> usize {
+        self.target_block
+    }
+
+    /// Estimate the fee rate for a transaction to be confirmed within the
+    /// given number of blocks, independent of the target block of this wallet.
+    pub async fn estimate_fee_rate(&self, target_block: usize) -> Result<FeeRate> {
+        self.client.lock().await.estimate_feerate(target_block)
+    }
+
+    /// Builds a transaction that spends our output of the unconfirmed `parent`
+    /// back into this wallet and pays enough fees for the package of
+    /// unconfirmed transactions to reach the given fee rate
+    /// (child-pays-for-parent).
+    ///
+    /// `package_weight` and `package_fee` describe all unconfirmed
+    /// transactions the child pays for, including the `parent`. Returns `None`
+    /// if the package already pays the given fee rate.
+    pub async fn child_pays_for_parent(
+        &self,
+        parent: &Transaction,
+        package_weight: usize,
+        package_fee: Amount,
+        fee_rate: FeeRate,
+    ) -> Result<Option<PartiallySignedTransaction>> {
+        let min_relay_fee = self.client.lock().await.min_relay_fee()?;
+        let fee = match child_pays_for_parent_fee(
+            package_weight,
+            package_fee,
+            fee_rate,
+            min_relay_fee,
+        )? {
+            Some(fee) => fee,
+            None => return Ok(None),
+        };
+
+        let psbt = self.build_child_pays_for_parent(parent, fee).await?;
+
+        Ok(Some(psbt))
+    }
+
+    /// Builds a transaction that spends our output of `parent` back into this
+    /// wallet, paying the given fee. The transaction signals replaceability so
+    /// its fee can be bumped again.
+    async fn build_child_pays_for_parent(
+        &self,
+        parent: &Transaction,
+        fee: Amount,
+    ) -> Result<PartiallySignedTransaction> {
+        let wallet = self.wallet.lock().await;
+
+        let mut our_output = None;
+        for (vout, txout) in parent.output.iter().enumerate() {
+            if wallet.is_mine(&txout.script_pubkey)? {
+                our_output = Some(OutPoint::new(parent.txid(), u32::try_from(vout)?));
+                break;
+            }
+        }
+        let our_output = our_output.with_context(|| {
+            format!(
+                "Transaction {} does not pay to this wallet, cannot bump its fee",
+                parent.txid()
+            )
+        })?;
+
+        let address = wallet
+            .get_address(AddressIndex::New)
+            .context("Failed to get new Bitcoin address")?
+            .address;
+
+        let mut tx_builder = wallet.build_tx();
+        tx_builder.add_utxo(our_output)?;
+        tx_builder.manually_selected_only();
+        tx_builder.drain_to(address.script_pubkey());
+        tx_builder.fee_absolute(fee.as_sat());
+        tx_builder.enable_rbf();
+        let (psbt, _details) = tx_builder.finish()?;
+
+        Ok(psbt)
+    }
+
+    /// Replaces the child transaction `child` with one paying the given fee.
+    async fn bump_fee_of_child(
+        &self,
+        child: Txid,
+        fee: Amount,
+    ) -> Result<PartiallySignedTransaction> {
+        let wallet = self.wallet.lock().await;
+
+        let mut tx_builder = wallet.build_fee_bump(child)?;
+        tx_builder.fee_absolute(fee.as_sat());
+        tx_builder.enable_rbf();
+        let (psbt, _details) = tx_builder.finish()?;
+
+        Ok(psbt)
+    }
+}
+
+fn build_psbt<B, D, Cs>(
+    mut tx_builder: TxBuilder<'_, B, D, Cs, CreateTx>,
+    script: Script,
+    amount: Amount,
+    fee_rate: FeeRate,
+    utxos: &[OutPoint],
+    unspendable: &[OutPoint],
+) -> Result<PartiallySignedTransaction>
+where
+    D: BatchDatabase,
+    Cs: CoinSelectionAlgorithm<D>,
+{
+    tx_builder.add_recipient(script, amount.as_sat());
+    tx_builder.fee_rate(fee_rate);
+    add_selected_utxos(&mut tx_builder, utxos)?;
+    tx_builder.unspendable(unspendable.to_vec());
+
+    let (psbt, _details) = match tx_builder.finish() {
+        Ok(psbt) => psbt,
+        Err(bdk::Error::InsufficientFunds { needed, available }) if !utxos.is_empty() => bail!(
+            "The selected UTXOs of {} do not cover the amount and fee of {}",
+            Amount::from_sat(available),
+            Amount::from_sat(needed)
+        ),
+        Err(bdk::Error::InsufficientFunds { needed, available }) if !unspendable.is_empty() => {
+            bail!(
+                "The UTXOs that are not reserved by other swaps amount to {}, which does not cover the amount and fee of {}",
+                Amount::from_sat(available),
+                Amount::from_sat(needed)
+            )
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(psbt)
+}
+
+/// Restricts the inputs of the transaction to `utxos`, every one of which has
+/// to belong to the wallet. Does nothing if `utxos` is empty.
+fn add_selected_utxos<B, D, Cs>(
+    tx_builder: &mut TxBuilder<'_, B, D, Cs, CreateTx>,
+    utxos: &[OutPoint],
+) -> Result<()>
+where
+    D: BatchDatabase,
+    Cs: CoinSelectionAlgorithm<D>,
+{
+    if utxos.is_empty() {
+        return Ok(());
+    }
+
+    for utxo in utxos {
+        tx_builder
+            .add_utxo(*utxo)
+            .with_context(|| format!("UTXO {} does not belong to the wallet", utxo))?;
+    }
+    tx_builder.manually_selected_only();
+
+    Ok(())
+}
+
+/// Weight of a transaction spending a single P2WPKH output to a single P2WPKH
+/// output, rounded up.
+const CHILD_PAYS_FOR_PARENT_WEIGHT: usize = 440;
+
+/// Calculates the fee a child transaction has to pay so that it and the
+/// unconfirmed package it spends from reach the given fee rate. Returns `None`
+/// if the package already pays the fee rate on its own.
+fn child_pays_for_parent_fee(
+    package_weight: usize,
+    package_fee: Amount,
+    fee_rate: FeeRate,
+    min_relay_fee: Amount,
+) -> Result<Option<Amount>> {
+    let fee_rate_svb = fee_rate.as_sat_vb();
+    if fee_rate_svb <= 0.0 {
+        bail!("Fee rate needs to be > 0")
+    }
+    if fee_rate_svb > 100_000_000.0 {
+        bail!("A fee_rate of > 1BTC does not make sense")
+    }
+
+    let package_weight = Decimal::from(package_weight);
+    let child_weight = Decimal::from(CHILD_PAYS_FOR_PARENT_WEIGHT);
+    let weight_factor = dec!(4.0);
+    let fee_rate = Decimal::from_f32(fee_rate_svb).context("Failed to parse fee rate")?;
+    let package_fee = Decimal::from(package_fee.as_sat());
+
+    if package_weight / weight_factor * fee_rate <= package_fee {
+        return Ok(None);
+    }
+
+    let child_fee =
+        ((package_weight + child_weight) / weight_factor * fee_rate).ceil() - package_fee;
+    let child_fee = child_fee.max(Decimal::from(min_relay_fee.as_sat()));
+
+    if child_fee > MAX_ABSOLUTE_TX_FEE {
+        bail!(
+            "Bumping the fee to the given fee rate requires {} sats, which exceeds the hard bound of {} sats",
+            child_fee,
+            MAX_ABSOLUTE_TX_FEE
+        );
+    }
+
+    let child_fee = child_fee
+        .to_u64()
+        .map(Amount::from_sat)
+        .context("Could not calculate child-pays-for-parent fee")?;
+
+    Ok(Some(child_fee))
+}
+
+/// Fails if the block hash of the genesis block the `node` reports does not
+/// belong to the network we are configured for.
+fn ensure_same_network(network: Network, genesis: BlockHash, node: &str) -> Result<()> {
+    let node_network = [
+        Network::Bitcoin,
+        Network::Testnet,
+        Network::Signet,
+        Network::Regtest,
+    ]
+    .iter()
+    .copied()
+    .find(|network| genesis_block(*network).block_hash() == genesis);
+
+    match node_network {
+        Some(node_network) if node_network == network => Ok(()),
+        Some(node_network) => bail!(
+            "Configured for {} but {} is on {}",
+            network_name(network),
+            node,
+            network_name(node_network)
+        ),
+        None => bail!(
+            "Configured for {} but {} is on an unknown network with genesis block {}",
+            network_name(network),
+            node,
+            genesis
+        ),
+    }
+}
+
+/// Connects to the Electrum server at `rpc_url` and fails if the connection is
+/// not established within `timeout`, which also applies to every request.
+///
+/// Electrum only supports timeouts of whole seconds up to 255 seconds.
+fn connect_electrum(rpc_url: &Url, timeout: Duration) -> Result<bdk::electrum_client::Client> {
+    let timeout_secs = u8::try_from(timeout.as_secs()).unwrap_or(u8::MAX).max(1);
+    let config = bdk::electrum_client::ConfigBuilder::default()
+        .retry(5)
+        .timeout(Some(timeout_secs))?
+        .build();
+
+    bdk::electrum_client::Client::from_config(rpc_url.as_str(), config).with_context(|| {
+        format!(
+            "Could not connect to Electrum server {} within {}s",
+            rpc_url, timeout_secs
+        )
+    })
+}
+
+/// Fails unless bitcoind indexes all transactions, without the index it only
+/// knows the transactions of its own wallets and the mempool.
+fn ensure_txindex(bitcoind: &core_rpc::Client) -> Result<()> {
+    let indices: serde_json::Value = bitcoind
+        .call("getindexinfo", &[])
+        .context("Failed to get index info from bitcoind")?;
+
+    if indices.get("txindex").is_none() {
+        bail!("bitcoind has to run with txindex=1 to be used by the Bitcoin wallet")
+    }
+
+    Ok(())
+}
+
+fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "mainnet",
+        Network::Testnet => "testnet",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+    }
+}
+
+/// Halves the target block with every escalation, down to the next block.
+fn escalated_target_block(initial_target_block: usize, escalation: u32) -> usize {
+    initial_target_block
+        .checked_shr(escalation)
+        .unwrap_or_default()
+        .max(1)
+}
+
+fn escalated_fee_rate(previous: Option<FeeRate>, estimate: FeeRate) -> FeeRate {
+    match previous {
+        Some(previous) if previous.as_sat_vb() * FEE_BUMP_MIN_INCREASE > estimate.as_sat_vb() => {
+            FeeRate::from_sat_per_vb(previous.as_sat_vb() * FEE_BUMP_MIN_INCREASE)
+        }
+        _ => estimate,
+    }
 }
 
 fn estimate_fee(
@@ -529,6 +1543,28 @@ where
         Ok(tx)
     }
 
+    /// Computes the fee of a signed transaction from the amounts of the
+    /// outputs it spends, which are looked up on the Electrum server.
+    pub async fn transaction_fee_details(&self, transaction: &Transaction) -> Result<TxFee> {
+        let mut input_amounts = Vec::with_capacity(transaction.input.len());
+
+        for input in &transaction.input {
+            let previous_output = input.previous_output;
+            let previous_tx = self
+                .get_tx(previous_output.txid)
+                .await?
+                .with_context(|| format!("Could not find transaction {}", previous_output.txid))?;
+            let spent_output = previous_tx
+                .output
+                .get(previous_output.vout as usize)
+                .with_context(|| format!("Transaction does not have output {}", previous_output))?;
+
+            input_amounts.push(Amount::from_sat(spent_output.value));
+        }
+
+        TxFee::new(transaction, &input_amounts)
+    }
+
     pub async fn sync(&self) -> Result<()> {
         self.wallet
             .lock()
@@ -541,12 +1577,121 @@ where
 }
 
 impl<B, D, C> Wallet<B, D, C> {
+    /// Use the given strategy to select the UTXOs that fund transactions.
+    pub fn with_coin_selection(self, coin_selection: CoinSelection) -> Self {
+        Self {
+            coin_selection,
+            ..self
+        }
+    }
+
+    /// Fund transactions from exactly the given UTXOs instead of selecting
+    /// them automatically. An empty list restores the automatic selection.
+    pub fn with_utxos(self, utxos: Vec<OutPoint>) -> Self {
+        Self { utxos, ..self }
+    }
+
+    /// Escalate the fee of our own unconfirmed transactions according to the
+    /// given schedule, see [`Wallet::escalate_fee`].
+    pub fn with_fee_bump_schedule(self, fee_bump_schedule: Option<FeeBumpSchedule>) -> Self {
+        Self {
+            fee_bump_schedule,
+            ..self
+        }
+    }
+
     // TODO: Get rid of this by changing bounds on bdk::Wallet
     pub fn get_network(&self) -> bitcoin::Network {
         self.network
     }
 }
 
+/// Converts an estimate of the Electrum server in BTC/kB to a fee rate.
+///
+/// Electrum servers return -1 if their node cannot estimate the fee rate. In
+/// that case, or if the estimation failed entirely, the fallback is used if
+/// there is one. The fallback is subject to the same bounds as an estimated
+/// fee rate when computing the fee.
+fn fee_rate_or_fallback(
+    estimate: Result<f64>,
+    fallback: Option<FeeRate>,
+    target_block: usize,
+) -> Result<FeeRate> {
+    match (estimate, fallback) {
+        // we do not expect fees being that high.
+        #[allow(clippy::cast_possible_truncation)]
+        (Ok(btc_per_kvb), _) if btc_per_kvb > 0.0 => {
+            Ok(FeeRate::from_btc_per_kvb(btc_per_kvb as f32))
+        }
+        (estimate, Some(fallback)) => {
+            let reason = match estimate {
+                Ok(btc_per_kvb) => format!("Electrum server returned {} BTC/kB", btc_per_kvb),
+                Err(e) => format!("{:#}", e),
+            };
+            tracing::warn!(
+                %target_block,
+                fallback_fee_rate = %fallback.as_sat_vb(),
+                %reason,
+                "Failed to estimate fee rate, using fallback fee rate in sat/vB"
+            );
+
+            Ok(fallback)
+        }
+        // Without a fallback an unusable estimate is rejected when computing
+        // the fee.
+        #[allow(clippy::cast_possible_truncation)]
+        (estimate, None) => Ok(FeeRate::from_btc_per_kvb(estimate? as f32)),
+    }
+}
+
+/// The fee a transaction pays and its size in virtual bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxFee {
+    pub fee: Amount,
+    pub vsize: usize,
+}
+
+impl TxFee {
+    /// `input_amounts` are the amounts of the outputs spent by the inputs of
+    /// `transaction`, in the same order.
+    pub fn new(transaction: &Transaction, input_amounts: &[Amount]) -> Result<Self> {
+        if input_amounts.len() != transaction.input.len() {
+            bail!(
+                "Expected {} input amounts but got {}",
+                transaction.input.len(),
+                input_amounts.len()
+            )
+        }
+
+        let total_in = input_amounts
+            .iter()
+            .try_fold(Amount::ZERO, |total, amount| total.checked_add(*amount))
+            .context("Total input amount overflows")?;
+        let total_out = transaction
+            .output
+            .iter()
+            .try_fold(Amount::ZERO, |total, output| {
+                total.checked_add(Amount::from_sat(output.value))
+            })
+            .context("Total output amount overflows")?;
+        let fee = total_in
+            .checked_sub(total_out)
+            .context("Transaction spends more than its inputs")?;
+
+        Ok(Self {
+            fee,
+            vsize: (transaction.get_weight() + 3) / 4,
+        })
+    }
+
+    pub fn fee_rate(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let fee_rate = self.fee.as_sat() as f64 / self.vsize as f64;
+
+        fee_rate
+    }
+}
+
 pub trait EstimateFeeRate {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate>;
     fn min_relay_fee(&self) -> Result<bitcoin::Amount>;
@@ -641,16 +1786,22 @@ impl WalletBuilder {
 
         let wallet =
             bdk::Wallet::new_offline(&descriptors.0, None, Network::Regtest, database).unwrap();
+        let wallet = Arc::new(Mutex::new(wallet));
 
         Wallet {
             client: Arc::new(Mutex::new(StaticFeeRate {
                 fee_rate: FeeRate::from_sat_per_vb(self.sats_per_vb),
                 min_relay_fee: bitcoin::Amount::from_sat(self.min_relay_fee_sats),
             })),
-            wallet: Arc::new(Mutex::new(wallet)),
+            signer: Arc::new(InProcessSigner::new(wallet.clone())),
+            wallet,
             finality_confirmations: 1,
+            electrum_reconnect_attempts: 0,
             network: Network::Regtest,
             target_block: 1,
+            coin_selection: CoinSelection::default(),
+            utxos: Vec::new(),
+            fee_bump_schedule: None,
         }
     }
 }
@@ -676,98 +1827,272 @@ impl Watchable for (Txid, Script) {
     }
 }
 
+/// The node the [`Client`] watches transactions and estimates fees with.
+enum Node {
+    Electrum(bdk::electrum_client::Client),
+    Bitcoind(core_rpc::Client),
+}
+
+impl Node {
+    fn name(&self) -> &'static str {
+        match self {
+            Node::Electrum(_) => "Electrum server",
+            Node::Bitcoind(_) => "bitcoind",
+        }
+    }
+
+    fn genesis_block_hash(&self) -> Result<BlockHash> {
+        match self {
+            Node::Electrum(electrum) => {
+                let genesis = electrum
+                    .block_header(0)
+                    .context("Failed to get genesis block header from Electrum server")?;
+
+                Ok(genesis.block_hash())
+            }
+            Node::Bitcoind(bitcoind) => bitcoind
+                .get_block_hash(0)
+                .context("Failed to get genesis block hash from bitcoind"),
+        }
+    }
+
+    fn latest_block_height(&self) -> Result<BlockHeight> {
+        match self {
+            // We do not act on this subscription after this call, as we cannot rely on
+            // subscription push notifications because eventually the Electrum server will
+            // close the connection and subscriptions are not automatically renewed
+            // upon renewing the connection.
+            Node::Electrum(electrum) => {
+                let latest_block = electrum
+                    .block_headers_subscribe()
+                    .context("Failed to subscribe to header notifications")?;
+
+                BlockHeight::try_from(latest_block)
+            }
+            Node::Bitcoind(bitcoind) => {
+                let block_count = bitcoind
+                    .get_block_count()
+                    .context("Failed to get block count from bitcoind")?;
+
+                Ok(BlockHeight::from(u32::try_from(block_count)?))
+            }
+        }
+    }
+}
+
 pub struct Client {
-    electrum: bdk::electrum_client::Client,
+    node: Node,
+    rpc_url: Url,
+    /// The number of consecutive syncs that failed to reach the Electrum
+    /// server or bitcoind, reset by the next successful one.
+    reconnect_attempts: u32,
     latest_block_height: BlockHeight,
     last_sync: Instant,
     sync_interval: Duration,
     script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
+    /// Only used with bitcoind, the statuses of Electrum are derived from the
+    /// script histories fetched in one batch per sync.
+    status_cache: StatusCache,
     subscriptions: HashMap<(Txid, Script), Subscription>,
+    /// Used in place of the estimate if the Electrum server cannot estimate
+    /// the fee rate.
+    fallback_fee_rate: Option<FeeRate>,
+    /// The servers besides `node` that are asked whether a transaction is
+    /// confirmed, see [`ConfirmationQuorum`].
+    quorum_servers: Vec<QuorumServer>,
+    quorum_size: usize,
+}
+
+/// An Electrum server of the [`ConfirmationQuorum`].
+struct QuorumServer {
+    rpc_url: Url,
+    electrum: bdk::electrum_client::Client,
+}
+
+impl QuorumServer {
+    fn status_of_script(&self, txid: Txid, script: &Script) -> Result<ScriptStatus> {
+        let latest_block = BlockHeight::try_from(
+            self.electrum
+                .block_headers_subscribe()
+                .context("Failed to subscribe to header notifications")?,
+        )?;
+        let history = self
+            .electrum
+            .script_get_history(script)
+            .context("Failed to get script history")?;
+
+        status_from_history(&history, txid, latest_block)
+    }
 }
 
-impl Client {
-    fn new(electrum: bdk::electrum_client::Client, interval: Duration) -> Result<Self> {
-        // Initially fetch the latest block for storing the height.
-        // We do not act on this subscription after this call.
-        let latest_block = electrum
-            .block_headers_subscribe()
-            .context("Failed to subscribe to header notifications")?;
+impl Client {
+    fn new(
+        node: Node,
+        rpc_url: Url,
+        interval: Duration,
+        status_cache_ttl: Duration,
+        fallback_fee_rate: Option<FeeRate>,
+    ) -> Result<Self> {
+        // Initially fetch the latest block for storing the height.
+        let latest_block_height = node.latest_block_height()?;
+
+        Ok(Self {
+            node,
+            rpc_url,
+            reconnect_attempts: 0,
+            latest_block_height,
+            last_sync: Instant::now(),
+            sync_interval: interval,
+            script_history: Default::default(),
+            // New blocks are only noticed once per sync, caching statuses for
+            // longer could hide their confirmations.
+            status_cache: StatusCache::new(status_cache_ttl.min(interval)),
+            subscriptions: Default::default(),
+            fallback_fee_rate,
+            quorum_servers: Vec::new(),
+            quorum_size: 1,
+        })
+    }
+
+    fn update_state(&mut self) -> Result<()> {
+        let now = Instant::now();
+        if now < self.last_sync + self.sync_interval {
+            return Ok(());
+        }
+
+        self.sync_now()
+    }
+
+    fn sync_now(&mut self) -> Result<()> {
+        self.last_sync = Instant::now();
+
+        match self
+            .update_latest_block()
+            .and_then(|()| self.update_script_histories())
+        {
+            Ok(()) => {
+                if self.reconnect_attempts > 0 {
+                    tracing::info!(
+                        server = %self.rpc_url,
+                        "Reached {} again after {} failed attempts",
+                        self.node.name(),
+                        self.reconnect_attempts
+                    );
+                    self.reconnect_attempts = 0;
+                }
+
+                Ok(())
+            }
+            Err(error) => {
+                self.reconnect_attempts += 1;
+                tracing::warn!(
+                    server = %self.rpc_url,
+                    attempt = self.reconnect_attempts,
+                    "Failed to reach {}: {:#}",
+                    self.node.name(),
+                    error
+                );
+
+                Err(error)
+            }
+        }
+    }
+
+    /// The status of the transaction as reported by the node, or by the
+    /// [`ConfirmationQuorum`] if the node reports it as confirmed.
+    fn status_of_script<T>(&mut self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable,
+    {
+        let status = self.status_of_script_on_node(tx)?;
+
+        if self.quorum_size <= 1 || !matches!(status, ScriptStatus::Confirmed(_)) {
+            return Ok(status);
+        }
+
+        let txid = tx.id();
+        let script = tx.script();
+        let statuses = self
+            .quorum_servers
+            .iter()
+            .map(|server| {
+                server
+                    .status_of_script(txid, &script)
+                    .unwrap_or_else(|error| {
+                        tracing::warn!(
+                            server = %server.rpc_url,
+                            %txid,
+                            "Failed to get transaction status from Electrum server of the quorum: {:#}",
+                            error
+                        );
+                        ScriptStatus::Unseen
+                    })
+            })
+            .chain(Some(status))
+            .collect();
+
+        let agreed = quorum_status(statuses, self.quorum_size);
+        if agreed != status {
+            tracing::debug!(
+                %txid,
+                node_status = %status,
+                quorum_status = %agreed,
+                "Not enough Electrum servers agree with the status of the transaction"
+            );
+        }
 
-        Ok(Self {
-            electrum,
-            latest_block_height: BlockHeight::try_from(latest_block)?,
-            last_sync: Instant::now(),
-            sync_interval: interval,
-            script_history: Default::default(),
-            subscriptions: Default::default(),
-        })
+        Ok(agreed)
     }
 
-    fn update_state(&mut self) -> Result<()> {
-        let now = Instant::now();
-        if now < self.last_sync + self.sync_interval {
-            return Ok(());
+    /// Like [`Client::status_of_script`], but syncs first instead of waiting
+    /// for the sync interval to pass, e.g. right after the client was created.
+    fn current_status_of_script<T>(&mut self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable,
+    {
+        if matches!(self.node, Node::Electrum(_)) {
+            self.script_history.entry(tx.script()).or_default();
         }
 
-        self.last_sync = now;
-        self.update_latest_block()?;
-        self.update_script_histories()?;
+        self.sync_now()?;
+        self.status_cache.clear();
 
-        Ok(())
+        self.status_of_script(tx)
     }
 
-    fn status_of_script<T>(&mut self, tx: &T) -> Result<ScriptStatus>
+    fn status_of_script_on_node<T>(&mut self, tx: &T) -> Result<ScriptStatus>
     where
         T: Watchable,
     {
         let txid = tx.id();
         let script = tx.script();
 
-        if !self.script_history.contains_key(&script) {
+        let is_electrum = matches!(self.node, Node::Electrum(_));
+        if is_electrum && !self.script_history.contains_key(&script) {
             self.script_history.insert(script.clone(), vec![]);
         }
 
         self.update_state()?;
 
-        let history = self.script_history.entry(script).or_default();
-
-        let history_of_tx = history
-            .iter()
-            .filter(|entry| entry.tx_hash == txid)
-            .collect::<Vec<_>>();
+        if let Node::Bitcoind(bitcoind) = &self.node {
+            let now = Instant::now();
+            if let Some(status) = self.status_cache.get(txid, now) {
+                return Ok(status);
+            }
 
-        match history_of_tx.as_slice() {
-            [] => Ok(ScriptStatus::Unseen),
-            [remaining @ .., last] => {
-                if !remaining.is_empty() {
-                    tracing::warn!("Found more than a single history entry for script. This is highly unexpected and those history entries will be ignored")
-                }
+            let status = bitcoind_status_of_tx(bitcoind, txid)?;
+            self.status_cache.insert(txid, status, now);
 
-                if last.height <= 0 {
-                    Ok(ScriptStatus::InMempool)
-                } else {
-                    Ok(ScriptStatus::Confirmed(
-                        Confirmed::from_inclusion_and_latest_block(
-                            u32::try_from(last.height)?,
-                            u32::from(self.latest_block_height),
-                        ),
-                    ))
-                }
-            }
+            return Ok(status);
         }
+
+        let history = self.script_history.entry(script).or_default();
+
+        status_from_history(history, txid, self.latest_block_height)
     }
 
     fn update_latest_block(&mut self) -> Result<()> {
-        // Fetch the latest block for storing the height.
-        // We do not act on this subscription after this call, as we cannot rely on
-        // subscription push notifications because eventually the Electrum server will
-        // close the connection and subscriptions are not automatically renewed
-        // upon renewing the connection.
-        let latest_block = self
-            .electrum
-            .block_headers_subscribe()
-            .context("Failed to subscribe to header notifications")?;
-        let latest_block_height = BlockHeight::try_from(latest_block)?;
+        let latest_block_height = self.node.latest_block_height()?;
 
         if latest_block_height > self.latest_block_height {
             tracing::debug!(
@@ -775,14 +2100,20 @@ impl Client {
                 "Got notification for new block"
             );
             self.latest_block_height = latest_block_height;
+            self.status_cache.clear();
         }
 
         Ok(())
     }
 
     fn update_script_histories(&mut self) -> Result<()> {
-        let histories = self
-            .electrum
+        // bitcoind is asked for the status of each transaction instead.
+        let electrum = match &self.node {
+            Node::Electrum(electrum) => electrum,
+            Node::Bitcoind(_) => return Ok(()),
+        };
+
+        let histories = electrum
             .batch_script_get_history(self.script_history.keys())
             .context("Failed to get script histories")?;
 
@@ -803,24 +2134,146 @@ impl Client {
     }
 }
 
+/// The status of the transaction `txid` according to the history of its
+/// script reported by an Electrum server.
+fn status_from_history(
+    history: &[GetHistoryRes],
+    txid: Txid,
+    latest_block_height: BlockHeight,
+) -> Result<ScriptStatus> {
+    let history_of_tx = history
+        .iter()
+        .filter(|entry| entry.tx_hash == txid)
+        .collect::<Vec<_>>();
+
+    match history_of_tx.as_slice() {
+        [] => Ok(ScriptStatus::Unseen),
+        [remaining @ .., last] => {
+            if !remaining.is_empty() {
+                tracing::warn!("Found more than a single history entry for script. This is highly unexpected and those history entries will be ignored")
+            }
+
+            if last.height <= 0 {
+                Ok(ScriptStatus::InMempool)
+            } else {
+                Ok(ScriptStatus::Confirmed(
+                    Confirmed::from_inclusion_and_latest_block(
+                        u32::try_from(last.height)?,
+                        u32::from(latest_block_height),
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+/// The most advanced status that at least `size` of the `statuses` reached.
+fn quorum_status(mut statuses: Vec<ScriptStatus>, size: usize) -> ScriptStatus {
+    statuses.sort_by_key(|status| match status {
+        ScriptStatus::Unseen | ScriptStatus::Retrying => (0, 0),
+        ScriptStatus::InMempool => (1, 0),
+        ScriptStatus::Confirmed(confirmed) => (2, confirmed.confirmations()),
+    });
+
+    statuses
+        .into_iter()
+        .rev()
+        .nth(size.saturating_sub(1))
+        .unwrap_or(ScriptStatus::Unseen)
+}
+
+/// Recently queried transaction statuses, so that consumers asking for the
+/// status of the same transaction in short succession share one request.
+///
+/// A status is reused for at most `ttl` and the cache is cleared whenever a
+/// new block arrives, so confirmations are never hidden by it.
+#[derive(Debug)]
+struct StatusCache {
+    ttl: Duration,
+    statuses: HashMap<Txid, (ScriptStatus, Instant)>,
+}
+
+impl StatusCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            statuses: HashMap::new(),
+        }
+    }
+
+    fn get(&self, txid: Txid, now: Instant) -> Option<ScriptStatus> {
+        let (status, cached_at) = self.statuses.get(&txid)?;
+
+        (now < *cached_at + self.ttl).then(|| *status)
+    }
+
+    fn insert(&mut self, txid: Txid, status: ScriptStatus, now: Instant) {
+        if self.ttl == Duration::ZERO {
+            return;
+        }
+
+        self.statuses.insert(txid, (status, now));
+    }
+
+    fn clear(&mut self) {
+        self.statuses.clear();
+    }
+}
+
 impl EstimateFeeRate for Client {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
-        // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L213
         // Returned estimated fees are per BTC/kb.
-        let fee_per_byte = self.electrum.estimate_fee(target_block)?;
-        // we do not expect fees being that high.
-        #[allow(clippy::cast_possible_truncation)]
-        Ok(FeeRate::from_btc_per_kvb(fee_per_byte as f32))
+        let estimate = match &self.node {
+            // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L213
+            Node::Electrum(electrum) => electrum
+                .estimate_fee(target_block)
+                .map_err(anyhow::Error::from),
+            Node::Bitcoind(bitcoind) => bitcoind_estimate_fee(bitcoind, target_block),
+        };
+
+        fee_rate_or_fallback(estimate, self.fallback_fee_rate, target_block)
     }
 
     fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
-        // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L219
         // Returned fee is in BTC/kb
-        let relay_fee = bitcoin::Amount::from_btc(self.electrum.relay_fee()?)?;
+        let relay_fee = match &self.node {
+            // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L219
+            Node::Electrum(electrum) => bitcoin::Amount::from_btc(electrum.relay_fee()?)?,
+            Node::Bitcoind(bitcoind) => bitcoind.get_network_info()?.relay_fee,
+        };
+
         Ok(relay_fee)
     }
 }
 
+fn bitcoind_status_of_tx(bitcoind: &core_rpc::Client, txid: Txid) -> Result<ScriptStatus> {
+    match bitcoind.get_raw_transaction_info(&txid, None) {
+        Ok(info) => Ok(ScriptStatus::from_confirmations(
+            info.confirmations.unwrap_or(0),
+        )),
+        Err(core_rpc::Error::JsonRpc(core_rpc::jsonrpc::Error::Rpc(error)))
+            if error.code == RPC_INVALID_ADDRESS_OR_KEY =>
+        {
+            Ok(ScriptStatus::Unseen)
+        }
+        Err(error) => Err(error).context("Failed to get transaction from bitcoind"),
+    }
+}
+
+fn bitcoind_estimate_fee(bitcoind: &core_rpc::Client, target_block: usize) -> Result<f64> {
+    let estimate = bitcoind
+        .estimate_smart_fee(u16::try_from(target_block)?, None)
+        .context("Failed to estimate fee rate with bitcoind")?;
+
+    match estimate.fee_rate {
+        Some(fee_rate) => Ok(fee_rate.as_btc()),
+        None => bail!(
+            "bitcoind cannot estimate the fee rate: {}",
+            estimate.errors.unwrap_or_default().join(", ")
+        ),
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ScriptStatus {
     Unseen,
@@ -916,8 +2369,60 @@ mod tests {
     use crate::bitcoin::{PublicKey, TxLock};
     use crate::tracing_ext::capture_logs;
     use proptest::prelude::*;
+    use std::str::FromStr;
     use tracing::level_filters::LevelFilter;
 
+    #[test]
+    fn cached_status_expires_after_ttl() {
+        let txid = Txid::default();
+        let now = Instant::now();
+        let mut cache = StatusCache::new(Duration::from_secs(10));
+
+        cache.insert(txid, ScriptStatus::InMempool, now);
+
+        assert_eq!(
+            cache.get(txid, now + Duration::from_secs(9)),
+            Some(ScriptStatus::InMempool)
+        );
+        assert_eq!(cache.get(txid, now + Duration::from_secs(10)), None);
+    }
+
+    #[test]
+    fn clearing_cache_or_zero_ttl_drops_statuses() {
+        let txid = Txid::default();
+        let now = Instant::now();
+        let mut cache = StatusCache::new(Duration::from_secs(10));
+
+        cache.insert(txid, ScriptStatus::InMempool, now);
+        cache.clear();
+
+        assert_eq!(cache.get(txid, now), None);
+
+        let mut cache = StatusCache::new(Duration::ZERO);
+        cache.insert(txid, ScriptStatus::InMempool, now);
+
+        assert_eq!(cache.get(txid, now), None);
+    }
+
+    #[test]
+    fn given_genesis_of_configured_network_then_network_matches() {
+        let genesis = genesis_block(Network::Testnet).block_hash();
+
+        assert!(ensure_same_network(Network::Testnet, genesis, "Electrum server").is_ok());
+    }
+
+    #[test]
+    fn given_genesis_of_other_network_then_fails_with_both_networks() {
+        let genesis = genesis_block(Network::Bitcoin).block_hash();
+
+        let error = ensure_same_network(Network::Testnet, genesis, "Electrum server").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Configured for testnet but Electrum server is on mainnet"
+        );
+    }
+
     #[test]
     fn given_depth_0_should_meet_confirmation_target_one() {
         let script = ScriptStatus::Confirmed(Confirmed { depth: 0 });
@@ -946,6 +2451,31 @@ mod tests {
         assert_eq!(confirmed.depth, 0)
     }
 
+    #[test]
+    fn quorum_status_is_most_advanced_status_enough_servers_agree_on() {
+        let confirmed = ScriptStatus::from_confirmations;
+        let statuses = vec![
+            confirmed(6),
+            ScriptStatus::InMempool,
+            confirmed(2),
+            ScriptStatus::Unseen,
+        ];
+
+        assert_eq!(quorum_status(statuses.clone(), 1), confirmed(6));
+        assert_eq!(quorum_status(statuses.clone(), 2), confirmed(2));
+        assert_eq!(quorum_status(statuses.clone(), 3), ScriptStatus::InMempool);
+        assert_eq!(quorum_status(statuses, 4), ScriptStatus::Unseen);
+    }
+
+    #[test]
+    fn quorum_cannot_exceed_configured_servers() {
+        let servers = vec![Url::parse("ssl://electrum.blockstream.info:50002").unwrap()];
+
+        assert!(ConfirmationQuorum::new(servers.clone(), 2).is_ok());
+        assert!(ConfirmationQuorum::new(servers.clone(), 0).is_err());
+        assert!(ConfirmationQuorum::new(servers, 3).is_err());
+    }
+
     #[test]
     fn given_one_BTC_and_100k_sats_per_vb_fees_should_not_hit_max() {
         // 400 weight = 100 vbyte
@@ -1017,6 +2547,134 @@ mod tests {
         assert_eq!(is_fee.as_sat(), MAX_ABSOLUTE_TX_FEE.to_u64().unwrap());
     }
 
+    #[test]
+    fn given_package_paying_fee_rate_child_pays_for_parent_is_not_needed() {
+        let package_weight = 1000;
+        let package_fee = bitcoin::Amount::from_sat(2500);
+        let fee_rate = FeeRate::from_sat_per_vb(10.0);
+
+        let relay_fee = bitcoin::Amount::ONE_SAT;
+        let child_fee =
+            child_pays_for_parent_fee(package_weight, package_fee, fee_rate, relay_fee).unwrap();
+
+        assert_eq!(child_fee, None);
+    }
+
+    #[test]
+    fn given_package_below_fee_rate_child_pays_for_package_and_itself() {
+        let package_weight = 1000;
+        let package_fee = bitcoin::Amount::from_sat(250);
+        let fee_rate = FeeRate::from_sat_per_vb(10.0);
+
+        let relay_fee = bitcoin::Amount::ONE_SAT;
+        let child_fee =
+            child_pays_for_parent_fee(package_weight, package_fee, fee_rate, relay_fee).unwrap();
+
+        // (1000 + 440) / 4 * 10 - 250
+        assert_eq!(child_fee, Some(bitcoin::Amount::from_sat(3350)));
+    }
+
+    #[test]
+    fn given_child_fee_below_min_relay_fee_child_pays_min_relay_fee() {
+        let package_weight = 1000;
+        let package_fee = bitcoin::Amount::from_sat(2499);
+        let fee_rate = FeeRate::from_sat_per_vb(10.0);
+
+        let relay_fee = bitcoin::Amount::from_sat(1_500);
+        let child_fee =
+            child_pays_for_parent_fee(package_weight, package_fee, fee_rate, relay_fee).unwrap();
+
+        assert_eq!(child_fee, Some(relay_fee));
+    }
+
+    #[test]
+    fn escalation_halves_target_block_down_to_next_block() {
+        let targets = (1..=5)
+            .map(|escalation| escalated_target_block(6, escalation))
+            .collect::<Vec<_>>();
+
+        assert_eq!(targets, vec![3, 1, 1, 1, 1]);
+        assert_eq!(escalated_target_block(6, 100), 1);
+    }
+
+    #[test]
+    fn escalation_always_increases_fee_rate() {
+        let estimate = FeeRate::from_sat_per_vb(10.0);
+
+        let first = escalated_fee_rate(None, estimate);
+        let second = escalated_fee_rate(Some(first), estimate);
+        let third = escalated_fee_rate(Some(second), FeeRate::from_sat_per_vb(20.0));
+
+        assert_eq!(first.as_sat_vb(), 10.0);
+        assert_eq!(second.as_sat_vb(), 12.5);
+        assert_eq!(third.as_sat_vb(), 20.0);
+    }
+
+    #[test]
+    fn usable_estimate_is_preferred_over_fallback() {
+        let fallback = Some(FeeRate::from_sat_per_vb(5.0));
+
+        let fee_rate = fee_rate_or_fallback(Ok(0.0001), fallback, 1).unwrap();
+
+        assert!((fee_rate.as_sat_vb() - 10.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn fallback_is_used_if_fee_rate_cannot_be_estimated() {
+        let fallback = Some(FeeRate::from_sat_per_vb(5.0));
+
+        let no_data = fee_rate_or_fallback(Ok(-1.0), fallback, 1).unwrap();
+        let failed = fee_rate_or_fallback(Err(anyhow::anyhow!("timeout")), fallback, 1).unwrap();
+
+        assert_eq!(no_data.as_sat_vb(), 5.0);
+        assert_eq!(failed.as_sat_vb(), 5.0);
+    }
+
+    #[test]
+    fn without_fallback_unusable_estimate_is_rejected() {
+        let no_data = fee_rate_or_fallback(Ok(-1.0), None, 1).unwrap();
+        let failed = fee_rate_or_fallback(Err(anyhow::anyhow!("timeout")), None, 1);
+
+        assert!(estimate_fee(400, Amount::from_sat(10_000), no_data, Amount::ONE_SAT).is_err());
+        assert!(failed.is_err());
+    }
+
+    fn transaction_with_output(value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![::bitcoin::TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: 0xFFFF_FFFF,
+                witness: vec![],
+            }],
+            output: vec![::bitcoin::TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn tx_fee_is_difference_of_inputs_and_outputs() {
+        let transaction = transaction_with_output(9_000);
+
+        let tx_fee = TxFee::new(&transaction, &[Amount::from_sat(10_000)]).unwrap();
+
+        assert_eq!(tx_fee.fee, Amount::from_sat(1_000));
+        assert_eq!(tx_fee.vsize, 60);
+        assert!((tx_fee.fee_rate() - 1_000.0 / 60.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn tx_fee_rejects_mismatching_or_insufficient_inputs() {
+        let transaction = transaction_with_output(9_000);
+
+        assert!(TxFee::new(&transaction, &[]).is_err());
+        assert!(TxFee::new(&transaction, &[Amount::from_sat(8_999)]).is_err());
+    }
+
     proptest! {
         #[test]
         fn given_randon_amount_random_fee_and_random_relay_rate_but_fix_weight_does_not_error(
@@ -1149,9 +2807,16 @@ mod tests {
         for amount in above_dust..(balance - (above_dust - 1)) {
             let (A, B) = (PublicKey::random(), PublicKey::random());
             let change = wallet.new_address().await.unwrap();
-            let txlock = TxLock::new(&wallet, bitcoin::Amount::from_sat(amount), A, B, change)
-                .await
-                .unwrap();
+            let txlock = TxLock::new(
+                &wallet,
+                bitcoin::Amount::from_sat(amount),
+                A,
+                B,
+                change,
+                &[],
+            )
+            .await
+            .unwrap();
             let txlock_output = txlock.script_pubkey();
 
             let tx = wallet.sign_and_finalize(txlock.into()).await.unwrap();
@@ -1191,6 +2856,164 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn every_coin_selection_funds_amount_and_fees() {
+        let utxo_amount = 10_000;
+        let amount = Amount::from_sat(25_000);
+
+        for coin_selection in &[
+            CoinSelection::LargestFirst,
+            CoinSelection::BranchAndBound,
+            CoinSelection::AvoidChange,
+        ] {
+            let wallet = WalletBuilder::new(utxo_amount)
+                .with_num_utxos(5)
+                .build()
+                .with_coin_selection(*coin_selection);
+
+            let psbt = wallet
+                .send_to_address(wallet.new_address().await.unwrap(), amount, None)
+                .await
+                .unwrap();
+            let transaction = wallet.sign_and_finalize(psbt).await.unwrap();
+
+            let total_in = utxo_amount * transaction.input.len() as u64;
+            let total_out = transaction
+                .output
+                .iter()
+                .map(|output| output.value)
+                .sum::<u64>();
+            let fee = total_in - total_out;
+
+            assert_eq!(
+                transaction.output[0].value,
+                amount.as_sat(),
+                "{} does not pay the amount",
+                coin_selection
+            );
+            assert!(
+                fee >= (transaction.get_weight() / 4) as u64,
+                "{} does not pay the fee rate",
+                coin_selection
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn only_selected_utxos_fund_transaction() {
+        let wallet = WalletBuilder::new(10_000).with_num_utxos(3).build();
+        let utxos = wallet
+            .wallet
+            .lock()
+            .await
+            .list_unspent()
+            .unwrap()
+            .into_iter()
+            .map(|utxo| utxo.outpoint)
+            .collect::<Vec<_>>();
+        let selected = vec![utxos[0], utxos[2]];
+        let wallet = wallet.with_utxos(selected.clone());
+
+        let psbt = wallet
+            .send_to_address(
+                wallet.new_address().await.unwrap(),
+                Amount::from_sat(5_000),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let mut inputs = psbt
+            .global
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect::<Vec<_>>();
+        inputs.sort();
+        let mut expected = selected;
+        expected.sort();
+        assert_eq!(inputs, expected);
+    }
+
+    #[tokio::test]
+    async fn only_own_unspent_outputs_are_unspent() {
+        let wallet = WalletBuilder::new(10_000).with_num_utxos(2).build();
+        let utxos = wallet
+            .wallet
+            .lock()
+            .await
+            .list_unspent()
+            .unwrap()
+            .into_iter()
+            .map(|utxo| utxo.outpoint)
+            .collect::<Vec<_>>();
+
+        assert!(wallet.are_unspent(&utxos).await.unwrap());
+        assert!(!wallet
+            .are_unspent(&[utxos[0], OutPoint::default()])
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn selected_utxos_have_to_cover_amount_and_belong_to_wallet() {
+        let wallet = WalletBuilder::new(10_000).with_num_utxos(2).build();
+        let utxo = wallet.wallet.lock().await.list_unspent().unwrap()[0].outpoint;
+        let address = wallet.new_address().await.unwrap();
+
+        let insufficient = wallet
+            .with_utxos(vec![utxo])
+            .send_to_address(address.clone(), Amount::from_sat(15_000), None)
+            .await;
+        assert!(insufficient.is_err());
+
+        let wallet = WalletBuilder::new(10_000)
+            .build()
+            .with_utxos(vec![OutPoint::default()]);
+        let unknown = wallet
+            .send_to_address(address, Amount::from_sat(5_000), None)
+            .await;
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn accounts_derive_bip84_paths_and_use_own_trees() {
+        assert_eq!(
+            bip84_path(Network::Bitcoin, 2, KeychainKind::External)
+                .unwrap()
+                .to_string(),
+            "m/84'/0'/2'/0"
+        );
+        assert_eq!(
+            bip84_path(Network::Testnet, 3, KeychainKind::Internal)
+                .unwrap()
+                .to_string(),
+            "m/84'/1'/3'/1"
+        );
+        assert!(bip84_path(Network::Bitcoin, 1 << 31, KeychainKind::External).is_err());
+
+        assert_eq!(sled_tree_name(0), SLED_TREE_NAME);
+        assert_eq!(sled_tree_name(1), "default_tree_account_1");
+    }
+
+    #[test]
+    fn coin_selection_can_be_parsed_from_kebab_case() {
+        assert_eq!(
+            CoinSelection::from_str("largest-first").unwrap(),
+            CoinSelection::LargestFirst
+        );
+        assert_eq!(
+            CoinSelection::from_str("branch-and-bound").unwrap(),
+            CoinSelection::BranchAndBound
+        );
+        assert_eq!(
+            CoinSelection::from_str("avoid-change").unwrap(),
+            CoinSelection::AvoidChange
+        );
+        assert!(CoinSelection::from_str("smallest-first").is_err());
+    }
+
     #[test]
     fn printing_status_change_doesnt_spam_on_same_status() {
         let writer = capture_logs(LevelFilter::DEBUG);
@@ -1221,6 +3044,92 @@ DEBUG swap::bitcoin::wallet: Bitcoin transaction status changed txid=00000000000
         )
     }
 
+    #[tokio::test]
+    async fn waiting_fails_once_reconnect_attempts_are_exhausted() {
+        let (_status_sender, receiver) = watch::channel(ScriptStatus::InMempool);
+        let (attempts_sender, reconnect_attempts) = watch::channel(0);
+        let subscription = Subscription {
+            receiver,
+            reconnect_attempts,
+            max_reconnect_attempts: 3,
+            finality_confirmations: 1,
+            txid: Txid::default(),
+        };
+
+        let wait = tokio::spawn(async move { subscription.wait_until_final().await });
+        for attempts in 1..=3 {
+            attempts_sender.send(attempts).unwrap();
+        }
+
+        let error = wait.await.unwrap().unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<ElectrumUnreachable>(),
+            Some(&ElectrumUnreachable { attempts: 3 })
+        );
+    }
+
+    #[tokio::test]
+    async fn waiting_continues_while_electrum_is_reachable() {
+        let (status_sender, receiver) = watch::channel(ScriptStatus::InMempool);
+        let (attempts_sender, reconnect_attempts) = watch::channel(0);
+        let subscription = Subscription {
+            receiver,
+            reconnect_attempts,
+            max_reconnect_attempts: 3,
+            finality_confirmations: 1,
+            txid: Txid::default(),
+        };
+
+        let wait = tokio::spawn(async move { subscription.wait_until_final().await });
+        attempts_sender.send(2).unwrap();
+        attempts_sender.send(0).unwrap();
+        status_sender.send(confs(1)).unwrap();
+
+        assert!(wait.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn attempts_failed_before_waiting_do_not_count() {
+        let (status_sender, receiver) = watch::channel(ScriptStatus::InMempool);
+        let (attempts_sender, reconnect_attempts) = watch::channel(5);
+        let subscription = Subscription {
+            receiver,
+            reconnect_attempts,
+            max_reconnect_attempts: 3,
+            finality_confirmations: 1,
+            txid: Txid::default(),
+        };
+
+        let wait = tokio::spawn(async move { subscription.wait_until_final().await });
+        attempts_sender.send(7).unwrap();
+        status_sender.send(confs(1)).unwrap();
+
+        assert!(wait.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn waiting_without_reconnect_limit_never_fails() {
+        let (status_sender, receiver) = watch::channel(ScriptStatus::InMempool);
+        let (attempts_sender, reconnect_attempts) = watch::channel(0);
+        let subscription = Subscription {
+            receiver,
+            reconnect_attempts,
+            max_reconnect_attempts: 3,
+            finality_confirmations: 1,
+            txid: Txid::default(),
+        }
+        .without_reconnect_limit();
+
+        let wait = tokio::spawn(async move { subscription.wait_until_final().await });
+        for attempts in 1..=10 {
+            attempts_sender.send(attempts).unwrap();
+        }
+        status_sender.send(confs(1)).unwrap();
+
+        assert!(wait.await.unwrap().is_ok());
+    }
+
     fn confs(confirmations: u32) -> ScriptStatus {
         ScriptStatus::from_confirmations(confirmations)
     }
@@ -1234,7 +3143,7 @@ DEBUG swap::bitcoin::wallet: Bitcoin transaction status changed txid=00000000000
                 let wallet = WalletBuilder::new(funding_amount as u64).with_key(key).with_num_utxos(num_utxos).with_fees(sats_per_vb, 1000).build();
 
                 let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
-                let psbt: PartiallySignedTransaction = TxLock::new(&wallet, amount, PublicKey::from(alice), PublicKey::from(bob), wallet.new_address().await.unwrap()).await.unwrap().into();
+                let psbt: PartiallySignedTransaction = TxLock::new(&wallet, amount, PublicKey::from(alice), PublicKey::from(bob), wallet.new_address().await.unwrap(), &[]).await.unwrap().into();
                 let result = wallet.sign_and_finalize(psbt).await;
 
                 result.expect("transaction to be signed");
@@ -1,17 +1,35 @@
 mod behaviour;
 pub mod cancel;
 pub mod command;
+pub mod doctor;
 mod event_loop;
+pub mod export_proof;
 mod list_sellers;
+mod quote_refresh;
+mod rate_bounds;
 pub mod refund;
+mod replace_seller;
+mod resume_terms;
+mod seller_whitelist;
+mod sellers_cache;
 pub mod tracing;
 pub mod transport;
 
 pub use behaviour::{Behaviour, OutEvent};
 pub use cancel::cancel;
+pub use doctor::{diagnose, Diagnosis, SellerReachability};
 pub use event_loop::{EventLoop, EventLoopHandle};
-pub use list_sellers::{list_sellers, Seller, Status as SellerStatus};
-pub use refund::refund;
+pub use export_proof::{export_proof, SwapProof};
+pub use list_sellers::{
+    list_sellers, Seller, Status as SellerStatus, DEFAULT_MAX_CONCURRENT_DIALS,
+};
+pub use quote_refresh::{check_quote_expiry, refresh_quote, QuoteRefresh};
+pub use rate_bounds::{RateBounds, RateOutOfBounds};
+pub use refund::{refund, refund_on_failure, RefundFeeRate};
+pub use replace_seller::replace_seller;
+pub use resume_terms::{check_resumed_terms, resume_needs_quote, StaleTerms};
+pub use seller_whitelist::{ensure_not_self, SellerWhitelist, SwapWithSelf};
+pub use sellers_cache::{unix_timestamp, SellersCache, DEFAULT_SELLERS_CACHE_TTL};
 
 #[cfg(test)]
 mod tests {
@@ -46,6 +64,7 @@ mod tests {
             namespace,
             0,
             identity::Keypair::generate_ed25519(),
+            1,
         );
         let sellers = tokio::time::timeout(Duration::from_secs(15), list_sellers)
             .await
@@ -81,6 +100,7 @@ mod tests {
             price: bitcoin::Amount::from_sat(1337),
             min_quantity: bitcoin::Amount::from_sat(42),
             max_quantity: bitcoin::Amount::from_sat(9001),
+            quote_expiry: None,
         };
 
         let mut asb = new_swarm(|_, identity| StaticQuoteAsbBehaviour {
@@ -92,7 +112,7 @@ mod tests {
                 None,
             ),
             ping: Default::default(),
-            quote: quote::asb(),
+            quote: quote::asb(quote::DEFAULT_TIMEOUT),
             static_quote,
             registered: false,
         });
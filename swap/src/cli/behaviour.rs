@@ -1,3 +1,4 @@
+use crate::cli::RateBounds;
 use crate::network::quote::BidQuote;
 use crate::network::swap_setup::bob;
 use crate::network::{encrypted_signature, quote, redial, transfer_proof};
@@ -76,12 +77,13 @@ impl Behaviour {
         alice: PeerId,
         env_config: env::Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
+        rate_bounds: RateBounds,
     ) -> Self {
         Self {
-            quote: quote::cli(),
-            swap_setup: bob::Behaviour::new(env_config, bitcoin_wallet),
-            transfer_proof: transfer_proof::bob(),
-            encrypted_signature: encrypted_signature::bob(),
+            quote: quote::cli(quote::DEFAULT_TIMEOUT),
+            swap_setup: bob::Behaviour::new(env_config, bitcoin_wallet, rate_bounds),
+            transfer_proof: transfer_proof::bob(transfer_proof::DEFAULT_TIMEOUT),
+            encrypted_signature: encrypted_signature::bob(encrypted_signature::DEFAULT_TIMEOUT),
             redial: redial::Behaviour::new(alice, Duration::from_secs(2)),
             ping: Ping::new(PingConfig::new().with_keep_alive(true)),
         }
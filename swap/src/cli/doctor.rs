@@ -0,0 +1,563 @@
+//! Diagnoses a swap that does not make progress and recommends what to do
+//! about it.
+//!
+//! The diagnosis only reads the database and the blockchain, it never
+//! publishes a transaction or changes the state of the swap.
+
+use crate::bitcoin::wallet::{ScriptStatus, Watchable};
+use crate::bitcoin::{self, ExpiredTimelocks, OutPoint, Txid, Wallet};
+use crate::protocol::bob::{BobState, State6};
+use crate::protocol::Database;
+use ::bitcoin::Script;
+use anyhow::Result;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Everything known about a swap and what to do about it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnosis {
+    pub swap_id: Uuid,
+    pub state: String,
+    pub transactions: Vec<TransactionStatus>,
+    /// Not known before the Bitcoin is locked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expired_timelocks: Option<ExpiredTimelocks>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks_until_cancel: Option<u32>,
+    /// Only known once the cancel transaction is confirmed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks_until_punish: Option<u32>,
+    pub seller: SellerReachability,
+    pub recommendation: Recommendation,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransactionStatus {
+    pub name: &'static str,
+    pub txid: Txid,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SellerReachability {
+    pub peer_id: String,
+    pub reachable: bool,
+    /// Why the seller could not be reached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Recommendation {
+    pub action: Action,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Action {
+    /// Nothing to do but wait for the blockchain or the seller.
+    Wait,
+    /// The swap continues if `resume` is run.
+    Resume,
+    Cancel,
+    Refund,
+    /// The swap is complete.
+    Nothing,
+}
+
+/// The Bitcoin side of a swap whose Bitcoin is locked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LockStatus {
+    lock: ScriptStatus,
+    cancel: ScriptStatus,
+    refund: ScriptStatus,
+    /// The seller's redeem transaction, [`ScriptStatus::Unseen`] if the lock
+    /// output is not spent by anything but our cancel transaction.
+    redeem: ScriptStatus,
+    /// The seller's punish transaction, [`ScriptStatus::Unseen`] if the cancel
+    /// output is not spent by anything but our refund transaction.
+    punish: ScriptStatus,
+    expired_timelocks: ExpiredTimelocks,
+    blocks_until_cancel: u32,
+    blocks_until_punish: Option<u32>,
+}
+
+/// Diagnoses the swap from its latest state in the database and the status of
+/// its Bitcoin transactions.
+pub async fn diagnose(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Arc<dyn Database>,
+    seller: SellerReachability,
+) -> Result<Diagnosis> {
+    let state: BobState = db.get_state(swap_id).await?.try_into()?;
+
+    let (mut transactions, lock_status) = match state.state6() {
+        Some(state6) => {
+            let (transactions, lock_status) = lock_status(&state6, bitcoin_wallet.as_ref()).await?;
+            (transactions, Some(lock_status))
+        }
+        None => (Vec::new(), None),
+    };
+
+    let mut stage = Stage::from(&state);
+
+    // The lock transaction is broadcast before the state is updated
+    if let BobState::SwapSetupCompleted(state2) = &state {
+        let lock = bitcoin_wallet.status_of_script(state2.tx_lock()).await?;
+
+        if lock.has_been_seen() {
+            stage = Stage::LockPublished;
+            transactions.push(transaction_status("lock", state2.tx_lock(), lock));
+        }
+    }
+
+    let recommendation = recommend(stage, lock_status, seller.reachable);
+
+    Ok(Diagnosis {
+        swap_id,
+        state: state.to_string(),
+        transactions,
+        expired_timelocks: lock_status.map(|status| status.expired_timelocks),
+        blocks_until_cancel: lock_status.map(|status| status.blocks_until_cancel),
+        blocks_until_punish: lock_status.and_then(|status| status.blocks_until_punish),
+        seller,
+        recommendation,
+    })
+}
+
+async fn lock_status(
+    state6: &State6,
+    bitcoin_wallet: &Wallet,
+) -> Result<(Vec<TransactionStatus>, LockStatus)> {
+    let tx_lock = state6.tx_lock();
+    let tx_cancel = state6.tx_cancel();
+    let tx_refund = state6.tx_refund();
+
+    let lock = bitcoin_wallet.status_of_script(tx_lock).await?;
+    let cancel = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+    let refund = bitcoin_wallet.status_of_script(&tx_refund).await?;
+
+    let redeem = other_spend(
+        bitcoin_wallet,
+        tx_lock.as_outpoint(),
+        tx_lock.script(),
+        tx_cancel.txid(),
+    )
+    .await?;
+    let punish = other_spend(
+        bitcoin_wallet,
+        tx_cancel.as_outpoint(),
+        tx_cancel.script(),
+        tx_refund.txid(),
+    )
+    .await?;
+
+    let blocks_until_punish = match cancel {
+        ScriptStatus::Confirmed(confirmed) => {
+            Some(u32::from(state6.punish_timelock()).saturating_sub(confirmed.confirmations()))
+        }
+        _ => None,
+    };
+
+    let mut transactions = vec![
+        transaction_status("lock", tx_lock, lock),
+        transaction_status("cancel", &tx_cancel, cancel),
+        transaction_status("refund", &tx_refund, refund),
+    ];
+    if let Some((txid, status)) = redeem {
+        transactions.push(transaction_status(
+            "redeem",
+            &(txid, tx_lock.script()),
+            status,
+        ));
+    }
+    if let Some((txid, status)) = punish {
+        transactions.push(transaction_status(
+            "punish",
+            &(txid, tx_cancel.script()),
+            status,
+        ));
+    }
+
+    Ok((
+        transactions,
+        LockStatus {
+            lock,
+            cancel,
+            refund,
+            redeem: redeem.map_or(ScriptStatus::Unseen, |(_, status)| status),
+            punish: punish.map_or(ScriptStatus::Unseen, |(_, status)| status),
+            expired_timelocks: bitcoin::current_epoch(
+                state6.cancel_timelock(),
+                state6.punish_timelock(),
+                lock,
+                cancel,
+            ),
+            blocks_until_cancel: bitcoin::blocks_until_cancel_timelock_expires(
+                state6.cancel_timelock(),
+                lock,
+            ),
+            blocks_until_punish,
+        },
+    ))
+}
+
+/// The first transaction that spends `outpoint` with the `script`, other than
+/// the `expected` one of the swap, and its status.
+async fn other_spend(
+    bitcoin_wallet: &Wallet,
+    outpoint: OutPoint,
+    script: Script,
+    expected: Txid,
+) -> Result<Option<(Txid, ScriptStatus)>> {
+    let spend = bitcoin_wallet
+        .spending_transactions(outpoint, &script)
+        .await?
+        .into_iter()
+        .map(|transaction| transaction.txid())
+        .find(|txid| *txid != expected);
+
+    match spend {
+        Some(txid) => {
+            let status = bitcoin_wallet.status_of_script(&(txid, script)).await?;
+            Ok(Some((txid, status)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn transaction_status(
+    name: &'static str,
+    tx: &impl Watchable,
+    status: ScriptStatus,
+) -> TransactionStatus {
+    TransactionStatus {
+        name,
+        txid: tx.id(),
+        status: status.to_string(),
+    }
+}
+
+/// How far the swap got according to its latest state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    NotLocked,
+    /// The swap was set up and its lock transaction was published, but the
+    /// state was not updated yet.
+    LockPublished,
+    Locked,
+    /// The swap was about to be cancelled or refunded.
+    Cancelling,
+    BtcRedeemed,
+    Punished,
+    Complete,
+}
+
+impl From<&BobState> for Stage {
+    fn from(state: &BobState) -> Self {
+        match state {
+            BobState::Started { .. } | BobState::SwapSetupCompleted(_) => Stage::NotLocked,
+            BobState::BtcLocked { .. }
+            | BobState::XmrLockProofReceived { .. }
+            | BobState::XmrLocked(_)
+            | BobState::EncSigSent(_) => Stage::Locked,
+            BobState::CancelTimelockExpired(_) | BobState::BtcCancelled(_) => Stage::Cancelling,
+            BobState::BtcRedeemed(_) => Stage::BtcRedeemed,
+            BobState::BtcPunished { .. } => Stage::Punished,
+            BobState::BtcRefunded(_) | BobState::XmrRedeemed { .. } | BobState::SafelyAborted => {
+                Stage::Complete
+            }
+        }
+    }
+}
+
+fn recommend(
+    stage: Stage,
+    lock_status: Option<LockStatus>,
+    seller_reachable: bool,
+) -> Recommendation {
+    let recommendation = |action, reason: &str| Recommendation {
+        action,
+        reason: reason.to_owned(),
+    };
+
+    let status = match (stage, lock_status) {
+        (Stage::Complete, _) => return recommendation(Action::Nothing, "The swap is complete."),
+        (Stage::Punished, _) => {
+            return recommendation(
+                Action::Nothing,
+                "The seller punished the swap, the Bitcoin cannot be refunded anymore.",
+            )
+        }
+        (Stage::BtcRedeemed, _) => {
+            return recommendation(
+                Action::Resume,
+                "The seller redeemed the Bitcoin, resume the swap to claim the Monero.",
+            )
+        }
+        (Stage::LockPublished, _) => {
+            return recommendation(
+                Action::Resume,
+                "The Bitcoin lock transaction was published, resume the swap to continue it.",
+            )
+        }
+        (Stage::NotLocked, _) | (_, None) => {
+            return recommendation(
+                Action::Resume,
+                "No Bitcoin is locked yet, nothing is at risk. Resume the swap to continue it.",
+            )
+        }
+        (_, Some(status)) => status,
+    };
+
+    if status.punish.has_been_seen() {
+        return recommendation(
+            Action::Nothing,
+            "The seller punished the swap, the Bitcoin cannot be refunded anymore.",
+        );
+    }
+
+    if status.redeem.has_been_seen() {
+        return recommendation(
+            Action::Resume,
+            "The seller redeemed the Bitcoin, resume the swap to claim the Monero.",
+        );
+    }
+
+    if status.refund.has_been_seen() {
+        return if status.refund.is_confirmed() {
+            recommendation(
+                Action::Refund,
+                "The refund transaction is confirmed, run refund to record the refund of the swap.",
+            )
+        } else {
+            recommendation(
+                Action::Wait,
+                "The refund transaction is published, wait for it to be confirmed.",
+            )
+        };
+    }
+
+    match status.expired_timelocks {
+        ExpiredTimelocks::Punish => recommendation(
+            Action::Refund,
+            "The punish timelock expired and the seller can punish the swap at any time, refund the Bitcoin now.",
+        ),
+        ExpiredTimelocks::Cancel if status.cancel.has_been_seen() => recommendation(
+            Action::Refund,
+            "The cancel transaction is published, refund the Bitcoin before the punish timelock expires.",
+        ),
+        ExpiredTimelocks::Cancel => recommendation(
+            Action::Cancel,
+            "The cancel timelock expired, cancel the swap now and refund the Bitcoin afterwards.",
+        ),
+        ExpiredTimelocks::None if !status.lock.has_been_seen() => recommendation(
+            Action::Wait,
+            "The Bitcoin lock transaction is not known to the Electrum server, wait for it to propagate or check the server.",
+        ),
+        ExpiredTimelocks::None if stage == Stage::Cancelling => Recommendation {
+            action: Action::Wait,
+            reason: format!(
+                "Wait until the cancel timelock expires in {} blocks, then refund the Bitcoin.",
+                status.blocks_until_cancel
+            ),
+        },
+        ExpiredTimelocks::None if seller_reachable => {
+            recommendation(Action::Resume, "The swap can still complete, resume it.")
+        }
+        ExpiredTimelocks::None => Recommendation {
+            action: Action::Wait,
+            reason: format!(
+                "The seller cannot be reached. Resume the swap once it is reachable again, or cancel it once the cancel timelock expires in {} blocks.",
+                status.blocks_until_cancel
+            ),
+        },
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Swap:           {}", self.swap_id)?;
+        writeln!(f, "State:          {}", self.state)?;
+
+        for transaction in &self.transactions {
+            writeln!(
+                f,
+                "Tx {:<12} {} ({})",
+                format!("{}:", transaction.name),
+                transaction.txid,
+                transaction.status
+            )?;
+        }
+
+        if let Some(expired_timelocks) = self.expired_timelocks {
+            writeln!(f, "Expired:        {}", expired_timelocks)?;
+        }
+        if let Some(blocks) = self.blocks_until_cancel {
+            writeln!(f, "Cancel in:      {} blocks", blocks)?;
+        }
+        if let Some(blocks) = self.blocks_until_punish {
+            writeln!(f, "Punish in:      {} blocks", blocks)?;
+        }
+
+        match &self.seller.error {
+            None => writeln!(f, "Seller:         {} is reachable", self.seller.peer_id)?,
+            Some(error) => writeln!(
+                f,
+                "Seller:         {} is not reachable: {}",
+                self.seller.peer_id, error
+            )?,
+        }
+
+        write!(
+            f,
+            "Recommendation: {}. {}",
+            self.recommendation.action, self.recommendation.reason
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_status(
+        cancel: ScriptStatus,
+        refund: ScriptStatus,
+        expired_timelocks: ExpiredTimelocks,
+    ) -> Option<LockStatus> {
+        Some(LockStatus {
+            lock: ScriptStatus::from_confirmations(10),
+            cancel,
+            refund,
+            redeem: ScriptStatus::Unseen,
+            punish: ScriptStatus::Unseen,
+            expired_timelocks,
+            blocks_until_cancel: 0,
+            blocks_until_punish: None,
+        })
+    }
+
+    fn confirmed() -> ScriptStatus {
+        ScriptStatus::from_confirmations(1)
+    }
+
+    #[test]
+    fn complete_swap_needs_nothing_whatever_the_blockchain_shows() {
+        let status = lock_status(
+            ScriptStatus::Unseen,
+            ScriptStatus::Unseen,
+            ExpiredTimelocks::Cancel,
+        );
+
+        assert_eq!(
+            recommend(Stage::Complete, status, true).action,
+            Action::Nothing
+        );
+        assert_eq!(
+            recommend(Stage::Complete, None, false).action,
+            Action::Nothing
+        );
+    }
+
+    #[test]
+    fn expired_cancel_timelock_recommends_cancel_then_refund() {
+        let not_cancelled = lock_status(
+            ScriptStatus::Unseen,
+            ScriptStatus::Unseen,
+            ExpiredTimelocks::Cancel,
+        );
+        let cancelled = lock_status(
+            ScriptStatus::InMempool,
+            ScriptStatus::Unseen,
+            ExpiredTimelocks::Cancel,
+        );
+
+        assert_eq!(
+            recommend(Stage::Locked, not_cancelled, true).action,
+            Action::Cancel
+        );
+        assert_eq!(
+            recommend(Stage::Locked, cancelled, true).action,
+            Action::Refund
+        );
+    }
+
+    #[test]
+    fn expired_punish_timelock_recommends_refund_until_refunded() {
+        let punishable = lock_status(confirmed(), ScriptStatus::Unseen, ExpiredTimelocks::Punish);
+        let refunding = lock_status(
+            confirmed(),
+            ScriptStatus::InMempool,
+            ExpiredTimelocks::Punish,
+        );
+
+        assert_eq!(
+            recommend(Stage::Cancelling, punishable, true).action,
+            Action::Refund
+        );
+        assert_eq!(
+            recommend(Stage::Cancelling, refunding, true).action,
+            Action::Wait
+        );
+    }
+
+    #[test]
+    fn redeemed_swap_is_resumed_even_after_cancel_timelock_expired() {
+        let mut status = lock_status(
+            ScriptStatus::Unseen,
+            ScriptStatus::Unseen,
+            ExpiredTimelocks::Cancel,
+        );
+        if let Some(status) = status.as_mut() {
+            status.redeem = confirmed();
+        }
+
+        assert_eq!(
+            recommend(Stage::Locked, status, false).action,
+            Action::Resume
+        );
+    }
+
+    #[test]
+    fn punished_swap_needs_nothing_even_before_the_state_is_updated() {
+        let mut status = lock_status(confirmed(), ScriptStatus::Unseen, ExpiredTimelocks::Punish);
+        if let Some(status) = status.as_mut() {
+            status.punish = ScriptStatus::InMempool;
+        }
+
+        assert_eq!(
+            recommend(Stage::Cancelling, status, true).action,
+            Action::Nothing
+        );
+    }
+
+    #[test]
+    fn published_lock_of_set_up_swap_is_not_reported_as_nothing_at_risk() {
+        let recommendation = recommend(Stage::LockPublished, None, true);
+
+        assert_eq!(recommendation.action, Action::Resume);
+        assert!(!recommendation.reason.contains("nothing is at risk"));
+    }
+
+    #[test]
+    fn running_swap_is_resumed_if_seller_is_reachable() {
+        let status = lock_status(
+            ScriptStatus::Unseen,
+            ScriptStatus::Unseen,
+            ExpiredTimelocks::None,
+        );
+
+        assert_eq!(
+            recommend(Stage::Locked, status, true).action,
+            Action::Resume
+        );
+        assert_eq!(recommend(Stage::Locked, status, false).action, Action::Wait);
+    }
+}
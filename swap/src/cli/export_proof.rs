@@ -0,0 +1,333 @@
+use crate::bitcoin::{self, Transaction, Txid, Wallet};
+use crate::monero::{self, TransferProof};
+use crate::protocol::bob::{BobState, State3};
+use crate::protocol::Database;
+use ::bitcoin::{OutPoint, Script};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::convert::TryInto;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The public data of a finished swap that allows a third party to verify
+/// which funds moved on both blockchains.
+///
+/// The proof contains no secret key. The transaction key of the XMR lock
+/// transaction only proves that this transaction paid the lock address, e.g.
+/// with `check_tx_key` of `monero-wallet-cli`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapProof {
+    pub swap_id: Uuid,
+    pub outcome: Outcome,
+    pub bitcoin: BitcoinProof,
+    /// Not known if the swap ended before Bob learned about the XMR lock.
+    pub monero: Option<MoneroProof>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    XmrRedeemed,
+    BtcRefunded,
+    BtcPunished,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BitcoinProof {
+    pub network: bitcoin::Network,
+    pub alice_public_key: bitcoin::PublicKey,
+    pub bob_public_key: bitcoin::PublicKey,
+    pub lock_txid: Txid,
+    pub lock_address: bitcoin::Address,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub lock_amount_sat: bitcoin::Amount,
+    pub alice_redeem_address: bitcoin::Address,
+    pub bob_refund_address: bitcoin::Address,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redeem_txid: Option<Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancel_txid: Option<Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refund_txid: Option<Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub punish_txid: Option<Txid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MoneroProof {
+    pub lock_tx_hash: String,
+    pub lock_tx_key: String,
+    pub lock_address: String,
+    pub lock_amount_piconero: monero::Amount,
+    pub public_spend_key: String,
+    pub public_view_key: String,
+}
+
+/// Builds the [`SwapProof`] of a finished swap from the states saved in the
+/// database and the transactions spending the lock output on the blockchain.
+pub async fn export_proof(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Arc<dyn Database>,
+    monero_network: monero::Network,
+) -> Result<SwapProof> {
+    let states = db
+        .get_states(swap_id)
+        .await?
+        .into_iter()
+        .map(|state| state.try_into())
+        .collect::<Result<Vec<BobState>, _>>()?;
+    let latest = states
+        .last()
+        .with_context(|| format!("No state in database for swap: {}", swap_id))?;
+
+    let outcome = match latest {
+        BobState::XmrRedeemed { .. } => Outcome::XmrRedeemed,
+        BobState::BtcRefunded(_) => Outcome::BtcRefunded,
+        BobState::BtcPunished { .. } => Outcome::BtcPunished,
+
+        BobState::Started { .. }
+        | BobState::SwapSetupCompleted(_)
+        | BobState::BtcLocked { .. }
+        | BobState::XmrLockProofReceived { .. }
+        | BobState::XmrLocked(_)
+        | BobState::EncSigSent(_)
+        | BobState::BtcRedeemed(_)
+        | BobState::CancelTimelockExpired(_)
+        | BobState::BtcCancelled(_)
+        | BobState::SafelyAborted => bail!(
+            "Cannot export a proof of swap {} in state {}, only swaps that finished after locking the Bitcoin can be proven",
+            swap_id,
+            latest
+        ),
+    };
+
+    let state3 = states
+        .iter()
+        .find_map(|state| match state {
+            BobState::BtcLocked { state3, .. } => Some(state3),
+            BobState::XmrLockProofReceived { state, .. } => Some(state),
+            _ => None,
+        })
+        .with_context(|| format!("No Bitcoin lock in database for swap: {}", swap_id))?;
+    let transfer_proof = states.iter().find_map(|state| match state {
+        BobState::XmrLockProofReceived {
+            lock_transfer_proof,
+            ..
+        } => Some(lock_transfer_proof.clone()),
+        _ => None,
+    });
+
+    let network = bitcoin_wallet.get_network();
+    let tx_lock = &state3.tx_lock;
+    let (alice_public_key, bob_public_key) = state3.bitcoin_public_keys();
+
+    let tx_lock_spend = spending_transaction(
+        bitcoin_wallet.as_ref(),
+        tx_lock.as_outpoint(),
+        tx_lock.script_pubkey(),
+    )
+    .await
+    .context("Failed to find the transaction spending the Bitcoin lock output")?;
+
+    let mut bitcoin_proof = BitcoinProof {
+        network,
+        alice_public_key,
+        bob_public_key,
+        lock_txid: tx_lock.txid(),
+        lock_address: bitcoin::Address::from_script(&tx_lock.script_pubkey(), network)
+            .context("Failed to derive the address of the Bitcoin lock output")?,
+        lock_amount_sat: tx_lock.lock_amount(),
+        alice_redeem_address: state3.redeem_address().clone(),
+        bob_refund_address: state3.refund_address().clone(),
+        redeem_txid: None,
+        cancel_txid: None,
+        refund_txid: None,
+        punish_txid: None,
+    };
+
+    match outcome {
+        Outcome::XmrRedeemed => bitcoin_proof.redeem_txid = Some(tx_lock_spend.txid()),
+        Outcome::BtcRefunded | Outcome::BtcPunished => {
+            let tx_cancel = tx_lock_spend;
+            let tx_cancel_spend = spending_transaction(
+                bitcoin_wallet.as_ref(),
+                OutPoint::new(tx_cancel.txid(), 0),
+                tx_cancel.output[0].script_pubkey.clone(),
+            )
+            .await
+            .context("Failed to find the transaction spending the Bitcoin cancel output")?;
+
+            bitcoin_proof.cancel_txid = Some(tx_cancel.txid());
+
+            if outcome == Outcome::BtcRefunded {
+                bitcoin_proof.refund_txid = Some(tx_cancel_spend.txid());
+            } else {
+                bitcoin_proof.punish_txid = Some(tx_cancel_spend.txid());
+            }
+        }
+    }
+
+    let monero =
+        transfer_proof.map(|transfer_proof| monero_proof(state3, transfer_proof, monero_network));
+
+    Ok(SwapProof {
+        swap_id,
+        outcome,
+        bitcoin: bitcoin_proof,
+        monero,
+    })
+}
+
+fn monero_proof(
+    state3: &State3,
+    transfer_proof: TransferProof,
+    monero_network: monero::Network,
+) -> MoneroProof {
+    let watch_request = state3.lock_xmr_watch_request(transfer_proof);
+    let public_view_key = monero::PublicKey::from(watch_request.public_view_key);
+
+    MoneroProof {
+        lock_tx_hash: watch_request.transfer_proof.tx_hash().to_string(),
+        lock_tx_key: watch_request.transfer_proof.tx_key().to_string(),
+        lock_address: monero::Address::standard(
+            monero_network,
+            watch_request.public_spend_key,
+            public_view_key,
+        )
+        .to_string(),
+        lock_amount_piconero: watch_request.expected,
+        public_spend_key: watch_request.public_spend_key.to_string(),
+        public_view_key: public_view_key.to_string(),
+    }
+}
+
+async fn spending_transaction(
+    bitcoin_wallet: &Wallet,
+    outpoint: OutPoint,
+    script: Script,
+) -> Result<Transaction> {
+    bitcoin_wallet
+        .spending_transactions(outpoint, &script)
+        .await?
+        .into_iter()
+        .next()
+        .with_context(|| format!("No transaction spends {}", outpoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{Amount, WalletBuilder};
+    use crate::database::SqliteDatabase;
+    use crate::env::{GetConfig, Regtest};
+    use crate::monero::TxHash;
+    use crate::protocol::{alice, bob};
+    use monero_rpc::wallet::BlockHeight;
+    use rand::rngs::OsRng;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn proof_of_stored_xmr_lock_matches_the_sellers_lock() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let transfer_proof = TransferProof::new(
+            TxHash("a1b2c3".repeat(10) + "a1b2"),
+            monero::PrivateKey::from_scalar(monero::Scalar::random(&mut OsRng)),
+        );
+        let db = test_db().await;
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(
+            swap_id,
+            BobState::XmrLockProofReceived {
+                state: bob_state3,
+                lock_transfer_proof: transfer_proof.clone(),
+                monero_wallet_restore_blockheight: BlockHeight { height: 0 },
+            }
+            .into(),
+        )
+        .await
+        .unwrap();
+
+        let (state3, lock_transfer_proof) = match db.get_state(swap_id).await.unwrap().try_into() {
+            Ok(BobState::XmrLockProofReceived {
+                state,
+                lock_transfer_proof,
+                ..
+            }) => (state, lock_transfer_proof),
+            other => panic!("Expected XmrLockProofReceived, got {:?}", other),
+        };
+        let proof = monero_proof(&state3, lock_transfer_proof, monero::Network::Stagenet);
+
+        let sellers_lock = alice_state3.xmr_lock_proof(transfer_proof, monero::Network::Stagenet);
+        assert_eq!(
+            proof.lock_tx_hash,
+            sellers_lock.transfer_proof.tx_hash().to_string()
+        );
+        assert_eq!(
+            proof.lock_tx_key,
+            sellers_lock.transfer_proof.tx_key().to_string()
+        );
+        assert_eq!(proof.lock_address, sellers_lock.address.to_string());
+        assert_eq!(proof.lock_amount_piconero, sellers_lock.amount);
+        assert_eq!(
+            proof.public_spend_key,
+            sellers_lock.address.public_spend.to_string()
+        );
+        assert_eq!(
+            proof.public_view_key,
+            sellers_lock.address.public_view.to_string()
+        );
+        assert!(!serde_json::to_string(&proof)
+            .unwrap()
+            .contains(&alice_state3.v.to_string()));
+    }
+
+    async fn locked_swap() -> (alice::State3, bob::State3) {
+        let alice_wallet = WalletBuilder::new(Amount::ONE_BTC.as_sat()).build();
+        let bob_wallet = WalletBuilder::new(Amount::ONE_BTC.as_sat()).build();
+        let fee = Amount::from_sat(1_000);
+        let config = Regtest::get_config();
+
+        let alice_state0 = alice::State0::new(
+            Amount::from_sat(500_000),
+            monero::Amount::from_piconero(10_000),
+            config,
+            alice_wallet.new_address().await.unwrap(),
+            alice_wallet.new_address().await.unwrap(),
+            fee,
+            fee,
+            &mut OsRng,
+        );
+        let bob_state0 = bob::State0::new(
+            Uuid::new_v4(),
+            &mut OsRng,
+            Amount::from_sat(500_000),
+            monero::Amount::from_piconero(10_000),
+            config.bitcoin_cancel_timelock,
+            config.bitcoin_punish_timelock,
+            bob_wallet.new_address().await.unwrap(),
+            config.monero_finality_confirmations,
+            fee,
+            fee,
+        );
+
+        let (_, alice_state1) = alice_state0.receive(bob_state0.next_message()).unwrap();
+        let bob_state1 = bob_state0
+            .receive(&bob_wallet, alice_state1.next_message(), &[])
+            .await
+            .unwrap();
+        let alice_state2 = alice_state1.receive(bob_state1.next_message()).unwrap();
+        let bob_state2 = bob_state1.receive(alice_state2.next_message()).unwrap();
+        let alice_state3 = alice_state2.receive(bob_state2.next_message()).unwrap();
+        let (bob_state3, _) = bob_state2.lock_btc().await.unwrap();
+
+        (alice_state3, bob_state3)
+    }
+
+    async fn test_db() -> Arc<dyn Database + Send + Sync> {
+        let db_path = tempdir().unwrap().into_path().join("sqlite");
+        std::fs::File::create(&db_path).unwrap();
+
+        Arc::new(SqliteDatabase::open(db_path).await.unwrap())
+    }
+}
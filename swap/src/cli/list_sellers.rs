@@ -8,28 +8,36 @@ use libp2p::ping::{Ping, PingConfig, PingEvent};
 use libp2p::request_response::{RequestResponseEvent, RequestResponseMessage};
 use libp2p::swarm::SwarmEvent;
 use libp2p::{identity, rendezvous, Multiaddr, PeerId, Swarm};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::time::Duration;
 
+/// How many sellers are dialed at the same time if not configured otherwise.
+pub const DEFAULT_MAX_CONCURRENT_DIALS: usize = 5;
+
 /// Returns sorted list of sellers, with [Online](Status::Online) listed first.
 ///
 /// First uses the rendezvous node to discover peers in the given namespace,
 /// then fetches a quote from each peer that was discovered. If fetching a quote
 /// from a discovered peer fails the seller's status will be
 /// [Unreachable](Status::Unreachable).
+///
+/// At most `max_concurrent_dials` sellers are asked for a quote at the same
+/// time, so that many sellers do not overwhelm the Tor proxy. The others wait
+/// until a request completed.
 pub async fn list_sellers(
     rendezvous_node_peer_id: PeerId,
     rendezvous_node_addr: Multiaddr,
     namespace: XmrBtcNamespace,
     tor_socks5_port: u16,
     identity: identity::Keypair,
+    max_concurrent_dials: usize,
 ) -> Result<Vec<Seller>> {
     let behaviour = Behaviour {
         rendezvous: rendezvous::client::Behaviour::new(identity.clone()),
-        quote: quote::cli(),
+        quote: quote::cli(quote::DEFAULT_TIMEOUT),
         ping: Ping::new(
             PingConfig::new()
                 .with_keep_alive(false)
@@ -51,6 +59,7 @@ pub async fn list_sellers(
         rendezvous_node_peer_id,
         rendezvous_node_addr,
         namespace,
+        max_concurrent_dials,
     );
     let sellers = event_loop.run().await;
 
@@ -58,14 +67,14 @@ pub async fn list_sellers(
 }
 
 #[serde_as]
-#[derive(Debug, Serialize, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct Seller {
     pub status: Status,
     #[serde_as(as = "DisplayFromStr")]
     pub multiaddr: Multiaddr,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq, Hash, Copy, Clone, Ord, PartialOrd)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Ord, PartialOrd)]
 pub enum Status {
     Online(BidQuote),
     Unreachable,
@@ -120,6 +129,12 @@ struct EventLoop {
     unreachable_asb_address: HashMap<PeerId, Multiaddr>,
     asb_quote_status: HashMap<PeerId, QuoteStatus>,
     state: State,
+    max_concurrent_dials: usize,
+    /// Discovered sellers that were not asked for a quote yet.
+    queued: VecDeque<PeerId>,
+    /// Sellers whose quote request did not complete yet.
+    in_flight: HashSet<PeerId>,
+    completed: usize,
 }
 
 impl EventLoop {
@@ -128,6 +143,7 @@ impl EventLoop {
         rendezvous_peer_id: PeerId,
         rendezvous_addr: Multiaddr,
         namespace: XmrBtcNamespace,
+        max_concurrent_dials: usize,
     ) -> Self {
         Self {
             swarm,
@@ -138,9 +154,51 @@ impl EventLoop {
             unreachable_asb_address: Default::default(),
             asb_quote_status: Default::default(),
             state: State::WaitForDiscovery,
+            max_concurrent_dials: max_concurrent_dials.max(1),
+            queued: Default::default(),
+            in_flight: Default::default(),
+            completed: 0,
         }
     }
 
+    /// Requests quotes from queued sellers until the limit of concurrent dials
+    /// is reached. If we are not connected to a seller it is dialed
+    /// automatically.
+    fn request_queued_quotes(&mut self) {
+        while self.in_flight.len() < self.max_concurrent_dials {
+            let peer = match self.queued.pop_front() {
+                Some(peer) => peer,
+                None => break,
+            };
+
+            let _request_id = self.swarm.behaviour_mut().quote.send_request(&peer, ());
+            self.in_flight.insert(peer);
+        }
+    }
+
+    /// Frees the slot of a seller whose quote request completed, reports the
+    /// progress once per batch and requests the next quotes.
+    fn quote_request_completed(&mut self, peer: PeerId) {
+        if !self.in_flight.remove(&peer) {
+            return;
+        }
+
+        self.completed += 1;
+        let total = self.completed + self.in_flight.len() + self.queued.len();
+
+        if self.completed % self.max_concurrent_dials == 0 || self.completed == total {
+            tracing::info!(
+                completed = self.completed,
+                total,
+                "Requested quotes from {} of {} sellers",
+                self.completed,
+                total
+            );
+        }
+
+        self.request_queued_quotes();
+    }
+
     async fn run(mut self) -> Vec<Seller> {
         loop {
             tokio::select! {
@@ -217,9 +275,12 @@ impl EventLoop {
                                     self.swarm.behaviour_mut().quote.add_address(&peer, address.clone());
                                 }
 
-                                // request the quote, if we are not connected to the peer it will be dialed automatically
-                                let _request_id = self.swarm.behaviour_mut().quote.send_request(&peer, ());
+                                if !self.in_flight.contains(&peer) && !self.queued.contains(&peer) {
+                                    self.queued.push_back(peer);
+                                }
                             }
+
+                            self.request_queued_quotes();
                         }
                         SwarmEvent::Behaviour(OutEvent::Quote(quote_response)) => {
                             match quote_response {
@@ -230,6 +291,7 @@ impl EventLoop {
                                                 tracing::error!(%peer, "Received bid quote from unexpected peer, this record will be removed!");
                                                 self.asb_quote_status.remove(&peer);
                                             }
+                                            self.quote_request_completed(peer);
                                         }
                                         RequestResponseMessage::Request { .. } => unreachable!()
                                     }
@@ -237,9 +299,14 @@ impl EventLoop {
                                 RequestResponseEvent::OutboundFailure { peer, error, .. } => {
                                     if peer == self.rendezvous_peer_id {
                                         tracing::debug!(%peer, "Outbound failure when communicating with rendezvous node: {:#}", error);
+                                    } else if self.unreachable_asb_address.contains_key(&peer) {
+                                        // keep the seller listed as unreachable
+                                        tracing::debug!(%peer, "Unable to request quote from unreachable seller: {:#}", error);
+                                        self.quote_request_completed(peer);
                                     } else {
                                         tracing::debug!(%peer, "Ignoring seller, because unable to request quote: {:#}", error);
                                         self.asb_quote_status.remove(&peer);
+                                        self.quote_request_completed(peer);
                                     }
                                 }
                                 RequestResponseEvent::InboundFailure { peer, error, .. } => {
@@ -336,6 +403,7 @@ mod tests {
                     price: Default::default(),
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
+                    quote_expiry: None,
                 }),
             },
         ];
@@ -349,6 +417,7 @@ mod tests {
                     price: Default::default(),
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
+                    quote_expiry: None,
                 })
             },
             Seller {
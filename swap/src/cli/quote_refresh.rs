@@ -0,0 +1,337 @@
+use crate::asb::Rate;
+use crate::bitcoin::Amount;
+use crate::cli::RateBounds;
+use crate::network::quote::{BidQuote, DEFAULT_CLOCK_SKEW_TOLERANCE, QUOTE_VALIDITY};
+use anyhow::{bail, Result};
+use rust_decimal::Decimal;
+use std::cmp::min;
+use std::future::Future;
+use std::time::Duration;
+
+/// When the CLI requests a new quote before starting a swap, and how far the
+/// price may move until the user has to confirm it.
+///
+/// Waiting for a deposit can take long enough for the first quote to be
+/// outdated, the amount of XMR is only fixed by the seller once the swap is
+/// set up.
+///
+/// A quote is also refreshed once the expiry set by the seller passed by more
+/// than the clock skew tolerance. The expiry is a Unix timestamp rather than
+/// a block height: the price follows the exchange rate by the second, while a
+/// Bitcoin block takes ten minutes on average and much longer at times, far
+/// longer than the validity of a quote.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuoteRefresh {
+    /// The age of the first quote after which a new one is requested.
+    pub after: Duration,
+    /// The relative change of the price that is accepted without
+    /// confirmation, e.g. 0.01 for 1%.
+    pub tolerance: Decimal,
+    /// Accept a price change beyond the tolerance without asking, e.g.
+    /// because the CLI runs unattended.
+    pub assume_yes: bool,
+    /// How long after its expiry according to our clock a quote is still
+    /// considered current, in case the clock of the seller is behind ours.
+    pub clock_skew_tolerance: Duration,
+}
+
+impl Default for QuoteRefresh {
+    fn default() -> Self {
+        Self {
+            after: QUOTE_VALIDITY,
+            tolerance: Decimal::new(1, 2),
+            assume_yes: false,
+            clock_skew_tolerance: DEFAULT_CLOCK_SKEW_TOLERANCE,
+        }
+    }
+}
+
+impl QuoteRefresh {
+    pub fn is_due(&self, quote_age: Duration) -> bool {
+        quote_age > self.after
+    }
+
+    /// Whether the price moved by more than the tolerance in either
+    /// direction.
+    pub fn needs_confirmation(&self, previous: Amount, refreshed: Amount) -> bool {
+        if previous == Amount::ZERO {
+            return refreshed != Amount::ZERO;
+        }
+
+        let previous_sats = Decimal::from(previous.as_sat());
+        let change = (Decimal::from(refreshed.as_sat()) - previous_sats).abs() / previous_sats;
+
+        change > self.tolerance
+    }
+}
+
+/// Fails if the `quote` expired at the Unix timestamp `now` by more than the
+/// clock skew tolerance, before a swap is started or resumed at its price.
+pub fn check_quote_expiry(
+    quote: &BidQuote,
+    now: u64,
+    clock_skew_tolerance: Duration,
+) -> Result<()> {
+    if quote.is_expired(now, clock_skew_tolerance) {
+        bail!(
+            "The quote of the seller expired at {} but it is {} now, more than the clock skew tolerance of {} seconds later. Check that the system clock is correct or pass a larger --clock-skew-tolerance",
+            quote.quote_expiry.unwrap_or_default(),
+            now,
+            clock_skew_tolerance.as_secs()
+        );
+    }
+
+    Ok(())
+}
+
+/// Requests a new quote if the one the `btc_amount` was determined with is
+/// older than allowed or expired at the Unix timestamp `now`, and returns the
+/// amount to swap at the new price.
+///
+/// The new price has to be within the `rate_bounds` and the amount within the
+/// new quantities of the seller. Amounts above the new maximum are lowered to
+/// it. If the price moved by more than the tolerance, `confirm` is asked with
+/// the previous and the new price whether to continue, unless the refresh
+/// assumes yes. A new quote that already expired fails the refresh.
+pub async fn refresh_quote(
+    refresh: QuoteRefresh,
+    previous_quote: BidQuote,
+    quote_age: Duration,
+    now: u64,
+    btc_amount: Amount,
+    rate_bounds: RateBounds,
+    request_quote: impl Future<Output = Result<BidQuote>>,
+    confirm: impl FnOnce(Amount, Amount) -> Result<bool>,
+) -> Result<Amount> {
+    if !refresh.is_due(quote_age) && !previous_quote.is_expired(now, refresh.clock_skew_tolerance) {
+        return Ok(btc_amount);
+    }
+
+    tracing::debug!(quote_age_secs = %quote_age.as_secs(), "Requesting a new quote");
+    let quote = request_quote.await?;
+
+    check_quote_expiry(&quote, now, refresh.clock_skew_tolerance)?;
+    rate_bounds.check(quote.price)?;
+
+    if btc_amount < quote.min_quantity {
+        bail!(
+            "Swap amount {} is below the minimum quantity of {} of the new quote",
+            btc_amount,
+            quote.min_quantity
+        );
+    }
+    let btc_amount = min(btc_amount, quote.max_quantity);
+    let xmr_amount = Rate::new(quote.price, Decimal::from(0u64)).sell_quote(btc_amount)?;
+
+    tracing::info!(
+        previous_price = %previous_quote.price,
+        price = %quote.price,
+        %btc_amount,
+        %xmr_amount,
+        "Received new quote",
+    );
+
+    if refresh.needs_confirmation(previous_quote.price, quote.price) {
+        if refresh.assume_yes {
+            tracing::warn!(
+                previous_price = %previous_quote.price,
+                price = %quote.price,
+                "The price changed by more than the tolerance, starting the swap without confirmation"
+            );
+        } else if !confirm(previous_quote.price, quote.price)? {
+            bail!("Swap aborted because the price changed");
+        }
+    }
+
+    Ok(btc_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_000;
+
+    fn quote(price: u64, min_quantity: u64, max_quantity: u64) -> BidQuote {
+        BidQuote {
+            price: Amount::from_sat(price),
+            min_quantity: Amount::from_sat(min_quantity),
+            max_quantity: Amount::from_sat(max_quantity),
+            quote_expiry: None,
+        }
+    }
+
+    #[test]
+    fn price_change_within_tolerance_needs_no_confirmation() {
+        let refresh = QuoteRefresh::default();
+        let price = Amount::from_sat(100_000);
+
+        assert!(!refresh.needs_confirmation(price, Amount::from_sat(101_000)));
+        assert!(!refresh.needs_confirmation(price, Amount::from_sat(99_000)));
+        assert!(refresh.needs_confirmation(price, Amount::from_sat(101_001)));
+        assert!(refresh.needs_confirmation(price, Amount::from_sat(98_999)));
+    }
+
+    #[tokio::test]
+    async fn recent_quote_is_not_refreshed() {
+        let amount = refresh_quote(
+            QuoteRefresh::default(),
+            quote(100_000, 0, 1_000_000),
+            Duration::from_secs(10),
+            NOW,
+            Amount::from_sat(500_000),
+            RateBounds::default(),
+            async { Err(anyhow::anyhow!("quote must not be requested")) },
+            |_, _| panic!("must not ask for confirmation"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(amount, Amount::from_sat(500_000));
+    }
+
+    #[tokio::test]
+    async fn amount_above_new_maximum_is_lowered() {
+        let amount = refresh_quote(
+            QuoteRefresh::default(),
+            quote(100_000, 0, 1_000_000),
+            Duration::from_secs(120),
+            NOW,
+            Amount::from_sat(500_000),
+            RateBounds::default(),
+            async { Ok(quote(100_500, 0, 400_000)) },
+            |_, _| panic!("must not ask for confirmation"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(amount, Amount::from_sat(400_000));
+    }
+
+    #[tokio::test]
+    async fn declined_price_change_aborts_the_swap() {
+        let result = refresh_quote(
+            QuoteRefresh::default(),
+            quote(100_000, 0, 1_000_000),
+            Duration::from_secs(120),
+            NOW,
+            Amount::from_sat(500_000),
+            RateBounds::default(),
+            async { Ok(quote(110_000, 0, 1_000_000)) },
+            |previous, refreshed| {
+                assert_eq!(previous, Amount::from_sat(100_000));
+                assert_eq!(refreshed, Amount::from_sat(110_000));
+                Ok(false)
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn price_change_is_accepted_without_asking_if_assuming_yes() {
+        let amount = refresh_quote(
+            QuoteRefresh {
+                assume_yes: true,
+                ..QuoteRefresh::default()
+            },
+            quote(100_000, 0, 1_000_000),
+            Duration::from_secs(120),
+            NOW,
+            Amount::from_sat(500_000),
+            RateBounds::default(),
+            async { Ok(quote(110_000, 0, 1_000_000)) },
+            |_, _| panic!("must not ask for confirmation"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(amount, Amount::from_sat(500_000));
+    }
+
+    #[tokio::test]
+    async fn amount_below_new_minimum_fails() {
+        let result = refresh_quote(
+            QuoteRefresh::default(),
+            quote(100_000, 0, 1_000_000),
+            Duration::from_secs(120),
+            NOW,
+            Amount::from_sat(500_000),
+            RateBounds::default(),
+            async { Ok(quote(100_000, 600_000, 1_000_000)) },
+            |_, _| Ok(true),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn recent_but_expired_quote_is_refreshed() {
+        let expired = BidQuote {
+            quote_expiry: Some(NOW - 121),
+            ..quote(100_000, 0, 1_000_000)
+        };
+
+        let amount = refresh_quote(
+            QuoteRefresh::default(),
+            expired,
+            Duration::from_secs(10),
+            NOW,
+            Amount::from_sat(500_000),
+            RateBounds::default(),
+            async { Ok(quote(100_000, 0, 400_000)) },
+            |_, _| panic!("must not ask for confirmation"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(amount, Amount::from_sat(400_000));
+    }
+
+    #[tokio::test]
+    async fn quote_expired_within_clock_skew_tolerance_is_kept() {
+        let expired = BidQuote {
+            quote_expiry: Some(NOW - 120),
+            ..quote(100_000, 0, 1_000_000)
+        };
+
+        let amount = refresh_quote(
+            QuoteRefresh::default(),
+            expired,
+            Duration::from_secs(10),
+            NOW,
+            Amount::from_sat(500_000),
+            RateBounds::default(),
+            async { Err(anyhow::anyhow!("quote must not be requested")) },
+            |_, _| panic!("must not ask for confirmation"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(amount, Amount::from_sat(500_000));
+    }
+
+    #[tokio::test]
+    async fn new_quote_expired_beyond_clock_skew_tolerance_fails() {
+        let result = refresh_quote(
+            QuoteRefresh::default(),
+            quote(100_000, 0, 1_000_000),
+            Duration::from_secs(120),
+            NOW,
+            Amount::from_sat(500_000),
+            RateBounds::default(),
+            async {
+                Ok(BidQuote {
+                    quote_expiry: Some(NOW - 121),
+                    ..quote(100_000, 0, 1_000_000)
+                })
+            },
+            |_, _| Ok(true),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::bitcoin::Amount;
+use crate::monero;
+use std::convert::TryFrom;
+
+/// The range of prices for 1 XMR at which the CLI is willing to swap.
+///
+/// Both bounds are optional, the default accepts any price.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RateBounds {
+    pub min: Option<Amount>,
+    pub max: Option<Amount>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum RateOutOfBounds {
+    #[error("Seller quoted {quoted} per XMR which is below the minimum rate of {min} per XMR")]
+    BelowMin { quoted: Amount, min: Amount },
+    #[error("Seller quoted {quoted} per XMR which is above the maximum rate of {max} per XMR")]
+    AboveMax { quoted: Amount, max: Amount },
+}
+
+impl RateBounds {
+    pub fn check(&self, quoted: Amount) -> Result<(), RateOutOfBounds> {
+        match (self.min, self.max) {
+            (Some(min), _) if quoted < min => Err(RateOutOfBounds::BelowMin { quoted, min }),
+            (_, Some(max)) if quoted > max => Err(RateOutOfBounds::AboveMax { quoted, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the rate of a swap of `btc` for `xmr`, which differs from the
+    /// quoted one if the seller changed its price after the quote.
+    pub fn check_amounts(&self, btc: Amount, xmr: monero::Amount) -> Result<(), RateOutOfBounds> {
+        self.check(rate(btc, xmr))
+    }
+}
+
+/// The price of 1 XMR in a swap of `btc` for `xmr`, rounded down.
+fn rate(btc: Amount, xmr: monero::Amount) -> Amount {
+    let sats = u128::from(btc.as_sat()) * u128::from(monero::Amount::ONE_XMR.as_piconero());
+    let rate = sats
+        .checked_div(u128::from(xmr.as_piconero()))
+        .unwrap_or(u128::MAX);
+
+    Amount::from_sat(u64::try_from(rate).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbounded_accepts_any_rate() {
+        let bounds = RateBounds::default();
+
+        assert_eq!(bounds.check(Amount::ZERO), Ok(()));
+        assert_eq!(bounds.check(Amount::from_sat(u64::MAX)), Ok(()));
+    }
+
+    #[test]
+    fn accepts_rate_within_bounds() {
+        let bounds = RateBounds {
+            min: Some(Amount::from_sat(1_000)),
+            max: Some(Amount::from_sat(2_000)),
+        };
+
+        assert_eq!(bounds.check(Amount::from_sat(1_000)), Ok(()));
+        assert_eq!(bounds.check(Amount::from_sat(1_500)), Ok(()));
+        assert_eq!(bounds.check(Amount::from_sat(2_000)), Ok(()));
+    }
+
+    #[test]
+    fn rejects_rate_outside_bounds() {
+        let min = Amount::from_sat(1_000);
+        let max = Amount::from_sat(2_000);
+        let bounds = RateBounds {
+            min: Some(min),
+            max: Some(max),
+        };
+
+        let below = Amount::from_sat(999);
+        let above = Amount::from_sat(2_001);
+
+        assert_eq!(
+            bounds.check(below),
+            Err(RateOutOfBounds::BelowMin { quoted: below, min })
+        );
+        assert_eq!(
+            bounds.check(above),
+            Err(RateOutOfBounds::AboveMax { quoted: above, max })
+        );
+    }
+
+    #[test]
+    fn checks_rate_implied_by_amounts() {
+        let bounds = RateBounds {
+            min: Some(Amount::from_sat(1_000)),
+            max: Some(Amount::from_sat(2_000)),
+        };
+
+        // 0.5 XMR for 750 sats is a rate of 1_500 sats per XMR
+        let half_xmr = monero::Amount::from_piconero(500_000_000_000);
+
+        assert_eq!(
+            bounds.check_amounts(Amount::from_sat(750), half_xmr),
+            Ok(())
+        );
+        assert_eq!(
+            bounds.check_amounts(Amount::from_sat(1_500), half_xmr),
+            Err(RateOutOfBounds::AboveMax {
+                quoted: Amount::from_sat(3_000),
+                max: Amount::from_sat(2_000)
+            })
+        );
+        assert!(bounds
+            .check_amounts(Amount::from_sat(750), monero::Amount::ZERO)
+            .is_err());
+    }
+}
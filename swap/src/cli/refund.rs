@@ -1,19 +1,155 @@
-use crate::bitcoin::Wallet;
-use crate::protocol::bob::BobState;
+use crate::bitcoin::{approximate_duration, CancelReorgPolicy, ExpiredTimelocks, Wallet};
+use crate::protocol::bob::{BobState, State6};
 use crate::protocol::Database;
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use bdk::FeeRate;
 use std::convert::TryInto;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
+/// Overrides the fee rate at which the refund should be confirmed.
+///
+/// The cancel and refund transactions are signed with fixed fees during swap
+/// setup. Overriding the fee rate is done by spending the refund output with a
+/// child-pays-for-parent transaction, which is only possible if the refund
+/// address belongs to the internal wallet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefundFeeRate {
+    /// The fee rate in sat/vB.
+    SatPerVb(f32),
+    /// Estimate the fee rate such that the refund is confirmed within the given
+    /// number of blocks.
+    TargetBlock(usize),
+}
+
 pub async fn refund(
     swap_id: Uuid,
     bitcoin_wallet: Arc<Wallet>,
     db: Arc<dyn Database>,
+    fee_rate: Option<RefundFeeRate>,
+    cancel_reorg_policy: CancelReorgPolicy,
 ) -> Result<BobState> {
     let state = db.get_state(swap_id).await?.try_into()?;
 
-    let state6 = match state {
+    let state6 = match refundable(&state) {
+        Some(state6) => state6,
+        None => bail!(
+            "Cannot refund swap {} because it is in state {} which is not refundable.",
+            swap_id,
+            state
+        ),
+    };
+
+    let fee_rate = match fee_rate {
+        Some(RefundFeeRate::SatPerVb(sat_per_vb)) => Some(FeeRate::from_sat_per_vb(sat_per_vb)),
+        Some(RefundFeeRate::TargetBlock(target_block)) => {
+            Some(bitcoin_wallet.estimate_fee_rate(target_block).await?)
+        }
+        None => None,
+    };
+
+    let subscription = state6
+        .publish_refund_btc(bitcoin_wallet.as_ref(), cancel_reorg_policy)
+        .await?;
+
+    if let Some(fee_rate) = fee_rate {
+        if let Err(error) = state6
+            .bump_refund_fee(bitcoin_wallet.as_ref(), fee_rate)
+            .await
+        {
+            tracing::warn!(
+                %swap_id,
+                "Failed to bump the fee of the refund transaction, it is mined at its original fee rate: {:#}",
+                error
+            );
+        }
+    }
+
+    subscription.wait_until_final().await?;
+
+    let state = BobState::BtcRefunded(state6);
+    db.insert_latest_state(swap_id, state.clone().into())
+        .await?;
+
+    Ok(state)
+}
+
+/// Recovers the Bitcoin of a swap that failed after the Bitcoin was locked by
+/// waiting for the cancel timelock to expire, then cancelling and refunding
+/// the swap.
+///
+/// Returns `None` without touching the swap if refunding is not the recovery
+/// for its state, e.g. because it finished or the Monero can be redeemed. This
+/// includes [`BobState::EncSigSent`], the seller can redeem the Bitcoin at
+/// any time once it has the encrypted signature and cancelling would race
+/// that, resuming the swap claims the Monero instead.
+pub async fn refund_on_failure(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Arc<dyn Database>,
+    avg_block_time: Duration,
+    cancel_reorg_policy: CancelReorgPolicy,
+) -> Result<Option<BobState>> {
+    let state = db.get_state(swap_id).await?.try_into()?;
+
+    let state6 = match refundable(&state) {
+        Some(_) if matches!(state, BobState::EncSigSent(_)) => return Ok(None),
+        Some(state6) => state6,
+        None => return Ok(None),
+    };
+
+    match state6.expired_timelock(bitcoin_wallet.as_ref()).await? {
+        ExpiredTimelocks::None => {
+            let blocks_left = state6
+                .blocks_until_cancel_timelock_expires(bitcoin_wallet.as_ref())
+                .await?;
+
+            tracing::info!(
+                %swap_id,
+                %blocks_left,
+                "The swap failed after the Bitcoin was locked. The Bitcoin will be refunded once the cancel timelock expires in {} blocks ({}), keep this process running until then. \
+                 If you stop it, run `swap cancel` and `swap refund` for this swap after that time",
+                blocks_left,
+                approximate_duration(blocks_left, avg_block_time),
+            );
+
+            state6
+                .wait_for_cancel_timelock(bitcoin_wallet.as_ref())
+                .await?;
+        }
+        ExpiredTimelocks::Cancel => {}
+        ExpiredTimelocks::Punish => bail!(
+            "Cannot refund swap {} because the punish timelock expired, the seller can take the Bitcoin",
+            swap_id
+        ),
+    }
+
+    if state6
+        .check_for_tx_cancel(bitcoin_wallet.as_ref())
+        .await
+        .is_err()
+    {
+        let txid = state6
+            .submit_tx_cancel(bitcoin_wallet.as_ref())
+            .await
+            .context("Failed to cancel the swap, if the seller redeemed the Bitcoin resume the swap to redeem the Monero")?;
+
+        tracing::info!(%swap_id, %txid, "Cancelled the swap");
+    }
+
+    db.insert_latest_state(swap_id, BobState::BtcCancelled(state6).into())
+        .await?;
+
+    refund(swap_id, bitcoin_wallet, db, None, cancel_reorg_policy)
+        .await
+        .map(Some)
+}
+
+/// The state to cancel and refund the swap from, `None` if the swap cannot be
+/// refunded in `state`.
+fn refundable(state: &BobState) -> Option<State6> {
+    let state6 = match state.clone() {
         BobState::BtcLocked { state3, .. } => state3.cancel(),
         BobState::XmrLockProofReceived { state, .. } => state.cancel(),
         BobState::XmrLocked(state4) => state4.cancel(),
@@ -26,18 +162,8 @@ pub async fn refund(
         | BobState::BtcRefunded(_)
         | BobState::XmrRedeemed { .. }
         | BobState::BtcPunished { .. }
-        | BobState::SafelyAborted => bail!(
-            "Cannot refund swap {} because it is in state {} which is not refundable.",
-            swap_id,
-            state
-        ),
+        | BobState::SafelyAborted => return None,
     };
 
-    state6.publish_refund_btc(bitcoin_wallet.as_ref()).await?;
-
-    let state = BobState::BtcRefunded(state6);
-    db.insert_latest_state(swap_id, state.clone().into())
-        .await?;
-
-    Ok(state)
+    Some(state6)
 }
@@ -0,0 +1,77 @@
+use crate::bitcoin::Wallet;
+use crate::libp2p_ext::MultiAddrExt;
+use crate::protocol::bob::BobState;
+use crate::protocol::Database;
+use anyhow::{bail, Context, Result};
+use libp2p::Multiaddr;
+use std::convert::TryInto;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Hands a swap whose setup did not lead to locking any Bitcoin to another
+/// seller, resuming it then starts the swap setup with that seller.
+///
+/// Refuses unless the Bitcoin lock transaction was certainly not published,
+/// i.e. it was never seen and its inputs are still unspent. Otherwise the
+/// swap can only continue with the seller it was set up with.
+pub async fn replace_seller(
+    swap_id: Uuid,
+    seller: Multiaddr,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Arc<dyn Database>,
+) -> Result<BobState> {
+    let peer_id = seller
+        .extract_peer_id()
+        .context("Seller address must contain peer ID")?;
+
+    if db.get_peer_id(swap_id).await? == peer_id {
+        bail!(
+            "Swap {} is already handed to seller {}, resume it to retry the swap setup",
+            swap_id,
+            peer_id
+        );
+    }
+
+    let state: BobState = db.get_state(swap_id).await?.try_into()?;
+    let reset = match &state {
+        BobState::Started { .. } => None,
+        BobState::SwapSetupCompleted(state2) => {
+            if !state2.lock_is_unpublished(&bitcoin_wallet).await? {
+                bail!(
+                    "Cannot replace the seller of swap {} because its Bitcoin lock transaction {} was published or its inputs were spent",
+                    swap_id,
+                    state2.tx_lock().txid()
+                );
+            }
+
+            Some(BobState::Started {
+                btc_amount: state2.tx_lock().lock_amount(),
+                change_address: state2.refund_address().clone(),
+            })
+        }
+        state => bail!(
+            "Cannot replace the seller of swap {} in state {}, only swaps that did not lock their Bitcoin can be handed to another seller",
+            swap_id,
+            state
+        ),
+    };
+
+    db.update_peer_id(swap_id, peer_id).await?;
+    db.insert_address(peer_id, seller).await?;
+
+    // The setup with the previous seller is discarded, the new seller sets up
+    // the swap with its own keys.
+    let state = match reset {
+        Some(reset) => {
+            db.insert_latest_state(swap_id, reset.clone().into())
+                .await?;
+            db.release_utxos(swap_id).await?;
+            reset
+        }
+        None => state,
+    };
+
+    tracing::info!(%swap_id, %peer_id, "Replaced the seller of the swap");
+
+    Ok(state)
+}
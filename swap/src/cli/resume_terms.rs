@@ -0,0 +1,171 @@
+//! Checks whether the terms a swap was set up with still match the quote of
+//! the seller when it is resumed.
+//!
+//! Which terms apply depends on the state the swap is resumed from:
+//!
+//! - [`BobState::Started`]: nothing was negotiated yet, the swap is set up at
+//!   the current price once it runs. The amount only has to be within the
+//!   current limits of the seller.
+//! - [`BobState::SwapSetupCompleted`]: the amounts were negotiated but no
+//!   Bitcoin is locked. The negotiated price must not differ from the current
+//!   one by more than the tolerance of [`QuoteRefresh`], otherwise the swap
+//!   would go ahead on stale terms.
+//! - Every later state: the Bitcoin is locked and the negotiated terms are
+//!   binding, whatever the seller quotes now.
+
+use crate::bitcoin;
+use crate::cli::QuoteRefresh;
+use crate::monero;
+use crate::network::quote::BidQuote;
+use crate::protocol::bob::BobState;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+/// The terms of a resumed swap that no longer match the quote of the seller.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+pub enum StaleTerms {
+    #[error("The swap amount of {amount} is no longer within the limits of the seller of {min_quantity} to {max_quantity}")]
+    AmountOutOfLimits {
+        amount: bitcoin::Amount,
+        min_quantity: bitcoin::Amount,
+        max_quantity: bitcoin::Amount,
+    },
+    #[error("The price of 1 XMR changed from the negotiated {negotiated} to {current} since the swap was set up")]
+    PriceChanged {
+        negotiated: bitcoin::Amount,
+        current: bitcoin::Amount,
+    },
+}
+
+/// Whether resuming from `state` depends on the current quote of the seller,
+/// which is the case as long as no Bitcoin is locked.
+pub fn resume_needs_quote(state: &BobState) -> bool {
+    matches!(
+        state,
+        BobState::Started { .. } | BobState::SwapSetupCompleted(..)
+    )
+}
+
+/// Checks the terms of the swap in `state` against the current `quote` of
+/// the seller, see the [module documentation](self) for the rules per state.
+pub fn check_resumed_terms(
+    state: &BobState,
+    quote: &BidQuote,
+    refresh: QuoteRefresh,
+) -> Result<(), StaleTerms> {
+    match state {
+        BobState::Started { btc_amount, .. } => check_limits(*btc_amount, quote),
+        BobState::SwapSetupCompleted(state2) => {
+            check_negotiated_terms(state2.tx_lock().lock_amount(), state2.xmr(), quote, refresh)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_limits(amount: bitcoin::Amount, quote: &BidQuote) -> Result<(), StaleTerms> {
+    if amount < quote.min_quantity || amount > quote.max_quantity {
+        return Err(StaleTerms::AmountOutOfLimits {
+            amount,
+            min_quantity: quote.min_quantity,
+            max_quantity: quote.max_quantity,
+        });
+    }
+
+    Ok(())
+}
+
+fn check_negotiated_terms(
+    btc: bitcoin::Amount,
+    xmr: monero::Amount,
+    quote: &BidQuote,
+    refresh: QuoteRefresh,
+) -> Result<(), StaleTerms> {
+    check_limits(btc, quote)?;
+
+    let negotiated = negotiated_price(btc, xmr);
+    if refresh.needs_confirmation(negotiated, quote.price) {
+        return Err(StaleTerms::PriceChanged {
+            negotiated,
+            current: quote.price,
+        });
+    }
+
+    Ok(())
+}
+
+/// The price of 1 XMR that `btc` was traded for `xmr` at.
+fn negotiated_price(btc: bitcoin::Amount, xmr: monero::Amount) -> bitcoin::Amount {
+    if xmr == monero::Amount::ZERO {
+        return bitcoin::Amount::ZERO;
+    }
+
+    let sats = Decimal::from(btc.as_sat()) * Decimal::from(monero::Amount::ONE_XMR.as_piconero())
+        / xmr.as_piconero_decimal();
+
+    bitcoin::Amount::from_sat(sats.round().to_u64().unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(price: u64, min_quantity: u64, max_quantity: u64) -> BidQuote {
+        BidQuote {
+            price: bitcoin::Amount::from_sat(price),
+            min_quantity: bitcoin::Amount::from_sat(min_quantity),
+            max_quantity: bitcoin::Amount::from_sat(max_quantity),
+            quote_expiry: None,
+        }
+    }
+
+    #[test]
+    fn negotiated_terms_within_tolerance_are_kept() {
+        // 0.005 BTC for 1 XMR
+        let btc = bitcoin::Amount::from_sat(500_000);
+        let xmr = monero::Amount::ONE_XMR;
+
+        assert_eq!(
+            check_negotiated_terms(
+                btc,
+                xmr,
+                &quote(504_000, 0, 1_000_000),
+                QuoteRefresh::default()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn negotiated_terms_at_changed_price_are_stale() {
+        let btc = bitcoin::Amount::from_sat(500_000);
+        let xmr = monero::Amount::ONE_XMR;
+
+        assert_eq!(
+            check_negotiated_terms(
+                btc,
+                xmr,
+                &quote(550_000, 0, 1_000_000),
+                QuoteRefresh::default()
+            ),
+            Err(StaleTerms::PriceChanged {
+                negotiated: bitcoin::Amount::from_sat(500_000),
+                current: bitcoin::Amount::from_sat(550_000),
+            })
+        );
+    }
+
+    #[test]
+    fn amount_outside_current_limits_is_stale() {
+        assert_eq!(
+            check_limits(
+                bitcoin::Amount::from_sat(500_000),
+                &quote(500_000, 0, 400_000)
+            ),
+            Err(StaleTerms::AmountOutOfLimits {
+                amount: bitcoin::Amount::from_sat(500_000),
+                min_quantity: bitcoin::Amount::ZERO,
+                max_quantity: bitcoin::Amount::from_sat(400_000),
+            })
+        );
+    }
+}
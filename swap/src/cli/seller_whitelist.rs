@@ -0,0 +1,167 @@
+use crate::libp2p_ext::MultiAddrExt;
+use anyhow::{Context, Result};
+use libp2p::core::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+
+/// The seller addresses the user explicitly trusts.
+///
+/// If the whitelist is empty every address is trusted. Otherwise only the
+/// whitelisted addresses of a seller are dialed, which protects against a
+/// MITM advertising a different address for a known peer id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SellerWhitelist {
+    entries: Vec<(PeerId, Multiaddr)>,
+}
+
+impl SellerWhitelist {
+    /// Every address has to include the peer id of the seller, i.e. end in
+    /// `/p2p/<peer-id>`.
+    pub fn new(addresses: Vec<Multiaddr>) -> Result<Self> {
+        let entries = addresses
+            .into_iter()
+            .map(|address| {
+                let peer_id = address.extract_peer_id().with_context(|| {
+                    format!("Whitelisted address {} must contain peer ID", address)
+                })?;
+
+                Ok((peer_id, without_peer_id(address)))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { entries })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn is_trusted(&self, peer_id: PeerId, address: &Multiaddr) -> bool {
+        if self.entries.is_empty() {
+            return true;
+        }
+
+        let address = without_peer_id(address.clone());
+
+        self.entries
+            .iter()
+            .any(|(whitelisted_peer_id, whitelisted_address)| {
+                *whitelisted_peer_id == peer_id && *whitelisted_address == address
+            })
+    }
+
+    /// Returns the addresses of `peer_id` that may be dialed.
+    ///
+    /// These are the `known` addresses if the whitelist is empty and the
+    /// whitelisted addresses of the peer otherwise, known addresses that are
+    /// not whitelisted are refused.
+    pub fn dialable_addresses(&self, peer_id: PeerId, known: Vec<Multiaddr>) -> Vec<Multiaddr> {
+        if self.entries.is_empty() {
+            return known;
+        }
+
+        for address in known
+            .iter()
+            .filter(|address| !self.is_trusted(peer_id, address))
+        {
+            tracing::warn!(
+                %peer_id,
+                %address,
+                "Refusing to dial seller address that is not whitelisted"
+            );
+        }
+
+        self.entries
+            .iter()
+            .filter(|(whitelisted_peer_id, _)| *whitelisted_peer_id == peer_id)
+            .map(|(_, address)| address.clone())
+            .collect()
+    }
+}
+
+/// The seller is one of our own nodes, swapping with it would deadlock.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("You cannot swap with yourself, the seller {seller} is your own node")]
+pub struct SwapWithSelf {
+    pub seller: PeerId,
+}
+
+/// Refuses a `seller` that has the `local` peer id of the CLI or one of the
+/// `own` peer ids the user configured, e.g. of their own ASB.
+pub fn ensure_not_self(seller: PeerId, local: PeerId, own: &[PeerId]) -> Result<(), SwapWithSelf> {
+    if seller == local || own.contains(&seller) {
+        return Err(SwapWithSelf { seller });
+    }
+
+    Ok(())
+}
+
+fn without_peer_id(mut address: Multiaddr) -> Multiaddr {
+    if let Some(Protocol::P2p(_)) = address.iter().last() {
+        address.pop();
+    }
+
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_whitelist_trusts_every_address() {
+        let whitelist = SellerWhitelist::default();
+        let peer_id = PeerId::random();
+        let known = vec!["/ip4/127.0.0.1/tcp/9939".parse().unwrap()];
+
+        assert!(whitelist.is_trusted(peer_id, &known[0]));
+        assert_eq!(whitelist.dialable_addresses(peer_id, known.clone()), known);
+    }
+
+    #[test]
+    fn only_whitelisted_addresses_of_the_peer_are_dialable() {
+        let peer_id = PeerId::random();
+        let other_peer_id = PeerId::random();
+        let trusted: Multiaddr = "/dns4/seller.example/tcp/9939".parse().unwrap();
+        let trusted_with_peer_id = trusted.clone().with(Protocol::P2p(*peer_id.as_ref()));
+        let untrusted: Multiaddr = "/ip4/10.0.0.1/tcp/9939".parse().unwrap();
+
+        let whitelist = SellerWhitelist::new(vec![trusted_with_peer_id.clone()]).unwrap();
+
+        assert!(whitelist.is_trusted(peer_id, &trusted));
+        assert!(whitelist.is_trusted(peer_id, &trusted_with_peer_id));
+        assert!(!whitelist.is_trusted(peer_id, &untrusted));
+        assert!(!whitelist.is_trusted(other_peer_id, &trusted));
+
+        assert_eq!(
+            whitelist.dialable_addresses(peer_id, vec![untrusted]),
+            vec![trusted]
+        );
+        assert!(whitelist
+            .dialable_addresses(other_peer_id, vec![])
+            .is_empty());
+    }
+
+    #[test]
+    fn swapping_with_own_peer_id_is_refused() {
+        let local = PeerId::random();
+        let own = PeerId::random();
+        let seller = PeerId::random();
+
+        assert_eq!(
+            ensure_not_self(local, local, &[]),
+            Err(SwapWithSelf { seller: local })
+        );
+        assert_eq!(
+            ensure_not_self(own, local, &[own]),
+            Err(SwapWithSelf { seller: own })
+        );
+        assert_eq!(ensure_not_self(seller, local, &[own]), Ok(()));
+    }
+
+    #[test]
+    fn whitelisted_address_without_peer_id_is_rejected() {
+        let result = SellerWhitelist::new(vec!["/ip4/127.0.0.1/tcp/9939".parse().unwrap()]);
+
+        assert!(result.is_err());
+    }
+}
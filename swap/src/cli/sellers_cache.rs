@@ -0,0 +1,205 @@
+//! Caches the sellers discovered by `list-sellers` in the data directory.
+//!
+//! Discovering sellers at a rendezvous point and asking each of them for a
+//! quote over Tor takes a while. The result of a discovery is stored per
+//! rendezvous point together with the time it was made, so that listing the
+//! sellers again within the TTL prints the cached result right away.
+
+use crate::cli::list_sellers::{Seller, Status};
+use anyhow::{Context, Result};
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long the sellers of a discovery are listed from the cache if not
+/// configured otherwise.
+pub const DEFAULT_SELLERS_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+const FILE_NAME: &str = "list-sellers-cache.json";
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SellersCache {
+    discoveries: Vec<Discovery>,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Discovery {
+    #[serde_as(as = "DisplayFromStr")]
+    rendezvous_point: Multiaddr,
+    /// The Unix timestamp in seconds at which the sellers were discovered.
+    discovered_at: u64,
+    sellers: Vec<Seller>,
+}
+
+impl SellersCache {
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(FILE_NAME)
+    }
+
+    /// Reads the cache from the data directory.
+    ///
+    /// A missing cache is empty. So is a cache that cannot be read, e.g.
+    /// because it was written by another version, it is replaced on the next
+    /// discovery.
+    pub fn load(data_dir: &Path) -> Self {
+        let path = Self::path(data_dir);
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        match serde_json::from_str(&contents) {
+            Ok(cache) => cache,
+            Err(error) => {
+                tracing::warn!(path = %path.display(), %error, "Ignoring unreadable sellers cache");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        let path = Self::path(data_dir);
+        let contents = serde_json::to_string(self)?;
+
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write sellers cache to {}", path.display()))
+    }
+
+    /// The sellers discovered at the rendezvous point and how long ago, if
+    /// they were discovered less than `ttl` before `now` and none of their
+    /// quotes expired since, by more than `clock_skew_tolerance`.
+    pub fn fresh_sellers(
+        &self,
+        rendezvous_point: &Multiaddr,
+        ttl: Duration,
+        clock_skew_tolerance: Duration,
+        now: u64,
+    ) -> Option<(Vec<Seller>, Duration)> {
+        let discovery = self
+            .discoveries
+            .iter()
+            .find(|discovery| &discovery.rendezvous_point == rendezvous_point)?;
+
+        let age = Duration::from_secs(now.saturating_sub(discovery.discovered_at));
+        if age >= ttl {
+            return None;
+        }
+
+        let quote_expired = discovery.sellers.iter().any(|seller| match seller.status {
+            Status::Online(quote) => quote.is_expired(now, clock_skew_tolerance),
+            Status::Unreachable => false,
+        });
+        if quote_expired {
+            return None;
+        }
+
+        Some((discovery.sellers.clone(), age))
+    }
+
+    /// Replaces the sellers cached for the rendezvous point.
+    pub fn insert(&mut self, rendezvous_point: Multiaddr, sellers: Vec<Seller>, now: u64) {
+        self.discoveries
+            .retain(|discovery| discovery.rendezvous_point != rendezvous_point);
+        self.discoveries.push(Discovery {
+            rendezvous_point,
+            discovered_at: now,
+            sellers,
+        });
+    }
+}
+
+pub fn unix_timestamp() -> Result<u64> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System time is before the Unix epoch")?;
+
+    Ok(timestamp.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::quote::BidQuote;
+    use tempfile::tempdir;
+
+    const RENDEZVOUS_POINT: &str =
+        "/ip4/127.0.0.1/tcp/8888/p2p/12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
+
+    fn sellers(quote_expiry: Option<u64>) -> Vec<Seller> {
+        vec![
+            Seller {
+                status: Status::Online(BidQuote {
+                    price: bitcoin::Amount::from_sat(1337),
+                    min_quantity: bitcoin::Amount::from_sat(42),
+                    max_quantity: bitcoin::Amount::from_sat(9001),
+                    quote_expiry,
+                }),
+                multiaddr: "/ip4/127.0.0.1/tcp/9939".parse().unwrap(),
+            },
+            Seller {
+                status: Status::Unreachable,
+                multiaddr: "/ip4/127.0.0.1/tcp/9940".parse().unwrap(),
+            },
+        ]
+    }
+
+    #[test]
+    fn sellers_are_fresh_until_ttl_passed() {
+        let rendezvous_point: Multiaddr = RENDEZVOUS_POINT.parse().unwrap();
+        let ttl = Duration::from_secs(60);
+        let mut cache = SellersCache::default();
+        cache.insert(rendezvous_point.clone(), sellers(None), 1_000);
+
+        assert_eq!(
+            cache.fresh_sellers(&rendezvous_point, ttl, Duration::ZERO, 1_059),
+            Some((sellers(None), Duration::from_secs(59)))
+        );
+        assert_eq!(
+            cache.fresh_sellers(&rendezvous_point, ttl, Duration::ZERO, 1_060),
+            None
+        );
+        assert_eq!(
+            cache.fresh_sellers(
+                &"/ip4/127.0.0.1/tcp/8889".parse().unwrap(),
+                ttl,
+                Duration::ZERO,
+                1_000
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn sellers_with_expired_quote_are_not_fresh() {
+        let rendezvous_point: Multiaddr = RENDEZVOUS_POINT.parse().unwrap();
+        let mut cache = SellersCache::default();
+        cache.insert(rendezvous_point.clone(), sellers(Some(1_010)), 1_000);
+
+        assert!(cache
+            .fresh_sellers(
+                &rendezvous_point,
+                Duration::from_secs(60),
+                Duration::ZERO,
+                1_011
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn saved_cache_is_loaded_and_unreadable_cache_is_empty() {
+        let data_dir = tempdir().unwrap();
+        let mut cache = SellersCache::default();
+        cache.insert(RENDEZVOUS_POINT.parse().unwrap(), sellers(None), 1_000);
+
+        cache.save(data_dir.path()).unwrap();
+        assert_eq!(SellersCache::load(data_dir.path()), cache);
+
+        fs::write(SellersCache::path(data_dir.path()), "not json").unwrap();
+        assert_eq!(SellersCache::load(data_dir.path()), SellersCache::default());
+    }
+}
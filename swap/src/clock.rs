@@ -0,0 +1,198 @@
+//! The source of time of the swap drivers.
+//!
+//! Timeouts and delays of the swaps are measured with a [`Clock`] instead of
+//! using [`tokio::time`] directly. Production code uses the [`SystemClock`],
+//! tests use a [`MockClock`] that only advances when told to, which makes
+//! time-dependent transitions testable without waiting for them.
+
+use async_trait::async_trait;
+use std::fmt;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// A source of the current time and of delays.
+#[async_trait]
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+    async fn sleep(&self, duration: Duration);
+}
+
+/// Like [`tokio::time::timeout`], but measures the time with `clock`.
+pub async fn timeout<F>(
+    clock: &dyn Clock,
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed>
+where
+    F: Future,
+{
+    tokio::select! {
+        output = future => Ok(output),
+        _ = clock.sleep(duration) => Err(Elapsed(duration)),
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("Timed out after {} s", .0.as_secs())]
+pub struct Elapsed(pub Duration);
+
+/// The time of the operating system as seen by [`tokio::time`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+/// A clock that stands still until it is advanced with
+/// [`MockClock::advance`].
+#[derive(Debug)]
+pub struct MockClock {
+    started: Instant,
+    state: Mutex<MockClockState>,
+}
+
+#[derive(Debug, Default)]
+struct MockClockState {
+    elapsed: Duration,
+    sleeps: Vec<(Duration, oneshot::Sender<()>)>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Mutex::new(MockClockState::default()),
+        }
+    }
+
+    /// Advances the clock by `duration` and wakes up every sleep that elapsed
+    /// by then.
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().expect("mock clock is not poisoned");
+        state.elapsed += duration;
+
+        let elapsed = state.elapsed;
+        let (woken, sleeping) = std::mem::take(&mut state.sleeps)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(wake_at, _)| *wake_at <= elapsed);
+        state.sleeps = sleeping;
+
+        for (_, wake) in woken {
+            let _ = wake.send(());
+        }
+    }
+
+    /// The number of sleeps that did not elapse yet.
+    pub fn pending_sleeps(&self) -> usize {
+        self.state
+            .lock()
+            .expect("mock clock is not poisoned")
+            .sleeps
+            .len()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.started
+            + self
+                .state
+                .lock()
+                .expect("mock clock is not poisoned")
+                .elapsed
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let woken = {
+            let mut state = self.state.lock().expect("mock clock is not poisoned");
+
+            if duration == Duration::ZERO {
+                return;
+            }
+
+            let (wake, woken) = oneshot::channel();
+            let wake_at = state.elapsed + duration;
+            state.sleeps.push((wake_at, wake));
+
+            woken
+        };
+
+        let _ = woken.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn mock_sleep_elapses_once_clock_is_advanced() {
+        let clock = MockClock::new();
+        let sleep = clock.sleep(Duration::from_secs(60));
+        tokio::pin!(sleep);
+
+        assert!((&mut sleep).now_or_never().is_none());
+        clock.advance(Duration::from_secs(59));
+        assert!((&mut sleep).now_or_never().is_none());
+        clock.advance(Duration::from_secs(1));
+        assert!((&mut sleep).now_or_never().is_some());
+
+        assert_eq!(clock.pending_sleeps(), 0);
+    }
+
+    #[tokio::test]
+    async fn timeout_elapses_with_mock_clock() {
+        let clock = MockClock::new();
+        let timeout = timeout(
+            &clock,
+            Duration::from_secs(3600),
+            futures::future::pending::<()>(),
+        );
+        tokio::pin!(timeout);
+
+        assert!((&mut timeout).now_or_never().is_none());
+        clock.advance(Duration::from_secs(3600));
+
+        assert_eq!(
+            timeout.now_or_never(),
+            Some(Err(Elapsed(Duration::from_secs(3600))))
+        );
+    }
+
+    #[tokio::test]
+    async fn timeout_returns_output_of_future_in_time() {
+        let clock = MockClock::new();
+
+        let output = timeout(&clock, Duration::from_secs(1), async { 42 }).await;
+
+        assert_eq!(output, Ok(42));
+    }
+
+    #[test]
+    fn mock_clock_only_moves_when_advanced() {
+        let clock = MockClock::new();
+        let before = clock.now();
+
+        clock.advance(Duration::from_secs(10));
+
+        assert_eq!(clock.now() - before, Duration::from_secs(10));
+    }
+}
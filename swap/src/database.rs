@@ -9,9 +9,13 @@ use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
 
 mod alice;
 mod bob;
+mod impl_try_into_inner_state;
 mod sqlite;
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -67,6 +71,15 @@ struct NotAlice;
 #[error("Not in the role of Bob")]
 struct NotBob;
 
+/// A swap state is not in any of the variants that one of its `try_into_*`
+/// accessors extracts.
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("Expected swap in state {}, but it is in state {actual}", .expected.join(" or "))]
+pub struct UnexpectedState {
+    pub expected: &'static [&'static str],
+    pub actual: String,
+}
+
 impl Swap {
     pub fn try_into_alice(self) -> Result<Alice> {
         match self {
@@ -96,3 +109,27 @@ pub async fn open_db(sqlite_path: impl AsRef<Path>) -> Result<Arc<dyn Database +
         Ok(Arc::new(sqlite))
     }
 }
+
+/// Returns all finished swaps whose latest state was entered more than
+/// `older_than` ago, together with the time it was entered.
+pub async fn prunable_swaps(
+    db: &(dyn Database + Send + Sync),
+    older_than: Duration,
+) -> Result<Vec<(Uuid, State, OffsetDateTime)>> {
+    let cutoff = OffsetDateTime::now_utc() - older_than;
+    let mut prunable = Vec::new();
+
+    for (swap_id, state) in db.all().await? {
+        if !state.swap_finished() {
+            continue;
+        }
+
+        let entered_at = db.get_entered_at(swap_id).await?;
+
+        if entered_at < cutoff {
+            prunable.push((swap_id, state, entered_at));
+        }
+    }
+
+    Ok(prunable)
+}
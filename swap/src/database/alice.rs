@@ -283,6 +283,22 @@ impl From<Alice> for AliceState {
     }
 }
 
+crate::impl_try_into_inner_state!(Alice {
+    try_into_started: Started { state3 } => state3: alice::State3,
+    try_into_btc_lock_transaction_seen: BtcLockTransactionSeen { state3 } => state3: alice::State3,
+    try_into_btc_locked: BtcLocked { state3 } => state3: alice::State3,
+    try_into_xmr_lock_transaction_sent: XmrLockTransactionSent { state3, .. } => state3: alice::State3,
+    try_into_xmr_locked: XmrLocked { state3, .. } => state3: alice::State3,
+    try_into_xmr_lock_transfer_proof_sent: XmrLockTransferProofSent { state3, .. } => state3: alice::State3,
+    try_into_enc_sig_learned: EncSigLearned { state3, .. } => state3: alice::State3,
+    try_into_btc_redeem_transaction_published: BtcRedeemTransactionPublished { state3 } => state3: alice::State3,
+    try_into_cancel_timelock_expired: CancelTimelockExpired { state3, .. } => state3: alice::State3,
+    try_into_btc_cancelled: BtcCancelled { state3, .. } => state3: alice::State3,
+    try_into_btc_punishable: BtcPunishable { state3, .. } => state3: alice::State3,
+    try_into_btc_refunded: BtcRefunded { state3, .. } => state3: alice::State3,
+    try_into_done: Done(end_state) => end_state: AliceEndState,
+});
+
 impl fmt::Display for Alice {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -308,3 +324,29 @@ impl fmt::Display for Alice {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::UnexpectedState;
+
+    #[test]
+    fn accessor_extracts_state_of_its_variant() {
+        let state = Alice::Done(AliceEndState::BtcPunished);
+
+        assert_eq!(state.try_into_done().unwrap(), AliceEndState::BtcPunished);
+    }
+
+    #[test]
+    fn accessor_fails_for_other_variants() {
+        let state = Alice::Done(AliceEndState::BtcRedeemed);
+
+        assert_eq!(
+            state.try_into_btc_punishable().unwrap_err(),
+            UnexpectedState {
+                expected: &["BtcPunishable"],
+                actual: "Done: BtcRedeemed".to_string(),
+            }
+        );
+    }
+}
@@ -133,6 +133,18 @@ impl From<Bob> for BobState {
     }
 }
 
+crate::impl_try_into_inner_state!(Bob {
+    try_into_execution_setup_done: ExecutionSetupDone { state2 } => state2: bob::State2,
+    try_into_btc_locked: BtcLocked { state3, .. } => state3: bob::State3,
+    try_into_xmr_lock_proof_received: XmrLockProofReceived { state, .. } => state: bob::State3,
+    try_into_xmr_locked: XmrLocked { state4 } => state4: bob::State4,
+    try_into_enc_sig_sent: EncSigSent { state4 } => state4: bob::State4,
+    try_into_btc_redeemed: BtcRedeemed(state5) => state5: bob::State5,
+    try_into_cancel_timelock_expired: CancelTimelockExpired(state6) => state6: bob::State6,
+    try_into_btc_cancelled: BtcCancelled(state6) => state6: bob::State6,
+    try_into_done: Done(end_state) => end_state: BobEndState,
+});
+
 impl fmt::Display for Bob {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -151,3 +163,29 @@ impl fmt::Display for Bob {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::UnexpectedState;
+
+    #[test]
+    fn accessor_extracts_state_of_its_variant() {
+        let state = Bob::Done(BobEndState::SafelyAborted);
+
+        assert_eq!(state.try_into_done().unwrap(), BobEndState::SafelyAborted);
+    }
+
+    #[test]
+    fn accessor_fails_for_other_variants() {
+        let state = Bob::Done(BobEndState::SafelyAborted);
+
+        assert_eq!(
+            state.try_into_btc_cancelled().unwrap_err(),
+            UnexpectedState {
+                expected: &["BtcCancelled"],
+                actual: "Done: SafelyAborted".to_string(),
+            }
+        );
+    }
+}
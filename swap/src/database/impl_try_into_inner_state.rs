@@ -0,0 +1,33 @@
+/// Helper macro to generate accessors on a swap state that extract the state
+/// it wraps, e.g. the [`State3`](crate::protocol::alice::State3) of
+/// [`Alice::BtcPunishable`](crate::database::Alice::BtcPunishable).
+///
+/// Every accessor fails with [`UnexpectedState`](crate::database::UnexpectedState)
+/// if the state is in any other variant. Each accessor is declared as
+/// `method: Variant <pattern> => binding: Type`, where the pattern binds the
+/// returned field, for example `try_into_btc_redeemed: BtcRedeemed(state5) =>
+/// state5: bob::State5`. An accessor can accept several variants separated by
+/// `|` as long as all of them bind the returned fields, and return several
+/// fields as a tuple, for example `try_into_xmr_locked: XmrLocked { transfer_proof,
+/// state3, .. } | BtcCancelled { transfer_proof, state3, .. } => (transfer_proof,
+/// state3): (TransferProof, Box<State3>)`.
+#[macro_export]
+macro_rules! impl_try_into_inner_state {
+    ($state:ident { $($method:ident: $($variant:ident $pattern:tt)|+ => $binding:tt: $inner:ty),+ $(,)? }) => {
+        impl $state {
+            $(
+                /// Returns the state wrapped by the variants of this accessor,
+                /// fails for every other variant.
+                pub fn $method(self) -> Result<$inner, $crate::database::UnexpectedState> {
+                    match self {
+                        $($state::$variant $pattern)|+ => Ok($binding),
+                        other => Err($crate::database::UnexpectedState {
+                            expected: &[$(stringify!($variant)),+],
+                            actual: other.to_string(),
+                        }),
+                    }
+                }
+            )+
+        }
+    };
+}
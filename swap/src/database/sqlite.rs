@@ -1,14 +1,17 @@
 use crate::database::Swap;
-use crate::monero::Address;
-use crate::protocol::{Database, State};
-use anyhow::{Context, Result};
+use crate::monero::{Address, LockProof, TransferProof, TxHash};
+use crate::protocol::{Database, State, UtxoReserved};
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use libp2p::{Multiaddr, PeerId};
 use sqlx::sqlite::Sqlite;
 use sqlx::{Pool, SqlitePool};
+use std::convert::TryFrom;
 use std::path::Path;
 use std::str::FromStr;
-use time::OffsetDateTime;
+use time::{format_description, OffsetDateTime};
+use url::Url;
 use uuid::Uuid;
 
 pub struct SqliteDatabase {
@@ -77,6 +80,31 @@ impl Database for SqliteDatabase {
         Ok(peer_id)
     }
 
+    async fn update_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let peer_id = peer_id.to_string();
+
+        let result = sqlx::query!(
+            r#"
+        UPDATE peers
+        SET peer_id = ?
+        WHERE swap_id = ?
+        "#,
+            peer_id,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            bail!("No peer id stored for swap {}", swap_id);
+        }
+
+        Ok(())
+    }
+
     async fn insert_monero_address(&self, swap_id: Uuid, address: Address) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
 
@@ -120,6 +148,265 @@ impl Database for SqliteDatabase {
         Ok(address)
     }
 
+    async fn insert_monero_lock_proof(&self, swap_id: Uuid, proof: LockProof) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let tx_hash = proof.transfer_proof.tx_hash().to_string();
+        let tx_key = proof.transfer_proof.tx_key().to_string();
+        let address = proof.address.to_string();
+        let amount = i64::try_from(proof.amount.as_piconero())?;
+
+        // A proof is only replaced if we had to lock the XMR again because the
+        // state after the first lock was not persisted.
+        sqlx::query!(
+            r#"
+        insert or replace into monero_lock_proofs (
+            swap_id,
+            tx_hash,
+            tx_key,
+            address,
+            amount
+            ) values (?, ?, ?, ?, ?);
+        "#,
+            swap_id,
+            tx_hash,
+            tx_key,
+            address,
+            amount
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_monero_lock_proof(&self, swap_id: Uuid) -> Result<LockProof> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+        SELECT tx_hash, tx_key, address, amount
+        FROM monero_lock_proofs
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_one(&mut conn)
+        .await
+        .with_context(|| format!("No XMR lock proof in database for swap: {}", swap_id))?;
+
+        let transfer_proof = TransferProof::new(TxHash(row.tx_hash), row.tx_key.parse()?);
+
+        Ok(LockProof {
+            transfer_proof,
+            address: row.address.parse()?,
+            amount: monero::Amount::from_piconero(u64::try_from(row.amount)?),
+        })
+    }
+
+    async fn insert_electrum_server(&self, swap_id: Uuid, rpc_url: Url) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let rpc_url = rpc_url.to_string();
+
+        sqlx::query!(
+            r#"
+        insert into electrum_servers (
+            swap_id,
+            rpc_url
+            ) values (?, ?);
+        "#,
+            swap_id,
+            rpc_url
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_electrum_server(&self, swap_id: Uuid) -> Result<Option<Url>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+        SELECT rpc_url
+        FROM electrum_servers
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        row.map(|row| Url::parse(&row.rpc_url).context("Failed to parse pinned Electrum server"))
+            .transpose()
+    }
+
+    async fn insert_fee_strategy(
+        &self,
+        swap_id: Uuid,
+        strategy: bitcoin::FeeStrategy,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let lock_target_block = i64::try_from(strategy.lock)?;
+        let cancel_target_block = i64::try_from(strategy.cancel)?;
+        let refund_target_block = i64::try_from(strategy.refund)?;
+        let redeem_target_block = i64::try_from(strategy.redeem)?;
+
+        sqlx::query!(
+            r#"
+        insert into fee_strategies (
+            swap_id,
+            lock_target_block,
+            cancel_target_block,
+            refund_target_block,
+            redeem_target_block
+            ) values (?, ?, ?, ?, ?);
+        "#,
+            swap_id,
+            lock_target_block,
+            cancel_target_block,
+            refund_target_block,
+            redeem_target_block
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_fee_strategy(&self, swap_id: Uuid) -> Result<Option<bitcoin::FeeStrategy>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+        SELECT lock_target_block, cancel_target_block, refund_target_block, redeem_target_block
+        FROM fee_strategies
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        row.map(|row| {
+            Ok(bitcoin::FeeStrategy {
+                lock: usize::try_from(row.lock_target_block)?,
+                cancel: usize::try_from(row.cancel_target_block)?,
+                refund: usize::try_from(row.refund_target_block)?,
+                redeem: usize::try_from(row.redeem_target_block)?,
+            })
+        })
+        .transpose()
+    }
+
+    async fn reserve_utxos(&self, swap_id: Uuid, utxos: Vec<bitcoin::OutPoint>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let swap_id = swap_id.to_string();
+
+        sqlx::query!(
+            r#"
+        DELETE FROM utxo_reservations
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        for utxo in utxos {
+            let outpoint = utxo.to_string();
+
+            sqlx::query!(
+                r#"
+            insert or ignore into utxo_reservations (
+                outpoint,
+                swap_id
+                ) values (?, ?);
+            "#,
+                outpoint,
+                swap_id
+            )
+            .execute(&mut tx)
+            .await?;
+
+            let row = sqlx::query!(
+                r#"
+            SELECT swap_id
+            FROM utxo_reservations
+            WHERE outpoint = ?
+            "#,
+                outpoint
+            )
+            .fetch_one(&mut tx)
+            .await?;
+
+            if row.swap_id != swap_id {
+                return Err(UtxoReserved {
+                    utxo,
+                    swap_id: Uuid::from_str(&row.swap_id)?,
+                }
+                .into());
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_reserved_utxos(&self) -> Result<Vec<(bitcoin::OutPoint, Uuid)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT outpoint, swap_id
+        FROM utxo_reservations
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok((
+                    bitcoin::OutPoint::from_str(&row.outpoint)?,
+                    Uuid::from_str(&row.swap_id)?,
+                ))
+            })
+            .collect()
+    }
+
+    async fn release_utxos(&self, swap_id: Uuid) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        sqlx::query!(
+            r#"
+        DELETE FROM utxo_reservations
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
     async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
 
@@ -169,6 +456,64 @@ impl Database for SqliteDatabase {
         addresses
     }
 
+    async fn insert_peer_label(&self, peer_id: PeerId, label: String) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = peer_id.to_string();
+
+        sqlx::query!(
+            r#"
+        insert or replace into peer_labels (
+            peer_id,
+            label
+            ) values (?, ?);
+        "#,
+            peer_id,
+            label
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_peer_label(&self, peer_id: PeerId) -> Result<Option<String>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = peer_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+        SELECT label
+        FROM peer_labels
+        WHERE peer_id = ?
+        "#,
+            peer_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(row.map(|row| row.label))
+    }
+
+    async fn all_peer_labels(&self) -> Result<Vec<(PeerId, String)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT peer_id, label
+        FROM peer_labels
+        ORDER BY label
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok((PeerId::from_str(&row.peer_id)?, row.label)))
+            .collect()
+    }
+
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
         let entered_at = OffsetDateTime::now_utc();
@@ -220,6 +565,82 @@ impl Database for SqliteDatabase {
         Ok(swap.into())
     }
 
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<State>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let rows = sqlx::query!(
+            r#"
+           SELECT state
+           FROM swap_states
+           WHERE swap_id = ?
+           ORDER BY id;
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        if rows.is_empty() {
+            bail!("No state in database for swap: {}", swap_id);
+        }
+
+        rows.iter()
+            .map(|row| {
+                let swap: Swap = serde_json::from_str(&row.state)?;
+                Ok(swap.into())
+            })
+            .collect()
+    }
+
+    async fn get_entered_at(&self, swap_id: Uuid) -> Result<OffsetDateTime> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let row = sqlx::query!(
+            r#"
+           SELECT entered_at
+           FROM swap_states
+           WHERE swap_id = ?
+           ORDER BY id desc
+           LIMIT 1;
+
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let row = row
+            .first()
+            .context(format!("No state in database for swap: {}", swap_id))?;
+
+        parse_entered_at(&row.entered_at)
+    }
+
+    /// The time the first state of the swap was saved, `None` if the swap was
+    /// saved by a version that wrote timestamps in a different format.
+    async fn get_started_at(&self, swap_id: Uuid) -> Result<Option<OffsetDateTime>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let row = sqlx::query!(
+            r#"
+           SELECT entered_at
+           FROM swap_states
+           WHERE swap_id = ?
+           ORDER BY id
+           LIMIT 1;
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let row = row
+            .first()
+            .context(format!("No state in database for swap: {}", swap_id))?;
+
+        Ok(parse_entered_at(&row.entered_at).ok())
+    }
+
     async fn all(&self) -> Result<Vec<(Uuid, State)>> {
         let mut conn = self.pool.acquire().await?;
         let rows = sqlx::query!(
@@ -249,6 +670,117 @@ impl Database for SqliteDatabase {
 
         result
     }
+
+    async fn delete(&self, swap_id: Uuid) -> Result<()> {
+        let state = self.get_state(swap_id).await?;
+
+        if !state.swap_finished() {
+            bail!(
+                "Refusing to delete swap {} because it is not finished yet",
+                swap_id
+            );
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let swap_id = swap_id.to_string();
+
+        sqlx::query!(
+            r#"
+        DELETE FROM swap_states
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        DELETE FROM monero_addresses
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        DELETE FROM peers
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        DELETE FROM monero_lock_proofs
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        DELETE FROM electrum_servers
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        DELETE FROM utxo_reservations
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        DELETE FROM fee_strategies
+        WHERE swap_id = ?;
+        "#,
+            swap_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn compact(&self) -> Result<()> {
+        // VACUUM cannot run inside a transaction and rebuilds the whole file,
+        // which is why it is never run while swaps are executed.
+        sqlx::query("VACUUM")
+            .execute(&self.pool)
+            .await
+            .context("Failed to compact database")?;
+
+        Ok(())
+    }
+}
+
+/// Parses the `entered_at` column, which holds the `Display` representation
+/// of an [`OffsetDateTime`].
+fn parse_entered_at(entered_at: &str) -> Result<OffsetDateTime> {
+    let format = format_description::parse(
+        "[year]-[month]-[day] [hour padding:none]:[minute]:[second].[subsecond] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]",
+    )?;
+    let entered_at = OffsetDateTime::parse(entered_at, &format)
+        .with_context(|| format!("Failed to parse timestamp {}", entered_at))?;
+
+    Ok(entered_at)
 }
 
 #[cfg(test)]
@@ -314,6 +846,27 @@ mod tests {
         assert!(!latest_loaded.contains(&(swap_id_1, state_2)));
     }
 
+    #[tokio::test]
+    async fn test_retrieve_all_states_of_a_swap_in_order() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        let state_2 = State::Alice(AliceState::SafelyAborted);
+        let swap_id = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id, state_1.clone()).await?;
+        db.insert_latest_state(swap_id, state_2.clone()).await?;
+        db.insert_latest_state(Uuid::new_v4(), state_1.clone())
+            .await?;
+
+        let states = db.get_states(swap_id).await?;
+
+        assert_eq!(states, vec![state_1, state_2]);
+        assert!(db.get_states(Uuid::new_v4()).await.is_err());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_insert_load_monero_address() -> Result<()> {
         let db = setup_test_db().await?;
@@ -330,6 +883,29 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_insert_load_monero_lock_proof() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let proof = LockProof {
+            transfer_proof: TransferProof::new(
+                TxHash("a7f4683b2612cd0da5cb0bde0a8feb9e6b2ad4ad4e5b2ed9c2fb3a4730d3e4b6".to_owned()),
+                monero::PrivateKey::from_str("b8ea744ddc55e338a5226dc1e8ed2d112b2a3094265f4cc8fec1f355f1ff0e0b")?,
+            ),
+            address: "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?,
+            amount: monero::Amount::from_piconero(1_000_000_000_000),
+        };
+
+        db.insert_monero_lock_proof(swap_id, proof.clone()).await?;
+
+        let loaded_proof = db.get_monero_lock_proof(swap_id).await?;
+
+        assert_eq!(proof, loaded_proof);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_insert_and_load_multiaddr() -> Result<()> {
         let db = setup_test_db().await?;
@@ -370,6 +946,191 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_insert_replace_and_load_peer_label() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let unlabeled_peer_id = PeerId::random();
+
+        db.insert_peer_label(peer_id, "exchange-X".to_owned())
+            .await?;
+        db.insert_peer_label(peer_id, "friend".to_owned()).await?;
+
+        assert_eq!(db.get_peer_label(peer_id).await?, Some("friend".to_owned()));
+        assert_eq!(db.get_peer_label(unlabeled_peer_id).await?, None);
+        assert_eq!(
+            db.all_peer_labels().await?,
+            vec![(peer_id, "friend".to_owned())]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_peer_id() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let new_peer_id = PeerId::random();
+
+        assert!(db.update_peer_id(swap_id, new_peer_id).await.is_err());
+
+        db.insert_peer_id(swap_id, PeerId::random()).await?;
+        db.update_peer_id(swap_id, new_peer_id).await?;
+
+        assert_eq!(db.get_peer_id(swap_id).await?, new_peer_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_electrum_server() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let unpinned_swap_id = Uuid::new_v4();
+        let rpc_url = Url::parse("ssl://blockstream.info:700")?;
+
+        db.insert_electrum_server(swap_id, rpc_url.clone()).await?;
+
+        assert_eq!(db.get_electrum_server(swap_id).await?, Some(rpc_url));
+        assert_eq!(db.get_electrum_server(unpinned_swap_id).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_fee_strategy() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let swap_id_without_strategy = Uuid::new_v4();
+        let strategy = bitcoin::FeeStrategy {
+            lock: 3,
+            cancel: 1,
+            refund: 1,
+            redeem: 6,
+        };
+
+        db.insert_fee_strategy(swap_id, strategy).await?;
+
+        assert_eq!(db.get_fee_strategy(swap_id).await?, Some(strategy));
+        assert_eq!(db.get_fee_strategy(swap_id_without_strategy).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_entered_at() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let before = OffsetDateTime::now_utc();
+
+        db.insert_latest_state(swap_id, State::Bob(BobState::SafelyAborted))
+            .await?;
+
+        let entered_at = db.get_entered_at(swap_id).await?;
+
+        assert!(entered_at >= before);
+        assert!(entered_at <= OffsetDateTime::now_utc());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_started_at_is_time_of_first_state() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id, State::Bob(BobState::SafelyAborted))
+            .await?;
+        let first_entered_at = db.get_entered_at(swap_id).await?;
+        db.insert_latest_state(swap_id, State::Bob(BobState::SafelyAborted))
+            .await?;
+
+        assert_eq!(db.get_started_at(swap_id).await?, Some(first_entered_at));
+        assert!(db.get_entered_at(swap_id).await? >= first_entered_at);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_finished_swap() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+        let monero_address = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?;
+
+        db.insert_latest_state(swap_id_1, State::Bob(BobState::SafelyAborted))
+            .await?;
+        db.insert_monero_address(swap_id_1, monero_address).await?;
+        db.insert_peer_id(swap_id_1, PeerId::random()).await?;
+        db.insert_electrum_server(swap_id_1, Url::parse("ssl://blockstream.info:700")?)
+            .await?;
+        db.insert_latest_state(swap_id_2, State::Alice(AliceState::BtcRedeemed))
+            .await?;
+
+        db.delete(swap_id_1).await?;
+
+        assert!(db.get_state(swap_id_1).await.is_err());
+        assert!(db.get_monero_address(swap_id_1).await.is_err());
+        assert!(db.get_peer_id(swap_id_1).await.is_err());
+        assert_eq!(db.get_electrum_server(swap_id_1).await?, None);
+        assert_eq!(db.all().await?.len(), 1);
+        assert!(db.get_state(swap_id_2).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refuse_to_delete_unfinished_swap() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let state = State::Bob(BobState::Started {
+            btc_amount: crate::bitcoin::Amount::from_sat(100_000),
+            change_address: "bc1qjl8t9r9xmqhqmvustf8n7j7mepyjjn3l6s6n6j".parse()?,
+        });
+
+        db.insert_latest_state(swap_id, state).await?;
+
+        assert!(db.delete(swap_id).await.is_err());
+        assert!(db.get_state(swap_id).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compact_keeps_swaps() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(swap_id, State::Bob(BobState::SafelyAborted))
+            .await?;
+
+        db.compact().await?;
+
+        assert!(matches!(
+            db.get_state(swap_id).await?,
+            State::Bob(BobState::SafelyAborted)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_displayed_timestamps() -> Result<()> {
+        let now = OffsetDateTime::now_utc();
+
+        assert_eq!(parse_entered_at(&now.to_string())?, now);
+
+        Ok(())
+    }
+
     async fn setup_test_db() -> Result<SqliteDatabase> {
         let temp_db = tempdir().unwrap().into_path().join("tempdb");
 
@@ -1,5 +1,5 @@
 use crate::asb;
-use crate::bitcoin::{CancelTimelock, PunishTimelock};
+use crate::bitcoin::{AddressType, CancelReorgPolicy, CancelTimelock, PunishTimelock};
 use serde::Serialize;
 use std::cmp::max;
 use std::time::Duration;
@@ -13,11 +13,92 @@ pub struct Config {
     pub bitcoin_avg_block_time: Duration,
     pub bitcoin_cancel_timelock: CancelTimelock,
     pub bitcoin_punish_timelock: PunishTimelock,
+    /// Alice only redeems if more than this number of blocks are left until
+    /// the cancel timelock expires. Otherwise her redeem transaction could race
+    /// Bob's cancel and refund transactions.
+    pub bitcoin_redeem_safety_margin: u32,
     pub bitcoin_network: bitcoin::Network,
+    /// A swap step that waits for a Bitcoin transaction fails once this many
+    /// consecutive attempts to reach the Electrum server failed during the
+    /// wait, 0 disables the limit. Alice never gives up redeeming or punishing.
+    pub bitcoin_electrum_reconnect_attempts: u32,
+    /// Initializing the wallet fails if the connection to the Electrum server
+    /// is not established within this time. Also the timeout of every request
+    /// to the server.
+    pub bitcoin_electrum_connect_timeout: Duration,
+    /// The fee rate in sat/vB used if the Electrum server cannot estimate one,
+    /// e.g. because its node has not seen enough blocks yet. Without a fallback
+    /// failing to estimate the fee rate is an error.
+    pub bitcoin_fallback_fee_rate: Option<f32>,
+    /// The type of the addresses the wallet receives its change at.
+    pub bitcoin_change_address_type: AddressType,
+    /// The BIP84 account of the wallet derived from the seed that funds our
+    /// transactions and receives their change. Account 0 is the wallet used
+    /// before accounts could be selected.
+    pub bitcoin_account: u32,
+    /// How long the status of a transaction queried from bitcoind is reused
+    /// by other swaps waiting for it. The cache is cleared on every new block
+    /// and never outlives the sync interval, zero disables it.
+    pub bitcoin_status_cache_ttl: Duration,
+    /// What Bob does if the cancel transaction disappeared, e.g. because of a
+    /// reorg, right before he publishes the refund spending it.
+    pub bitcoin_cancel_reorg_policy: CancelReorgPolicy,
     pub monero_avg_block_time: Duration,
     pub monero_finality_confirmations: u64,
     #[serde(with = "monero_network")]
     pub monero_network: monero::Network,
+    /// How often a failed refresh of the Monero wallet is retried if the
+    /// failure looks transient, e.g. because the daemon is busy.
+    pub monero_refresh_retries: u32,
+    /// The delay before the first retry of a failed refresh, doubled for every
+    /// further retry.
+    pub monero_refresh_backoff: Duration,
+    /// A sweep of a wallet with many inputs is split into several
+    /// transactions. At most this many are relayed at once, the remaining
+    /// inputs are swept in further rounds. `None` relays all of them together.
+    pub monero_sweep_batch_size: Option<usize>,
+    /// A warning describing what the swap is waiting for is logged whenever a
+    /// swap did not advance to the next state within this interval.
+    pub swap_watchdog_interval: Duration,
+    /// Alice considers a swap abandoned if Bob does not send the encrypted
+    /// signature within this time after acknowledging the transfer proof. An
+    /// abandoned swap moves on to cancelling, which waits for the cancel
+    /// timelock to expire, and no longer accepts the encrypted signature.
+    /// `None` waits for the signature until the timelock expired.
+    pub alice_counterparty_silence_timeout: Option<Duration>,
+    /// How often Alice restarts a swap that failed because of a transient
+    /// error, e.g. an unreachable Electrum server, from its last saved state.
+    pub alice_swap_restart_attempts: u32,
+    /// The delay before the first restart of a failed swap, doubled for every
+    /// further restart.
+    pub alice_swap_restart_backoff: Duration,
+    /// Alice only punishes once the cancel transaction has this many
+    /// confirmations more than the punish timelock requires. This gives Bob a
+    /// last chance to refund if his refund transaction is late, e.g. because
+    /// of a congested connection or mempool.
+    pub alice_punish_grace_blocks: u32,
+    /// Alice logs every new confirmation of her XMR lock transaction until it
+    /// reaches `monero_finality_confirmations` while she waits for Bob.
+    pub alice_log_xmr_lock_confirmations: bool,
+    /// Bob warns if the XMR lock transaction did not reach finality once this
+    /// fraction of the cancel timelock passed, `None` never warns.
+    pub bob_xmr_finality_warn_fraction: Option<f64>,
+    /// Bob no longer waits for the XMR lock transaction to reach finality once
+    /// this fraction of the cancel timelock passed. Sending the encrypted
+    /// signature that late would race the cancel timelock, so the swap is
+    /// cancelled once it expires instead. `None` waits until it expires.
+    pub bob_xmr_finality_abort_fraction: Option<f64>,
+    /// Bob does not sweep the redeemed Monero if the network fee of the sweep
+    /// exceeds this amount, `None` accepts any fee.
+    pub bob_max_xmr_fee: Option<crate::monero::Amount>,
+    /// Bob only transfers this amount of the redeemed Monero and leaves the
+    /// rest in the wallet of the swap, `None` sweeps everything without a
+    /// change output.
+    pub bob_xmr_claim_amount: Option<crate::monero::Amount>,
+    /// How long Bob refreshes the wallet of the swap until it holds the
+    /// redeemed Monero, before he concludes that it is not synced yet or the
+    /// Monero are missing.
+    pub bob_xmr_balance_timeout: Duration,
 }
 
 impl Config {
@@ -52,10 +133,32 @@ impl GetConfig for Mainnet {
             bitcoin_avg_block_time: 10.std_minutes(),
             bitcoin_cancel_timelock: CancelTimelock::new(72),
             bitcoin_punish_timelock: PunishTimelock::new(72),
+            bitcoin_redeem_safety_margin: 6,
             bitcoin_network: bitcoin::Network::Bitcoin,
+            bitcoin_electrum_reconnect_attempts: 30,
+            bitcoin_electrum_connect_timeout: 30.std_seconds(),
+            bitcoin_fallback_fee_rate: None,
+            bitcoin_change_address_type: AddressType::P2wpkh,
+            bitcoin_account: 0,
+            bitcoin_status_cache_ttl: 30.std_seconds(),
+            bitcoin_cancel_reorg_policy: CancelReorgPolicy::Rebroadcast,
             monero_avg_block_time: 2.std_minutes(),
             monero_finality_confirmations: 10,
             monero_network: monero::Network::Mainnet,
+            monero_refresh_retries: 5,
+            monero_refresh_backoff: 2.std_seconds(),
+            monero_sweep_batch_size: None,
+            swap_watchdog_interval: 1.std_hours(),
+            alice_counterparty_silence_timeout: None,
+            alice_swap_restart_attempts: 0,
+            alice_swap_restart_backoff: 30.std_seconds(),
+            alice_punish_grace_blocks: 0,
+            alice_log_xmr_lock_confirmations: true,
+            bob_xmr_finality_warn_fraction: Some(0.5),
+            bob_xmr_finality_abort_fraction: Some(0.75),
+            bob_max_xmr_fee: None,
+            bob_xmr_claim_amount: None,
+            bob_xmr_balance_timeout: 10.std_minutes(),
         }
     }
 }
@@ -69,10 +172,32 @@ impl GetConfig for Testnet {
             bitcoin_avg_block_time: 10.std_minutes(),
             bitcoin_cancel_timelock: CancelTimelock::new(12),
             bitcoin_punish_timelock: PunishTimelock::new(6),
+            bitcoin_redeem_safety_margin: 2,
             bitcoin_network: bitcoin::Network::Testnet,
+            bitcoin_electrum_reconnect_attempts: 30,
+            bitcoin_electrum_connect_timeout: 30.std_seconds(),
+            bitcoin_fallback_fee_rate: None,
+            bitcoin_change_address_type: AddressType::P2wpkh,
+            bitcoin_account: 0,
+            bitcoin_status_cache_ttl: 30.std_seconds(),
+            bitcoin_cancel_reorg_policy: CancelReorgPolicy::Rebroadcast,
             monero_avg_block_time: 2.std_minutes(),
             monero_finality_confirmations: 10,
             monero_network: monero::Network::Stagenet,
+            monero_refresh_retries: 5,
+            monero_refresh_backoff: 2.std_seconds(),
+            monero_sweep_batch_size: None,
+            swap_watchdog_interval: 1.std_hours(),
+            alice_counterparty_silence_timeout: None,
+            alice_swap_restart_attempts: 0,
+            alice_swap_restart_backoff: 30.std_seconds(),
+            alice_punish_grace_blocks: 0,
+            alice_log_xmr_lock_confirmations: true,
+            bob_xmr_finality_warn_fraction: Some(0.5),
+            bob_xmr_finality_abort_fraction: Some(0.75),
+            bob_max_xmr_fee: None,
+            bob_xmr_claim_amount: None,
+            bob_xmr_balance_timeout: 10.std_minutes(),
         }
     }
 }
@@ -86,10 +211,32 @@ impl GetConfig for Regtest {
             bitcoin_avg_block_time: 5.std_seconds(),
             bitcoin_cancel_timelock: CancelTimelock::new(100),
             bitcoin_punish_timelock: PunishTimelock::new(50),
+            bitcoin_redeem_safety_margin: 2,
             bitcoin_network: bitcoin::Network::Regtest,
+            bitcoin_electrum_reconnect_attempts: 30,
+            bitcoin_electrum_connect_timeout: 10.std_seconds(),
+            bitcoin_fallback_fee_rate: None,
+            bitcoin_change_address_type: AddressType::P2wpkh,
+            bitcoin_account: 0,
+            bitcoin_status_cache_ttl: Duration::ZERO,
+            bitcoin_cancel_reorg_policy: CancelReorgPolicy::Rebroadcast,
             monero_avg_block_time: 1.std_seconds(),
             monero_finality_confirmations: 10,
             monero_network: monero::Network::Mainnet, // yes this is strange
+            monero_refresh_retries: 5,
+            monero_refresh_backoff: 100.std_milliseconds(),
+            monero_sweep_batch_size: None,
+            swap_watchdog_interval: 5.std_minutes(),
+            alice_counterparty_silence_timeout: None,
+            alice_swap_restart_attempts: 0,
+            alice_swap_restart_backoff: 1.std_seconds(),
+            alice_punish_grace_blocks: 0,
+            alice_log_xmr_lock_confirmations: true,
+            bob_xmr_finality_warn_fraction: None,
+            bob_xmr_finality_abort_fraction: None,
+            bob_max_xmr_fee: None,
+            bob_xmr_claim_amount: None,
+            bob_xmr_balance_timeout: 1.std_minutes(),
         }
     }
 }
@@ -115,9 +262,123 @@ pub fn new(is_testnet: bool, asb_config: &asb::config::Config) -> Config {
             env_config
         };
 
-    if let Some(monero_finality_confirmations) = asb_config.monero.finality_confirmations {
+    let env_config = if let Some(bitcoin_electrum_reconnect_attempts) =
+        asb_config.bitcoin.electrum_reconnect_attempts
+    {
+        Config {
+            bitcoin_electrum_reconnect_attempts,
+            ..env_config
+        }
+    } else {
+        env_config
+    };
+
+    let env_config = if let Some(secs) = asb_config.bitcoin.electrum_connect_timeout_secs {
+        Config {
+            bitcoin_electrum_connect_timeout: Duration::from_secs(secs),
+            ..env_config
+        }
+    } else {
+        env_config
+    };
+
+    let env_config =
+        if let Some(bitcoin_fallback_fee_rate) = asb_config.bitcoin.fallback_fee_sat_per_vb {
+            Config {
+                bitcoin_fallback_fee_rate: Some(bitcoin_fallback_fee_rate),
+                ..env_config
+            }
+        } else {
+            env_config
+        };
+
+    let env_config =
+        if let Some(bitcoin_change_address_type) = asb_config.bitcoin.change_address_type {
+            Config {
+                bitcoin_change_address_type,
+                ..env_config
+            }
+        } else {
+            env_config
+        };
+
+    let env_config = if let Some(status_cache_ttl_secs) = asb_config.bitcoin.status_cache_ttl_secs {
+        Config {
+            bitcoin_status_cache_ttl: Duration::from_secs(status_cache_ttl_secs),
+            ..env_config
+        }
+    } else {
+        env_config
+    };
+
+    let env_config =
+        if let Some(monero_finality_confirmations) = asb_config.monero.finality_confirmations {
+            Config {
+                monero_finality_confirmations,
+                ..env_config
+            }
+        } else {
+            env_config
+        };
+
+    let env_config = if let Some(monero_refresh_retries) = asb_config.monero.refresh_retries {
+        Config {
+            monero_refresh_retries,
+            ..env_config
+        }
+    } else {
+        env_config
+    };
+
+    let env_config = if let Some(secs) = asb_config.monero.refresh_backoff_secs {
+        Config {
+            monero_refresh_backoff: Duration::from_secs(secs),
+            ..env_config
+        }
+    } else {
+        env_config
+    };
+
+    let env_config = if let Some(log) = asb_config.monero.log_lock_confirmations {
+        Config {
+            alice_log_xmr_lock_confirmations: log,
+            ..env_config
+        }
+    } else {
+        env_config
+    };
+
+    let env_config = if let Some(secs) = asb_config.maker.counterparty_silence_timeout_secs {
+        Config {
+            alice_counterparty_silence_timeout: Some(Duration::from_secs(secs)),
+            ..env_config
+        }
+    } else {
+        env_config
+    };
+
+    let env_config =
+        if let Some(alice_swap_restart_attempts) = asb_config.maker.swap_restart_attempts {
+            Config {
+                alice_swap_restart_attempts,
+                ..env_config
+            }
+        } else {
+            env_config
+        };
+
+    let env_config = if let Some(secs) = asb_config.maker.swap_restart_backoff_secs {
+        Config {
+            alice_swap_restart_backoff: Duration::from_secs(secs),
+            ..env_config
+        }
+    } else {
+        env_config
+    };
+
+    if let Some(alice_punish_grace_blocks) = asb_config.maker.punish_grace_blocks {
         Config {
-            monero_finality_confirmations,
+            alice_punish_grace_blocks,
             ..env_config
         }
     } else {
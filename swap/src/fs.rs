@@ -32,3 +32,52 @@ pub fn ensure_directory_exists(file: &Path) -> Result<(), std::io::Error> {
     }
     Ok(())
 }
+
+/// Restricts the permissions of the data directory to the current user.
+///
+/// The directory is created if it does not exist. If the directory is
+/// accessible by group or others we warn and repair it to `0700`.
+/// On non-Unix platforms this is a no-op.
+pub fn ensure_directory_is_private(dir: &Path) -> Result<(), std::io::Error> {
+    if !dir.exists() {
+        tracing::info!(
+            directory = %dir.display(),
+            "Directory does not exist, creating recursively",
+        );
+        std::fs::create_dir_all(dir)?;
+    }
+
+    restrict_permissions(dir, 0o700)
+}
+
+/// Restricts the permissions of a file to be only readable and writable by
+/// the current user (`0600`), warning if it was previously accessible by group
+/// or others. On non-Unix platforms this is a no-op.
+pub fn ensure_file_is_private(file: &Path) -> Result<(), std::io::Error> {
+    restrict_permissions(file, 0o600)
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path, mode: u32) -> Result<(), std::io::Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let current_mode = std::fs::metadata(path)?.permissions().mode();
+
+    if current_mode & 0o077 == 0 {
+        return Ok(());
+    }
+
+    tracing::warn!(
+        path = %path.display(),
+        mode = %format!("{:o}", current_mode & 0o777),
+        "Path is accessible by other users, restricting permissions to {:o}",
+        mode
+    );
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path, _mode: u32) -> Result<(), std::io::Error> {
+    Ok(())
+}
@@ -1,14 +1,16 @@
+pub mod daemon;
 pub mod wallet;
 mod wallet_rpc;
 
 pub use ::monero::network::Network;
 pub use ::monero::{Address, PrivateKey, PublicKey};
 pub use curve25519_dalek::scalar::Scalar;
-pub use wallet::Wallet;
+pub use wallet::{Subaddress, Transferred, Wallet, WalletKeys};
 pub use wallet_rpc::{WalletRpc, WalletRpcProcess};
 
 use crate::bitcoin;
 use anyhow::Result;
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use rand::{CryptoRng, RngCore};
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
@@ -75,9 +77,75 @@ impl From<PublicViewKey> for PublicKey {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct PublicViewKey(PublicKey);
 
+/// Reasons for rejecting a hex encoded private or public key.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum InvalidKey {
+    #[error("Key is not hex encoded")]
+    NotHex,
+    #[error("Key must be 32 bytes long, got {0} bytes")]
+    WrongLength(usize),
+    #[error("Private key is not a canonical scalar")]
+    NonCanonicalScalar,
+    #[error("Public key is not a point on the curve")]
+    NotOnCurve,
+}
+
+fn decode_key(s: &str) -> Result<[u8; 32], InvalidKey> {
+    let bytes = hex::decode(s).map_err(|_| InvalidKey::NotHex)?;
+    let len = bytes.len();
+
+    <[u8; 32]>::try_from(bytes).map_err(|_| InvalidKey::WrongLength(len))
+}
+
+/// Parses the 64 hex characters of a private spend or view key, as shown by
+/// `monero-wallet-cli`.
+pub fn private_key_from_hex(s: &str) -> Result<PrivateKey, InvalidKey> {
+    let scalar =
+        Scalar::from_canonical_bytes(decode_key(s)?).ok_or(InvalidKey::NonCanonicalScalar)?;
+
+    Ok(PrivateKey::from_scalar(scalar))
+}
+
+impl FromStr for PrivateViewKey {
+    type Err = InvalidKey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(private_key_from_hex(s)?))
+    }
+}
+
+impl fmt::Display for PrivateViewKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0.as_bytes()))
+    }
+}
+
+/// Parses the 64 hex characters of a compressed public view key.
+impl FromStr for PublicViewKey {
+    type Err = InvalidKey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = decode_key(s)?;
+
+        if CompressedEdwardsY(bytes).decompress().is_none() {
+            return Err(InvalidKey::NotOnCurve);
+        }
+
+        let key = PublicKey::from_slice(&bytes).map_err(|_| InvalidKey::NotOnCurve)?;
+
+        Ok(Self(key))
+    }
+}
+
+impl fmt::Display for PublicViewKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0.as_bytes()))
+    }
+}
+
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
 pub struct Amount(u64);
 
@@ -182,6 +250,18 @@ impl TransferProof {
     }
 }
 
+/// The XMR lock transaction together with the address and amount it was
+/// supposed to transfer.
+///
+/// This is enough for anyone to verify that the XMR were locked to the address
+/// shared by both parties, e.g. with `check_tx_key` of `monero-wallet-cli`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockProof {
+    pub transfer_proof: TransferProof,
+    pub address: Address,
+    pub amount: Amount,
+}
+
 // TODO: add constructor/ change String to fixed length byte array
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct TxHash(pub String);
@@ -205,6 +285,75 @@ pub struct InsufficientFunds {
     pub actual: Amount,
 }
 
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("network fee of {fee} exceeds the maximum of {max_fee}")]
+pub struct FeeTooHigh {
+    pub fee: Amount,
+    pub max_fee: Amount,
+}
+
+/// A sweep that cannot create any transaction, unlike a sweep that needs
+/// several transactions for the many inputs of a wallet.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum CannotSweep {
+    #[error("the wallet holds no Monero")]
+    Empty,
+    #[error("{balance} are not spendable yet, try again in {blocks_to_unlock} blocks")]
+    Locked {
+        balance: Amount,
+        blocks_to_unlock: u32,
+    },
+    #[error("no transaction could be created to sweep the unlocked {unlocked_balance}")]
+    NoTransaction { unlocked_balance: Amount },
+}
+
+/// The balance of a wallet that did not reach the expected amount while we
+/// waited for it.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum BalanceNotReached {
+    #[error("the wallet holds {balance} instead of at least {expected} because it is not synced yet: {reason}")]
+    NotSynced {
+        balance: Amount,
+        expected: Amount,
+        reason: String,
+    },
+    #[error("the wallet holds {balance} instead of at least {expected} although it is synced, the Monero are missing or were transferred already")]
+    FundsMissing { balance: Amount, expected: Amount },
+}
+
+impl BalanceNotReached {
+    /// Tells a wallet that is still catching up apart from one that is
+    /// missing funds, based on the sync status of the daemon and wallet if
+    /// known, otherwise on the blocks fetched by the last refresh of the
+    /// wallet.
+    pub fn diagnose(
+        balance: Amount,
+        expected: Amount,
+        sync_status: Option<daemon::NotFound>,
+        blocks_fetched: u32,
+    ) -> Self {
+        match sync_status {
+            Some(daemon::NotFound::NotOnBlockchain) => {
+                BalanceNotReached::FundsMissing { balance, expected }
+            }
+            Some(not_synced) => BalanceNotReached::NotSynced {
+                balance,
+                expected,
+                reason: not_synced.to_string(),
+            },
+            None if blocks_fetched > 0 => BalanceNotReached::NotSynced {
+                balance,
+                expected,
+                reason: format!(
+                    "Monero wallet fetched {} blocks in its last refresh",
+                    blocks_fetched
+                ),
+            },
+            None => BalanceNotReached::FundsMissing { balance, expected },
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 #[error("Overflow, cannot convert {0} to u64")]
 pub struct OverflowError(pub String);
@@ -302,6 +451,49 @@ pub mod monero_amount {
 mod tests {
     use super::*;
 
+    #[test]
+    fn missing_balance_of_wallet_that_is_syncing_is_not_synced() {
+        let balance = Amount::ZERO;
+        let expected = Amount::from_piconero(1_000);
+
+        assert!(matches!(
+            BalanceNotReached::diagnose(
+                balance,
+                expected,
+                Some(daemon::NotFound::DaemonSyncing {
+                    height: 10,
+                    network_height: 100
+                }),
+                0
+            ),
+            BalanceNotReached::NotSynced { .. }
+        ));
+        assert!(matches!(
+            BalanceNotReached::diagnose(balance, expected, None, 5),
+            BalanceNotReached::NotSynced { .. }
+        ));
+    }
+
+    #[test]
+    fn missing_balance_of_synced_wallet_is_missing_funds() {
+        let balance = Amount::ZERO;
+        let expected = Amount::from_piconero(1_000);
+
+        assert_eq!(
+            BalanceNotReached::diagnose(
+                balance,
+                expected,
+                Some(daemon::NotFound::NotOnBlockchain),
+                3
+            ),
+            BalanceNotReached::FundsMissing { balance, expected }
+        );
+        assert_eq!(
+            BalanceNotReached::diagnose(balance, expected, None, 0),
+            BalanceNotReached::FundsMissing { balance, expected }
+        );
+    }
+
     #[test]
     fn display_monero_min() {
         let min_pics = 1;
@@ -363,6 +555,60 @@ mod tests {
     use rand::rngs::OsRng;
     use serde::{Deserialize, Serialize};
 
+    #[test]
+    fn view_keys_roundtrip_through_hex() {
+        let private_view_key = PrivateViewKey::new_random(&mut OsRng);
+        let public_view_key = private_view_key.public();
+
+        assert_eq!(
+            PrivateViewKey::from_str(&private_view_key.to_string()).unwrap(),
+            private_view_key
+        );
+        assert_eq!(
+            PublicViewKey::from_str(&public_view_key.to_string()).unwrap(),
+            public_view_key
+        );
+    }
+
+    #[test]
+    fn keys_of_wrong_length_or_not_hex_are_rejected() {
+        let short_key = "58666666666666666666666666666666";
+
+        assert_eq!(
+            PrivateViewKey::from_str(short_key).unwrap_err(),
+            InvalidKey::WrongLength(16)
+        );
+        assert_eq!(
+            PublicViewKey::from_str(&format!("{}00", short_key.repeat(2))).unwrap_err(),
+            InvalidKey::WrongLength(33)
+        );
+        assert_eq!(
+            PublicViewKey::from_str(&"zz".repeat(32)).unwrap_err(),
+            InvalidKey::NotHex
+        );
+    }
+
+    #[test]
+    fn public_key_off_the_curve_is_rejected() {
+        // There is no point with y = 2 on ed25519.
+        let off_curve = format!("02{}", "00".repeat(31));
+        let base_point = "5866666666666666666666666666666666666666666666666666666666666666";
+
+        assert_eq!(
+            PublicViewKey::from_str(&off_curve).unwrap_err(),
+            InvalidKey::NotOnCurve
+        );
+        assert!(PublicViewKey::from_str(base_point).is_ok());
+    }
+
+    #[test]
+    fn private_key_above_group_order_is_rejected() {
+        assert_eq!(
+            PrivateViewKey::from_str(&"ff".repeat(32)).unwrap_err(),
+            InvalidKey::NonCanonicalScalar
+        );
+    }
+
     #[derive(Debug, Serialize, Deserialize, PartialEq)]
     pub struct MoneroPrivateKey(#[serde(with = "monero_private_key")] crate::monero::PrivateKey);
 
@@ -0,0 +1,809 @@
+use crate::monero::Network;
+use anyhow::{bail, Context, Result};
+use monero_rpc::monerod;
+use monero_rpc::monerod::MonerodRpc as _;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use url::{Host, Url};
+
+/// A daemon is considered synced if it is at most this many blocks behind the
+/// network height it knows about.
+const MAX_BLOCKS_BEHIND: u64 = 10;
+
+/// How often the daemons of a [`DaemonPool`] are checked, which brings
+/// daemons that recovered back into rotation.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The address of a monero daemon as `[http(s)://]<host>[:<port>]`.
+///
+/// Addresses without a scheme are reached over http. Without a port the
+/// standard RPC port of the network is used, see
+/// [`MoneroDaemonAddress::with_default_port`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoneroDaemonAddress {
+    https: bool,
+    host: String,
+    port: Option<u16>,
+}
+
+impl MoneroDaemonAddress {
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn is_https(&self) -> bool {
+        self.https
+    }
+
+    /// Sets the port to the standard RPC port of `network` unless the address
+    /// has one.
+    pub fn with_default_port(self, network: Network) -> Self {
+        let default_port = match network {
+            Network::Mainnet => 18081,
+            Network::Stagenet => 38081,
+            Network::Testnet => 28081,
+        };
+
+        Self {
+            port: self.port.or(Some(default_port)),
+            ..self
+        }
+    }
+
+    fn url(&self) -> Result<Url> {
+        Url::parse(&self.to_string())
+            .with_context(|| format!("Invalid monero daemon address {}", self))
+    }
+}
+
+impl FromStr for MoneroDaemonAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (https, rest) = match s.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => (false, rest),
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("https") => (true, rest),
+            Some((scheme, _)) => bail!(
+                "Unsupported scheme {} in monero daemon address {}, expected http or https",
+                scheme,
+                s
+            ),
+            None => (false, s),
+        };
+        let authority = rest.strip_suffix('/').unwrap_or(rest);
+
+        if authority.contains(&['/', '?', '#', '@'][..]) {
+            bail!(
+                "Monero daemon address {} must be [http(s)://]<host>[:<port>] without a path",
+                s
+            );
+        }
+
+        let (host, port) = match authority.rfind(':') {
+            // The colons of an IPv6 address are within brackets.
+            Some(index) if !authority[index..].contains(']') => {
+                let port = authority[index + 1..]
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|port| *port != 0)
+                    .with_context(|| format!("Invalid port in monero daemon address {}", s))?;
+
+                (&authority[..index], Some(port))
+            }
+            _ => (authority, None),
+        };
+
+        if host.is_empty() {
+            bail!("Monero daemon address {} has no host", s);
+        }
+        let host = Host::parse(host)
+            .with_context(|| format!("Invalid host in monero daemon address {}", s))?;
+
+        Ok(Self {
+            https,
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for MoneroDaemonAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.https { "https" } else { "http" };
+
+        match self.port {
+            Some(port) => write!(f, "{}://{}:{}", scheme, self.host, port),
+            None => write!(f, "{}://{}", scheme, self.host),
+        }
+    }
+}
+
+/// A monero daemon the `monero-wallet-rpc` can connect to.
+#[derive(Debug, Clone)]
+pub struct Daemon {
+    address: String,
+    client: monerod::Client,
+}
+
+impl Daemon {
+    /// Creates a client for the daemon at `address`, at the standard RPC port
+    /// of `network` if the address has no port.
+    pub fn new(address: MoneroDaemonAddress, network: Network) -> Result<Self> {
+        let address = address.with_default_port(network);
+        let client = monerod::Client::from_base_url(address.url()?)?;
+
+        Ok(Self {
+            address: address.to_string(),
+            client,
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub async fn sync_status(&self) -> Result<SyncStatus> {
+        let info = self
+            .client
+            .get_info()
+            .await
+            .with_context(|| format!("Failed to get info of monero daemon {}", self.address))?;
+
+        Ok(SyncStatus::from(info))
+    }
+
+    pub async fn height(&self) -> Result<u64> {
+        let info =
+            self.client.get_info().await.with_context(|| {
+                format!("Failed to get height of monero daemon {}", self.address)
+            })?;
+
+        Ok(info.height)
+    }
+
+    /// The number of confirmations of the transaction, `None` if the daemon
+    /// does not know the transaction.
+    pub async fn tx_confirmations(&self, txid: &str) -> Result<Option<u64>> {
+        let height = self.height().await?;
+
+        let confirmations = self.transaction(txid).await?.map(|tx| {
+            if tx.in_pool {
+                0
+            } else {
+                height.saturating_sub(tx.block_height)
+            }
+        });
+
+        Ok(confirmations)
+    }
+
+    /// The height of the block the transaction is in, `None` if the daemon
+    /// does not know the transaction or it is still in the pool.
+    pub async fn tx_block_height(&self, txid: &str) -> Result<Option<u64>> {
+        let block_height = self
+            .transaction(txid)
+            .await?
+            .filter(|tx| !tx.in_pool)
+            .map(|tx| tx.block_height);
+
+        Ok(block_height)
+    }
+
+    async fn transaction(&self, txid: &str) -> Result<Option<monerod::TransactionEntry>> {
+        let transactions = self
+            .client
+            .get_transactions(vec![txid.to_owned()])
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get transaction {} from monero daemon {}",
+                    txid, self.address
+                )
+            })?;
+
+        Ok(transactions.txs.into_iter().find(|tx| tx.tx_hash == txid))
+    }
+
+    /// Fails if the daemon is on a different network than `network`.
+    ///
+    /// Daemons that cannot be reached are not rejected here, they are skipped
+    /// like any other daemon that is not synced.
+    pub async fn ensure_network(&self, network: Network) -> Result<()> {
+        let info = match self.client.get_info().await {
+            Ok(info) => info,
+            Err(error) => {
+                tracing::debug!(daemon = %self.address, "Failed to get network of monero daemon: {:#}", error);
+                return Ok(());
+            }
+        };
+
+        match network_of(&info) {
+            Some(daemon_network) if daemon_network != network => bail!(
+                "Configured for {} but monero daemon {} is on {}",
+                network_name(network),
+                self.address,
+                network_name(daemon_network)
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The network the daemon is on, or `None` if it runs in regtest mode.
+fn network_of(info: &monerod::GetInfo) -> Option<Network> {
+    if info.mainnet {
+        Some(Network::Mainnet)
+    } else if info.stagenet {
+        Some(Network::Stagenet)
+    } else if info.testnet {
+        Some(Network::Testnet)
+    } else {
+        None
+    }
+}
+
+fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => "mainnet",
+        Network::Stagenet => "stagenet",
+        Network::Testnet => "testnet",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncStatus {
+    Synced { height: u64 },
+    Syncing { height: u64, network_height: u64 },
+}
+
+impl From<monerod::GetInfo> for SyncStatus {
+    fn from(info: monerod::GetInfo) -> Self {
+        let network_height = info.target_height.max(info.height);
+
+        if info.synchronized && network_height - info.height <= MAX_BLOCKS_BEHIND {
+            SyncStatus::Synced {
+                height: info.height,
+            }
+        } else {
+            SyncStatus::Syncing {
+                height: info.height,
+                network_height,
+            }
+        }
+    }
+}
+
+/// The daemons available to the `monero-wallet-rpc`, the first of which is the
+/// one the wallet is connected to unless we failed over to another one.
+#[derive(Debug)]
+pub struct Daemons {
+    daemons: Vec<Daemon>,
+    current: usize,
+}
+
+impl Daemons {
+    /// Picks the first daemon that is synced with the network.
+    ///
+    /// If none of the daemons is synced we log a warning and pick the first
+    /// one, the swap can still progress once it caught up. Fails if any of the
+    /// daemons is on a different network than `network`.
+    pub async fn select(addresses: Vec<MoneroDaemonAddress>, network: Network) -> Result<Self> {
+        let daemons = addresses
+            .into_iter()
+            .map(|address| Daemon::new(address, network))
+            .collect::<Result<Vec<_>>>()?;
+
+        if daemons.is_empty() {
+            bail!("At least one monero daemon is required");
+        }
+
+        for daemon in &daemons {
+            daemon.ensure_network(network).await?;
+        }
+
+        let mut daemons = Self {
+            daemons,
+            current: 0,
+        };
+
+        if daemons.current_is_synced().await {
+            return Ok(daemons);
+        }
+
+        if daemons.fail_over().await.is_none() {
+            tracing::warn!(
+                daemon = %daemons.current().address(),
+                "None of the monero daemons is synced, transactions may not be visible until the daemon caught up with the network"
+            );
+        }
+
+        Ok(daemons)
+    }
+
+    pub fn current(&self) -> &Daemon {
+        &self.daemons[self.current]
+    }
+
+    /// Switches to the next daemon that is synced with the network and returns
+    /// it, or `None` if there is no other synced daemon.
+    pub async fn fail_over(&mut self) -> Option<&Daemon> {
+        for index in (0..self.daemons.len()).filter(|index| *index != self.current) {
+            let daemon = &self.daemons[index];
+
+            match daemon.sync_status().await {
+                Ok(SyncStatus::Synced { .. }) => {
+                    tracing::info!(daemon = %daemon.address(), "Failing over to synced monero daemon");
+                    self.current = index;
+
+                    return Some(&self.daemons[index]);
+                }
+                Ok(status) => log_sync_status(daemon, status),
+                Err(error) => tracing::warn!("{:#}", error),
+            }
+        }
+
+        None
+    }
+
+    async fn current_is_synced(&self) -> bool {
+        let daemon = self.current();
+
+        match daemon.sync_status().await {
+            Ok(SyncStatus::Synced { .. }) => true,
+            Ok(status) => {
+                log_sync_status(daemon, status);
+                false
+            }
+            Err(error) => {
+                tracing::warn!("{:#}", error);
+                false
+            }
+        }
+    }
+}
+
+/// Daemons that read-only queries are spread across, while the
+/// `monero-wallet-rpc` stays connected to the primary daemon for everything
+/// that writes, e.g. transfers.
+///
+/// Daemons that cannot be reached or are not synced are dropped from the
+/// rotation and checked again every [`HEALTH_CHECK_INTERVAL`]. If no daemon is
+/// healthy, queries fall back to the wallet.
+#[derive(Debug)]
+pub struct DaemonPool {
+    daemons: Vec<PoolMember>,
+    primary: usize,
+    next: usize,
+    last_health_check: Instant,
+}
+
+#[derive(Debug)]
+struct PoolMember {
+    daemon: Daemon,
+    healthy: bool,
+}
+
+impl DaemonPool {
+    /// Creates a pool of the given daemons, exactly one of which has to be
+    /// marked as primary. Fails if any of the daemons is on a different
+    /// network than `network`.
+    pub async fn new(
+        addresses: Vec<(MoneroDaemonAddress, bool)>,
+        network: Network,
+    ) -> Result<Self> {
+        let primaries = addresses.iter().filter(|(_, primary)| *primary).count();
+        if primaries != 1 {
+            bail!(
+                "Exactly one monero daemon has to be the primary, got {}",
+                primaries
+            );
+        }
+
+        let primary = addresses
+            .iter()
+            .position(|(_, primary)| *primary)
+            .expect("exactly one primary");
+        let daemons = addresses
+            .into_iter()
+            .map(|(address, _)| {
+                Ok(PoolMember {
+                    daemon: Daemon::new(address, network)?,
+                    healthy: true,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for member in &daemons {
+            member.daemon.ensure_network(network).await?;
+        }
+
+        let mut pool = Self {
+            daemons,
+            primary,
+            next: 0,
+            last_health_check: Instant::now(),
+        };
+        pool.health_check().await;
+
+        Ok(pool)
+    }
+
+    pub fn primary(&self) -> &Daemon {
+        &self.daemons[self.primary].daemon
+    }
+
+    /// The height of the blockchain according to the next healthy daemon.
+    pub async fn height(&mut self) -> Option<u64> {
+        for index in self.rotation().await {
+            let result = self.daemons[index].daemon.height().await;
+
+            match result {
+                Ok(height) => return Some(height),
+                Err(error) => self.drop_from_rotation(index, error),
+            }
+        }
+
+        None
+    }
+
+    /// The confirmations of the transaction according to the next healthy
+    /// daemon, `None` if no healthy daemon knows the transaction.
+    pub async fn tx_confirmations(&mut self, txid: &str) -> Option<u64> {
+        for index in self.rotation().await {
+            let result = self.daemons[index].daemon.tx_confirmations(txid).await;
+
+            match result {
+                Ok(Some(confirmations)) => return Some(confirmations),
+                Ok(None) => continue,
+                Err(error) => self.drop_from_rotation(index, error),
+            }
+        }
+
+        None
+    }
+
+    /// The height of the block the transaction is in according to the next
+    /// healthy daemon, `None` if no healthy daemon knows it mined.
+    pub async fn tx_block_height(&mut self, txid: &str) -> Option<u64> {
+        for index in self.rotation().await {
+            let result = self.daemons[index].daemon.tx_block_height(txid).await;
+
+            match result {
+                Ok(Some(block_height)) => return Some(block_height),
+                Ok(None) => continue,
+                Err(error) => self.drop_from_rotation(index, error),
+            }
+        }
+
+        None
+    }
+
+    /// Checks every daemon and logs whenever one is dropped from or brought
+    /// back into the rotation.
+    pub async fn health_check(&mut self) {
+        for member in &mut self.daemons {
+            let healthy = match member.daemon.sync_status().await {
+                Ok(SyncStatus::Synced { .. }) => true,
+                Ok(status) => {
+                    log_sync_status(&member.daemon, status);
+                    false
+                }
+                Err(error) => {
+                    tracing::debug!("{:#}", error);
+                    false
+                }
+            };
+
+            if healthy && !member.healthy {
+                tracing::info!(daemon = %member.daemon.address(), "Monero daemon is healthy again, adding it back to the rotation");
+            } else if !healthy && member.healthy {
+                tracing::warn!(daemon = %member.daemon.address(), "Monero daemon is unhealthy, dropping it from the rotation");
+            }
+
+            member.healthy = healthy;
+        }
+
+        if !self.daemons[self.primary].healthy {
+            tracing::warn!(
+                daemon = %self.primary().address(),
+                "Primary monero daemon is unhealthy, transfers may fail until it recovered"
+            );
+        }
+
+        self.last_health_check = Instant::now();
+    }
+
+    /// The indexes of the healthy daemons in the order they should be tried
+    /// for the next query. Every query starts at the daemon after the one the
+    /// previous query started at.
+    async fn rotation(&mut self) -> Vec<usize> {
+        if self.last_health_check.elapsed() >= HEALTH_CHECK_INTERVAL {
+            self.health_check().await;
+        }
+
+        let rotation = rotation(
+            self.daemons.iter().map(|member| member.healthy).collect(),
+            self.next,
+        );
+        self.next = (self.next + 1) % self.daemons.len();
+
+        rotation
+    }
+
+    fn drop_from_rotation(&mut self, index: usize, error: anyhow::Error) {
+        let member = &mut self.daemons[index];
+
+        tracing::warn!(daemon = %member.daemon.address(), "Monero daemon failed to answer, dropping it from the rotation: {:#}", error);
+        member.healthy = false;
+    }
+}
+
+fn rotation(healthy: Vec<bool>, start: usize) -> Vec<usize> {
+    let len = healthy.len();
+
+    (0..len)
+        .map(|offset| (start + offset) % len)
+        .filter(|index| healthy[*index])
+        .collect()
+}
+
+fn log_sync_status(daemon: &Daemon, status: SyncStatus) {
+    match status {
+        SyncStatus::Synced { height } => {
+            tracing::debug!(daemon = %daemon.address(), %height, "Monero daemon is synced")
+        }
+        SyncStatus::Syncing {
+            height,
+            network_height,
+        } => tracing::warn!(
+            daemon = %daemon.address(),
+            %height,
+            %network_height,
+            "Monero daemon is not synced with the network"
+        ),
+    }
+}
+
+/// Why a transaction we are waiting for could not be found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotFound {
+    /// The daemon did not yet see the blocks the transaction may be in.
+    DaemonSyncing { height: u64, network_height: u64 },
+    /// The daemon is synced but the wallet has not yet caught up with it.
+    WalletSyncing {
+        wallet_height: u64,
+        daemon_height: u64,
+    },
+    /// Both daemon and wallet are synced, the transaction is not on the
+    /// blockchain (yet).
+    NotOnBlockchain,
+}
+
+impl NotFound {
+    pub fn diagnose(daemon: SyncStatus, wallet_height: u64) -> Self {
+        match daemon {
+            SyncStatus::Syncing {
+                height,
+                network_height,
+            } => NotFound::DaemonSyncing {
+                height,
+                network_height,
+            },
+            SyncStatus::Synced {
+                height: daemon_height,
+            } if daemon_height.saturating_sub(wallet_height) > MAX_BLOCKS_BEHIND => {
+                NotFound::WalletSyncing {
+                    wallet_height,
+                    daemon_height,
+                }
+            }
+            SyncStatus::Synced { .. } => NotFound::NotOnBlockchain,
+        }
+    }
+}
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotFound::DaemonSyncing {
+                height,
+                network_height,
+            } => write!(
+                f,
+                "Monero daemon is still syncing (at block {} of {})",
+                height, network_height
+            ),
+            NotFound::WalletSyncing {
+                wallet_height,
+                daemon_height,
+            } => write!(
+                f,
+                "Monero wallet is still syncing with the daemon (at block {} of {})",
+                wallet_height, daemon_height
+            ),
+            NotFound::NotOnBlockchain => {
+                write!(f, "Monero transaction is not on the blockchain yet")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(height: u64, target_height: u64, synchronized: bool) -> monerod::GetInfo {
+        monerod::GetInfo {
+            height,
+            target_height,
+            synchronized,
+            busy_syncing: !synchronized,
+            mainnet: true,
+            stagenet: false,
+            testnet: false,
+        }
+    }
+
+    #[test]
+    fn daemon_network_is_taken_from_network_flags() {
+        let mainnet = info(2_500_000, 0, true);
+        let stagenet = monerod::GetInfo {
+            mainnet: false,
+            stagenet: true,
+            ..info(2_500_000, 0, true)
+        };
+
+        assert_eq!(network_of(&mainnet), Some(Network::Mainnet));
+        assert_eq!(network_of(&stagenet), Some(Network::Stagenet));
+    }
+
+    #[test]
+    fn daemon_in_regtest_mode_has_no_network() {
+        let regtest = monerod::GetInfo {
+            mainnet: false,
+            ..info(150, 0, true)
+        };
+
+        assert_eq!(network_of(&regtest), None);
+    }
+
+    #[test]
+    fn synchronized_daemon_without_target_height_is_synced() {
+        let status = SyncStatus::from(info(2_500_000, 0, true));
+
+        assert_eq!(status, SyncStatus::Synced { height: 2_500_000 });
+    }
+
+    #[test]
+    fn daemon_far_behind_target_height_is_syncing() {
+        let status = SyncStatus::from(info(2_400_000, 2_500_000, false));
+
+        assert_eq!(status, SyncStatus::Syncing {
+            height: 2_400_000,
+            network_height: 2_500_000
+        });
+    }
+
+    #[test]
+    fn rotation_starts_at_given_daemon_and_wraps_around() {
+        assert_eq!(rotation(vec![true, true, true], 1), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn rotation_skips_unhealthy_daemons() {
+        assert_eq!(rotation(vec![true, false, true], 1), vec![2, 0]);
+        assert!(rotation(vec![false, false], 0).is_empty());
+    }
+
+    #[test]
+    fn given_daemon_syncing_then_daemon_is_blamed() {
+        let daemon = SyncStatus::Syncing {
+            height: 2_400_000,
+            network_height: 2_500_000,
+        };
+
+        let not_found = NotFound::diagnose(daemon, 2_400_000);
+
+        assert_eq!(not_found, NotFound::DaemonSyncing {
+            height: 2_400_000,
+            network_height: 2_500_000
+        });
+    }
+
+    #[test]
+    fn given_wallet_behind_synced_daemon_then_wallet_is_blamed() {
+        let daemon = SyncStatus::Synced { height: 2_500_000 };
+
+        let not_found = NotFound::diagnose(daemon, 2_400_000);
+
+        assert_eq!(not_found, NotFound::WalletSyncing {
+            wallet_height: 2_400_000,
+            daemon_height: 2_500_000
+        });
+    }
+
+    #[test]
+    fn given_daemon_and_wallet_synced_then_tx_is_not_on_blockchain() {
+        let daemon = SyncStatus::Synced { height: 2_500_000 };
+
+        let not_found = NotFound::diagnose(daemon, 2_499_999);
+
+        assert_eq!(not_found, NotFound::NotOnBlockchain);
+    }
+
+    fn normalized(address: &str, network: Network) -> String {
+        address
+            .parse::<MoneroDaemonAddress>()
+            .unwrap()
+            .with_default_port(network)
+            .to_string()
+    }
+
+    #[test]
+    fn daemon_address_defaults_to_http() {
+        assert_eq!(
+            normalized("node.melo.tools:18081", Network::Mainnet),
+            "http://node.melo.tools:18081"
+        );
+        assert_eq!(
+            normalized("HTTP://node.melo.tools:18081/", Network::Mainnet),
+            "http://node.melo.tools:18081"
+        );
+    }
+
+    #[test]
+    fn daemon_address_preserves_https() {
+        let address = "https://node.example.com:18089"
+            .parse::<MoneroDaemonAddress>()
+            .unwrap();
+
+        assert!(address.is_https());
+        assert_eq!(address.host(), "node.example.com");
+        assert_eq!(address.port(), Some(18089));
+    }
+
+    #[test]
+    fn daemon_address_without_port_uses_port_of_network() {
+        assert_eq!(
+            normalized("https://node.example.com", Network::Mainnet),
+            "https://node.example.com:18081"
+        );
+        assert_eq!(
+            normalized("stagenet.melo.tools", Network::Stagenet),
+            "http://stagenet.melo.tools:38081"
+        );
+        assert_eq!(normalized("[::1]", Network::Testnet), "http://[::1]:28081");
+        assert_eq!(
+            normalized("[::1]:18081", Network::Testnet),
+            "http://[::1]:18081"
+        );
+    }
+
+    #[test]
+    fn malformed_daemon_addresses_are_rejected() {
+        for address in [
+            "",
+            ":18081",
+            "node.melo.tools:",
+            "node.melo.tools:port",
+            "node.melo.tools:0",
+            "node.melo.tools:65536",
+            "tcp://node.melo.tools:18081",
+            "http://node.melo.tools:18081/json_rpc",
+            "http://user@node.melo.tools:18081",
+            "::1",
+            "node melo tools",
+        ] {
+            assert!(
+                address.parse::<MoneroDaemonAddress>().is_err(),
+                "{} should be rejected",
+                address
+            );
+        }
+    }
+}
@@ -1,14 +1,18 @@
 use crate::env::Config;
+use crate::monero::daemon::{DaemonPool, Daemons, NotFound};
 use crate::monero::{
-    Amount, InsufficientFunds, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
+    Amount, BalanceNotReached, CannotSweep, FeeTooHigh, InsufficientFunds, PrivateViewKey,
+    PublicViewKey, TransferProof, TxHash,
 };
 use ::monero::{Address, Network, PrivateKey, PublicKey};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use monero_rpc::wallet::{BlockHeight, MoneroWalletRpc as _, Refreshed};
 use monero_rpc::{jsonrpc, wallet};
+use std::convert::TryFrom;
+use std::mem;
 use std::str::FromStr;
 use std::time::Duration;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, MutexGuard};
 use tokio::time::Interval;
 use url::Url;
 
@@ -18,7 +22,26 @@ pub struct Wallet {
     network: Network,
     name: String,
     main_address: monero::Address,
+    /// The account that funds transfers and receives deposits.
+    account_index: u32,
     sync_interval: Duration,
+    refresh_retries: u32,
+    refresh_backoff: Duration,
+    sweep_batch_size: Option<usize>,
+    daemons: Option<Mutex<Daemons>>,
+    daemon_pool: Option<Mutex<DaemonPool>>,
+    /// Further `monero-wallet-rpc`s that the wallets of swaps are loaded in,
+    /// none of them has a wallet loaded while it is not locked.
+    swap_wallet_rpcs: Vec<Mutex<wallet::Client>>,
+}
+
+/// The transactions that moved funds out of a wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transferred {
+    pub tx_hashes: Vec<TxHash>,
+    /// The amount received by the destination, without the network fee.
+    pub amount: Amount,
+    pub fee: Amount,
 }
 
 impl Wallet {
@@ -49,10 +72,109 @@ impl Wallet {
             network: env_config.monero_network,
             name,
             main_address,
+            account_index: 0,
             sync_interval: env_config.monero_sync_interval(),
+            refresh_retries: env_config.monero_refresh_retries,
+            refresh_backoff: env_config.monero_refresh_backoff,
+            sweep_batch_size: env_config.monero_sweep_batch_size,
+            daemons: None,
+            daemon_pool: None,
+            swap_wallet_rpcs: vec![],
         })
     }
 
+    /// Lets the wallet tell a daemon that is not synced apart from a
+    /// transaction that is not on the blockchain, and fail over to another of
+    /// the given daemons if the current one falls behind.
+    ///
+    /// The `monero-wallet-rpc` has to be connected to the current daemon.
+    pub fn with_daemons(mut self, daemons: Daemons) -> Self {
+        self.daemons = Some(Mutex::new(daemons));
+        self
+    }
+
+    /// Spreads read-only queries, i.e. the block height and the confirmations
+    /// of transfers we wait for, across the daemons of the pool.
+    ///
+    /// The `monero-wallet-rpc` is connected to the primary daemon of the pool,
+    /// which all transfers go through.
+    pub async fn with_daemon_pool(mut self, pool: DaemonPool) -> Result<Self> {
+        let primary = pool.primary().address().to_owned();
+
+        self.inner
+            .lock()
+            .await
+            .set_daemon(primary.clone())
+            .await
+            .with_context(|| format!("Failed to connect monero wallet to daemon {}", primary))?;
+        tracing::info!(daemon = %primary, "Connected monero wallet to primary daemon");
+
+        self.daemon_pool = Some(Mutex::new(pool));
+        Ok(self)
+    }
+
+    /// Generates and sweeps the wallets of swaps with the `monero-wallet-rpc`s
+    /// at `urls` instead of the one of this wallet, so that concurrent swaps
+    /// neither close this wallet nor wait for each other.
+    ///
+    /// A wallet loaded in one of them is closed. The wallet of a swap is always
+    /// loaded in the same `monero-wallet-rpc`, which stores its file, as long
+    /// as the list does not change.
+    pub async fn with_swap_wallet_rpcs(mut self, urls: Vec<Url>) -> Result<Self> {
+        for url in urls {
+            let client = wallet::Client::new(url.clone())?;
+            let _ = client.close_wallet().await;
+
+            tracing::info!(%url, "Loading the Monero wallets of swaps with additional monero-wallet-rpc");
+            self.swap_wallet_rpcs.push(Mutex::new(client));
+        }
+
+        Ok(self)
+    }
+
+    /// Locks the `monero-wallet-rpc` that the wallet `file_name` of a swap is
+    /// loaded in.
+    ///
+    /// Wallets are assigned to the additional `monero-wallet-rpc`s by their
+    /// name, so different swaps are spread across them and only wait for
+    /// each other if they share one. Without them the `monero-wallet-rpc` of
+    /// this wallet is used, which serializes all operations on the wallets of
+    /// swaps with the ones on this wallet.
+    async fn lock_swap_wallet_rpc(&self, file_name: &str) -> SwapWalletRpc<'_> {
+        if self.swap_wallet_rpcs.is_empty() {
+            return SwapWalletRpc {
+                client: self.inner.lock().await,
+                main_wallet: Some(&self.name),
+            };
+        }
+
+        let index = swap_wallet_rpc_index(file_name, self.swap_wallet_rpcs.len());
+
+        SwapWalletRpc {
+            client: self.swap_wallet_rpcs[index].lock().await,
+            main_wallet: None,
+        }
+    }
+
+    /// Uses the given account of the wallet instead of the primary account
+    /// for transfers, balances and subaddresses. Monero swept from the wallets
+    /// of swaps is sent to the main address of this account.
+    pub async fn with_account_index(mut self, account_index: u32) -> Result<Self> {
+        let main_address = self
+            .inner
+            .lock()
+            .await
+            .get_address(account_index)
+            .await
+            .with_context(|| format!("Failed to get address of account {}", account_index))?
+            .address;
+
+        self.main_address = monero::Address::from_str(&main_address)?;
+        self.account_index = account_index;
+
+        Ok(self)
+    }
+
     /// Re-open the wallet using the internally stored name.
     pub async fn re_open(&self) -> Result<()> {
         self.inner
@@ -124,13 +246,14 @@ impl Wallet {
         let temp_wallet_address =
             Address::standard(self.network, public_spend_key, public_view_key);
 
-        let wallet = self.inner.lock().await;
+        let wallet = self.lock_swap_wallet_rpc(&file_name).await;
 
         // Close the default wallet before generating the other wallet to ensure that
         // it saves its state correctly
-        let _ = wallet.close_wallet().await?;
+        wallet.unload_main_wallet().await?;
 
         let _ = wallet
+            .client
             .generate_from_keys(
                 file_name,
                 temp_wallet_address.to_string(),
@@ -143,8 +266,13 @@ impl Wallet {
             .await?;
 
         // Try to send all the funds from the generated wallet to the default wallet
-        match wallet.refresh().await {
-            Ok(_) => match wallet.sweep_all(self.main_address.to_string()).await {
+        match refresh_with_retry(&*wallet.client, self.refresh_retries, self.refresh_backoff).await
+        {
+            Ok(_) => match wallet
+                .client
+                .sweep_all(self.main_address.to_string(), false, false)
+                .await
+            {
                 Ok(sweep_all) => {
                     for tx in sweep_all.tx_hash_list {
                         tracing::info!(
@@ -165,11 +293,117 @@ impl Wallet {
             }
         }
 
-        let _ = wallet.open_wallet(self.name.clone()).await?;
+        wallet.restore().await?;
 
         Ok(())
     }
 
+    /// Close the wallet, open the wallet `file_name` and sweep all of its funds
+    /// to `destination`. If the wallet file does not exist it is generated from
+    /// `keys`. The default wallet is re-loaded afterwards, even if sweeping
+    /// failed.
+    ///
+    /// A wallet without any funds left is not an error, so this can be retried
+    /// safely.
+    pub async fn sweep_from(
+        &self,
+        file_name: String,
+        keys: Option<WalletKeys>,
+        destination: Address,
+    ) -> Result<Vec<TxHash>> {
+        let rpc = self.lock_swap_wallet_rpc(&file_name).await;
+        let wallet = &*rpc.client;
+
+        rpc.unload_main_wallet().await?;
+
+        let result: Result<Vec<TxHash>> = async {
+            if wallet.open_wallet(file_name.clone()).await.is_err() {
+                let keys = keys
+                    .with_context(|| format!("No keys to generate Monero wallet {}", file_name))?;
+                let public_spend_key = PublicKey::from_private_key(&keys.spend_key);
+                let public_view_key = PublicKey::from_private_key(&keys.view_key.into());
+                let address = Address::standard(self.network, public_spend_key, public_view_key);
+
+                let _ = wallet
+                    .generate_from_keys(
+                        file_name.clone(),
+                        address.to_string(),
+                        keys.spend_key.to_string(),
+                        PrivateKey::from(keys.view_key).to_string(),
+                        keys.restore_height.height,
+                        String::from(""),
+                        true,
+                    )
+                    .await
+                    .context("Failed to generate new wallet from keys")?;
+            }
+
+            refresh_with_retry(wallet, self.refresh_retries, self.refresh_backoff)
+                .await
+                .with_context(|| format!("Failed to refresh Monero wallet {}", file_name))?;
+
+            let balance = wallet.get_balance(0).await?;
+            if balance.balance == 0 {
+                tracing::info!(monero_wallet_name = %file_name, "No Monero left to sweep");
+                return Ok(vec![]);
+            }
+            if balance.unlocked_balance == 0 {
+                bail!(
+                    "Monero in wallet {} is not spendable yet, try again in {} blocks",
+                    file_name,
+                    balance.blocks_to_unlock
+                );
+            }
+
+            let sweep_all = wallet
+                .sweep_all(destination.to_string(), false, false)
+                .await
+                .with_context(|| format!("Failed to sweep Monero to {}", destination))?;
+
+            Ok(sweep_all.tx_hash_list.into_iter().map(TxHash).collect())
+        }
+        .await;
+
+        rpc.restore().await?;
+
+        result
+    }
+
+    /// Close the wallet and generate the wallet `file_name` from `keys`
+    /// without touching its funds, e.g. to recover the Monero of a swap in a
+    /// wallet that is opened with another tool. The default wallet is
+    /// re-loaded afterwards.
+    ///
+    /// Fails if a wallet with this name exists already. Returns the main
+    /// address of the generated wallet.
+    pub async fn generate_from_keys(&self, file_name: String, keys: WalletKeys) -> Result<Address> {
+        let public_spend_key = PublicKey::from_private_key(&keys.spend_key);
+        let public_view_key = PublicKey::from_private_key(&keys.view_key.into());
+        let address = Address::standard(self.network, public_spend_key, public_view_key);
+
+        let wallet = self.inner.lock().await;
+
+        let _ = wallet.close_wallet().await?;
+
+        let result = wallet
+            .generate_from_keys(
+                file_name.clone(),
+                address.to_string(),
+                keys.spend_key.to_string(),
+                PrivateKey::from(keys.view_key).to_string(),
+                keys.restore_height.height,
+                String::from(""),
+                true,
+            )
+            .await
+            .with_context(|| format!("Failed to generate Monero wallet {} from keys", file_name));
+
+        let _ = wallet.open_wallet(self.name.clone()).await?;
+        let _ = result?;
+
+        Ok(address)
+    }
+
     pub async fn transfer(&self, request: TransferRequest) -> Result<TransferProof> {
         let inner = self.inner.lock().await;
 
@@ -188,7 +422,11 @@ impl Wallet {
             Address::standard(self.network, public_spend_key, public_view_key.into());
 
         let res = inner
-            .transfer_single(0, amount.as_piconero(), &destination_address.to_string())
+            .transfer_single(
+                self.account_index,
+                amount.as_piconero(),
+                &destination_address.to_string(),
+            )
             .await?;
 
         tracing::debug!(
@@ -228,6 +466,8 @@ impl Wallet {
 
         wait_for_confirmations(
             &self.inner,
+            self.daemons.as_ref(),
+            self.daemon_pool.as_ref(),
             transfer_proof,
             address,
             expected,
@@ -240,26 +480,275 @@ impl Wallet {
         Ok(())
     }
 
-    pub async fn sweep_all(&self, address: Address) -> Result<Vec<TxHash>> {
-        let sweep_all = self
+    /// Sweeps all funds of the loaded wallet to `address`.
+    ///
+    /// The sweep transactions are created without relaying them first, so the
+    /// network fee is known before any funds move. If it exceeds `max_fee` the
+    /// transactions are dropped and [`FeeTooHigh`] is returned, which leaves
+    /// the funds in the wallet to retry later.
+    ///
+    /// A wallet with many inputs is swept with several transactions. With a
+    /// sweep batch size only that many are relayed at once, the inputs left
+    /// over are swept in another round. [`CannotSweep`] is returned if no
+    /// transaction can be created at all.
+    pub async fn sweep_all(
+        &self,
+        address: Address,
+        max_fee: Option<Amount>,
+    ) -> Result<Transferred> {
+        let wallet = self.inner.lock().await;
+
+        let balance = wallet.get_balance(0).await?;
+        if balance.balance == 0 {
+            return Err(CannotSweep::Empty.into());
+        }
+        if balance.unlocked_balance == 0 {
+            return Err(CannotSweep::Locked {
+                balance: Amount::from_piconero(balance.balance),
+                blocks_to_unlock: balance.blocks_to_unlock,
+            }
+            .into());
+        }
+
+        let batch_size = self.sweep_batch_size.unwrap_or(usize::MAX);
+        let mut swept = Transferred {
+            tx_hashes: vec![],
+            amount: Amount::ZERO,
+            fee: Amount::ZERO,
+        };
+
+        loop {
+            let sweep_all = wallet
+                .sweep_all(address.to_string(), true, true)
+                .await
+                .context("Failed to create Monero sweep transactions")?;
+            let transactions = sweep_all.tx_metadata_list.len();
+
+            if transactions == 0 {
+                if swept.tx_hashes.is_empty() {
+                    return Err(CannotSweep::NoTransaction {
+                        unlocked_balance: Amount::from_piconero(balance.unlocked_balance),
+                    }
+                    .into());
+                }
+                break;
+            }
+
+            // The first round creates the transactions of the whole sweep, later rounds
+            // only recreate the ones that were not relayed yet.
+            if swept.tx_hashes.is_empty() {
+                let fee = Amount::from_piconero(sweep_all.fee_list.iter().sum());
+
+                tracing::info!(%fee, %address, %transactions, "Estimated Monero network fee of sweep");
+
+                if let Some(max_fee) = max_fee {
+                    if fee > max_fee {
+                        return Err(FeeTooHigh { fee, max_fee }.into());
+                    }
+                }
+            }
+
+            let batch = sweep_all
+                .tx_metadata_list
+                .into_iter()
+                .zip(sweep_all.amount_list)
+                .zip(sweep_all.fee_list)
+                .take(batch_size);
+            for ((tx_metadata, amount), fee) in batch {
+                let relayed = wallet.relay_tx(tx_metadata).await.with_context(|| {
+                    format!(
+                        "Failed to relay Monero sweep transaction after {} were relayed",
+                        swept.tx_hashes.len()
+                    )
+                })?;
+
+                tracing::debug!(txid = %relayed.tx_hash, "Relayed Monero sweep transaction");
+
+                swept.tx_hashes.push(TxHash(relayed.tx_hash));
+                swept.amount = swept.amount + Amount::from_piconero(amount);
+                swept.fee = swept.fee + Amount::from_piconero(fee);
+            }
+
+            if transactions <= batch_size {
+                break;
+            }
+
+            tracing::info!(
+                remaining = transactions - batch_size,
+                "Sweeping the inputs left over in another round"
+            );
+        }
+
+        tracing::info!(
+            amount = %swept.amount,
+            fee = %swept.fee,
+            transactions = swept.tx_hashes.len(),
+            %address,
+            "Paid Monero network fee of sweep"
+        );
+
+        Ok(swept)
+    }
+
+    /// Transfers the redeemed funds of the loaded wallet to `address`.
+    ///
+    /// Without an `amount` the wallet is swept, so no change output is left
+    /// behind in it. An `amount` is transferred on its own only if the wallet
+    /// keeps more than the network fee of a sweep, otherwise it is swept as
+    /// well: such a transfer would fail for the missing fee or leave dust.
+    ///
+    /// The fee of a transfer is only known once it is relayed, so `max_fee`
+    /// is checked against the estimated fee of a sweep instead.
+    pub async fn claim(
+        &self,
+        address: Address,
+        amount: Option<Amount>,
+        max_fee: Option<Amount>,
+    ) -> Result<Transferred> {
+        let amount = match amount {
+            Some(amount) => amount,
+            None => return self.sweep_all(address, max_fee).await,
+        };
+
+        let wallet = self.inner.lock().await;
+
+        let balance = Amount::from_piconero(
+            wallet
+                .get_balance(self.account_index)
+                .await?
+                .unlocked_balance,
+        );
+        let sweep = wallet.sweep_all(address.to_string(), true, false).await?;
+        let sweep_fee = Amount::from_piconero(sweep.fee_list.iter().sum());
+
+        if amount + sweep_fee >= balance {
+            tracing::info!(%amount, %balance, fee = %sweep_fee, "Sweeping the whole balance because it barely exceeds the amount to transfer");
+            drop(wallet);
+
+            return self.sweep_all(address, max_fee).await;
+        }
+
+        if let Some(max_fee) = max_fee {
+            if sweep_fee > max_fee {
+                return Err(FeeTooHigh {
+                    fee: sweep_fee,
+                    max_fee,
+                }
+                .into());
+            }
+        }
+
+        let transfer = wallet
+            .transfer_single(
+                self.account_index,
+                amount.as_piconero(),
+                &address.to_string(),
+            )
+            .await
+            .context("Failed to transfer Monero")?;
+        let fee = Amount::from_piconero(transfer.fee);
+
+        tracing::info!(%amount, %fee, %address, "Transferred part of the Monero, the rest stays in the wallet");
+
+        Ok(Transferred {
+            tx_hashes: vec![TxHash(transfer.tx_hash)],
+            amount,
+            fee,
+        })
+    }
+
+    /// Get the balance of the account used by this wallet.
+    pub async fn get_balance(&self) -> Result<Amount> {
+        let amount = self
             .inner
             .lock()
             .await
-            .sweep_all(address.to_string())
-            .await?;
+            .get_balance(self.account_index)
+            .await?
+            .balance;
 
-        let tx_hashes = sweep_all.tx_hash_list.into_iter().map(TxHash).collect();
-        Ok(tx_hashes)
+        Ok(Amount::from_piconero(amount))
     }
 
-    /// Get the balance of the primary account.
-    pub async fn get_balance(&self) -> Result<Amount> {
-        let amount = self.inner.lock().await.get_balance(0).await?.balance;
+    /// Get the balance of the account used by this wallet that can be spent
+    /// right away, i.e. without outputs that are still locked.
+    pub async fn get_unlocked_balance(&self) -> Result<Amount> {
+        let amount = self
+            .inner
+            .lock()
+            .await
+            .get_balance(self.account_index)
+            .await?
+            .unlocked_balance;
 
         Ok(Amount::from_piconero(amount))
     }
 
+    /// Creates a new subaddress in the account used by this wallet.
+    ///
+    /// The label is stored in the wallet file, which allows telling deposits
+    /// from different sources apart later on.
+    pub async fn create_subaddress(&self, label: String) -> Result<Subaddress> {
+        let created = self
+            .inner
+            .lock()
+            .await
+            .create_address(self.account_index, label.clone())
+            .await?;
+
+        Ok(Subaddress {
+            index: created.address_index,
+            label,
+            address: Address::from_str(&created.address)?,
+            balance: Amount::ZERO,
+            unlocked_balance: Amount::ZERO,
+        })
+    }
+
+    /// Lists all subaddresses of the account used by this wallet with their
+    /// balances, starting with the main address at index 0.
+    pub async fn subaddresses(&self) -> Result<Vec<Subaddress>> {
+        let inner = self.inner.lock().await;
+        let addresses = inner.get_address(self.account_index).await?.addresses;
+        let balances = inner.get_balance(self.account_index).await?.per_subaddress;
+
+        addresses
+            .into_iter()
+            .map(|subaddress| {
+                let balance = balances
+                    .iter()
+                    .find(|balance| balance.address_index == subaddress.address_index);
+
+                Ok(Subaddress {
+                    index: subaddress.address_index,
+                    label: subaddress.label,
+                    address: Address::from_str(&subaddress.address)?,
+                    balance: Amount::from_piconero(balance.map_or(0, |b| b.balance)),
+                    unlocked_balance: Amount::from_piconero(
+                        balance.map_or(0, |b| b.unlocked_balance),
+                    ),
+                })
+            })
+            .collect()
+    }
+
     pub async fn block_height(&self) -> Result<BlockHeight> {
+        if let Some(daemon_pool) = &self.daemon_pool {
+            if let Some(height) = daemon_pool.lock().await.height().await {
+                return Ok(BlockHeight {
+                    height: u32::try_from(height)?,
+                });
+            }
+        }
+
+        Ok(self.inner.lock().await.get_height().await?)
+    }
+
+    /// The height up to which the loaded wallet scanned the blockchain.
+    ///
+    /// Unlike [`Wallet::block_height`] this is never the height of a daemon,
+    /// the wallet may lag behind it.
+    pub async fn get_height(&self) -> Result<BlockHeight> {
         Ok(self.inner.lock().await.get_height().await?)
     }
 
@@ -267,11 +756,125 @@ impl Wallet {
         self.main_address
     }
 
+    /// Refreshes the loaded wallet until it holds at least `min_balance`.
+    ///
+    /// A wallet that was just generated from keys may not show its funds
+    /// after the first refresh if the daemon is slow. The balance is read
+    /// again with backoff until `timeout` passed, then [`BalanceNotReached`]
+    /// tells whether the wallet is still syncing or the funds are missing.
+    pub async fn wait_for_balance(&self, min_balance: Amount, timeout: Duration) -> Result<Amount> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut backoff = self.refresh_backoff;
+
+        loop {
+            let inner = self.inner.lock().await;
+
+            let refreshed =
+                refresh_with_retry(&*inner, self.refresh_retries, self.refresh_backoff).await?;
+            let balance = Amount::from_piconero(inner.get_balance(0).await?.balance);
+            if balance >= min_balance {
+                return Ok(balance);
+            }
+
+            if tokio::time::Instant::now() + backoff > deadline {
+                let sync_status = match &self.daemons {
+                    Some(daemons) => sync_status(&*inner, daemons).await,
+                    None => None,
+                };
+
+                return Err(BalanceNotReached::diagnose(
+                    balance,
+                    min_balance,
+                    sync_status,
+                    refreshed.blocks_fetched,
+                )
+                .into());
+            }
+            drop(inner);
+
+            tracing::info!(
+                %balance,
+                expected = %min_balance,
+                "Monero wallet does not hold the expected balance yet, refreshing again in {}s",
+                backoff.as_secs()
+            );
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.sync_interval);
+        }
+    }
+
+    /// Refreshes the wallet, retrying with backoff if the `monero-wallet-rpc`
+    /// or its daemon cannot be reached or is busy.
     pub async fn refresh(&self) -> Result<Refreshed> {
-        Ok(self.inner.lock().await.refresh().await?)
+        let inner = self.inner.lock().await;
+
+        refresh_with_retry(&*inner, self.refresh_retries, self.refresh_backoff).await
     }
 }
 
+/// The index of the additional `monero-wallet-rpc` that the wallet
+/// `file_name` of a swap is loaded in, stable across restarts.
+fn swap_wallet_rpc_index(file_name: &str, swap_wallet_rpcs: usize) -> usize {
+    let hash = file_name.bytes().fold(0usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(usize::from(byte))
+    });
+
+    hash % swap_wallet_rpcs
+}
+
+/// A locked `monero-wallet-rpc` for loading the wallet of a swap.
+struct SwapWalletRpc<'a> {
+    client: MutexGuard<'a, wallet::Client>,
+    /// The name of the main wallet if it is loaded in this
+    /// `monero-wallet-rpc`, i.e. no additional ones are configured.
+    main_wallet: Option<&'a String>,
+}
+
+impl SwapWalletRpc<'_> {
+    /// Closes the main wallet so it saves its state before the wallet of the
+    /// swap is loaded. Additional `monero-wallet-rpc`s have no wallet loaded.
+    async fn unload_main_wallet(&self) -> Result<()> {
+        if self.main_wallet.is_some() {
+            let _ = self.client.close_wallet().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Re-opens the main wallet, or closes the wallet of the swap in an
+    /// additional `monero-wallet-rpc` so it saves its state.
+    async fn restore(&self) -> Result<()> {
+        match self.main_wallet {
+            Some(name) => {
+                let _ = self.client.open_wallet(name.clone()).await?;
+            }
+            None => {
+                let _ = self.client.close_wallet().await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subaddress {
+    pub index: u32,
+    pub label: String,
+    pub address: Address,
+    pub balance: Amount,
+    pub unlocked_balance: Amount,
+}
+
+/// The keys a wallet is generated from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WalletKeys {
+    pub spend_key: PrivateKey,
+    pub view_key: PrivateViewKey,
+    pub restore_height: BlockHeight,
+}
+
 #[derive(Debug)]
 pub struct TransferRequest {
     pub public_spend_key: PublicKey,
@@ -290,6 +893,8 @@ pub struct WatchRequest {
 
 async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync>(
     client: &Mutex<C>,
+    daemons: Option<&Mutex<Daemons>>,
+    daemon_pool: Option<&Mutex<DaemonPool>>,
     transfer_proof: TransferProof,
     to_address: Address,
     expected: Amount,
@@ -298,13 +903,40 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
     wallet_name: String,
 ) -> Result<(), InsufficientFunds> {
     let mut seen_confirmations = 0u64;
+    let mut not_found = None;
+    let mut amount_checked = false;
+    let mut lock_height = None;
+    let mut scan_lag_logged = false;
 
     while seen_confirmations < conf_target {
         check_interval.tick().await; // tick() at the beginning of the loop so every `continue` tick()s as well
 
         let txid = transfer_proof.tx_hash().to_string();
+
+        // Only the wallet can check the amount with the tx key, once it did so
+        // the confirmations can be fetched from any daemon of the pool.
+        if let (true, Some(daemon_pool)) = (amount_checked, daemon_pool) {
+            if let Some(confirmations) = daemon_pool.lock().await.tx_confirmations(&txid).await {
+                record_confirmations(&txid, confirmations, &mut seen_confirmations, conf_target);
+                continue;
+            }
+        }
+
+        // Checking the proof before the wallet scanned the block of the lock
+        // transaction can report it as not found, so we wait for the wallet
+        // if a daemon tells us the block.
+        if !amount_checked && lock_height.is_none() {
+            lock_height = lock_block_height(daemons, daemon_pool, &txid).await;
+        }
+
         let client = client.lock().await;
 
+        if let (false, Some(lock_height)) = (amount_checked, lock_height) {
+            if !wallet_scanned(&*client, lock_height, &txid, &mut scan_lag_logged).await {
+                continue;
+            }
+        }
+
         let tx = match client
             .check_tx_key(
                 txid.clone(),
@@ -315,7 +947,14 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
         {
             Ok(proof) => proof,
             Err(jsonrpc::Error::JsonRpc(jsonrpc::JsonRpcError { code: -1, .. })) => {
-                tracing::warn!(%txid, "`monero-wallet-rpc` failed to fetch transaction, may need to be restarted");
+                match daemons {
+                    Some(daemons) => {
+                        explain_missing_transaction(&*client, daemons, &txid, &mut not_found).await
+                    }
+                    None => {
+                        tracing::warn!(%txid, "`monero-wallet-rpc` failed to fetch transaction, may need to be restarted")
+                    }
+                }
                 continue;
             }
             // TODO: Implement this using a generic proxy for each function call once https://github.com/thomaseizinger/rust-jsonrpc-client/issues/47 is fixed.
@@ -332,6 +971,9 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
                     %txid,
                     "Failed to retrieve tx from blockchain: {:#}", other
                 );
+                if let Some(daemons) = daemons {
+                    explain_missing_transaction(&*client, daemons, &txid, &mut not_found).await;
+                }
                 continue; // treating every error as transient and retrying
                           // is obviously wrong but the jsonrpc client is
                           // too primitive to differentiate between all the
@@ -339,6 +981,8 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
             }
         };
 
+        not_found = None;
+
         let received = Amount::from_piconero(tx.received);
 
         if received != expected {
@@ -348,20 +992,209 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
             });
         }
 
-        if tx.confirmations > seen_confirmations {
-            seen_confirmations = tx.confirmations;
-            tracing::info!(
-                %txid,
-                %seen_confirmations,
-                needed_confirmations = %conf_target,
-                "Received new confirmation for Monero lock tx"
-            );
-        }
+        amount_checked = true;
+        record_confirmations(
+            &txid,
+            tx.confirmations,
+            &mut seen_confirmations,
+            conf_target,
+        );
     }
 
     Ok(())
 }
 
+fn record_confirmations(
+    txid: &str,
+    confirmations: u64,
+    seen_confirmations: &mut u64,
+    conf_target: u64,
+) {
+    if confirmations > *seen_confirmations {
+        *seen_confirmations = confirmations;
+        tracing::info!(
+            %txid,
+            %seen_confirmations,
+            needed_confirmations = %conf_target,
+            "Received new confirmation for Monero lock tx"
+        );
+    }
+}
+
+/// The height of the block the transaction is in according to the daemons,
+/// `None` if it is not mined or there is no daemon to ask.
+async fn lock_block_height(
+    daemons: Option<&Mutex<Daemons>>,
+    daemon_pool: Option<&Mutex<DaemonPool>>,
+    txid: &str,
+) -> Option<u64> {
+    if let Some(daemon_pool) = daemon_pool {
+        if let Some(block_height) = daemon_pool.lock().await.tx_block_height(txid).await {
+            return Some(block_height);
+        }
+    }
+
+    let daemons = daemons?.lock().await;
+    match daemons.current().tx_block_height(txid).await {
+        Ok(block_height) => block_height,
+        Err(error) => {
+            tracing::debug!(%txid, "{:#}", error);
+            None
+        }
+    }
+}
+
+/// Whether the wallet scanned the block at `lock_height`, logs once that we
+/// wait for the wallet otherwise.
+async fn wallet_scanned<C>(client: &C, lock_height: u64, txid: &str, logged: &mut bool) -> bool
+where
+    C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync,
+{
+    // The height of the wallet is the number of blocks it scanned, i.e. one
+    // more than the height of the last block it scanned.
+    let wallet_height = match client.get_height().await {
+        Ok(height) => u64::from(height.height),
+        Err(error) => {
+            tracing::debug!(%txid, "Failed to get height of monero wallet: {:#}", error);
+            return false;
+        }
+    };
+
+    if wallet_height > lock_height {
+        return true;
+    }
+
+    if !*logged {
+        *logged = true;
+        tracing::info!(
+            %txid,
+            %wallet_height,
+            %lock_height,
+            "Waiting for the Monero wallet to scan the block of the lock transaction"
+        );
+    }
+
+    false
+}
+
+async fn refresh_with_retry<C>(
+    client: &C,
+    max_retries: u32,
+    initial_backoff: Duration,
+) -> Result<Refreshed>
+where
+    C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync,
+{
+    let mut backoff = initial_backoff;
+    let mut retries = 0;
+
+    loop {
+        match client.refresh().await {
+            Ok(refreshed) => return Ok(refreshed),
+            Err(error) if retries < max_retries && is_transient(&error) => {
+                retries += 1;
+
+                tracing::warn!(
+                    %retries,
+                    %max_retries,
+                    "Failed to refresh Monero wallet, retrying in {} ms: {:#}",
+                    backoff.as_millis(),
+                    error
+                );
+
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(error) => return Err(error).context("Failed to refresh Monero wallet"),
+        }
+    }
+}
+
+/// Whether an error of the `monero-wallet-rpc` is worth retrying.
+///
+/// Only failed connections, timeouts and the wallet reporting a busy or
+/// unreachable daemon are transient, everything else would fail again.
+pub(crate) fn is_transient(error: &jsonrpc::Error<reqwest::Error>) -> bool {
+    const DAEMON_IS_BUSY: i64 = -3;
+    const NO_DAEMON_CONNECTION: i64 = -38;
+
+    match error {
+        jsonrpc::Error::Client(error) => error.is_connect() || error.is_timeout(),
+        jsonrpc::Error::JsonRpc(jsonrpc::JsonRpcError { code, .. }) => {
+            *code == DAEMON_IS_BUSY || *code == NO_DAEMON_CONNECTION
+        }
+        _ => false,
+    }
+}
+
+/// Whether the current daemon and the wallet are synced, `None` if either
+/// cannot be asked.
+async fn sync_status<C>(client: &C, daemons: &Mutex<Daemons>) -> Option<NotFound>
+where
+    C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync,
+{
+    let daemon_status = daemons.lock().await.current().sync_status().await.ok()?;
+    let wallet_height = client.get_height().await.ok()?.height;
+
+    Some(NotFound::diagnose(daemon_status, u64::from(wallet_height)))
+}
+
+/// Figures out why a transaction could not be found and logs the reason
+/// whenever it changes. If the daemon is not synced we fail over to another
+/// daemon, if there is a synced one.
+async fn explain_missing_transaction<C>(
+    client: &C,
+    daemons: &Mutex<Daemons>,
+    txid: &str,
+    previous: &mut Option<NotFound>,
+) where
+    C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync,
+{
+    let mut daemons = daemons.lock().await;
+
+    let daemon_status = match daemons.current().sync_status().await {
+        Ok(status) => status,
+        Err(error) => {
+            tracing::debug!(%txid, "{:#}", error);
+            return;
+        }
+    };
+    let wallet_height = match client.get_height().await {
+        Ok(height) => u64::from(height.height),
+        Err(error) => {
+            tracing::debug!(%txid, "Failed to get height of monero wallet: {:#}", error);
+            return;
+        }
+    };
+
+    let current = NotFound::diagnose(daemon_status, wallet_height);
+    if previous.as_ref().map(mem::discriminant) == Some(mem::discriminant(&current)) {
+        return;
+    }
+    *previous = Some(current);
+
+    match current {
+        NotFound::DaemonSyncing { .. } => {
+            tracing::warn!(%txid, daemon = %daemons.current().address(), "{}", current);
+
+            if let Some(daemon) = daemons.fail_over().await {
+                if let Err(error) = client.set_daemon(daemon.address().to_owned()).await {
+                    tracing::warn!(
+                        %txid,
+                        "Failed to connect monero wallet to daemon {}: {:#}",
+                        daemon.address(),
+                        error
+                    );
+                }
+                *previous = None;
+            }
+        }
+        NotFound::WalletSyncing { .. } | NotFound::NotOnBlockchain => {
+            tracing::info!(%txid, "{}", current)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -370,6 +1203,21 @@ mod tests {
     use std::sync::atomic::{AtomicU32, Ordering};
     use tracing::metadata::LevelFilter;
 
+    #[test]
+    fn wallets_of_swaps_are_spread_across_swap_wallet_rpcs() {
+        let indices = (0..6)
+            .map(|i| {
+                let file_name = format!("ea030832-3be9-454f-bb98-5ea9a78840{:02}", i);
+                let index = swap_wallet_rpc_index(&file_name, 3);
+                assert_eq!(swap_wallet_rpc_index(&file_name, 3), index);
+
+                index
+            })
+            .collect::<std::collections::HashSet<_>>();
+
+        assert_eq!(indices.len(), 3);
+    }
+
     #[tokio::test]
     async fn given_exact_confirmations_does_not_fetch_tx_again() {
         let client = Mutex::new(DummyClient::new(vec![Ok(CheckTxKey {
@@ -379,6 +1227,8 @@ mod tests {
 
         let result = wait_for_confirmations(
             &client,
+            None,
+            None,
             TransferProof::new(TxHash("<FOO>".to_owned()), PrivateKey {
                 scalar: crate::monero::Scalar::random(&mut rand::thread_rng())
             }),
@@ -430,6 +1280,8 @@ mod tests {
 
         wait_for_confirmations(
             &client,
+            None,
+            None,
             TransferProof::new(TxHash("<FOO>".to_owned()), PrivateKey {
                 scalar: crate::monero::Scalar::random(&mut rand::thread_rng())
             }),
@@ -477,6 +1329,8 @@ mod tests {
 
         wait_for_confirmations(
             &client,
+            None,
+            None,
             TransferProof::new(TxHash("<FOO>".to_owned()), PrivateKey {
                 scalar: crate::monero::Scalar::random(&mut rand::thread_rng())
             }),
@@ -507,6 +1361,60 @@ DEBUG swap::monero::wallet: Opening wallet `foo-wallet` because no wallet is loa
         );
     }
 
+    #[tokio::test]
+    async fn retries_refresh_if_daemon_is_busy() {
+        let client = RefreshClient::new(vec![
+            Err((-3, "daemon is busy".to_owned())),
+            Err((-38, "no connection to daemon".to_owned())),
+            Ok(Refreshed {
+                blocks_fetched: 10,
+                received_money: true,
+            }),
+        ]);
+
+        let refreshed = refresh_with_retry(&client, 3, Duration::from_millis(1))
+            .await
+            .unwrap();
+
+        assert_eq!(refreshed.blocks_fetched, 10);
+        assert_eq!(client.invocations.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_refresh_after_max_retries() {
+        let client = RefreshClient::new(vec![
+            Err((-3, "daemon is busy".to_owned())),
+            Err((-3, "daemon is busy".to_owned())),
+            Err((-3, "daemon is busy".to_owned())),
+        ]);
+
+        let result = refresh_with_retry(&client, 2, Duration::from_millis(1)).await;
+
+        assert!(result.is_err());
+        assert_eq!(client.invocations.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_definitive_refresh_errors() {
+        let client = RefreshClient::new(vec![Err((-13, "No wallet file".to_owned()))]);
+
+        let result = refresh_with_retry(&client, 3, Duration::from_millis(1)).await;
+
+        assert!(result.is_err());
+        assert_eq!(client.invocations.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn proof_is_only_checked_after_wallet_scanned_lock_block() {
+        let client = HeightClient::new(vec![99, 100, 101]);
+        let mut logged = false;
+
+        assert!(!wallet_scanned(&client, 100, "<FOO>", &mut logged).await);
+        assert!(!wallet_scanned(&client, 100, "<FOO>", &mut logged).await);
+        assert!(wallet_scanned(&client, 100, "<FOO>", &mut logged).await);
+        assert!(logged);
+    }
+
     type ErrorCode = i64;
     type ErrorMessage = String;
 
@@ -569,4 +1477,84 @@ DEBUG swap::monero::wallet: Opening wallet `foo-wallet` because no wallet is loa
             todo!()
         }
     }
+
+    struct HeightClient {
+        heights: Vec<u32>,
+
+        invocations: AtomicU32,
+    }
+
+    impl HeightClient {
+        fn new(heights: Vec<u32>) -> Self {
+            Self {
+                heights,
+                invocations: Default::default(),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> for HeightClient {
+        async fn get_height(
+            &self,
+        ) -> Result<BlockHeight, monero_rpc::jsonrpc::Error<reqwest::Error>> {
+            let index = self.invocations.fetch_add(1, Ordering::SeqCst);
+
+            Ok(BlockHeight {
+                height: self.heights[index as usize],
+            })
+        }
+
+        async fn send_request<P>(
+            &self,
+            _: String,
+        ) -> Result<monero_rpc::jsonrpc::Response<P>, reqwest::Error>
+        where
+            P: serde::de::DeserializeOwned,
+        {
+            todo!()
+        }
+    }
+
+    struct RefreshClient {
+        responses: Vec<Result<Refreshed, (ErrorCode, ErrorMessage)>>,
+
+        invocations: AtomicU32,
+    }
+
+    impl RefreshClient {
+        fn new(responses: Vec<Result<Refreshed, (ErrorCode, ErrorMessage)>>) -> Self {
+            Self {
+                responses,
+                invocations: Default::default(),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> for RefreshClient {
+        async fn refresh(&self) -> Result<Refreshed, monero_rpc::jsonrpc::Error<reqwest::Error>> {
+            let index = self.invocations.fetch_add(1, Ordering::SeqCst);
+
+            self.responses[index as usize]
+                .clone()
+                .map_err(|(code, message)| {
+                    monero_rpc::jsonrpc::Error::JsonRpc(monero_rpc::jsonrpc::JsonRpcError {
+                        code,
+                        message,
+                        data: None,
+                    })
+                })
+        }
+
+        async fn send_request<P>(
+            &self,
+            _: String,
+        ) -> Result<monero_rpc::jsonrpc::Response<P>, reqwest::Error>
+        where
+            P: serde::de::DeserializeOwned,
+        {
+            todo!()
+        }
+    }
 }
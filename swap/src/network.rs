@@ -1,6 +1,7 @@
 mod impl_from_rr_event;
 
 pub mod cbor_request_response;
+pub mod compact_address;
 pub mod encrypted_signature;
 pub mod json_pull_codec;
 pub mod quote;
@@ -14,3 +15,20 @@ pub mod transport;
 
 #[cfg(any(test, feature = "test"))]
 pub mod test;
+
+use libp2p::request_response::RequestResponseConfig;
+use std::time::Duration;
+
+/// A request-response config whose requests fail if no response arrived
+/// within `timeout`.
+///
+/// Every protocol picks its own timeout because the time it takes the peer to
+/// respond differs widely, from a quote that is answered right away to a
+/// transfer proof that is only acknowledged once the peer's swap processed
+/// it.
+pub fn request_response_config(timeout: Duration) -> RequestResponseConfig {
+    let mut config = RequestResponseConfig::default();
+    config.set_request_timeout(timeout);
+
+    config
+}
@@ -0,0 +1,146 @@
+//! A compact textual encoding of seller addresses.
+//!
+//! Full multiaddrs like `/onion3/<56 chars>:9939/p2p/<52 chars>` mix upper and
+//! lower case characters and are error-prone to type or paste. The compact
+//! encoding is the prefix [`PREFIX`] followed by the base32 encoded binary
+//! representation of the multiaddr and a 4 byte checksum. It only consists of
+//! characters of the QR code alphanumeric mode, is decoded case-insensitively
+//! and mistyped addresses are detected through the checksum.
+
+use anyhow::{bail, Context, Result};
+use data_encoding::BASE32_NOPAD;
+use libp2p::Multiaddr;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+pub const PREFIX: &str = "XMRSELLER:";
+
+const CHECKSUM_LENGTH: usize = 4;
+
+/// Encodes the given address in the compact format.
+pub fn encode(address: &Multiaddr) -> String {
+    let mut bytes = address.to_vec();
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum);
+
+    format!("{}{}", PREFIX, BASE32_NOPAD.encode(&bytes))
+}
+
+/// Decodes an address in the compact format into the full multiaddr.
+pub fn decode(compact: &str) -> Result<Multiaddr> {
+    let compact = compact.trim().to_uppercase();
+    let encoded = match compact.strip_prefix(PREFIX) {
+        Some(encoded) => encoded,
+        None => bail!("Compact seller address must start with {}", PREFIX),
+    };
+
+    let bytes = BASE32_NOPAD
+        .decode(encoded.as_bytes())
+        .context("Failed to decode compact seller address")?;
+
+    if bytes.len() <= CHECKSUM_LENGTH {
+        bail!("Compact seller address is too short");
+    }
+
+    let (address, expected_checksum) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+    if checksum(address) != expected_checksum {
+        bail!(
+            "Checksum of compact seller address does not match, the address was probably mistyped"
+        );
+    }
+
+    let address = Multiaddr::try_from(address.to_vec())
+        .context("Compact seller address does not contain a valid multiaddr")?;
+
+    Ok(address)
+}
+
+/// Parses a seller address given either as full multiaddr or in the compact
+/// format.
+pub fn parse_seller_address(s: &str) -> Result<Multiaddr> {
+    if s.trim().to_uppercase().starts_with(PREFIX) {
+        return decode(s);
+    }
+
+    let address = Multiaddr::from_str(s).context("Failed to parse seller address")?;
+
+    Ok(address)
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let hash = Sha256::digest(bytes);
+
+    let mut checksum = [0u8; CHECKSUM_LENGTH];
+    checksum.copy_from_slice(&hash[..CHECKSUM_LENGTH]);
+
+    checksum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONION_ADDRESS: &str = "/onion3/oarchy4tamydxcitaki6bc2v4leza6v35iezmu2chg2bap63sv6f2did:1024/p2p/12D3KooWPD4uHN74SHotLN7VCH7Fm8zZgaNVymYcpeF1fpD2guc9";
+    const TCP_ADDRESS: &str =
+        "/ip4/127.0.0.1/tcp/9939/p2p/12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
+
+    #[test]
+    fn compact_address_roundtrip() {
+        for address in &[ONION_ADDRESS, TCP_ADDRESS] {
+            let address = Multiaddr::from_str(address).unwrap();
+
+            let compact = encode(&address);
+
+            assert!(compact.starts_with(PREFIX));
+            assert_eq!(decode(&compact).unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn compact_address_is_qr_alphanumeric() {
+        let address = Multiaddr::from_str(ONION_ADDRESS).unwrap();
+
+        let compact = encode(&address);
+
+        assert!(compact
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == ':'));
+    }
+
+    #[test]
+    fn compact_address_is_decoded_case_insensitive() {
+        let address = Multiaddr::from_str(TCP_ADDRESS).unwrap();
+
+        let compact = encode(&address).to_lowercase();
+
+        assert_eq!(decode(&compact).unwrap(), address);
+    }
+
+    #[test]
+    fn mistyped_compact_address_is_rejected() {
+        let address = Multiaddr::from_str(TCP_ADDRESS).unwrap();
+        let compact = encode(&address);
+
+        let position = PREFIX.len() + 10;
+        let original = compact.as_bytes()[position];
+        let replacement = if original == b'A' { "B" } else { "A" };
+        let mistyped = format!(
+            "{}{}{}",
+            &compact[..position],
+            replacement,
+            &compact[position + 1..]
+        );
+
+        assert!(decode(&mistyped).is_err());
+    }
+
+    #[test]
+    fn parse_seller_address_accepts_both_formats() {
+        let address = Multiaddr::from_str(TCP_ADDRESS).unwrap();
+
+        assert_eq!(parse_seller_address(TCP_ADDRESS).unwrap(), address);
+        assert_eq!(parse_seller_address(&encode(&address)).unwrap(), address);
+        assert!(parse_seller_address("not an address").is_err());
+    }
+}
@@ -1,15 +1,22 @@
 use crate::network::cbor_request_response::CborCodec;
+use crate::network::request_response_config;
 use crate::{asb, cli};
 use libp2p::core::ProtocolName;
 use libp2p::request_response::{
-    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
-    RequestResponseMessage,
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use uuid::Uuid;
 
 const PROTOCOL: &str = "/comit/xmr/btc/encrypted_signature/1.0.0";
+
+/// How long Bob waits for Alice to acknowledge the encrypted signature. Alice
+/// acknowledges it as soon as her swap, which is already waiting for it,
+/// received it.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
 type OutEvent = RequestResponseEvent<Request, ()>;
 type Message = RequestResponseMessage<Request, ()>;
 
@@ -30,19 +37,19 @@ pub struct Request {
     pub tx_redeem_encsig: crate::bitcoin::EncryptedSignature,
 }
 
-pub fn alice() -> Behaviour {
+pub fn alice(timeout: Duration) -> Behaviour {
     Behaviour::new(
         CborCodec::default(),
         vec![(EncryptedSignatureProtocol, ProtocolSupport::Inbound)],
-        RequestResponseConfig::default(),
+        request_response_config(timeout),
     )
 }
 
-pub fn bob() -> Behaviour {
+pub fn bob(timeout: Duration) -> Behaviour {
     Behaviour::new(
         CborCodec::default(),
         vec![(EncryptedSignatureProtocol, ProtocolSupport::Outbound)],
-        RequestResponseConfig::default(),
+        request_response_config(timeout),
     )
 }
 
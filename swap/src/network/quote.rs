@@ -1,25 +1,56 @@
 use crate::network::json_pull_codec::JsonPullCodec;
+use crate::network::request_response_config;
 use crate::{asb, bitcoin, cli};
+use async_trait::async_trait;
+use futures::prelude::*;
 use libp2p::core::ProtocolName;
 use libp2p::request_response::{
-    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
+    ProtocolSupport, RequestResponse, RequestResponseCodec, RequestResponseEvent,
     RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use std::io;
+use std::time::Duration;
+
+const PROTOCOL: &str = "/comit/xmr/btc/bid-quote";
+const PROTOCOL_V1: &str = "/comit/xmr/btc/bid-quote/1.0.0";
+const PROTOCOL_V2: &str = "/comit/xmr/btc/bid-quote/2.0.0";
+
+/// How long a quote of the ASB is considered current.
+pub const QUOTE_VALIDITY: Duration = Duration::from_secs(60);
+
+/// How much later than the expiry of a quote our clock may be for the quote
+/// to still be considered current if not configured otherwise. The clocks of
+/// the ASB and the CLI are never quite in sync.
+pub const DEFAULT_CLOCK_SKEW_TOLERANCE: Duration = Duration::from_secs(120);
+
+/// How long the CLI waits for a quote. The ASB answers right away, so a peer
+/// that does not respond within seconds is considered dead.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
 
-const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.0.0";
 pub type OutEvent = RequestResponseEvent<(), BidQuote>;
 pub type Message = RequestResponseMessage<(), BidQuote>;
 
-pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, BidQuote>>;
+pub type Behaviour = RequestResponse<BidQuoteCodec>;
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct BidQuoteProtocol;
+/// The versions of the quote protocol.
+///
+/// Both versions share the same message, but [`BidQuote::quote_expiry`] is
+/// only exchanged with version 2, see [`BidQuoteCodec`]. Nodes support both versions and prefer
+/// version 2, so they keep working with peers that only know version 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BidQuoteProtocol {
+    V1,
+    V2,
+}
 
 impl ProtocolName for BidQuoteProtocol {
     fn protocol_name(&self) -> &[u8] {
-        PROTOCOL.as_bytes()
+        match self {
+            BidQuoteProtocol::V1 => PROTOCOL_V1.as_bytes(),
+            BidQuoteProtocol::V2 => PROTOCOL_V2.as_bytes(),
+        }
     }
 }
 
@@ -35,17 +66,133 @@ pub struct BidQuote {
     /// The maximum quantity the maker is willing to buy.
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub max_quantity: bitcoin::Amount,
+    /// The Unix timestamp in seconds after which the price is likely outdated
+    /// and a new quote should be requested.
+    ///
+    /// Not sent by makers that only speak version 1 of the protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_expiry: Option<u64>,
+}
+
+impl BidQuote {
+    /// Whether the quote expired at the Unix timestamp `now` in seconds, by
+    /// more than `clock_skew_tolerance`.
+    ///
+    /// Quotes without expiry never expire.
+    pub fn is_expired(&self, now: u64, clock_skew_tolerance: Duration) -> bool {
+        let expiry = match self.quote_expiry {
+            Some(expiry) if now > expiry => expiry,
+            _ => return false,
+        };
+
+        let expired_for = now - expiry;
+        if expired_for > clock_skew_tolerance.as_secs() {
+            return true;
+        }
+
+        tracing::info!(
+            expired_secs_ago = expired_for,
+            clock_skew_tolerance_secs = clock_skew_tolerance.as_secs(),
+            "Quote expired {} seconds ago, still considered current within the clock skew tolerance",
+            expired_for
+        );
+
+        false
+    }
+
+    /// The quote as exchanged with `protocol`.
+    fn for_protocol(self, protocol: BidQuoteProtocol) -> Self {
+        match protocol {
+            BidQuoteProtocol::V1 => Self {
+                quote_expiry: None,
+                ..self
+            },
+            BidQuoteProtocol::V2 => self,
+        }
+    }
+}
+
+/// Encodes quotes like [`JsonPullCodec`], but drops the expiry of quotes
+/// exchanged with version 1 of the protocol.
+#[derive(Clone, Debug, Default)]
+pub struct BidQuoteCodec {
+    inner: JsonPullCodec<BidQuoteProtocol, BidQuote>,
+}
+
+#[async_trait]
+impl RequestResponseCodec for BidQuoteCodec {
+    type Protocol = BidQuoteProtocol;
+    type Request = ();
+    type Response = BidQuote;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        self.inner.read_request(protocol, io).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let quote = self.inner.read_response(protocol, io).await?;
+
+        Ok(quote.for_protocol(*protocol))
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        request: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        self.inner.write_request(protocol, io, request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        quote: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        self.inner
+            .write_response(protocol, io, quote.for_protocol(*protocol))
+            .await
+    }
+}
+
+/// The expiry of a quote made at the Unix timestamp `now` in seconds.
+pub fn quote_expiry(now: u64) -> u64 {
+    now + QUOTE_VALIDITY.as_secs()
 }
 
 /// Constructs a new instance of the `quote` behaviour to be used by the ASB.
 ///
 /// The ASB is always listening and only supports inbound connections, i.e.
 /// handing out quotes.
-pub fn asb() -> Behaviour {
+pub fn asb(timeout: Duration) -> Behaviour {
     Behaviour::new(
-        JsonPullCodec::default(),
-        vec![(BidQuoteProtocol, ProtocolSupport::Inbound)],
-        RequestResponseConfig::default(),
+        BidQuoteCodec::default(),
+        vec![
+            (BidQuoteProtocol::V2, ProtocolSupport::Inbound),
+            (BidQuoteProtocol::V1, ProtocolSupport::Inbound),
+        ],
+        request_response_config(timeout),
     )
 }
 
@@ -53,11 +200,14 @@ pub fn asb() -> Behaviour {
 ///
 /// The CLI is always dialing and only supports outbound connections, i.e.
 /// requesting quotes.
-pub fn cli() -> Behaviour {
+pub fn cli(timeout: Duration) -> Behaviour {
     Behaviour::new(
-        JsonPullCodec::default(),
-        vec![(BidQuoteProtocol, ProtocolSupport::Outbound)],
-        RequestResponseConfig::default(),
+        BidQuoteCodec::default(),
+        vec![
+            (BidQuoteProtocol::V2, ProtocolSupport::Outbound),
+            (BidQuoteProtocol::V1, ProtocolSupport::Outbound),
+        ],
+        request_response_config(timeout),
     )
 }
 
@@ -86,3 +236,80 @@ impl From<(PeerId, Message)> for cli::OutEvent {
     }
 }
 crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_of_version_1_deserializes_without_expiry() {
+        let quote = serde_json::from_str::<BidQuote>(
+            r#"{"price":1000,"min_quantity":10,"max_quantity":100}"#,
+        )
+        .unwrap();
+
+        assert_eq!(quote.quote_expiry, None);
+        assert!(!quote.is_expired(u64::MAX, Duration::ZERO));
+    }
+
+    #[test]
+    fn quote_expiry_roundtrips_and_expires() {
+        let quote = BidQuote {
+            price: bitcoin::Amount::from_sat(1000),
+            min_quantity: bitcoin::Amount::from_sat(10),
+            max_quantity: bitcoin::Amount::from_sat(100),
+            quote_expiry: Some(quote_expiry(1_000)),
+        };
+
+        let serialized = serde_json::to_string(&quote).unwrap();
+        let deserialized = serde_json::from_str::<BidQuote>(&serialized).unwrap();
+
+        assert_eq!(deserialized, quote);
+        assert!(!quote.is_expired(1_060, Duration::ZERO));
+        assert!(quote.is_expired(1_061, Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn quote_expiry_is_only_sent_with_version_2() {
+        let quote = BidQuote {
+            price: bitcoin::Amount::from_sat(1000),
+            min_quantity: bitcoin::Amount::from_sat(10),
+            max_quantity: bitcoin::Amount::from_sat(100),
+            quote_expiry: Some(quote_expiry(1_000)),
+        };
+
+        for &(protocol, expected_expiry) in &[
+            (BidQuoteProtocol::V1, None),
+            (BidQuoteProtocol::V2, quote.quote_expiry),
+        ] {
+            let mut io = futures::io::Cursor::new(Vec::new());
+            BidQuoteCodec::default()
+                .write_response(&protocol, &mut io, quote)
+                .await
+                .unwrap();
+
+            // Read as raw JSON, i.e. without dropping what was sent
+            io.set_position(0);
+            let sent = JsonPullCodec::<BidQuoteProtocol, BidQuote>::default()
+                .read_response(&protocol, &mut io)
+                .await
+                .unwrap();
+
+            assert_eq!(sent.quote_expiry, expected_expiry);
+        }
+    }
+
+    #[test]
+    fn quote_expires_after_clock_skew_tolerance() {
+        let quote = BidQuote {
+            price: bitcoin::Amount::from_sat(1000),
+            min_quantity: bitcoin::Amount::from_sat(10),
+            max_quantity: bitcoin::Amount::from_sat(100),
+            quote_expiry: Some(1_000),
+        };
+        let tolerance = Duration::from_secs(120);
+
+        assert!(!quote.is_expired(1_120, tolerance));
+        assert!(quote.is_expired(1_121, tolerance));
+    }
+}
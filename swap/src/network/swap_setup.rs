@@ -112,3 +112,52 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The spot price is exchanged before the protocol version of the swap
+    // setup is known, so its encoding must never change.
+
+    const MAINNET: BlockchainNetwork = BlockchainNetwork {
+        bitcoin: bitcoin::Network::Bitcoin,
+        monero: monero::Network::Mainnet,
+    };
+
+    #[test]
+    fn spot_price_request_wire_format() {
+        let request = SpotPriceRequest {
+            btc: bitcoin::Amount::from_sat(100_000),
+            blockchain_network: MAINNET,
+        };
+
+        assert_eq!(
+            hex::encode(serde_cbor::to_vec(&request).unwrap()),
+            "a2636274631a000186a072626c6f636b636861696e5f6e6574776f726ba267626974636f696e674d61696e6e6574666d6f6e65726f674d61696e6e6574"
+        );
+    }
+
+    #[test]
+    fn spot_price_response_wire_format() {
+        let xmr = SpotPriceResponse::Xmr(monero::Amount::from_piconero(1_000_000_000_000));
+        let below_minimum = SpotPriceResponse::Error(SpotPriceError::AmountBelowMinimum {
+            min: bitcoin::Amount::from_sat(10_000),
+            buy: bitcoin::Amount::from_sat(5_000),
+        });
+        let no_swaps_accepted = SpotPriceResponse::Error(SpotPriceError::NoSwapsAccepted);
+
+        assert_eq!(
+            hex::encode(serde_cbor::to_vec(&xmr).unwrap()),
+            "a163586d721b000000e8d4a51000"
+        );
+        assert_eq!(
+            hex::encode(serde_cbor::to_vec(&below_minimum).unwrap()),
+            "a1654572726f72a172416d6f756e7442656c6f774d696e696d756da2636d696e19271063627579191388"
+        );
+        assert_eq!(
+            hex::encode(serde_cbor::to_vec(&no_swaps_accepted).unwrap()),
+            "a1654572726f726f4e6f53776170734163636570746564"
+        );
+    }
+}
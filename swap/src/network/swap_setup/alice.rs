@@ -1,4 +1,4 @@
-use crate::asb::LatestRate;
+use crate::asb::{LatestRate, MakerParamsUpdates};
 use crate::network::swap_setup;
 use crate::network::swap_setup::{
     protocol, BlockchainNetwork, SpotPriceError, SpotPriceRequest, SpotPriceResponse,
@@ -109,8 +109,7 @@ impl From<OutEvent> for asb::OutEvent {
 #[allow(missing_debug_implementations)]
 pub struct Behaviour<LR> {
     events: VecDeque<OutEvent>,
-    min_buy: bitcoin::Amount,
-    max_buy: bitcoin::Amount,
+    maker_params: MakerParamsUpdates,
     env_config: env::Config,
 
     latest_rate: LR,
@@ -119,16 +118,14 @@ pub struct Behaviour<LR> {
 
 impl<LR> Behaviour<LR> {
     pub fn new(
-        min_buy: bitcoin::Amount,
-        max_buy: bitcoin::Amount,
+        maker_params: MakerParamsUpdates,
         env_config: env::Config,
         latest_rate: LR,
         resume_only: bool,
     ) -> Self {
         Self {
             events: Default::default(),
-            min_buy,
-            max_buy,
+            maker_params,
             env_config,
             latest_rate,
             resume_only,
@@ -145,8 +142,7 @@ where
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
         Handler::new(
-            self.min_buy,
-            self.max_buy,
+            self.maker_params.clone(),
             self.env_config,
             self.latest_rate.clone(),
             self.resume_only,
@@ -200,8 +196,7 @@ pub struct Handler<LR> {
     inbound_stream: OptionFuture<InboundStream>,
     events: VecDeque<HandlerOutEvent>,
 
-    min_buy: bitcoin::Amount,
-    max_buy: bitcoin::Amount,
+    maker_params: MakerParamsUpdates,
     env_config: env::Config,
 
     latest_rate: LR,
@@ -213,8 +208,7 @@ pub struct Handler<LR> {
 
 impl<LR> Handler<LR> {
     fn new(
-        min_buy: bitcoin::Amount,
-        max_buy: bitcoin::Amount,
+        maker_params: MakerParamsUpdates,
         env_config: env::Config,
         latest_rate: LR,
         resume_only: bool,
@@ -222,8 +216,7 @@ impl<LR> Handler<LR> {
         Self {
             inbound_stream: OptionFuture::from(None),
             events: Default::default(),
-            min_buy,
-            max_buy,
+            maker_params,
             env_config,
             latest_rate,
             resume_only,
@@ -268,8 +261,11 @@ where
             Duration::from_secs(5),
         );
         let resume_only = self.resume_only;
-        let min_buy = self.min_buy;
-        let max_buy = self.max_buy;
+        // Read the parameters when the swap is set up so adjustments made while
+        // the ASB is running apply to new swaps only.
+        let maker_params = self.maker_params.latest();
+        let min_buy = maker_params.min_buy;
+        let max_buy = maker_params.max_buy;
         let latest_rate = self.latest_rate.latest_rate();
         let env_config = self.env_config;
 
@@ -318,6 +314,15 @@ where
                     });
                 }
 
+                let min_lock_amount = maker_params.min_lock_amount(wallet_snapshot.redeem_fee);
+                if btc < min_lock_amount {
+                    return Err(Error::AmountTooLowToRedeem {
+                        min: min_lock_amount,
+                        buy: btc,
+                        redeem_fee: wallet_snapshot.redeem_fee,
+                    });
+                }
+
                 let rate = latest_rate.map_err(|e| Error::LatestRateFetchFailed(Box::new(e)))?;
                 let xmr = rate
                     .sell_quote(btc)
@@ -479,6 +484,12 @@ pub enum Error {
         max: bitcoin::Amount,
         buy: bitcoin::Amount,
     },
+    #[error("Amount {buy} does not cover our redeem fee of {redeem_fee} with margin, at least {min} is required")]
+    AmountTooLowToRedeem {
+        min: bitcoin::Amount,
+        buy: bitcoin::Amount,
+        redeem_fee: bitcoin::Amount,
+    },
     #[error("Balance {balance} too low to fulfill swapping {buy}")]
     BalanceTooLow {
         balance: monero::Amount,
@@ -507,6 +518,12 @@ impl Error {
                 max: *max,
                 buy: *buy,
             },
+            // Reported as a minimum amount so CLIs that do not know about
+            // the redeem fee can still explain the rejection.
+            Error::AmountTooLowToRedeem { min, buy, .. } => SpotPriceError::AmountBelowMinimum {
+                min: *min,
+                buy: *buy,
+            },
             Error::BalanceTooLow { buy, .. } => SpotPriceError::BalanceTooLow { buy: *buy },
             Error::BlockchainNetworkMismatch { cli, asb } => {
                 SpotPriceError::BlockchainNetworkMismatch {
@@ -1,3 +1,4 @@
+use crate::cli::RateBounds;
 use crate::network::swap_setup::{
     protocol, read_cbor_message, write_cbor_message, BlockchainNetwork, SpotPriceError,
     SpotPriceRequest, SpotPriceResponse,
@@ -27,15 +28,23 @@ use void::Void;
 pub struct Behaviour {
     env_config: env::Config,
     bitcoin_wallet: Arc<bitcoin::Wallet>,
+    rate_bounds: RateBounds,
     new_swaps: VecDeque<(PeerId, NewSwap)>,
     completed_swaps: VecDeque<(PeerId, Completed)>,
 }
 
 impl Behaviour {
-    pub fn new(env_config: env::Config, bitcoin_wallet: Arc<bitcoin::Wallet>) -> Self {
+    /// The rate of the amounts the seller offers in the swap setup has to be
+    /// within `rate_bounds`.
+    pub fn new(
+        env_config: env::Config,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        rate_bounds: RateBounds,
+    ) -> Self {
         Self {
             env_config,
             bitcoin_wallet,
+            rate_bounds,
             new_swaps: VecDeque::default(),
             completed_swaps: VecDeque::default(),
         }
@@ -57,7 +66,11 @@ impl NetworkBehaviour for Behaviour {
     type OutEvent = Completed;
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
-        Handler::new(self.env_config, self.bitcoin_wallet.clone())
+        Handler::new(
+            self.env_config,
+            self.bitcoin_wallet.clone(),
+            self.rate_bounds,
+        )
     }
 
     fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
@@ -101,17 +114,23 @@ pub struct Handler {
     timeout: Duration,
     new_swaps: VecDeque<NewSwap>,
     bitcoin_wallet: Arc<bitcoin::Wallet>,
+    rate_bounds: RateBounds,
     keep_alive: KeepAlive,
 }
 
 impl Handler {
-    fn new(env_config: env::Config, bitcoin_wallet: Arc<bitcoin::Wallet>) -> Self {
+    fn new(
+        env_config: env::Config,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        rate_bounds: RateBounds,
+    ) -> Self {
         Self {
             env_config,
             outbound_stream: OptionFuture::from(None),
             timeout: Duration::from_secs(120),
             new_swaps: VecDeque::default(),
             bitcoin_wallet,
+            rate_bounds,
             keep_alive: KeepAlive::Yes,
         }
     }
@@ -124,6 +143,9 @@ pub struct NewSwap {
     pub tx_refund_fee: bitcoin::Amount,
     pub tx_cancel_fee: bitcoin::Amount,
     pub bitcoin_refund_address: bitcoin::Address,
+    /// The UTXOs reserved by other swaps, which the lock transaction must not
+    /// spend.
+    pub reserved_utxos: Vec<bitcoin::OutPoint>,
 }
 
 #[derive(Debug)]
@@ -153,6 +175,7 @@ impl ProtocolsHandler for Handler {
     ) {
         let bitcoin_wallet = self.bitcoin_wallet.clone();
         let env_config = self.env_config;
+        let rate_bounds = self.rate_bounds;
 
         let protocol = tokio::time::timeout(self.timeout, async move {
             write_cbor_message(&mut substream, SpotPriceRequest {
@@ -165,6 +188,8 @@ impl ProtocolsHandler for Handler {
             .await?;
 
             let xmr = Result::from(read_cbor_message::<SpotPriceResponse>(&mut substream).await?)?;
+            // The seller may have changed its price since the quote
+            rate_bounds.check_amounts(info.btc, xmr)?;
 
             let state0 = State0::new(
                 info.swap_id,
@@ -181,7 +206,9 @@ impl ProtocolsHandler for Handler {
 
             write_cbor_message(&mut substream, state0.next_message()).await?;
             let message1 = read_cbor_message::<Message1>(&mut substream).await?;
-            let state1 = state0.receive(bitcoin_wallet.as_ref(), message1).await?;
+            let state1 = state0
+                .receive(bitcoin_wallet.as_ref(), message1, &info.reserved_utxos)
+                .await?;
 
             write_cbor_message(&mut substream, state1.next_message()).await?;
             let message3 = read_cbor_message::<Message3>(&mut substream).await?;
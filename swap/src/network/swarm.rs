@@ -1,8 +1,7 @@
-use crate::asb::LatestRate;
+use crate::asb::{LatestRate, MakerParamsUpdates};
 use crate::libp2p_ext::MultiAddrExt;
 use crate::network::rendezvous::XmrBtcNamespace;
-use crate::seed::Seed;
-use crate::{asb, bitcoin, cli, env, tor};
+use crate::{asb, cli, env, tor};
 use anyhow::{Context, Result};
 use libp2p::swarm::{NetworkBehaviour, SwarmBuilder};
 use libp2p::{identity, Multiaddr, Swarm};
@@ -10,19 +9,17 @@ use std::fmt::Debug;
 
 #[allow(clippy::too_many_arguments)]
 pub fn asb<LR>(
-    seed: &Seed,
-    min_buy: bitcoin::Amount,
-    max_buy: bitcoin::Amount,
+    identity: identity::Keypair,
+    maker_params: MakerParamsUpdates,
     latest_rate: LR,
     resume_only: bool,
     env_config: env::Config,
+    listen_backlog: Option<u32>,
     rendezvous_params: Option<(Multiaddr, XmrBtcNamespace)>,
 ) -> Result<Swarm<asb::Behaviour<LR>>>
 where
     LR: LatestRate + Send + 'static + Debug + Clone,
 {
-    let identity = seed.derive_libp2p_identity();
-
     let rendezvous_params = if let Some((address, namespace)) = rendezvous_params {
         let peer_id = address
             .extract_peer_id()
@@ -34,15 +31,14 @@ where
     };
 
     let behaviour = asb::Behaviour::new(
-        min_buy,
-        max_buy,
+        maker_params,
         latest_rate,
         resume_only,
         env_config,
         rendezvous_params,
     );
 
-    let transport = asb::transport::new(&identity)?;
+    let transport = asb::transport::new(&identity, listen_backlog)?;
     let peer_id = identity.public().into();
 
     let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
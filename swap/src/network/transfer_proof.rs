@@ -1,15 +1,22 @@
 use crate::network::cbor_request_response::CborCodec;
+use crate::network::request_response_config;
 use crate::{asb, cli, monero};
 use libp2p::core::ProtocolName;
 use libp2p::request_response::{
-    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
-    RequestResponseMessage,
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use uuid::Uuid;
 
 const PROTOCOL: &str = "/comit/xmr/btc/transfer_proof/1.0.0";
+
+/// How long Alice waits for Bob to acknowledge the transfer proof. Bob only
+/// acknowledges it once his swap picked it up, which may first have to wait
+/// for the Bitcoin lock transaction to be confirmed.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
 type OutEvent = RequestResponseEvent<Request, ()>;
 type Message = RequestResponseMessage<Request, ()>;
 
@@ -30,19 +37,19 @@ pub struct Request {
     pub tx_lock_proof: monero::TransferProof,
 }
 
-pub fn alice() -> Behaviour {
+pub fn alice(timeout: Duration) -> Behaviour {
     Behaviour::new(
         CborCodec::default(),
         vec![(TransferProofProtocol, ProtocolSupport::Outbound)],
-        RequestResponseConfig::default(),
+        request_response_config(timeout),
     )
 }
 
-pub fn bob() -> Behaviour {
+pub fn bob(timeout: Duration) -> Behaviour {
     Behaviour::new(
         CborCodec::default(),
         vec![(TransferProofProtocol, ProtocolSupport::Inbound)],
-        RequestResponseConfig::default(),
+        request_response_config(timeout),
     )
 }
 
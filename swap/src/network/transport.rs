@@ -3,17 +3,51 @@ use futures::{AsyncRead, AsyncWrite};
 use libp2p::core::muxing::StreamMuxerBox;
 use libp2p::core::transport::Boxed;
 use libp2p::core::upgrade::{SelectUpgrade, Version};
+use libp2p::core::ConnectedPoint;
 use libp2p::mplex::MplexConfig;
 use libp2p::noise::{self, NoiseConfig, X25519Spec};
 use libp2p::{identity, yamux, PeerId, Transport};
+use std::fmt;
 use std::time::Duration;
 
+/// The security layer that encrypts and authenticates the connections of a
+/// transport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    /// The Noise protocol with the XX handshake pattern.
+    NoiseXx,
+    /// No encryption at all, which is only acceptable for testing on a
+    /// single machine.
+    Plaintext,
+}
+
+impl Security {
+    pub fn is_encrypted(&self) -> bool {
+        match self {
+            Security::NoiseXx => true,
+            Security::Plaintext => false,
+        }
+    }
+}
+
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Security::NoiseXx => write!(f, "noise-xx"),
+            Security::Plaintext => write!(f, "plaintext (INSECURE)"),
+        }
+    }
+}
+
 /// "Completes" a transport by applying the authentication and multiplexing
 /// upgrades.
 ///
 /// Even though the actual transport technology in use might be different, for
 /// two libp2p applications to be compatible, the authentication and
 /// multiplexing upgrades need to be compatible.
+///
+/// The security layer of every connection is logged once the upgrades are
+/// negotiated, connections that are not encrypted are logged as a warning.
 pub fn authenticate_and_multiplex<T>(
     transport: Boxed<T>,
     identity: &identity::Keypair,
@@ -25,6 +59,9 @@ where
         let noise_identity = noise::Keypair::<X25519Spec>::new().into_authentic(identity)?;
         NoiseConfig::xx(noise_identity).into_authenticated()
     };
+    // Noise is the only authentication upgrade offered, so it is the one every
+    // connection negotiates
+    let security = Security::NoiseXx;
     let multiplex_upgrade = SelectUpgrade::new(yamux::YamuxConfig::default(), MplexConfig::new());
 
     let transport = transport
@@ -32,8 +69,73 @@ where
         .authenticate(auth_upgrade)
         .multiplex(multiplex_upgrade)
         .timeout(Duration::from_secs(20))
-        .map(|(peer, muxer), _| (peer, StreamMuxerBox::new(muxer)))
+        .map(move |(peer, muxer), endpoint| {
+            log_negotiated_security(&peer, &endpoint, security);
+            (peer, StreamMuxerBox::new(muxer))
+        })
         .boxed();
 
     Ok(transport)
 }
+
+fn log_negotiated_security(peer: &PeerId, endpoint: &ConnectedPoint, security: Security) {
+    let address = endpoint.get_remote_address();
+
+    if security.is_encrypted() {
+        tracing::debug!(%peer, %address, %security, "Negotiated transport security");
+    } else {
+        tracing::warn!(%peer, %address, %security, "Connection is not encrypted");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use libp2p::core::transport::{ListenerEvent, MemoryTransport};
+    use libp2p::Multiaddr;
+
+    #[tokio::test]
+    async fn connections_are_authenticated_with_the_identities_of_both_peers() {
+        let listener_identity = identity::Keypair::generate_ed25519();
+        let dialer_identity = identity::Keypair::generate_ed25519();
+        let address = format!("/memory/{}", rand::random::<u64>())
+            .parse::<Multiaddr>()
+            .unwrap();
+
+        let mut listener =
+            authenticate_and_multiplex(MemoryTransport::default().boxed(), &listener_identity)
+                .unwrap()
+                .listen_on(address.clone())
+                .unwrap();
+        let dialer =
+            authenticate_and_multiplex(MemoryTransport::default().boxed(), &dialer_identity)
+                .unwrap();
+
+        let accept = async {
+            loop {
+                if let ListenerEvent::Upgrade { upgrade, .. } =
+                    listener.next().await.unwrap().unwrap()
+                {
+                    let (peer, _) = upgrade.await.unwrap();
+                    return peer;
+                }
+            }
+        };
+        let dial = async {
+            let (peer, _) = dialer.dial(address).unwrap().await.unwrap();
+            peer
+        };
+        let (accepted_peer, dialed_peer) = tokio::join!(accept, dial);
+
+        assert_eq!(accepted_peer, PeerId::from(dialer_identity.public()));
+        assert_eq!(dialed_peer, PeerId::from(listener_identity.public()));
+    }
+
+    #[test]
+    fn insecure_transports_are_marked_as_such() {
+        assert!(!Security::Plaintext.is_encrypted());
+        assert!(Security::Plaintext.to_string().contains("INSECURE"));
+        assert!(!Security::NoiseXx.to_string().contains("INSECURE"));
+    }
+}
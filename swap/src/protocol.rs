@@ -13,10 +13,14 @@ use sha2::Sha256;
 use sigma_fun::ext::dl_secp256k1_ed25519_eq::{CrossCurveDLEQ, CrossCurveDLEQProof};
 use sigma_fun::HashTranscript;
 use std::convert::TryInto;
+use time::OffsetDateTime;
+use url::Url;
 use uuid::Uuid;
 
 pub mod alice;
 pub mod bob;
+pub mod summary;
+pub mod watchdog;
 
 pub static CROSS_CURVE_PROOF_SYSTEM: Lazy<
     CrossCurveDLEQ<HashTranscript<Sha256, rand_chacha::ChaCha20Rng>>,
@@ -110,6 +114,13 @@ pub struct NotAlice;
 #[error("Not in the role of Bob")]
 pub struct NotBob;
 
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("UTXO {utxo} is reserved by swap {swap_id}")]
+pub struct UtxoReserved {
+    pub utxo: bitcoin::OutPoint,
+    pub swap_id: Uuid,
+}
+
 impl TryInto<BobState> for State {
     type Error = NotBob;
 
@@ -136,11 +147,192 @@ impl TryInto<AliceState> for State {
 pub trait Database {
     async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()>;
     async fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId>;
+    /// Hands a swap to another peer, e.g. if the swap setup failed.
+    async fn update_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()>;
     async fn insert_monero_address(&self, swap_id: Uuid, address: monero::Address) -> Result<()>;
     async fn get_monero_address(&self, swap_id: Uuid) -> Result<monero::Address>;
+    async fn insert_monero_lock_proof(&self, swap_id: Uuid, proof: monero::LockProof)
+        -> Result<()>;
+    async fn get_monero_lock_proof(&self, swap_id: Uuid) -> Result<monero::LockProof>;
+    /// Pins the swap to the Electrum server it is resumed with.
+    async fn insert_electrum_server(&self, swap_id: Uuid, rpc_url: Url) -> Result<()>;
+    async fn get_electrum_server(&self, swap_id: Uuid) -> Result<Option<Url>>;
+    /// Records the target blocks the swap estimates its fees for.
+    async fn insert_fee_strategy(&self, swap_id: Uuid, strategy: bitcoin::FeeStrategy)
+        -> Result<()>;
+    /// `None` for swaps that were created before fee strategies were recorded.
+    async fn get_fee_strategy(&self, swap_id: Uuid) -> Result<Option<bitcoin::FeeStrategy>>;
+    /// Reserves the UTXOs that fund the lock transaction of the swap in place
+    /// of the ones it reserved before. Fails with [`UtxoReserved`] if another
+    /// swap reserved one of them.
+    async fn reserve_utxos(&self, swap_id: Uuid, utxos: Vec<bitcoin::OutPoint>) -> Result<()>;
+    async fn get_reserved_utxos(&self) -> Result<Vec<(bitcoin::OutPoint, Uuid)>>;
+    async fn release_utxos(&self, swap_id: Uuid) -> Result<()>;
     async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()>;
     async fn get_addresses(&self, peer_id: PeerId) -> Result<Vec<Multiaddr>>;
+    async fn insert_peer_label(&self, peer_id: PeerId, label: String) -> Result<()>;
+    async fn get_peer_label(&self, peer_id: PeerId) -> Result<Option<String>>;
+    async fn all_peer_labels(&self) -> Result<Vec<(PeerId, String)>>;
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()>;
     async fn get_state(&self, swap_id: Uuid) -> Result<State>;
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<State>>;
+    async fn get_entered_at(&self, swap_id: Uuid) -> Result<OffsetDateTime>;
+    async fn get_started_at(&self, swap_id: Uuid) -> Result<Option<OffsetDateTime>>;
     async fn all(&self) -> Result<Vec<(Uuid, State)>>;
+    async fn delete(&self, swap_id: Uuid) -> Result<()>;
+    /// Returns the space of deleted data to the file system.
+    async fn compact(&self) -> Result<()>;
+}
+
+/// The fee strategy the swap was created with. Swaps created before fee
+/// strategies were recorded estimate every fee for `target_block`.
+pub async fn fee_strategy(
+    db: &(dyn Database + Send + Sync),
+    swap_id: Uuid,
+    target_block: usize,
+) -> Result<bitcoin::FeeStrategy> {
+    let strategy = db
+        .get_fee_strategy(swap_id)
+        .await?
+        .unwrap_or_else(|| bitcoin::FeeStrategy::new(target_block));
+
+    Ok(strategy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::WalletBuilder;
+    use crate::env::{GetConfig, Regtest};
+    use rand::rngs::OsRng;
+    use serde_cbor::Value;
+    use std::collections::BTreeMap;
+
+    // The messages are exchanged as CBOR maps keyed by field name. Renaming a
+    // field or changing how a field is encoded breaks compatibility with peers
+    // running another version and requires a new protocol version.
+
+    #[tokio::test]
+    async fn swap_setup_messages_keep_their_wire_format() {
+        let alice_wallet = WalletBuilder::new(bitcoin::Amount::ONE_BTC.as_sat()).build();
+        let bob_wallet = WalletBuilder::new(bitcoin::Amount::ONE_BTC.as_sat()).build();
+        let config = Regtest::get_config();
+
+        let alice_state0 = alice::State0::new(
+            bitcoin::Amount::from_sat(500_000),
+            monero::Amount::from_piconero(10_000),
+            config,
+            alice_wallet.new_address().await.unwrap(),
+            alice_wallet.new_address().await.unwrap(),
+            bitcoin::Amount::from_sat(3_000),
+            bitcoin::Amount::from_sat(4_000),
+            &mut OsRng,
+        );
+        let bob_state0 = bob::State0::new(
+            Uuid::new_v4(),
+            &mut OsRng,
+            bitcoin::Amount::from_sat(500_000),
+            monero::Amount::from_piconero(10_000),
+            config.bitcoin_cancel_timelock,
+            config.bitcoin_punish_timelock,
+            bob_wallet.new_address().await.unwrap(),
+            config.monero_finality_confirmations,
+            bitcoin::Amount::from_sat(1_000),
+            bitcoin::Amount::from_sat(2_000),
+        );
+
+        let message0 = bob_state0.next_message();
+        let message0_fields = fields(&message0);
+        assert_eq!(
+            field_names(&message0_fields),
+            [
+                "B",
+                "S_b_bitcoin",
+                "S_b_monero",
+                "dleq_proof_s_b",
+                "refund_address",
+                "swap_id",
+                "tx_cancel_fee",
+                "tx_refund_fee",
+                "v_b"
+            ]
+        );
+        assert_eq!(message0_fields["tx_refund_fee"], Value::Integer(1_000));
+        assert_eq!(message0_fields["tx_cancel_fee"], Value::Integer(2_000));
+
+        let (_, alice_state1) = alice_state0.receive(message0).unwrap();
+        let message1 = alice_state1.next_message();
+        let message1_fields = fields(&message1);
+        assert_eq!(
+            field_names(&message1_fields),
+            [
+                "A",
+                "S_a_bitcoin",
+                "S_a_monero",
+                "dleq_proof_s_a",
+                "punish_address",
+                "redeem_address",
+                "tx_punish_fee",
+                "tx_redeem_fee",
+                "v_a"
+            ]
+        );
+        assert_eq!(message1_fields["tx_redeem_fee"], Value::Integer(3_000));
+        assert_eq!(message1_fields["tx_punish_fee"], Value::Integer(4_000));
+
+        let bob_state1 = bob_state0
+            .receive(&bob_wallet, message1, &[])
+            .await
+            .unwrap();
+        let message2 = bob_state1.next_message();
+        assert_eq!(field_names(&fields(&message2)), ["psbt"]);
+
+        let alice_state2 = alice_state1.receive(message2).unwrap();
+        let message3 = alice_state2.next_message();
+        assert_eq!(
+            field_names(&fields(&message3)),
+            ["tx_cancel_sig", "tx_refund_encsig"]
+        );
+
+        let bob_state2 = bob_state1.receive(message3).unwrap();
+        let message4 = bob_state2.next_message();
+        assert_eq!(
+            field_names(&fields(&message4)),
+            ["tx_cancel_sig", "tx_punish_sig"]
+        );
+    }
+
+    #[test]
+    fn message4_wire_format() {
+        // R_x = 1, s = 2 and R_x = s = 0x2a..2a
+        let mut tx_punish_sig = [0; 64];
+        tx_punish_sig[31] = 1;
+        tx_punish_sig[63] = 2;
+        let message4 = Message4 {
+            tx_punish_sig: bitcoin::Signature::from_bytes(tx_punish_sig).unwrap(),
+            tx_cancel_sig: bitcoin::Signature::from_bytes([42; 64]).unwrap(),
+        };
+
+        assert_eq!(
+            hex::encode(serde_cbor::to_vec(&message4).unwrap()),
+            "a26d74785f70756e6973685f7369679840000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000026d74785f63616e63656c5f7369679840182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a182a"
+        );
+    }
+
+    fn fields(message: &impl Serialize) -> BTreeMap<String, Value> {
+        match serde_cbor::value::to_value(message).unwrap() {
+            Value::Map(map) => map
+                .into_iter()
+                .map(|(key, value)| match key {
+                    Value::Text(key) => (key, value),
+                    key => panic!("Expected field name, got {:?}", key),
+                })
+                .collect(),
+            value => panic!("Expected message to be a map, got {:?}", value),
+        }
+    }
+
+    fn field_names(fields: &BTreeMap<String, Value>) -> Vec<&str> {
+        fields.keys().map(String::as_str).collect()
+    }
 }
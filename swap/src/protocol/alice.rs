@@ -1,5 +1,6 @@
 //! Run an XMR/BTC swap in the role of Alice.
 //! Alice holds XMR and wishes receive BTC.
+use crate::clock::Clock;
 use crate::env::Config;
 use crate::protocol::Database;
 use crate::{asb, bitcoin, monero};
@@ -20,4 +21,6 @@ pub struct Swap {
     pub env_config: Config,
     pub swap_id: Uuid,
     pub db: Arc<dyn Database + Send + Sync>,
+    /// Measures the timeouts and delays of the swap.
+    pub clock: Arc<dyn Clock>,
 }
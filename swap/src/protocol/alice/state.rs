@@ -107,6 +107,32 @@ impl fmt::Display for AliceState {
     }
 }
 
+crate::impl_try_into_inner_state!(AliceState {
+    try_into_state3: Started { state3 }
+        | BtcLockTransactionSeen { state3 }
+        | BtcLocked { state3 }
+        | XmrLockTransactionSent { state3, .. }
+        | XmrLocked { state3, .. }
+        | XmrLockTransferProofSent { state3, .. }
+        | EncSigLearned { state3, .. }
+        | BtcRedeemTransactionPublished { state3 }
+        | CancelTimelockExpired { state3, .. }
+        | BtcCancelled { state3, .. }
+        | BtcPunishable { state3, .. }
+        | BtcRefunded { state3, .. } => state3: Box<State3>,
+    try_into_xmr_locked: XmrLockTransactionSent { monero_wallet_restore_blockheight, transfer_proof, state3 }
+        | XmrLocked { monero_wallet_restore_blockheight, transfer_proof, state3 }
+        | XmrLockTransferProofSent { monero_wallet_restore_blockheight, transfer_proof, state3 }
+        | EncSigLearned { monero_wallet_restore_blockheight, transfer_proof, state3, .. }
+        | CancelTimelockExpired { monero_wallet_restore_blockheight, transfer_proof, state3 }
+        | BtcCancelled { monero_wallet_restore_blockheight, transfer_proof, state3 }
+        | BtcPunishable { monero_wallet_restore_blockheight, transfer_proof, state3 }
+        | BtcRefunded { monero_wallet_restore_blockheight, transfer_proof, state3, .. }
+        => (monero_wallet_restore_blockheight, transfer_proof, state3): (BlockHeight, TransferProof, Box<State3>),
+    try_into_enc_sig_learned: EncSigLearned { state3, encrypted_signature, .. }
+        => (state3, encrypted_signature): (Box<State3>, Box<bitcoin::EncryptedSignature>),
+});
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     a: bitcoin::SecretKey,
@@ -253,6 +279,14 @@ impl State1 {
     pub fn receive(self, msg: Message2) -> Result<State2> {
         let tx_lock = bitcoin::TxLock::from_psbt(msg.psbt, self.a.public(), self.B, self.btc)
             .context("Failed to re-construct TxLock from received PSBT")?;
+        bitcoin::TxCancel::new_checked(
+            &tx_lock,
+            self.cancel_timelock,
+            self.a.public(),
+            self.B,
+            self.tx_cancel_fee,
+        )
+        .context("Received TxLock does not allow for a valid TxCancel")?;
 
         Ok(State2 {
             a: self.a,
@@ -416,6 +450,18 @@ impl State3 {
         ))
     }
 
+    pub async fn blocks_until_cancel_timelock_expires(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<u32> {
+        let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
+
+        Ok(bitcoin::blocks_until_cancel_timelock_expires(
+            self.cancel_timelock,
+            tx_lock_status,
+        ))
+    }
+
     pub fn lock_xmr_transfer_request(&self) -> TransferRequest {
         let S_a = monero::PublicKey::from_private_key(&monero::PrivateKey { scalar: self.s_a });
 
@@ -429,6 +475,24 @@ impl State3 {
         }
     }
 
+    pub fn xmr_lock_proof(
+        &self,
+        transfer_proof: TransferProof,
+        network: monero::Network,
+    ) -> monero::LockProof {
+        let TransferRequest {
+            public_spend_key,
+            public_view_key,
+            amount,
+        } = self.lock_xmr_transfer_request();
+
+        monero::LockProof {
+            transfer_proof,
+            address: monero::Address::standard(network, public_spend_key, public_view_key.into()),
+            amount,
+        }
+    }
+
     pub fn lock_xmr_watch_request(
         &self,
         transfer_proof: TransferProof,
@@ -447,6 +511,10 @@ impl State3 {
         }
     }
 
+    pub fn xmr(&self) -> monero::Amount {
+        self.xmr
+    }
+
     pub fn tx_cancel(&self) -> TxCancel {
         TxCancel::new(
             &self.tx_lock,
@@ -530,7 +598,10 @@ impl State3 {
         let signed_tx_punish = self.signed_punish_transaction()?;
 
         let (txid, subscription) = bitcoin_wallet.broadcast(signed_tx_punish, "punish").await?;
-        subscription.wait_until_final().await?;
+        subscription
+            .without_reconnect_limit()
+            .wait_until_final()
+            .await?;
 
         Ok(txid)
     }
@@ -556,7 +627,7 @@ impl State3 {
             .context("Failed to complete Bitcoin punish transaction")
     }
 
-    fn tx_punish(&self) -> TxPunish {
+    pub fn tx_punish(&self) -> TxPunish {
         bitcoin::TxPunish::new(
             &self.tx_cancel(),
             &self.punish_address,
@@ -1,13 +1,17 @@
 //! Run an XMR/BTC swap in the role of Alice.
 //! Alice holds XMR and wishes receive BTC.
 use crate::asb::{EventLoopHandle, LatestRate};
-use crate::bitcoin::ExpiredTimelocks;
+use crate::bitcoin::{ExpiredTimelocks, PunishTimelock};
+use crate::clock::{timeout, Clock};
 use crate::env::Config;
-use crate::protocol::alice::{AliceState, Swap};
+use crate::protocol::alice::{AliceState, State3, Swap};
+use crate::protocol::{fee_strategy, watchdog};
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
+use futures::future;
+use std::convert::Infallible;
+use std::time::Duration;
 use tokio::select;
-use tokio::time::timeout;
 use uuid::Uuid;
 
 pub async fn run<LR>(swap: Swap, rate_service: LR) -> Result<AliceState>
@@ -27,19 +31,51 @@ where
     LR: LatestRate + Clone,
 {
     let mut current_state = swap.state;
+    let fee_strategy = fee_strategy(
+        swap.db.as_ref(),
+        swap.swap_id,
+        swap.bitcoin_wallet.target_block(),
+    )
+    .await?;
 
     while !is_complete(&current_state) && !exit_early(&current_state) {
-        current_state = next_state(
-            swap.swap_id,
-            current_state,
-            &mut swap.event_loop_handle,
-            swap.bitcoin_wallet.as_ref(),
-            swap.monero_wallet.as_ref(),
-            &swap.env_config,
-            rate_service.clone(),
+        let state = current_state;
+        let bitcoin_wallet = swap.bitcoin_wallet.as_ref();
+        let env_config = &swap.env_config;
+        let clock = swap.clock.as_ref();
+
+        current_state = watchdog::watch(
+            next_state(
+                swap.swap_id,
+                state.clone(),
+                &mut swap.event_loop_handle,
+                bitcoin_wallet,
+                swap.monero_wallet.as_ref(),
+                env_config,
+                fee_strategy,
+                clock,
+                rate_service.clone(),
+            ),
+            clock,
+            env_config.swap_watchdog_interval,
+            || waiting_for(&state, bitcoin_wallet, env_config),
         )
         .await?;
 
+        // Keep the proof of the XMR lock around for disputes, later states no
+        // longer contain it.
+        if let AliceState::XmrLockTransactionSent {
+            transfer_proof,
+            state3,
+            ..
+        } = &current_state
+        {
+            let proof = state3.xmr_lock_proof(transfer_proof.clone(), env_config.monero_network);
+            swap.db
+                .insert_monero_lock_proof(swap.swap_id, proof)
+                .await?;
+        }
+
         swap.db
             .insert_latest_state(swap.swap_id, current_state.clone().into())
             .await?;
@@ -48,6 +84,7 @@ where
     Ok(current_state)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn next_state<LR>(
     swap_id: Uuid,
     state: AliceState,
@@ -55,6 +92,8 @@ async fn next_state<LR>(
     bitcoin_wallet: &bitcoin::Wallet,
     monero_wallet: &monero::Wallet,
     env_config: &Config,
+    fee_strategy: bitcoin::FeeStrategy,
+    clock: &dyn Clock,
     mut rate_service: LR,
 ) -> Result<AliceState>
 where
@@ -70,6 +109,7 @@ where
         AliceState::Started { state3 } => {
             let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
             match timeout(
+                clock,
                 env_config.bitcoin_lock_mempool_timeout,
                 tx_lock_status.wait_until_seen(),
             )
@@ -91,6 +131,7 @@ where
         AliceState::BtcLockTransactionSeen { state3 } => {
             let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
             match timeout(
+                clock,
                 env_config.bitcoin_lock_confirmed_timeout,
                 tx_lock_status.wait_until_final(),
             )
@@ -213,6 +254,27 @@ where
                         state3,
                     }
                 }
+                never = log_xmr_lock_confirmations(monero_wallet, &state3, transfer_proof.clone(), env_config) => match never {},
+                silence = counterparty_silence(clock, env_config.alice_counterparty_silence_timeout) => {
+                    let blocks_left = state3
+                        .blocks_until_cancel_timelock_expires(bitcoin_wallet)
+                        .await?;
+                    tracing::warn!(
+                        minutes = %silence.as_secs_f64() / 60.0,
+                        %blocks_left,
+                        expires_in = %bitcoin::approximate_duration(blocks_left, env_config.bitcoin_avg_block_time),
+                        "Bob did not send the encrypted signature in time, considering the swap abandoned. \
+                        The swap will be cancelled once the cancel timelock expires"
+                    );
+
+                    // The cancel path waits for the timelock, a late encrypted
+                    // signature is no longer accepted, also after a restart
+                    AliceState::CancelTimelockExpired {
+                        monero_wallet_restore_blockheight,
+                        transfer_proof,
+                        state3,
+                    }
+                }
             }
         }
         AliceState::EncSigLearned {
@@ -222,10 +284,39 @@ where
             state3,
         } => match state3.expired_timelocks(bitcoin_wallet).await? {
             ExpiredTimelocks::None => {
-                let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
+                let tx_lock_status = bitcoin_wallet
+                    .subscribe_to(state3.tx_lock.clone())
+                    .await
+                    .without_reconnect_limit();
+
+                let blocks_left = state3
+                    .blocks_until_cancel_timelock_expires(bitcoin_wallet)
+                    .await?;
+                if blocks_left <= env_config.bitcoin_redeem_safety_margin {
+                    tracing::warn!(
+                        %blocks_left,
+                        safety_margin = %env_config.bitcoin_redeem_safety_margin,
+                        "Not enough blocks left until the cancel timelock expires to safely redeem, waiting for cancel timelock to expire instead",
+                    );
+
+                    tx_lock_status
+                        .wait_until_confirmed_with(state3.cancel_timelock)
+                        .await?;
+
+                    return Ok(AliceState::CancelTimelockExpired {
+                        monero_wallet_restore_blockheight,
+                        transfer_proof,
+                        state3,
+                    });
+                }
+
                 match state3.signed_redeem_transaction(*encrypted_signature) {
                     Ok(tx) => match bitcoin_wallet.broadcast(tx, "redeem").await {
-                        Ok((_, subscription)) => match subscription.wait_until_seen().await {
+                        Ok((_, subscription)) => match subscription
+                            .without_reconnect_limit()
+                            .wait_until_seen()
+                            .await
+                        {
                             Ok(_) => AliceState::BtcRedeemTransactionPublished { state3 },
                             Err(e) => {
                                 bail!("Waiting for Bitcoin redeem transaction to be in mempool failed with {}! The redeem transaction was published, but it is not ensured that the transaction was included! You're screwed.", e)
@@ -270,9 +361,17 @@ where
             },
         },
         AliceState::BtcRedeemTransactionPublished { state3 } => {
-            let subscription = bitcoin_wallet.subscribe_to(state3.tx_redeem()).await;
+            let subscription = bitcoin_wallet
+                .subscribe_to(state3.tx_redeem())
+                .await
+                .without_reconnect_limit();
+
+            let finality = select! {
+                finality = subscription.wait_until_final() => finality,
+                never = bitcoin_wallet.escalate_fee(state3.tx_redeem().txid(), "redeem", fee_strategy.redeem) => match never {},
+            };
 
-            match subscription.wait_until_final().await {
+            match finality {
                 Ok(_) => AliceState::BtcRedeemed,
                 Err(e) => {
                     bail!("The Bitcoin redeem transaction was seen in mempool, but waiting for finality timed out with {}. Manual investigation might be needed to ensure that the transaction was included.", e)
@@ -284,6 +383,14 @@ where
             transfer_proof,
             state3,
         } => {
+            // An abandoned swap enters the cancel path before the timelock
+            // expired, see `counterparty_silence`
+            bitcoin_wallet
+                .subscribe_to(state3.tx_lock.clone())
+                .await
+                .wait_until_confirmed_with(state3.cancel_timelock)
+                .await?;
+
             if state3.check_for_tx_cancel(bitcoin_wallet).await.is_err() {
                 // If Bob hasn't yet broadcasted the cancel transaction, Alice has to publish it
                 // to be able to eventually punish. Since the punish timelock is
@@ -308,8 +415,15 @@ where
             transfer_proof,
             state3,
         } => {
-            let tx_refund_status = bitcoin_wallet.subscribe_to(state3.tx_refund()).await;
-            let tx_cancel_status = bitcoin_wallet.subscribe_to(state3.tx_cancel()).await;
+            let tx_refund_status = bitcoin_wallet
+                .subscribe_to(state3.tx_refund())
+                .await
+                .without_reconnect_limit();
+            let tx_cancel_status = bitcoin_wallet
+                .subscribe_to(state3.tx_cancel())
+                .await
+                .without_reconnect_limit();
+            let punish_after = punish_after(state3.punish_timelock, env_config);
 
             select! {
                 seen_refund = tx_refund_status.wait_until_seen() => {
@@ -325,7 +439,7 @@ where
                         state3,
                     }
                 }
-                result = tx_cancel_status.wait_until_confirmed_with(state3.punish_timelock) => {
+                result = tx_cancel_status.wait_until_confirmed_with(punish_after) => {
                     let _ = result?;
 
                     AliceState::BtcPunishable {
@@ -397,6 +511,53 @@ where
     })
 }
 
+/// Resolves with the configured timeout once Bob was silent for that long,
+/// never resolves if no timeout is configured.
+async fn counterparty_silence(clock: &dyn Clock, timeout: Option<Duration>) -> Duration {
+    match timeout {
+        Some(timeout) => {
+            clock.sleep(timeout).await;
+            timeout
+        }
+        None => future::pending().await,
+    }
+}
+
+/// Logs every new confirmation of the XMR lock transaction until it is final,
+/// so operators can tell what a swap waits for. Never resolves.
+async fn log_xmr_lock_confirmations(
+    monero_wallet: &monero::Wallet,
+    state3: &State3,
+    transfer_proof: monero::TransferProof,
+    env_config: &Config,
+) -> Infallible {
+    if env_config.alice_log_xmr_lock_confirmations {
+        let conf_target = env_config.monero_finality_confirmations;
+        let txid = transfer_proof.tx_hash();
+
+        match monero_wallet
+            .watch_for_transfer(state3.lock_xmr_watch_request(transfer_proof, conf_target))
+            .await
+        {
+            Ok(()) => tracing::info!(%txid, "XMR lock transaction reached finality"),
+            Err(error) => tracing::warn!(
+                %txid,
+                "Stopped following the confirmations of the XMR lock transaction: {:#}",
+                error
+            ),
+        }
+    }
+
+    future::pending().await
+}
+
+/// The number of confirmations of the cancel transaction after which Alice
+/// punishes, which is the punish timelock extended by the configured grace
+/// period. Bob can still refund until the punish transaction is published.
+fn punish_after(punish_timelock: PunishTimelock, env_config: &Config) -> u32 {
+    u32::from(punish_timelock).saturating_add(env_config.alice_punish_grace_blocks)
+}
+
 pub(crate) fn is_complete(state: &AliceState) -> bool {
     matches!(
         state,
@@ -406,3 +567,143 @@ pub(crate) fn is_complete(state: &AliceState) -> bool {
             | AliceState::SafelyAborted
     )
 }
+
+/// Describes what the swap is waiting for in the given state.
+async fn waiting_for(
+    state: &AliceState,
+    bitcoin_wallet: &bitcoin::Wallet,
+    env_config: &Config,
+) -> String {
+    match state {
+        AliceState::Started { state3 } => format!(
+            "Bob to publish {}",
+            watchdog::bitcoin_tx_status(
+                bitcoin_wallet,
+                "the lock transaction",
+                &state3.tx_lock,
+                env_config.bitcoin_finality_confirmations
+            )
+            .await
+        ),
+        AliceState::BtcLockTransactionSeen { state3 } => format!(
+            "the finality of {}",
+            watchdog::bitcoin_tx_status(
+                bitcoin_wallet,
+                "the lock transaction",
+                &state3.tx_lock,
+                env_config.bitcoin_finality_confirmations
+            )
+            .await
+        ),
+        AliceState::BtcLocked { .. } => "the Monero lock transfer to be sent".to_owned(),
+        AliceState::XmrLockTransactionSent { transfer_proof, .. } => format!(
+            "the first confirmation of Monero lock transaction {}",
+            transfer_proof.tx_hash()
+        ),
+        AliceState::XmrLocked { state3, .. } => format!(
+            "Bob to acknowledge the transfer proof, {}",
+            cancel_timelock_status(bitcoin_wallet, state3).await
+        ),
+        AliceState::XmrLockTransferProofSent { state3, .. } => format!(
+            "Bob to send the encrypted signature, {}",
+            cancel_timelock_status(bitcoin_wallet, state3).await
+        ),
+        AliceState::EncSigLearned { state3, .. } => format!(
+            "the redeem transaction to be published, {}",
+            cancel_timelock_status(bitcoin_wallet, state3).await
+        ),
+        AliceState::BtcRedeemTransactionPublished { state3 } => format!(
+            "the finality of {}",
+            watchdog::bitcoin_tx_status(
+                bitcoin_wallet,
+                "the redeem transaction",
+                &state3.tx_redeem(),
+                env_config.bitcoin_finality_confirmations
+            )
+            .await
+        ),
+        AliceState::CancelTimelockExpired { state3, .. } => format!(
+            "the Bitcoin cancel transaction to be published, {}",
+            cancel_timelock_status(bitcoin_wallet, state3).await
+        ),
+        AliceState::BtcCancelled { state3, .. } => format!(
+            "Bob to refund or the punish timelock to expire at {}",
+            watchdog::bitcoin_tx_status(
+                bitcoin_wallet,
+                "the cancel transaction",
+                &state3.tx_cancel(),
+                punish_after(state3.punish_timelock, env_config)
+            )
+            .await
+        ),
+        AliceState::BtcRefunded { .. } => "the Monero to be refunded".to_owned(),
+        AliceState::BtcPunishable { .. } => "the punish transaction to be published".to_owned(),
+        AliceState::BtcRedeemed
+        | AliceState::XmrRefunded
+        | AliceState::BtcPunished
+        | AliceState::SafelyAborted => "nothing, the swap is complete".to_owned(),
+    }
+}
+
+async fn cancel_timelock_status(bitcoin_wallet: &bitcoin::Wallet, state3: &State3) -> String {
+    let status = watchdog::bitcoin_tx_status(
+        bitcoin_wallet,
+        "the lock transaction",
+        &state3.tx_lock,
+        state3.cancel_timelock.into(),
+    )
+    .await;
+
+    format!("the cancel timelock expires at {}", status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::env::{GetConfig, Mainnet};
+    use futures::FutureExt;
+
+    #[test]
+    fn punishes_at_punish_timelock_without_grace_period() {
+        let env_config = Mainnet::get_config();
+
+        assert_eq!(punish_after(PunishTimelock::new(72), &env_config), 72);
+    }
+
+    #[test]
+    fn grace_period_delays_punish() {
+        let env_config = Config {
+            alice_punish_grace_blocks: 6,
+            ..Mainnet::get_config()
+        };
+
+        assert_eq!(punish_after(PunishTimelock::new(72), &env_config), 78);
+    }
+
+    #[tokio::test]
+    async fn counterparty_is_silent_once_timeout_passed() {
+        let clock = MockClock::new();
+        let timeout = Duration::from_secs(6 * 60 * 60);
+        let silence = counterparty_silence(&clock, Some(timeout));
+        tokio::pin!(silence);
+
+        assert!((&mut silence).now_or_never().is_none());
+        clock.advance(timeout - Duration::from_secs(1));
+        assert!((&mut silence).now_or_never().is_none());
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(silence.now_or_never(), Some(timeout));
+    }
+
+    #[tokio::test]
+    async fn counterparty_is_never_silent_without_timeout() {
+        let clock = MockClock::new();
+        let silence = counterparty_silence(&clock, None);
+        tokio::pin!(silence);
+
+        clock.advance(Duration::from_secs(365 * 24 * 60 * 60));
+
+        assert!(silence.now_or_never().is_none());
+    }
+}
@@ -3,11 +3,12 @@ use std::sync::Arc;
 use anyhow::Result;
 use uuid::Uuid;
 
+use crate::clock::{Clock, SystemClock};
 use crate::protocol::Database;
 use crate::{bitcoin, cli, env, monero};
 
 pub use self::state::*;
-pub use self::swap::{run, run_until};
+pub use self::swap::{release_utxos_unless_locked, run, run_until};
 use std::convert::TryInto;
 
 pub mod state;
@@ -22,6 +23,8 @@ pub struct Swap {
     pub env_config: env::Config,
     pub id: Uuid,
     pub monero_receive_address: monero::Address,
+    /// Measures the timeouts and delays of the swap.
+    pub clock: Arc<dyn Clock>,
 }
 
 impl Swap {
@@ -49,6 +52,7 @@ impl Swap {
             env_config,
             id,
             monero_receive_address,
+            clock: Arc::new(SystemClock),
         }
     }
 
@@ -73,6 +77,7 @@ impl Swap {
             env_config,
             id,
             monero_receive_address,
+            clock: Arc::new(SystemClock),
         })
     }
 }
@@ -1,8 +1,9 @@
-use crate::bitcoin::wallet::EstimateFeeRate;
+use crate::bitcoin::wallet::{EstimateFeeRate, Subscription};
 use crate::bitcoin::{
-    self, current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxLock, Txid,
+    self, current_epoch, CancelReorgPolicy, CancelTimelock, ExpiredTimelocks, PunishTimelock,
+    Transaction, TxCancel, TxLock, Txid,
 };
+use crate::clock::Clock;
 use crate::monero;
 use crate::monero::wallet::WatchRequest;
 use crate::monero::{monero_private_key, TransferProof};
@@ -10,6 +11,7 @@ use crate::monero_ext::ScalarExt;
 use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
 use anyhow::{anyhow, bail, Context, Result};
 use bdk::database::BatchDatabase;
+use bdk::FeeRate;
 use ecdsa_fun::adaptor::{Adaptor, HashTranscript};
 use ecdsa_fun::nonce::Deterministic;
 use ecdsa_fun::Signature;
@@ -21,6 +23,10 @@ use sigma_fun::ext::dl_secp256k1_ed25519_eq::CrossCurveDLEQProof;
 use std::fmt;
 use uuid::Uuid;
 
+/// Waiting for the redeem transaction fails after this many consecutive
+/// failures to look up the spends of the lock output.
+const MAX_FAILED_REDEEM_LOOKUPS: u32 = 10;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum BobState {
     Started {
@@ -74,6 +80,36 @@ impl fmt::Display for BobState {
     }
 }
 
+impl BobState {
+    /// The swap as it is cancelled and refunded, if the Bitcoin is locked and
+    /// neither redeemed nor refunded yet.
+    pub fn state6(&self) -> Option<State6> {
+        match self {
+            BobState::BtcLocked { state3, .. } => Some(state3.cancel()),
+            BobState::XmrLockProofReceived { state, .. } => Some(state.cancel()),
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                Some(state4.clone().cancel())
+            }
+            BobState::CancelTimelockExpired(state6) | BobState::BtcCancelled(state6) => {
+                Some(state6.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Which timelocks of the swap expired, `None` if the Bitcoin is not
+    /// locked or the swap is over, see [`BobState::state6`].
+    pub async fn expired_timelocks(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<Option<ExpiredTimelocks>> {
+        match self.state6() {
+            Some(state6) => Ok(Some(state6.expired_timelock(bitcoin_wallet).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     swap_id: Uuid,
@@ -149,10 +185,13 @@ impl State0 {
         }
     }
 
+    /// Verifies the keys of Alice and funds the Bitcoin lock transaction
+    /// without spending any of the `unspendable` UTXOs.
     pub async fn receive<B, D, C>(
         self,
         wallet: &bitcoin::Wallet<B, D, C>,
         msg: Message1,
+        unspendable: &[bitcoin::OutPoint],
     ) -> Result<State1>
     where
         C: EstimateFeeRate,
@@ -179,6 +218,7 @@ impl State0 {
             msg.A,
             self.b.public(),
             self.refund_address.clone(),
+            unspendable,
         )
         .await?;
         let v = msg.v_a + self.v_b;
@@ -236,13 +276,13 @@ impl State1 {
     }
 
     pub fn receive(self, msg: Message3) -> Result<State2> {
-        let tx_cancel = TxCancel::new(
+        let tx_cancel = TxCancel::new_checked(
             &self.tx_lock,
             self.cancel_timelock,
             self.A,
             self.b.public(),
             self.tx_cancel_fee,
-        );
+        )?;
         let tx_refund =
             bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, self.tx_refund_fee);
 
@@ -308,6 +348,44 @@ pub struct State2 {
 }
 
 impl State2 {
+    pub fn cancel_timelock(&self) -> CancelTimelock {
+        self.cancel_timelock
+    }
+
+    pub fn tx_lock(&self) -> &TxLock {
+        &self.tx_lock
+    }
+
+    pub fn xmr(&self) -> monero::Amount {
+        self.xmr
+    }
+
+    pub fn refund_address(&self) -> &bitcoin::Address {
+        &self.refund_address
+    }
+
+    pub fn punish_timelock(&self) -> PunishTimelock {
+        self.punish_timelock
+    }
+
+    /// Whether the Bitcoin lock transaction was certainly not published: it
+    /// was never seen and all its inputs are still unspent outputs of the
+    /// `bitcoin_wallet`. The lock is broadcast before the swap leaves this
+    /// state, hence the setup may only be discarded if this holds.
+    pub async fn lock_is_unpublished(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<bool> {
+        bitcoin_wallet.sync().await?;
+
+        if bitcoin_wallet
+            .status_of_script(&self.tx_lock)
+            .await?
+            .has_been_seen()
+        {
+            return Ok(false);
+        }
+
+        bitcoin_wallet.are_unspent(&self.tx_lock.inputs()).await
+    }
+
     pub fn next_message(&self) -> Message4 {
         let tx_cancel = TxCancel::new(
             &self.tx_lock,
@@ -403,8 +481,10 @@ impl State3 {
             A: self.A,
             b: self.b,
             s_b: self.s_b,
+            S_a_monero: Some(self.S_a_monero),
             S_a_bitcoin: self.S_a_bitcoin,
             v: self.v,
+            xmr: Some(self.xmr),
             cancel_timelock: self.cancel_timelock,
             punish_timelock: self.punish_timelock,
             refund_address: self.refund_address,
@@ -439,6 +519,19 @@ impl State3 {
         self.tx_lock.txid()
     }
 
+    /// The public keys of Alice and Bob the Bitcoin lock output is locked to.
+    pub fn bitcoin_public_keys(&self) -> (bitcoin::PublicKey, bitcoin::PublicKey) {
+        (self.A, self.b.public())
+    }
+
+    pub fn redeem_address(&self) -> &bitcoin::Address {
+        &self.redeem_address
+    }
+
+    pub fn refund_address(&self) -> &bitcoin::Address {
+        &self.refund_address
+    }
+
     pub async fn current_epoch(
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
@@ -468,8 +561,14 @@ pub struct State4 {
     A: bitcoin::PublicKey,
     b: bitcoin::SecretKey,
     s_b: monero::Scalar,
+    /// Not known for swaps that reached this state before it was stored.
+    #[serde(default)]
+    S_a_monero: Option<monero::PublicKey>,
     S_a_bitcoin: bitcoin::PublicKey,
     v: monero::PrivateViewKey,
+    /// Not known for swaps that reached this state before it was stored.
+    #[serde(default)]
+    xmr: Option<monero::Amount>,
     pub cancel_timelock: CancelTimelock,
     punish_timelock: PunishTimelock,
     refund_address: bitcoin::Address,
@@ -488,38 +587,109 @@ pub struct State4 {
 
 impl State4 {
     pub fn tx_redeem_encsig(&self) -> bitcoin::EncryptedSignature {
-        let tx_redeem =
-            bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
-        self.b.encsign(self.S_a_bitcoin, tx_redeem.digest())
+        self.b.encsign(self.S_a_bitcoin, self.tx_redeem().digest())
     }
 
-    pub async fn watch_for_redeem_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<State5> {
+    pub fn tx_redeem(&self) -> bitcoin::TxRedeem {
+        bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee)
+    }
+
+    pub async fn watch_for_redeem_btc(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+        clock: &dyn Clock,
+    ) -> Result<State5> {
         let tx_redeem =
             bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
         let tx_redeem_encsig = self.b.encsign(self.S_a_bitcoin, tx_redeem.digest());
 
-        bitcoin_wallet
-            .subscribe_to(tx_redeem.clone())
-            .await
-            .wait_until_seen()
+        let tx_redeem_sig = self
+            .wait_for_redeem_signature(bitcoin_wallet, clock, &tx_redeem)
             .await?;
-
-        let tx_redeem_candidate = bitcoin_wallet.get_raw_transaction(tx_redeem.txid()).await?;
-
-        let tx_redeem_sig =
-            tx_redeem.extract_signature_by_key(tx_redeem_candidate, self.b.public())?;
         let s_a = bitcoin::recover(self.S_a_bitcoin, tx_redeem_sig, tx_redeem_encsig)?;
         let s_a = monero::private_key_from_secp256k1_scalar(s_a.into());
 
+        match self.S_a_monero {
+            Some(S_a_monero) => verify_recovered_s_a(s_a, S_a_monero)?,
+            None => tracing::warn!(
+                "Cannot verify the recovered Monero key because the swap was started with an older version"
+            ),
+        }
+
         Ok(State5 {
             s_a,
             s_b: self.s_b,
             v: self.v,
+            xmr: self.xmr,
             tx_lock: self.tx_lock.clone(),
             monero_wallet_restore_blockheight: self.monero_wallet_restore_blockheight,
         })
     }
 
+    /// Waits for the expected redeem transaction like for any other
+    /// transaction. Alice's redeem transaction does not necessarily match the
+    /// one we expect, so Electrum servers are also asked for every spend of
+    /// the lock output.
+    async fn wait_for_redeem_signature(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+        clock: &dyn Clock,
+        tx_redeem: &bitcoin::TxRedeem,
+    ) -> Result<Signature> {
+        let subscription = bitcoin_wallet.subscribe_to(tx_redeem.clone()).await;
+        let look_for_spends = bitcoin_wallet.can_look_up_spending_transactions().await;
+        let poll_interval = bitcoin_wallet.sync_interval().await;
+
+        let lock_outpoint = self.tx_lock.as_outpoint();
+        let lock_script = self.tx_lock.script_pubkey();
+        let mut failed_lookups = 0;
+
+        loop {
+            tokio::select! {
+                seen = subscription.wait_until_seen() => {
+                    seen?;
+
+                    let candidate = bitcoin_wallet.get_raw_transaction(tx_redeem.txid()).await?;
+                    return tx_redeem.extract_signature_by_key(candidate, self.b.public());
+                }
+                _ = clock.sleep(poll_interval), if look_for_spends => {}
+            }
+
+            match bitcoin_wallet
+                .spending_transactions(lock_outpoint, &lock_script)
+                .await
+            {
+                Ok(candidates) => {
+                    failed_lookups = 0;
+
+                    let sig = candidates.into_iter().find_map(|candidate| {
+                        tx_redeem
+                            .extract_signature_by_key(candidate, self.b.public())
+                            .ok()
+                    });
+
+                    if let Some(sig) = sig {
+                        return Ok(sig);
+                    }
+                }
+                Err(error) => {
+                    failed_lookups += 1;
+                    if failed_lookups >= MAX_FAILED_REDEEM_LOOKUPS {
+                        return Err(
+                            error.context("Failed to look for the Bitcoin redeem transaction")
+                        );
+                    }
+
+                    tracing::warn!(
+                        attempt = failed_lookups,
+                        "Failed to look for Bitcoin redeem transaction: {:#}",
+                        error
+                    );
+                }
+            }
+        }
+    }
+
     pub async fn expired_timelock(
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
@@ -560,12 +730,39 @@ impl State4 {
     }
 }
 
+/// The key Bob recovered from Alice's redeem transaction is not the private key
+/// of her share of the Monero lock, claiming the Monero with it would fail.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("The recovered Monero key does not match Alice's public key {expected}, refusing to claim the Monero with it")]
+pub struct RecoveredKeyMismatch {
+    expected: monero::PublicKey,
+}
+
+/// Ensures that `s_a` is the private key of `S_a_monero`, and thereby that
+/// `s_a` combined with `s_b` spends the Monero lock.
+#[allow(non_snake_case)]
+fn verify_recovered_s_a(
+    s_a: monero::PrivateKey,
+    S_a_monero: monero::PublicKey,
+) -> Result<(), RecoveredKeyMismatch> {
+    if monero::PublicKey::from_private_key(&s_a) != S_a_monero {
+        return Err(RecoveredKeyMismatch {
+            expected: S_a_monero,
+        });
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct State5 {
     #[serde(with = "monero_private_key")]
     s_a: monero::PrivateKey,
     s_b: monero::Scalar,
     v: monero::PrivateViewKey,
+    /// Not known for swaps that reached this state before it was stored.
+    #[serde(default)]
+    xmr: Option<monero::Amount>,
     tx_lock: bitcoin::TxLock,
     pub monero_wallet_restore_blockheight: BlockHeight,
 }
@@ -578,6 +775,11 @@ impl State5 {
         (s, self.v)
     }
 
+    /// The Monero locked by Alice, which the wallet of the swap receives.
+    pub fn xmr(&self) -> Option<monero::Amount> {
+        self.xmr
+    }
+
     pub fn tx_lock_id(&self) -> bitcoin::Txid {
         self.tx_lock.txid()
     }
@@ -624,6 +826,26 @@ impl State6 {
         ))
     }
 
+    pub async fn blocks_until_cancel_timelock_expires(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<u32> {
+        let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
+
+        Ok(bitcoin::blocks_until_cancel_timelock_expires(
+            self.cancel_timelock,
+            tx_lock_status,
+        ))
+    }
+
+    pub async fn wait_for_cancel_timelock(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
+        bitcoin_wallet
+            .subscribe_to(self.tx_lock.clone())
+            .await
+            .wait_until_confirmed_with(self.cancel_timelock)
+            .await
+    }
+
     pub async fn check_for_tx_cancel(
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
@@ -657,10 +879,111 @@ impl State6 {
         Ok(tx_id)
     }
 
-    pub async fn publish_refund_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
+    /// Publishes the refund transaction, the returned subscription tells when
+    /// it is final.
+    pub async fn publish_refund_btc(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+        cancel_reorg_policy: CancelReorgPolicy,
+    ) -> Result<Subscription> {
+        self.ensure_tx_cancel_published(bitcoin_wallet, cancel_reorg_policy)
+            .await?;
+
         let signed_tx_refund = self.signed_refund_transaction()?;
         let (_, subscription) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
-        subscription.wait_until_final().await?;
+
+        Ok(subscription)
+    }
+
+    /// Checks that the cancel transaction is still in the blockchain or the
+    /// mempool before the refund spending it is published, and deals with a
+    /// cancel transaction that disappeared according to the policy.
+    async fn ensure_tx_cancel_published(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+        cancel_reorg_policy: CancelReorgPolicy,
+    ) -> Result<()> {
+        let tx_cancel = bitcoin::TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.A,
+            self.b.public(),
+            self.tx_cancel_fee,
+        );
+        let txid = tx_cancel.txid();
+
+        let status = bitcoin_wallet.current_status_of_script(&tx_cancel).await?;
+        tracing::info!(%txid, %status, "Checked the cancel transaction before publishing the refund");
+
+        if cancel_reorg_policy.rebroadcast_tx_cancel(status)? {
+            tracing::warn!(%txid, %status, "The cancel transaction disappeared, likely because of a reorg. Publishing it again");
+
+            self.submit_tx_cancel(bitcoin_wallet)
+                .await
+                .context("Failed to publish the cancel transaction again")?;
+
+            let status = bitcoin_wallet.current_status_of_script(&tx_cancel).await?;
+            tracing::info!(%txid, %status, "Checked the cancel transaction after publishing it again");
+        }
+
+        Ok(())
+    }
+
+    /// Publishes a child-pays-for-parent transaction spending the refund
+    /// output, so the published refund (and the cancel transaction if it is
+    /// still unconfirmed) gets mined at `fee_rate`.
+    ///
+    /// The fees of the cancel and refund transactions are fixed by the
+    /// signatures exchanged during swap setup, this is the only way to pay
+    /// more.
+    pub async fn bump_refund_fee(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+        fee_rate: FeeRate,
+    ) -> Result<()> {
+        let signed_tx_refund = self.signed_refund_transaction()?;
+        let tx_cancel = bitcoin::TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.A,
+            self.b.public(),
+            self.tx_cancel_fee,
+        );
+
+        let mut package_weight = bitcoin::TxRefund::weight();
+        let mut package_fee = self.tx_refund_fee;
+        if !bitcoin_wallet
+            .status_of_script(&tx_cancel)
+            .await?
+            .is_confirmed()
+        {
+            package_weight += TxCancel::weight();
+            package_fee += self.tx_cancel_fee;
+        }
+
+        // The refund output has to be known to the wallet before we can spend it
+        bitcoin_wallet.sync().await?;
+
+        let psbt = match bitcoin_wallet
+            .child_pays_for_parent(&signed_tx_refund, package_weight, package_fee, fee_rate)
+            .await
+            .context("Failed to bump fee of refund transaction, is the refund address part of the internal wallet?")?
+        {
+            Some(psbt) => psbt,
+            None => {
+                tracing::info!(
+                    fee_rate = %fee_rate.as_sat_vb(),
+                    "Refund transaction already pays the requested fee rate"
+                );
+                return Ok(());
+            }
+        };
+
+        let signed_tx = bitcoin_wallet
+            .sign_and_finalize(psbt)
+            .await
+            .context("Failed to sign child-pays-for-parent transaction")?;
+        bitcoin_wallet.broadcast(signed_tx, "refund-cpfp").await?;
 
         Ok(())
     }
@@ -690,4 +1013,54 @@ impl State6 {
     pub fn tx_lock_id(&self) -> bitcoin::Txid {
         self.tx_lock.txid()
     }
+
+    pub fn tx_lock(&self) -> &bitcoin::TxLock {
+        &self.tx_lock
+    }
+
+    pub fn tx_cancel(&self) -> TxCancel {
+        TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.A,
+            self.b.public(),
+            self.tx_cancel_fee,
+        )
+    }
+
+    pub fn tx_refund(&self) -> bitcoin::TxRefund {
+        bitcoin::TxRefund::new(&self.tx_cancel(), &self.refund_address, self.tx_refund_fee)
+    }
+
+    pub fn cancel_timelock(&self) -> CancelTimelock {
+        self.cancel_timelock
+    }
+
+    pub fn punish_timelock(&self) -> PunishTimelock {
+        self.punish_timelock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn recovered_s_a_must_match_alices_public_key() {
+        let s_a = monero::Scalar::random(&mut OsRng);
+        let S_a_monero = monero::PublicKey::from_private_key(&monero::PrivateKey::from_scalar(s_a));
+
+        let recovered = monero::private_key_from_secp256k1_scalar(s_a.to_secpfun_scalar());
+        assert_eq!(verify_recovered_s_a(recovered, S_a_monero), Ok(()));
+
+        let wrong = monero::PrivateKey::from_scalar(monero::Scalar::random(&mut OsRng));
+        assert_eq!(
+            verify_recovered_s_a(wrong, S_a_monero),
+            Err(RecoveredKeyMismatch {
+                expected: S_a_monero
+            })
+        );
+    }
 }
@@ -1,10 +1,17 @@
 use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxRefund};
 use crate::cli::EventLoopHandle;
+use crate::clock::Clock;
 use crate::network::swap_setup::bob::NewSwap;
 use crate::protocol::bob;
 use crate::protocol::bob::state::*;
-use crate::{bitcoin, monero};
+use crate::protocol::{fee_strategy, watchdog, Database};
+use crate::{bitcoin, env, monero};
 use anyhow::{bail, Context, Result};
+use futures::future;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::future::Future;
+use std::sync::Arc;
 use tokio::select;
 use uuid::Uuid;
 
@@ -23,6 +30,19 @@ pub async fn run(swap: bob::Swap) -> Result<BobState> {
     run_until(swap, is_complete).await
 }
 
+/// Runs the swap until it reaches a state for which `is_target_state` is true.
+///
+/// The returned future is cancellation safe: It can be dropped at any point,
+/// e.g. because the event loop stopped first, and the swap resumed from the
+/// database later on. Every transition either saved its new state or is
+/// repeated when the swap is resumed, in which case it publishes the same
+/// transactions again. Publishing a transaction that was already published
+/// does nothing, see [`bitcoin::Wallet::broadcast`]. Transitions whose new
+/// state depends on more than the transactions they publish, e.g. the Monero
+/// wallet height recorded before the Bitcoin is locked, publish and save the
+/// new state in a task of their own that is not dropped with the swap, see
+/// [`commit`]. That task does not outlive the process, if the CLI is stopped
+/// in between the transition is repeated like any other.
 pub async fn run_until(
     mut swap: bob::Swap,
     is_target_state: fn(&BobState) -> bool,
@@ -30,45 +50,110 @@ pub async fn run_until(
     let mut current_state = swap.state;
 
     while !is_target_state(&current_state) {
-        current_state = next_state(
-            swap.id,
-            current_state.clone(),
-            &mut swap.event_loop_handle,
-            swap.bitcoin_wallet.as_ref(),
-            swap.monero_wallet.as_ref(),
-            swap.monero_receive_address,
+        let state = current_state;
+        let bitcoin_wallet = swap.bitcoin_wallet.as_ref();
+        let env_config = &swap.env_config;
+        let clock = swap.clock.as_ref();
+
+        let transition = watchdog::watch(
+            next_state(
+                swap.id,
+                state.clone(),
+                &mut swap.event_loop_handle,
+                &swap.db,
+                &swap.bitcoin_wallet,
+                &swap.monero_wallet,
+                swap.monero_receive_address,
+                env_config,
+                clock,
+            ),
+            clock,
+            env_config.swap_watchdog_interval,
+            || waiting_for(&state, bitcoin_wallet, env_config),
         )
         .await?;
 
-        swap.db
-            .insert_latest_state(swap.id, current_state.clone().into())
-            .await?;
+        current_state = match transition {
+            Transition::Committed(state) => state,
+            Transition::Advanced(state) => {
+                swap.db
+                    .insert_latest_state(swap.id, state.clone().into())
+                    .await?;
+                state
+            }
+        };
     }
 
     Ok(current_state)
 }
 
+/// The new state of a swap after a transition of [`next_state`].
+#[derive(Debug)]
+enum Transition {
+    /// The new state still has to be saved.
+    Advanced(BobState),
+    /// The new state was already saved by [`commit`].
+    Committed(BobState),
+}
+
+/// Runs `action` and saves `state` as the latest state of the swap afterwards
+/// in a task of its own.
+///
+/// Dropping the returned future does not stop the task, so it is not possible
+/// that the action was performed but the state not saved just because the
+/// swap was dropped in between. Stopping the process does stop the task. If
+/// the action fails nothing is saved.
+async fn commit<A>(
+    db: Arc<dyn Database + Send + Sync>,
+    swap_id: Uuid,
+    state: BobState,
+    action: A,
+) -> Result<Transition>
+where
+    A: Future<Output = Result<()>> + Send + 'static,
+{
+    let committed = state.clone();
+
+    tokio::spawn(async move {
+        action.await?;
+        db.insert_latest_state(swap_id, state.into()).await
+    })
+    .await
+    .context("Failed to commit state transition")??;
+
+    Ok(Transition::Committed(committed))
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn next_state(
     swap_id: Uuid,
     state: BobState,
     event_loop_handle: &mut EventLoopHandle,
-    bitcoin_wallet: &bitcoin::Wallet,
-    monero_wallet: &monero::Wallet,
+    db: &Arc<dyn Database + Send + Sync>,
+    bitcoin_wallet: &Arc<bitcoin::Wallet>,
+    monero_wallet: &Arc<monero::Wallet>,
     monero_receive_address: monero::Address,
-) -> Result<BobState> {
+    env_config: &env::Config,
+    clock: &dyn Clock,
+) -> Result<Transition> {
     tracing::debug!(%state, "Advancing state");
 
-    Ok(match state {
+    Ok(Transition::Advanced(match state {
         BobState::Started {
             btc_amount,
             change_address,
         } => {
+            // The lock transaction is funded for the target block of the
+            // wallet, which the CLI sets to the lock target of the swap.
+            let fee_strategy =
+                fee_strategy(db.as_ref(), swap_id, bitcoin_wallet.target_block()).await?;
             let tx_refund_fee = bitcoin_wallet
-                .estimate_fee(TxRefund::weight(), btc_amount)
+                .estimate_fee_within(TxRefund::weight(), btc_amount, fee_strategy.refund)
                 .await?;
             let tx_cancel_fee = bitcoin_wallet
-                .estimate_fee(TxCancel::weight(), btc_amount)
+                .estimate_fee_within(TxCancel::weight(), btc_amount, fee_strategy.cancel)
                 .await?;
+            let reserved_utxos = reserved_by_other_swaps(db.as_ref(), swap_id).await?;
 
             let state2 = event_loop_handle
                 .setup_swap(NewSwap {
@@ -77,14 +162,40 @@ async fn next_state(
                     tx_refund_fee,
                     tx_cancel_fee,
                     bitcoin_refund_address: change_address,
+                    reserved_utxos,
                 })
                 .await?;
 
             tracing::info!(%swap_id, "Starting new swap");
 
-            BobState::SwapSetupCompleted(state2)
+            // A swap started concurrently may have selected the same UTXOs
+            // before either reserved them. Only one of them gets to lock,
+            // resuming the other sets it up again with the remaining UTXOs.
+            // The reservation is saved together with the setup, other swaps
+            // ignore reservations of swaps that did not complete their setup.
+            let utxos = state2.tx_lock().inputs();
+            let reservations = db.clone();
+
+            return commit(
+                db.clone(),
+                swap_id,
+                BobState::SwapSetupCompleted(state2),
+                async move {
+                    reservations
+                        .reserve_utxos(swap_id, utxos)
+                        .await
+                        .context("Failed to reserve the UTXOs of the Bitcoin lock transaction")
+                },
+            )
+            .await;
         }
         BobState::SwapSetupCompleted(state2) => {
+            // The reservation is released if the swap failed or was stopped
+            // before it locked the Bitcoin, it is taken again before locking.
+            db.reserve_utxos(swap_id, state2.tx_lock().inputs())
+                .await
+                .context("Failed to reserve the UTXOs of the Bitcoin lock transaction")?;
+
             // Record the current monero wallet block height so we don't have to scan from
             // block 0 once we create the redeem wallet.
             // This has to be done **before** the Bitcoin is locked in order to ensure that
@@ -96,18 +207,40 @@ async fn next_state(
             // which can lead to the wallet not detect the transaction.
             let monero_wallet_restore_blockheight = monero_wallet.block_height().await?;
 
+            let cancel_timelock = state2.cancel_timelock();
+            let punish_timelock = state2.punish_timelock();
+            let avg_block_time = env_config.bitcoin_avg_block_time;
+            tracing::info!(
+                %cancel_timelock,
+                %punish_timelock,
+                "If the swap does not complete, the Bitcoin can be refunded {} ({}) after the lock transaction is confirmed. \
+                 If it is not refunded within another {} ({}), the seller can punish the swap and keep the Bitcoin",
+                cancel_timelock,
+                bitcoin::approximate_duration(cancel_timelock.into(), avg_block_time),
+                punish_timelock,
+                bitcoin::approximate_duration(punish_timelock.into(), avg_block_time),
+            );
+
             // Alice and Bob have exchanged info
             let (state3, tx_lock) = state2.lock_btc().await?;
             let signed_tx = bitcoin_wallet
                 .sign_and_finalize(tx_lock.clone().into())
                 .await
                 .context("Failed to sign Bitcoin lock transaction")?;
-            let (..) = bitcoin_wallet.broadcast(signed_tx, "lock").await?;
 
-            BobState::BtcLocked {
+            // The recorded wallet height would be lost if the swap was dropped after
+            // publishing the lock transaction but before saving it.
+            let bitcoin_wallet = bitcoin_wallet.clone();
+            let state = BobState::BtcLocked {
                 state3,
                 monero_wallet_restore_blockheight,
-            }
+            };
+
+            return commit(db.clone(), swap_id, state, async move {
+                bitcoin_wallet.broadcast(signed_tx, "lock").await?;
+                Ok(())
+            })
+            .await;
         }
         // Bob has locked Btc
         // Watch for Alice to Lock Xmr or for cancel timelock to elapse
@@ -116,6 +249,8 @@ async fn next_state(
             monero_wallet_restore_blockheight,
         } => {
             let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
+            let fee_strategy =
+                fee_strategy(db.as_ref(), swap_id, bitcoin_wallet.target_block()).await?;
 
             if let ExpiredTimelocks::None = state3.current_epoch(bitcoin_wallet).await? {
                 let transfer_proof_watcher = event_loop_handle.recv_transfer_proof();
@@ -143,6 +278,7 @@ async fn next_state(
                         let state4 = state3.cancel();
                         BobState::CancelTimelockExpired(state4)
                     },
+                    never = bitcoin_wallet.escalate_fee(state3.tx_lock.txid(), "lock", fee_strategy.lock) => match never {},
                 }
             } else {
                 let state4 = state3.cancel();
@@ -173,6 +309,15 @@ async fn next_state(
                             },
                         }
                     }
+                    result = xmr_finality_deadline(&tx_lock_status, state.cancel_timelock, env_config) => {
+                        let _ = result?;
+                        tracing::warn!("The Monero lock transaction did not reach finality in time, no longer waiting for it to not race the cancel timelock");
+                        tracing::info!(timelock = %state.cancel_timelock, "Waiting for cancel timelock to expire");
+
+                        tx_lock_status.wait_until_confirmed_with(state.cancel_timelock).await?;
+
+                        BobState::CancelTimelockExpired(state.cancel())
+                    }
                     result = tx_lock_status.wait_until_confirmed_with(state.cancel_timelock) => {
                         let _ = result?;
                         BobState::CancelTimelockExpired(state.cancel())
@@ -211,7 +356,7 @@ async fn next_state(
 
             if let ExpiredTimelocks::None = state.expired_timelock(bitcoin_wallet).await? {
                 select! {
-                    state5 = state.watch_for_redeem_btc(bitcoin_wallet) => {
+                    state5 = state.watch_for_redeem_btc(bitcoin_wallet, clock) => {
                         BobState::BtcRedeemed(state5?)
                     },
                     result = tx_lock_status.wait_until_confirmed_with(state.cancel_timelock) => {
@@ -246,18 +391,54 @@ async fn next_state(
                 monero_wallet.open(wallet_file_name).await?;
             }
 
-            // Ensure that the generated wallet is synced so we have a proper balance
-            monero_wallet.refresh().await?;
-            // Sweep (transfer all funds) to the given address
-            let tx_hashes = monero_wallet.sweep_all(monero_receive_address).await?;
-
-            for tx_hash in tx_hashes {
-                tracing::info!(%monero_receive_address, txid=%tx_hash.0, "Successfully transferred XMR to wallet");
-            }
+            // Ensure that the generated wallet is synced so we have a proper balance. A slow
+            // daemon may not show the redeemed Monero after the first refresh. The amount is
+            // not known for swaps that were redeemed with an older version, any balance will do.
+            let min_balance = state
+                .xmr()
+                .unwrap_or_else(|| monero::Amount::from_piconero(1));
+            monero_wallet
+                .wait_for_balance(min_balance, env_config.bob_xmr_balance_timeout)
+                .await
+                .context("The redeemed XMR do not show up in the wallet of the swap, resume the swap to retry")?;
 
-            BobState::XmrRedeemed {
+            // Sweeping again after the swap was dropped would fail because the wallet is
+            // already empty.
+            let monero_wallet = monero_wallet.clone();
+            let max_fee = env_config.bob_max_xmr_fee;
+            let claim_amount = env_config.bob_xmr_claim_amount;
+            let new_state = BobState::XmrRedeemed {
                 tx_lock_id: state.tx_lock_id(),
-            }
+            };
+
+            return commit(db.clone(), swap_id, new_state, async move {
+                // Sweep (transfer all funds) to the given address unless only an amount was
+                // requested
+                let transferred = monero_wallet
+                    .claim(monero_receive_address, claim_amount, max_fee)
+                    .await
+                    .map_err(|error| match error.downcast_ref() {
+                        Some(monero::CannotSweep::Empty) => error.context(
+                            "Failed to transfer the redeemed XMR, the wallet of the swap is empty because they were transferred already",
+                        ),
+                        _ => error
+                            .context("Failed to transfer the redeemed XMR, resume the swap to retry"),
+                    })?;
+
+                for tx_hash in &transferred.tx_hashes {
+                    tracing::info!(%monero_receive_address, txid=%tx_hash.0, "Successfully transferred XMR to wallet");
+                }
+                tracing::info!(
+                    %monero_receive_address,
+                    amount = %transferred.amount,
+                    fee = %transferred.fee,
+                    transactions = transferred.tx_hashes.len(),
+                    "Transferred the redeemed XMR"
+                );
+
+                Ok(())
+            })
+            .await;
         }
         BobState::CancelTimelockExpired(state4) => {
             if state4.check_for_tx_cancel(bitcoin_wallet).await.is_err() {
@@ -275,7 +456,11 @@ async fn next_state(
                     );
                 }
                 ExpiredTimelocks::Cancel => {
-                    state.publish_refund_btc(bitcoin_wallet).await?;
+                    state
+                        .publish_refund_btc(bitcoin_wallet, env_config.bitcoin_cancel_reorg_policy)
+                        .await?
+                        .wait_until_final()
+                        .await?;
                     BobState::BtcRefunded(state)
                 }
                 ExpiredTimelocks::Punish => BobState::BtcPunished {
@@ -287,5 +472,354 @@ async fn next_state(
         BobState::BtcPunished { tx_lock_id } => BobState::BtcPunished { tx_lock_id },
         BobState::SafelyAborted => BobState::SafelyAborted,
         BobState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
-    })
+    }))
+}
+
+/// Releases the UTXOs reserved for the lock transaction of the swap unless
+/// it may have locked its Bitcoin, e.g. because it failed or was stopped
+/// during or right after the swap setup. Resuming the swap reserves them again
+/// before locking.
+pub async fn release_utxos_unless_locked(
+    db: &(dyn Database + Send + Sync),
+    bitcoin_wallet: &bitcoin::Wallet,
+    swap_id: Uuid,
+) -> Result<()> {
+    let state: BobState = match db.get_state(swap_id).await {
+        Ok(state) => state.try_into()?,
+        // Not even the swap setup was saved
+        Err(_) => return db.release_utxos(swap_id).await,
+    };
+
+    let unlocked = match &state {
+        BobState::Started { .. } => true,
+        BobState::SwapSetupCompleted(state2) => state2.lock_is_unpublished(bitcoin_wallet).await?,
+        _ => false,
+    };
+
+    if unlocked {
+        db.release_utxos(swap_id).await?;
+    }
+
+    Ok(())
+}
+
+/// The UTXOs reserved for the lock transactions of other swaps.
+///
+/// Swaps that finished or did not complete their setup cannot lock the UTXOs
+/// they reserved, their reservations are released instead of returned so
+/// they do not fail reserving the UTXOs either.
+async fn reserved_by_other_swaps(
+    db: &(dyn Database + Send + Sync),
+    swap_id: Uuid,
+) -> Result<Vec<bitcoin::OutPoint>> {
+    let mut may_lock = HashMap::new();
+    let mut reserved = Vec::new();
+
+    for (utxo, reserved_by) in db.get_reserved_utxos().await? {
+        if reserved_by == swap_id {
+            continue;
+        }
+
+        let reservation_held = match may_lock.get(&reserved_by) {
+            Some(held) => *held,
+            None => {
+                let held = may_lock_reserved_utxos(db, reserved_by).await?;
+                if !held {
+                    db.release_utxos(reserved_by).await?;
+                }
+                may_lock.insert(reserved_by, held);
+                held
+            }
+        };
+
+        if reservation_held {
+            reserved.push(utxo);
+        }
+    }
+
+    Ok(reserved)
+}
+
+/// Whether the swap may still lock the UTXOs it reserved. A swap whose state
+/// was not saved yet is in the middle of saving its setup together with the
+/// reservation.
+async fn may_lock_reserved_utxos(db: &(dyn Database + Send + Sync), swap_id: Uuid) -> Result<bool> {
+    let state: BobState = match db.get_state(swap_id).await {
+        Ok(state) => state.try_into()?,
+        Err(_) => return Ok(true),
+    };
+
+    Ok(!is_complete(&state) && !matches!(state, BobState::Started { .. }))
+}
+
+/// Describes what the swap is waiting for in the given state.
+async fn waiting_for(
+    state: &BobState,
+    bitcoin_wallet: &bitcoin::Wallet,
+    env_config: &env::Config,
+) -> String {
+    match state {
+        BobState::Started { .. } => "the swap setup with Alice to complete".to_owned(),
+        BobState::SwapSetupCompleted(..) => {
+            "the Bitcoin lock transaction to be published".to_owned()
+        }
+        BobState::BtcLocked { state3, .. } => format!(
+            "Alice to lock the Monero, {}",
+            cancel_timelock_status(bitcoin_wallet, &state3.tx_lock, state3.cancel_timelock).await
+        ),
+        BobState::XmrLockProofReceived {
+            state,
+            lock_transfer_proof,
+            ..
+        } => format!(
+            "{} confirmations of Monero lock transaction {}, {}",
+            env_config.monero_finality_confirmations,
+            lock_transfer_proof.tx_hash(),
+            cancel_timelock_status(bitcoin_wallet, &state.tx_lock, state.cancel_timelock).await
+        ),
+        BobState::XmrLocked(state) => format!(
+            "Alice to acknowledge the encrypted signature, {}",
+            cancel_timelock_status(bitcoin_wallet, &state.tx_lock, state.cancel_timelock).await
+        ),
+        BobState::EncSigSent(state) => format!(
+            "Alice to redeem the Bitcoin, {}",
+            cancel_timelock_status(bitcoin_wallet, &state.tx_lock, state.cancel_timelock).await
+        ),
+        BobState::BtcRedeemed(..) => {
+            "the Monero wallet to sync and sweep the redeemed Monero".to_owned()
+        }
+        BobState::CancelTimelockExpired(..) => {
+            "the Bitcoin cancel transaction to be published".to_owned()
+        }
+        BobState::BtcCancelled(..) => "the Bitcoin refund transaction to be published".to_owned(),
+        BobState::BtcRefunded(..)
+        | BobState::XmrRedeemed { .. }
+        | BobState::BtcPunished { .. }
+        | BobState::SafelyAborted => "nothing, the swap is complete".to_owned(),
+    }
+}
+
+async fn cancel_timelock_status(
+    bitcoin_wallet: &bitcoin::Wallet,
+    tx_lock: &bitcoin::TxLock,
+    cancel_timelock: bitcoin::CancelTimelock,
+) -> String {
+    let status = watchdog::bitcoin_tx_status(
+        bitcoin_wallet,
+        "lock transaction",
+        tx_lock,
+        cancel_timelock.into(),
+    )
+    .await;
+
+    format!("the cancel timelock expires at {}", status)
+}
+
+/// Resolves once the lock transaction has as many confirmations as the
+/// fraction of the cancel timelock after which Bob no longer waits for the XMR
+/// lock transaction to reach finality. Warns on the way if configured.
+async fn xmr_finality_deadline(
+    tx_lock_status: &bitcoin::wallet::Subscription,
+    cancel_timelock: bitcoin::CancelTimelock,
+    env_config: &env::Config,
+) -> Result<()> {
+    let abort_after = env_config
+        .bob_xmr_finality_abort_fraction
+        .map(|fraction| blocks_of(cancel_timelock, fraction));
+    let warn_after = env_config
+        .bob_xmr_finality_warn_fraction
+        .map(|fraction| blocks_of(cancel_timelock, fraction))
+        .filter(|warn_after| abort_after.map_or(true, |abort_after| *warn_after < abort_after));
+
+    if let Some(warn_after) = warn_after {
+        tx_lock_status.wait_until_confirmed_with(warn_after).await?;
+
+        let blocks_left = u32::from(cancel_timelock).saturating_sub(warn_after);
+        tracing::warn!(
+            %blocks_left,
+            expires_in = %bitcoin::approximate_duration(blocks_left, env_config.bitcoin_avg_block_time),
+            "The Monero lock transaction did not reach finality yet, the cancel timelock expires soon"
+        );
+    }
+
+    match abort_after {
+        Some(abort_after) => tx_lock_status.wait_until_confirmed_with(abort_after).await,
+        None => future::pending().await,
+    }
+}
+
+/// The number of blocks after which `fraction` of the cancel timelock passed.
+fn blocks_of(cancel_timelock: bitcoin::CancelTimelock, fraction: f64) -> u32 {
+    let blocks = (f64::from(u32::from(cancel_timelock)) * fraction).ceil() as u32;
+
+    blocks.min(u32::from(cancel_timelock))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::StaticFeeRate;
+    use crate::bitcoin::{PublicKey, TxLock, WalletBuilder};
+    use crate::database::SqliteDatabase;
+    use crate::protocol::{State, UtxoReserved};
+    use std::time::Duration;
+    use tempfile::tempdir;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn dropping_the_swap_during_a_commit_still_saves_the_new_state() {
+        tokio::time::pause();
+        let db = test_db().await;
+        let swap_id = Uuid::new_v4();
+        let (broadcast_done, broadcast) = oneshot::channel::<()>();
+
+        // Drop the commit while the action, e.g. a broadcast, is still pending.
+        let committed = commit(db.clone(), swap_id, BobState::SafelyAborted, async move {
+            broadcast.await?;
+            Ok(())
+        });
+        let result = tokio::time::timeout(Duration::from_millis(100), committed).await;
+        assert!(result.is_err());
+        assert!(db.get_state(swap_id).await.is_err());
+
+        broadcast_done.send(()).unwrap();
+
+        // The task of the commit is the only one left that makes progress
+        let state = loop {
+            match db.get_state(swap_id).await {
+                Ok(state) => break state,
+                Err(_) => tokio::task::yield_now().await,
+            }
+        };
+        assert!(matches!(state, State::Bob(BobState::SafelyAborted)));
+    }
+
+    #[tokio::test]
+    async fn failed_commit_does_not_save_the_new_state() {
+        let db = test_db().await;
+        let swap_id = Uuid::new_v4();
+
+        let result = commit(db.clone(), swap_id, BobState::SafelyAborted, async {
+            Err(anyhow::anyhow!("Failed to broadcast"))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert!(db.get_state(swap_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrent_swaps_cannot_lock_the_same_utxo() {
+        let db = test_db().await;
+        let wallet = WalletBuilder::new(50_000).build();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // Both swaps select the only UTXO of the wallet before either of them
+        // reserved it.
+        let (first_lock, second_lock) = futures::join!(
+            tx_lock(&wallet, db.as_ref(), first),
+            tx_lock(&wallet, db.as_ref(), second)
+        );
+        let (first_lock, second_lock) = (first_lock.unwrap(), second_lock.unwrap());
+        assert_eq!(first_lock.inputs(), second_lock.inputs());
+
+        let (first_reserved, second_reserved) = futures::join!(
+            db.reserve_utxos(first, first_lock.inputs()),
+            db.reserve_utxos(second, second_lock.inputs())
+        );
+        let (winner, loser, error) = match (first_reserved, second_reserved) {
+            (Ok(()), Err(error)) => (first, second, error),
+            (Err(error), Ok(())) => (second, first, error),
+            results => panic!("Expected exactly one reservation to fail: {:?}", results),
+        };
+        assert_eq!(
+            error.downcast_ref::<UtxoReserved>(),
+            Some(&UtxoReserved {
+                utxo: first_lock.inputs()[0],
+                swap_id: winner,
+            })
+        );
+
+        // Setting up the losing swap again does not select the reserved UTXO.
+        assert!(tx_lock(&wallet, db.as_ref(), loser).await.is_err());
+
+        // Aborting the winning swap before it locked releases the UTXO.
+        db.release_utxos(winner).await.unwrap();
+        let lock = tx_lock(&wallet, db.as_ref(), loser).await.unwrap();
+        db.reserve_utxos(loser, lock.inputs()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reservations_of_swaps_that_cannot_lock_are_ignored() {
+        let db = test_db().await;
+        let wallet = WalletBuilder::new(50_000).build();
+        let (finished, started, swap_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let lock = tx_lock(&wallet, db.as_ref(), finished).await.unwrap();
+        db.reserve_utxos(finished, lock.inputs()).await.unwrap();
+        db.insert_latest_state(finished, BobState::SafelyAborted.into())
+            .await
+            .unwrap();
+        assert!(reserved_by_other_swaps(db.as_ref(), swap_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // The reservation of the finished swap was released
+        db.reserve_utxos(started, lock.inputs()).await.unwrap();
+        db.insert_latest_state(
+            started,
+            BobState::Started {
+                btc_amount: lock.lock_amount(),
+                change_address: wallet.new_address().await.unwrap(),
+            }
+            .into(),
+        )
+        .await
+        .unwrap();
+
+        let lock = tx_lock(&wallet, db.as_ref(), swap_id).await.unwrap();
+        db.reserve_utxos(swap_id, lock.inputs()).await.unwrap();
+        assert!(db
+            .get_reserved_utxos()
+            .await
+            .unwrap()
+            .iter()
+            .all(|(_, reserved_by)| *reserved_by == swap_id));
+    }
+
+    #[test]
+    fn fraction_of_cancel_timelock_is_rounded_up_to_whole_blocks() {
+        let cancel_timelock = bitcoin::CancelTimelock::new(72);
+
+        assert_eq!(blocks_of(cancel_timelock, 0.1), 8);
+        assert_eq!(blocks_of(cancel_timelock, 0.5), 36);
+        assert_eq!(blocks_of(cancel_timelock, 0.75), 54);
+        assert_eq!(blocks_of(cancel_timelock, 1.0), 72);
+    }
+
+    async fn tx_lock(
+        wallet: &bitcoin::Wallet<(), bdk::database::MemoryDatabase, StaticFeeRate>,
+        db: &(dyn Database + Send + Sync),
+        swap_id: Uuid,
+    ) -> Result<TxLock> {
+        let reserved = reserved_by_other_swaps(db, swap_id).await?;
+
+        TxLock::new(
+            wallet,
+            bitcoin::Amount::from_sat(10_000),
+            PublicKey::random(),
+            PublicKey::random(),
+            wallet.new_address().await?,
+            &reserved,
+        )
+        .await
+    }
+
+    async fn test_db() -> Arc<dyn Database + Send + Sync> {
+        let db_path = tempdir().unwrap().into_path().join("sqlite");
+        std::fs::File::create(&db_path).unwrap();
+
+        Arc::new(SqliteDatabase::open(db_path).await.unwrap())
+    }
 }
@@ -0,0 +1,361 @@
+//! The final report of a swap, built from the states the swap went through.
+//!
+//! The summary only contains what the saved states tell, the transactions
+//! are listed by the ids they are published with and not looked up on the
+//! blockchain.
+
+use crate::protocol::alice::AliceState;
+use crate::protocol::bob::BobState;
+use crate::protocol::Database;
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
+use serde::{Serialize, Serializer};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapSummary {
+    pub swap_id: Uuid,
+    pub outcome: Outcome,
+    /// The amounts agreed on in the swap setup, not known if the swap was
+    /// aborted before.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub btc: Option<bitcoin::Amount>,
+    pub xmr: Option<monero::Amount>,
+    pub txids: Txids,
+    /// From the first to the last saved state, not known for swaps saved by
+    /// a version that wrote timestamps in a different format.
+    #[serde(rename = "duration_secs", serialize_with = "as_secs")]
+    pub duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Outcome {
+    /// Both parties received the funds they swapped for.
+    Completed,
+    /// The swap was cancelled after the Bitcoin was locked and both parties
+    /// got their own funds back.
+    Refunded,
+    /// The swap was aborted before any funds were locked.
+    Cancelled,
+    /// The Bitcoin of the buyer was punished.
+    Failed,
+}
+
+/// The transactions the swap published.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Txids {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btc_lock: Option<bitcoin::Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btc_redeem: Option<bitcoin::Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btc_cancel: Option<bitcoin::Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btc_refund: Option<bitcoin::Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btc_punish: Option<bitcoin::Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xmr_lock: Option<monero::TxHash>,
+}
+
+impl SwapSummary {
+    /// Summarizes a finished swap of the CLI from its states in the database.
+    pub async fn of_bob_swap(swap_id: Uuid, db: &(dyn Database + Send + Sync)) -> Result<Self> {
+        let states = db
+            .get_states(swap_id)
+            .await?
+            .into_iter()
+            .map(|state| state.try_into())
+            .collect::<Result<Vec<BobState>, _>>()?;
+
+        Self::from_bob_states(swap_id, &states, duration(swap_id, db).await?)
+    }
+
+    /// Summarizes a finished swap of the ASB from its states in the database.
+    pub async fn of_alice_swap(swap_id: Uuid, db: &(dyn Database + Send + Sync)) -> Result<Self> {
+        let states = db
+            .get_states(swap_id)
+            .await?
+            .into_iter()
+            .map(|state| state.try_into())
+            .collect::<Result<Vec<AliceState>, _>>()?;
+
+        Self::from_alice_states(swap_id, &states, duration(swap_id, db).await?)
+    }
+
+    /// Summarizes a swap from its states, the oldest first. Fails if the
+    /// latest state is not final.
+    pub fn from_bob_states(
+        swap_id: Uuid,
+        states: &[BobState],
+        duration: Option<Duration>,
+    ) -> Result<Self> {
+        let latest = states
+            .last()
+            .with_context(|| format!("No state in database for swap: {}", swap_id))?;
+        let outcome = match latest {
+            BobState::XmrRedeemed { .. } => Outcome::Completed,
+            BobState::BtcRefunded(_) => Outcome::Refunded,
+            BobState::SafelyAborted => Outcome::Cancelled,
+            BobState::BtcPunished { .. } => Outcome::Failed,
+            _ => bail!(
+                "Swap {} is not finished, it is in state {}",
+                swap_id,
+                latest
+            ),
+        };
+
+        let mut summary = Self {
+            swap_id,
+            outcome,
+            btc: None,
+            xmr: None,
+            txids: Txids::default(),
+            duration,
+        };
+
+        for (index, state) in states.iter().enumerate() {
+            match state {
+                BobState::SwapSetupCompleted(state2) => {
+                    summary.btc = Some(state2.tx_lock().lock_amount());
+                    summary.xmr = Some(state2.xmr());
+                }
+                BobState::BtcLocked { state3, .. } => {
+                    summary.txids.btc_lock = Some(state3.tx_lock_id())
+                }
+                BobState::XmrLockProofReceived {
+                    lock_transfer_proof,
+                    ..
+                } => summary.txids.xmr_lock = Some(lock_transfer_proof.tx_hash()),
+                // Bob only learns that the Bitcoin was redeemed, the redeem
+                // transaction is the one he signed before.
+                BobState::EncSigSent(state4) => {
+                    if let Some(BobState::BtcRedeemed(_)) = states.get(index + 1) {
+                        summary.txids.btc_redeem = Some(state4.tx_redeem().txid());
+                    }
+                }
+                BobState::BtcCancelled(state6) => {
+                    summary.txids.btc_cancel = Some(state6.tx_cancel().txid())
+                }
+                BobState::BtcRefunded(state6) => {
+                    summary.txids.btc_refund = Some(state6.tx_refund().txid())
+                }
+                BobState::XmrRedeemed { tx_lock_id } | BobState::BtcPunished { tx_lock_id } => {
+                    summary.txids.btc_lock = Some(*tx_lock_id)
+                }
+                _ => {}
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Summarizes a swap from its states, the oldest first. Fails if the
+    /// latest state is not final.
+    pub fn from_alice_states(
+        swap_id: Uuid,
+        states: &[AliceState],
+        duration: Option<Duration>,
+    ) -> Result<Self> {
+        let latest = states
+            .last()
+            .with_context(|| format!("No state in database for swap: {}", swap_id))?;
+        let outcome = match latest {
+            AliceState::BtcRedeemed => Outcome::Completed,
+            AliceState::XmrRefunded => Outcome::Refunded,
+            AliceState::SafelyAborted => Outcome::Cancelled,
+            AliceState::BtcPunished => Outcome::Failed,
+            _ => bail!(
+                "Swap {} is not finished, it is in state {}",
+                swap_id,
+                latest
+            ),
+        };
+
+        let mut summary = Self {
+            swap_id,
+            outcome,
+            btc: None,
+            xmr: None,
+            txids: Txids::default(),
+            duration,
+        };
+
+        for state in states {
+            match state {
+                AliceState::Started { state3 } => {
+                    summary.btc = Some(state3.tx_lock.lock_amount());
+                    summary.xmr = Some(state3.xmr());
+                }
+                AliceState::BtcLockTransactionSeen { state3 }
+                | AliceState::BtcLocked { state3 } => {
+                    summary.txids.btc_lock = Some(state3.tx_lock.txid())
+                }
+                AliceState::XmrLockTransactionSent { transfer_proof, .. } => {
+                    summary.txids.xmr_lock = Some(transfer_proof.tx_hash())
+                }
+                AliceState::BtcRedeemTransactionPublished { state3 } => {
+                    summary.txids.btc_redeem = Some(state3.tx_redeem().txid())
+                }
+                AliceState::BtcCancelled { state3, .. } => {
+                    summary.txids.btc_cancel = Some(state3.tx_cancel().txid())
+                }
+                AliceState::BtcRefunded { state3, .. } => {
+                    summary.txids.btc_refund = Some(state3.tx_refund().txid())
+                }
+                AliceState::BtcPunishable { state3, .. } => {
+                    if let Some(AliceState::BtcPunished) = states.last() {
+                        summary.txids.btc_punish = Some(state3.tx_punish().txid());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+impl fmt::Display for SwapSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Swap:           {}", self.swap_id)?;
+        write!(f, "Outcome:        {}", self.outcome)?;
+
+        if let Some(btc) = self.btc {
+            write!(f, "\nBTC:            {}", btc)?;
+        }
+        if let Some(xmr) = self.xmr {
+            write!(f, "\nXMR:            {}", xmr)?;
+        }
+        if let Some(duration) = self.duration {
+            let secs = duration.as_secs();
+            write!(f, "\nDuration:       {} min {} s", secs / 60, secs % 60)?;
+        }
+
+        let txids = &self.txids;
+        let bitcoin_txids = [
+            ("lock", txids.btc_lock),
+            ("redeem", txids.btc_redeem),
+            ("cancel", txids.btc_cancel),
+            ("refund", txids.btc_refund),
+            ("punish", txids.btc_punish),
+        ];
+        for (name, txid) in bitcoin_txids.iter() {
+            if let Some(txid) = txid {
+                write!(f, "\nTx BTC {:<8} {}", format!("{}:", name), txid)?;
+            }
+        }
+        if let Some(tx_hash) = &txids.xmr_lock {
+            write!(f, "\nTx XMR {:<8} {}", "lock:", tx_hash)?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn duration(swap_id: Uuid, db: &(dyn Database + Send + Sync)) -> Result<Option<Duration>> {
+    let finished_at = db.get_entered_at(swap_id).await?;
+    let started_at = db.get_started_at(swap_id).await?;
+
+    Ok(started_at.and_then(|started_at| duration_between(started_at, finished_at)))
+}
+
+fn duration_between(from: OffsetDateTime, to: OffsetDateTime) -> Option<Duration> {
+    let secs = u64::try_from((to - from).whole_seconds()).ok()?;
+
+    Some(Duration::from_secs(secs))
+}
+
+fn as_secs<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    duration
+        .map(|duration| duration.as_secs())
+        .serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn tx_lock_id() -> bitcoin::Txid {
+        bitcoin::Txid::from_str("e2d6a9eb8e6d7e5ee2a7ab4d7a3b90f1d1c2c2a4f4ab3c3a1e4d1c0b9a8f7e6d")
+            .unwrap()
+    }
+
+    #[test]
+    fn redeemed_xmr_completes_the_swap_of_bob() {
+        let swap_id = Uuid::new_v4();
+        let states = [
+            BobState::Started {
+                btc_amount: bitcoin::Amount::from_sat(100_000),
+                change_address: bitcoin::Address::from_str(
+                    "bcrt1qgm9necwzx6nmu8u50pgmhrtzzj6vvvcd328sc787nryefcszf8hsyzc88z",
+                )
+                .unwrap(),
+            },
+            BobState::XmrRedeemed {
+                tx_lock_id: tx_lock_id(),
+            },
+        ];
+
+        let summary =
+            SwapSummary::from_bob_states(swap_id, &states, Some(Duration::from_secs(754))).unwrap();
+
+        assert_eq!(summary.outcome, Outcome::Completed);
+        assert_eq!(
+            summary.txids,
+            Txids {
+                btc_lock: Some(tx_lock_id()),
+                ..Txids::default()
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&summary).unwrap(),
+            serde_json::json!({
+                "swap_id": swap_id,
+                "outcome": "completed",
+                "btc": null,
+                "xmr": null,
+                "txids": { "btc_lock": tx_lock_id() },
+                "duration_secs": 754
+            })
+        );
+    }
+
+    #[test]
+    fn final_states_of_alice_map_to_outcomes() {
+        let outcome = |state: AliceState| {
+            SwapSummary::from_alice_states(Uuid::new_v4(), &[state], None)
+                .unwrap()
+                .outcome
+        };
+
+        assert_eq!(outcome(AliceState::BtcRedeemed), Outcome::Completed);
+        assert_eq!(outcome(AliceState::XmrRefunded), Outcome::Refunded);
+        assert_eq!(outcome(AliceState::SafelyAborted), Outcome::Cancelled);
+        assert_eq!(outcome(AliceState::BtcPunished), Outcome::Failed);
+    }
+
+    #[test]
+    fn unfinished_swap_has_no_summary() {
+        let states = [BobState::Started {
+            btc_amount: bitcoin::Amount::from_sat(100_000),
+            change_address: bitcoin::Address::from_str(
+                "bcrt1qgm9necwzx6nmu8u50pgmhrtzzj6vvvcd328sc787nryefcszf8hsyzc88z",
+            )
+            .unwrap(),
+        }];
+
+        assert!(SwapSummary::from_bob_states(Uuid::new_v4(), &states, None).is_err());
+        assert!(SwapSummary::from_alice_states(Uuid::new_v4(), &[], None).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Detects swaps that silently hang.
+//!
+//! Waiting for the counterparty or for confirmations can legitimately take a
+//! long time, but without any output a stalled swap looks exactly the same. The
+//! watchdog periodically logs what a swap is waiting for as long as it does not
+//! advance to the next state.
+
+use crate::bitcoin;
+use crate::bitcoin::wallet::{ScriptStatus, Watchable};
+use crate::clock::Clock;
+use std::future::Future;
+use std::time::Duration;
+
+/// Drives `future` to completion, logging a warning with the output of
+/// `waiting_for` every `interval` of `clock` until it completes.
+pub async fn watch<F, W, WF>(
+    future: F,
+    clock: &dyn Clock,
+    interval: Duration,
+    waiting_for: W,
+) -> F::Output
+where
+    F: Future,
+    W: Fn() -> WF,
+    WF: Future<Output = String>,
+{
+    let started = clock.now();
+
+    tokio::pin!(future);
+
+    loop {
+        tokio::select! {
+            output = &mut future => return output,
+            _ = clock.sleep(interval) => {
+                let waiting_for = waiting_for().await;
+
+                tracing::warn!(
+                    minutes = %((clock.now() - started).as_secs() / 60),
+                    "Swap has not made any progress, still waiting for {}",
+                    waiting_for
+                );
+            }
+        }
+    }
+}
+
+/// Describes how far the given transaction is from reaching `target`
+/// confirmations, including whether we can reach the electrum server at all.
+pub async fn bitcoin_tx_status<T>(
+    wallet: &bitcoin::Wallet,
+    name: &str,
+    tx: &T,
+    target: u32,
+) -> String
+where
+    T: Watchable,
+{
+    let txid = tx.id();
+
+    match wallet.status_of_script(tx).await {
+        Ok(ScriptStatus::Confirmed(inner)) => format!(
+            "{} {} ({}/{} confirmations)",
+            name,
+            txid,
+            inner.confirmations(),
+            target
+        ),
+        Ok(status) => format!("{} {} ({}, 0/{} confirmations)", name, txid, status, target),
+        Err(error) => format!(
+            "{} {} (status unknown, electrum unreachable: {:#})",
+            name, txid, error
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{MockClock, SystemClock};
+    use crate::tracing_ext::capture_logs;
+    use tracing::level_filters::LevelFilter;
+
+    #[tokio::test]
+    async fn warns_while_future_is_pending() {
+        tokio::time::pause();
+        let writer = capture_logs(LevelFilter::WARN);
+
+        let output = watch(
+            async {
+                tokio::time::sleep(Duration::from_millis(250)).await;
+                42
+            },
+            &SystemClock,
+            Duration::from_millis(100),
+            || async { "the answer".to_owned() },
+        )
+        .await;
+
+        assert_eq!(output, 42);
+        assert_eq!(
+            writer
+                .captured()
+                .matches("Swap has not made any progress, still waiting for the answer")
+                .count(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_warn_if_future_completes_in_time() {
+        tokio::time::pause();
+        let writer = capture_logs(LevelFilter::WARN);
+
+        let output = watch(
+            async { 42 },
+            &SystemClock,
+            Duration::from_millis(100),
+            || async { "the answer".to_owned() },
+        )
+        .await;
+
+        assert_eq!(output, 42);
+        assert_eq!(writer.captured(), "");
+    }
+
+    #[tokio::test]
+    async fn warns_every_interval_of_the_clock() {
+        let writer = capture_logs(LevelFilter::WARN);
+        let clock = MockClock::new();
+        let interval = Duration::from_secs(60 * 60);
+
+        let output = watch(
+            async {
+                for _ in 0..2 {
+                    wait_until_watchdog_sleeps(&clock).await;
+                    clock.advance(interval);
+                }
+                wait_until_watchdog_sleeps(&clock).await;
+
+                42
+            },
+            &clock,
+            interval,
+            || async { "the answer".to_owned() },
+        )
+        .await;
+
+        assert_eq!(output, 42);
+        let captured = writer.captured();
+        assert_eq!(
+            captured
+                .matches("Swap has not made any progress, still waiting for the answer")
+                .count(),
+            2
+        );
+        assert!(captured.contains("minutes=120"));
+    }
+
+    async fn wait_until_watchdog_sleeps(clock: &MockClock) {
+        while clock.pending_sleeps() == 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::fs::ensure_directory_exists;
+use crate::fs::{ensure_directory_exists, ensure_directory_is_private, ensure_file_is_private};
 use ::bitcoin::secp256k1::constants::SECRET_KEY_SIZE;
 use ::bitcoin::secp256k1::{self, SecretKey};
 use anyhow::{Context, Result};
@@ -42,14 +42,30 @@ impl Seed {
     }
 
     pub fn derive_libp2p_identity(&self) -> identity::Keypair {
-        let bytes = self.derive(b"NETWORK").derive(b"LIBP2P_IDENTITY").bytes();
+        self.derive_libp2p_identity_at(0)
+    }
+
+    /// Derives the libp2p identity of the given generation, see
+    /// [`Seed::derive_generation`].
+    pub fn derive_libp2p_identity_at(&self, generation: u32) -> identity::Keypair {
+        let bytes = self
+            .derive(b"NETWORK")
+            .derive(b"LIBP2P_IDENTITY")
+            .derive_generation(generation)
+            .bytes();
         let key = identity::ed25519::SecretKey::from_bytes(bytes).expect("we always pass 32 bytes");
 
         identity::Keypair::Ed25519(key.into())
     }
 
     pub fn derive_torv3_key(&self) -> TorSecretKeyV3 {
-        let bytes = self.derive(b"TOR").bytes();
+        self.derive_torv3_key_at(0)
+    }
+
+    /// Derives the key of the onion address of the given generation, see
+    /// [`Seed::derive_generation`].
+    pub fn derive_torv3_key_at(&self, generation: u32) -> TorSecretKeyV3 {
+        let bytes = self.derive(b"TOR").derive_generation(generation).bytes();
         let sk = ed25519_dalek::SecretKey::from_bytes(&bytes)
             .expect("Failed to create a new extended secret key for Tor.");
         let esk = ed25519_dalek::ExpandedSecretKey::from(&sk);
@@ -60,7 +76,10 @@ impl Seed {
         let file_path_buf = data_dir.join("seed.pem");
         let file_path = Path::new(&file_path_buf);
 
+        ensure_directory_is_private(data_dir)?;
+
         if file_path.exists() {
+            ensure_file_is_private(file_path)?;
             return Self::from_file(&file_path);
         }
 
@@ -88,6 +107,19 @@ impl Seed {
         Self(hash.into_inner())
     }
 
+    /// Derive the seed of a generation of the network identity, which allows
+    /// rotating the peer id and the onion address without a new root seed.
+    ///
+    /// Generation 0 is the seed itself, so that identities derived before
+    /// generations existed stay the same.
+    fn derive_generation(self, generation: u32) -> Self {
+        if generation == 0 {
+            return self;
+        }
+
+        self.derive(b"GENERATION").derive(&generation.to_be_bytes())
+    }
+
     fn bytes(&self) -> [u8; SEED_LENGTH] {
         self.0
     }
@@ -129,13 +161,30 @@ impl Seed {
 
         let pem_string = encode(&pem);
 
-        let mut file = File::create(seed_file)?;
+        let mut file = create_private_file(&seed_file)?;
         file.write_all(pem_string.as_bytes())?;
 
         Ok(())
     }
 }
 
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> Result<File, io::Error> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> Result<File, io::Error> {
+    File::create(path)
+}
+
 impl fmt::Debug for Seed {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Seed([*****])")
@@ -240,6 +289,44 @@ mbKANv2qKGmNVg1qtquj6Hx1pFPelpqOfE2JaJJAMEg1FlFhNRNlFlE=
         }
     }
 
+    #[test]
+    fn generation_zero_is_the_identity_derived_without_generation() {
+        let seed = Seed::from(*b"this string is exactly 32 bytes!");
+
+        let legacy_bytes = seed.derive(b"NETWORK").derive(b"LIBP2P_IDENTITY").bytes();
+        let legacy_key = identity::ed25519::SecretKey::from_bytes(legacy_bytes).unwrap();
+        let legacy_identity = identity::Keypair::Ed25519(legacy_key.into());
+
+        assert_eq!(
+            seed.derive_libp2p_identity_at(0).public(),
+            legacy_identity.public()
+        );
+    }
+
+    #[test]
+    fn every_generation_derives_a_different_identity() {
+        let seed = Seed::from(*b"this string is exactly 32 bytes!");
+
+        assert_ne!(
+            seed.derive_libp2p_identity_at(0).public(),
+            seed.derive_libp2p_identity_at(1).public()
+        );
+        assert_ne!(
+            seed.derive_libp2p_identity_at(1).public(),
+            seed.derive_libp2p_identity_at(2).public()
+        );
+        assert_ne!(
+            seed.derive_torv3_key_at(0)
+                .public()
+                .get_onion_address()
+                .get_address_without_dot_onion(),
+            seed.derive_torv3_key_at(1)
+                .public()
+                .get_onion_address()
+                .get_address_without_dot_onion()
+        );
+    }
+
     #[test]
     fn round_trip_through_file_write_read() {
         let tmpfile = temp_dir().join("seed.pem");
@@ -251,4 +338,41 @@ mbKANv2qKGmNVg1qtquj6Hx1pFPelpqOfE2JaJJAMEg1FlFhNRNlFlE=
         let rinsed = Seed::from_file(tmpfile).expect("Read from temp file");
         assert_eq!(seed.0, rinsed.0);
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn generated_seed_file_and_data_dir_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let data_dir = tempfile::tempdir().unwrap();
+        std::fs::set_permissions(data_dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let _ = Seed::from_file_or_generate(data_dir.path()).unwrap();
+
+        let dir_mode = fs::metadata(data_dir.path()).unwrap().permissions().mode();
+        let file_mode = fs::metadata(data_dir.path().join("seed.pem"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(dir_mode & 0o777, 0o700);
+        assert_eq!(file_mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn existing_world_readable_seed_file_is_repaired() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let data_dir = tempfile::tempdir().unwrap();
+        let seed_file = data_dir.path().join("seed.pem");
+        let seed = Seed::random().unwrap();
+        seed.write_to(seed_file.clone()).unwrap();
+        std::fs::set_permissions(&seed_file, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let loaded = Seed::from_file_or_generate(data_dir.path()).unwrap();
+
+        let file_mode = fs::metadata(&seed_file).unwrap().permissions().mode();
+        assert_eq!(loaded, seed);
+        assert_eq!(file_mode & 0o777, 0o600);
+    }
 }
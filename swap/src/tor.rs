@@ -1,6 +1,7 @@
 use anyhow::{bail, Context, Result};
 use std::future::Future;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
 use tokio::net::TcpStream;
 use torut::control::{AsyncEvent, AuthenticatedConn, ConnError, UnauthenticatedConn};
 use torut::onion::TorSecretKeyV3;
@@ -8,6 +9,9 @@ use torut::onion::TorSecretKeyV3;
 pub const DEFAULT_SOCKS5_PORT: u16 = 9050;
 pub const DEFAULT_CONTROL_PORT: u16 = 9051;
 
+const BOOTSTRAP_DONE: u8 = 100;
+const BOOTSTRAP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone, Copy)]
 pub struct Client {
     socks5_address: SocketAddrV4,
@@ -74,6 +78,55 @@ impl Client {
     pub async fn into_authenticated_client(self) -> Result<AuthenticatedClient> {
         self.assert_tor_running().await?;
 
+        self.authenticate().await
+    }
+
+    /// Waits until Tor built enough circuits to reach onion services, as
+    /// reported through the control port, or fails after `timeout`.
+    ///
+    /// If the control port cannot be reached Tor is assumed not to be running
+    /// and there is nothing to wait for.
+    pub async fn wait_until_bootstrapped(&self, timeout: Duration) -> Result<()> {
+        let mut client = match self.authenticate().await {
+            Ok(client) => client,
+            Err(error) => {
+                tracing::warn!(
+                    "Not waiting for Tor to bootstrap because its control port cannot be reached: {:#}",
+                    error
+                );
+                return Ok(());
+            }
+        };
+
+        let wait = async {
+            let mut last_progress = None;
+
+            loop {
+                let progress = client.bootstrap_progress().await?;
+
+                if progress >= BOOTSTRAP_DONE {
+                    tracing::info!("Tor finished bootstrapping");
+                    return Ok::<_, anyhow::Error>(());
+                }
+
+                if last_progress != Some(progress) {
+                    tracing::info!(%progress, "Waiting for Tor to bootstrap");
+                    last_progress = Some(progress);
+                }
+
+                tokio::time::sleep(BOOTSTRAP_POLL_INTERVAL).await;
+            }
+        };
+
+        tokio::time::timeout(timeout, wait).await.with_context(|| {
+            format!(
+                "Tor did not finish bootstrapping within {} seconds",
+                timeout.as_secs()
+            )
+        })?
+    }
+
+    async fn authenticate(&self) -> Result<AuthenticatedClient> {
         let mut uc = self
             .init_unauthenticated_connection()
             .await
@@ -111,6 +164,18 @@ pub struct AuthenticatedClient {
 }
 
 impl AuthenticatedClient {
+    /// The bootstrap progress of Tor in percent, 100 once it can build
+    /// circuits.
+    pub async fn bootstrap_progress(&mut self) -> Result<u8> {
+        let phase = self
+            .inner
+            .get_info("status/bootstrap-phase")
+            .await
+            .context("Failed to get bootstrap phase from Tor")?;
+
+        parse_bootstrap_progress(&phase)
+    }
+
     /// Add an ephemeral tor service on localhost with the provided key
     /// `service_port` and `onion_port` can be different but don't have to as
     /// they are on different networks.
@@ -126,3 +191,29 @@ impl AuthenticatedClient {
             .context("Failed to add onion service")
     }
 }
+
+/// Parses the progress out of a bootstrap phase like `NOTICE BOOTSTRAP
+/// PROGRESS=85 TAG=ap_handshake_done SUMMARY="..."`.
+fn parse_bootstrap_progress(phase: &str) -> Result<u8> {
+    let progress = phase
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("PROGRESS="))
+        .with_context(|| format!("No progress in bootstrap phase {}", phase))?;
+
+    progress
+        .parse()
+        .with_context(|| format!("Invalid progress in bootstrap phase {}", phase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_progress_of_bootstrap_phase() {
+        let phase = r#"NOTICE BOOTSTRAP PROGRESS=85 TAG=ap_handshake_done SUMMARY="Handshake finished with a relay to build circuits""#;
+
+        assert_eq!(parse_bootstrap_progress(phase).unwrap(), 85);
+        assert!(parse_bootstrap_progress("NOTICE BOOTSTRAP TAG=starting").is_err());
+    }
+}
@@ -69,9 +69,15 @@ async fn given_alice_and_bob_manually_cancel_when_timelock_not_expired_errors()
         assert!(matches!(bob_swap.state, BobState::BtcLocked { .. }));
 
         // Bob tries but fails to manually refund
-        let error = cli::refund(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db)
-            .await
-            .unwrap_err();
+        let error = cli::refund(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet,
+            bob_swap.db,
+            None,
+            bob_swap.env_config.bitcoin_cancel_reorg_policy,
+        )
+        .await
+        .unwrap_err();
         assert_eq!(
             parse_rpc_error_code(&error).unwrap(),
             i64::from(RpcErrorCode::RpcVerifyError)
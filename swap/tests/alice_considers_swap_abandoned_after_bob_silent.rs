@@ -0,0 +1,30 @@
+pub mod harness;
+
+use harness::bob_run_until::is_lock_proof_received;
+use harness::SilentBobConfig;
+use swap::asb::FixedRate;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob};
+
+/// Bob locks Btc and receives the transfer proof but never sends the encsig.
+/// Alice considers the swap abandoned once Bob was silent for too long, waits
+/// for the cancel timelock and eventually punishes.
+#[tokio::test]
+async fn alice_considers_swap_abandoned_if_bob_stays_silent() {
+    harness::setup_test(SilentBobConfig, |mut ctx| async move {
+        let (bob_swap, _) = ctx.bob_swap().await;
+        let bob_swap = tokio::spawn(bob::run_until(bob_swap, is_lock_proof_received));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        let bob_state = bob_swap.await??;
+        assert!(matches!(bob_state, BobState::XmrLockProofReceived { .. }));
+
+        let alice_state = alice_swap.await??;
+        ctx.assert_alice_punished(alice_state).await;
+
+        Ok(())
+    })
+    .await;
+}
@@ -0,0 +1,54 @@
+pub mod harness;
+
+use harness::alice_run_until::is_encsig_learned;
+use harness::bob_run_until::is_encsig_sent;
+use harness::FastCancelConfig;
+use std::convert::TryInto;
+use swap::asb::FixedRate;
+use swap::cli;
+use swap::protocol::bob::BobState;
+use swap::protocol::Database;
+use swap::protocol::{alice, bob};
+
+/// Once Bob sent the encrypted signature Alice can redeem at any time,
+/// refunding on failure leaves the swap alone so it can be resumed.
+#[tokio::test]
+async fn given_bob_fails_after_enc_sig_sent_then_refund_on_failure_does_nothing() {
+    harness::setup_test(FastCancelConfig, |mut ctx| async move {
+        let (bob_swap, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+        let bob_swap = tokio::spawn(bob::run_until(bob_swap, is_encsig_sent));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run_until(
+            alice_swap,
+            is_encsig_learned,
+            FixedRate::default(),
+        ));
+
+        let bob_state = bob_swap.await??;
+        assert!(matches!(bob_state, BobState::EncSigSent { .. }));
+        alice_swap.await??;
+
+        let (bob_swap, bob_join_handle) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+        bob_join_handle.abort();
+
+        let refunded = cli::refund_on_failure(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet,
+            bob_swap.db.clone(),
+            bob_swap.env_config.bitcoin_avg_block_time,
+            bob_swap.env_config.bitcoin_cancel_reorg_policy,
+        )
+        .await?;
+        assert!(refunded.is_none());
+
+        let bob_state: BobState = bob_swap.db.get_state(bob_swap_id).await?.try_into()?;
+        assert!(matches!(bob_state, BobState::EncSigSent { .. }));
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,51 @@
+pub mod harness;
+
+use harness::alice_run_until::is_xmr_lock_transaction_sent;
+use harness::bob_run_until::is_btc_locked;
+use harness::FastCancelConfig;
+use swap::asb::FixedRate;
+use swap::cli;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob};
+
+/// Bob's swap fails after he locked the Bitcoin, refunding on failure waits
+/// for the cancel timelock and refunds the Bitcoin.
+#[tokio::test]
+async fn given_bob_fails_after_btc_locked_then_refund_on_failure_refunds() {
+    harness::setup_test(FastCancelConfig, |mut ctx| async move {
+        let (bob_swap, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+        let bob_swap = tokio::spawn(bob::run_until(bob_swap, is_btc_locked));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run_until(
+            alice_swap,
+            is_xmr_lock_transaction_sent,
+            FixedRate::default(),
+        ));
+
+        let bob_state = bob_swap.await??;
+        assert!(matches!(bob_state, BobState::BtcLocked { .. }));
+        alice_swap.await??;
+
+        let (bob_swap, bob_join_handle) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+        bob_join_handle.abort();
+
+        let bob_state = cli::refund_on_failure(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet,
+            bob_swap.db,
+            bob_swap.env_config.bitcoin_avg_block_time,
+            bob_swap.env_config.bitcoin_cancel_reorg_policy,
+        )
+        .await?
+        .expect("swap to be refunded");
+
+        ctx.assert_bob_refunded(bob_state).await;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,62 @@
+pub mod harness;
+
+use harness::bob_run_until::{
+    is_btc_locked, is_btc_redeemed, is_encsig_sent, is_lock_proof_received,
+    is_swap_setup_completed, is_xmr_locked,
+};
+use harness::SlowCancelConfig;
+use swap::asb::FixedRate;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob};
+
+/// Bob is stopped and resumed from the database in every state the swap passes
+/// through. The swap still has to complete with the balances of a swap that was
+/// never interrupted, i.e. without any transaction being published twice.
+#[tokio::test]
+async fn given_bob_restarts_in_every_state_resume_swap() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, mut bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+        let bob_swap = tokio::spawn(bob::run_until(bob_swap, is_swap_setup_completed));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        let mut bob_state = bob_swap.await??;
+        assert!(matches!(bob_state, BobState::SwapSetupCompleted(..)));
+
+        let checkpoints: [fn(&BobState) -> bool; 5] = [
+            is_btc_locked,
+            is_lock_proof_received,
+            is_xmr_locked,
+            is_encsig_sent,
+            is_btc_redeemed,
+        ];
+
+        for &is_checkpoint in &checkpoints {
+            let (bob_swap, join_handle) = ctx
+                .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+                .await;
+            assert_eq!(bob_swap.state, bob_state);
+            bob_join_handle = join_handle;
+
+            bob_state = bob::run_until(bob_swap, is_checkpoint).await?;
+            assert!(is_checkpoint(&bob_state), "unexpected state {}", bob_state);
+        }
+
+        let (bob_swap, _) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+        assert_eq!(bob_swap.state, bob_state);
+
+        let bob_state = bob::run(bob_swap).await?;
+
+        ctx.assert_bob_redeemed(bob_state).await;
+
+        let alice_state = alice_swap.await??;
+        ctx.assert_alice_redeemed(alice_state).await;
+
+        Ok(())
+    })
+    .await;
+}
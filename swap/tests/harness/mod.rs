@@ -14,8 +14,9 @@ use std::fmt;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use swap::asb::FixedRate;
+use swap::asb::{FixedRate, MakerParams, MakerParamsUpdates};
 use swap::bitcoin::{CancelTimelock, PunishTimelock, TxCancel, TxPunish, TxRedeem, TxRefund};
+use swap::cli::RateBounds;
 use swap::database::SqliteDatabase;
 use swap::env::{Config, GetConfig};
 use swap::fs::ensure_directory_exists;
@@ -36,7 +37,26 @@ use tracing_subscriber::util::SubscriberInitExt;
 use url::Url;
 use uuid::Uuid;
 
-pub async fn setup_test<T, F, C>(_config: C, testfn: T)
+/// How blocks are produced on the Monero regtest chain during a test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoneroMiner {
+    /// A background task mines a block every second.
+    Background,
+    /// Blocks are only mined when the test calls
+    /// [`TestContext::generate_monero_blocks`].
+    OnDemand,
+}
+
+pub async fn setup_test<T, F, C>(config: C, testfn: T)
+where
+    T: Fn(TestContext) -> F,
+    F: Future<Output = Result<()>>,
+    C: GetConfig,
+{
+    setup_test_with_monero_miner(config, MoneroMiner::Background, testfn).await
+}
+
+pub async fn setup_test_with_monero_miner<T, F, C>(_config: C, miner: MoneroMiner, testfn: T)
 where
     T: Fn(TestContext) -> F,
     F: Future<Output = Result<()>>,
@@ -119,10 +139,13 @@ where
         env_config,
     };
 
-    monero.start_miner().await.unwrap();
+    if miner == MoneroMiner::Background {
+        monero.start_miner().await.unwrap();
+    }
 
     let test = TestContext {
         env_config,
+        electrs_rpc_port,
         btc_amount,
         xmr_amount,
         alice_seed,
@@ -137,6 +160,7 @@ where
         bob_starting_balances,
         bob_bitcoin_wallet,
         bob_monero_wallet,
+        monero,
     };
 
     testfn(test).await.unwrap()
@@ -231,19 +255,24 @@ async fn start_alice(
     }
     let db = Arc::new(SqliteDatabase::open(db_path.as_path()).await.unwrap());
 
-    let min_buy = bitcoin::Amount::from_sat(u64::MIN);
-    let max_buy = bitcoin::Amount::from_sat(u64::MAX);
+    let maker_params = MakerParamsUpdates::fixed(MakerParams {
+        min_buy: bitcoin::Amount::from_sat(u64::MIN),
+        max_buy: bitcoin::Amount::from_sat(u64::MAX),
+        ask_spread: Default::default(),
+        fixed_rate: None,
+        redeem_fee_margin: Default::default(),
+    });
     let latest_rate = FixedRate::default();
     let resume_only = false;
 
     let mut swarm = swarm::asb(
-        &seed,
-        min_buy,
-        max_buy,
+        seed.derive_libp2p_identity(),
+        maker_params.clone(),
         latest_rate,
         resume_only,
         env_config,
         None,
+        None,
     )
     .unwrap();
     swarm.listen_on(listen_address).unwrap();
@@ -255,8 +284,7 @@ async fn start_alice(
         monero_wallet,
         db,
         FixedRate::default(),
-        min_buy,
-        max_buy,
+        maker_params,
     )
     .unwrap();
 
@@ -303,7 +331,9 @@ async fn init_test_wallets(
     };
 
     let btc_wallet = swap::bitcoin::Wallet::new(
-        electrum_rpc_url,
+        swap::bitcoin::Backend::Electrum {
+            rpc_url: electrum_rpc_url,
+        },
         datadir,
         seed.derive_extended_private_key(env_config.bitcoin_network)
             .expect("Could not create extended private key from seed"),
@@ -474,6 +504,7 @@ impl BobParams {
             self.alice_peer_id,
             self.env_config,
             self.bitcoin_wallet.clone(),
+            RateBounds::default(),
         );
         let mut swarm = swarm::cli(
             self.seed.derive_libp2p_identity(),
@@ -510,6 +541,7 @@ impl AliceApplicationHandle {
 
 pub struct TestContext {
     env_config: Config,
+    electrs_rpc_port: u16,
 
     btc_amount: bitcoin::Amount,
     xmr_amount: monero::Amount,
@@ -528,9 +560,18 @@ pub struct TestContext {
     bob_starting_balances: StartingBalances,
     bob_bitcoin_wallet: Arc<bitcoin::Wallet>,
     bob_monero_wallet: Arc<monero::Wallet>,
+
+    monero: Monero,
 }
 
 impl TestContext {
+    /// Mines `amount` Monero blocks.
+    ///
+    /// Only needed for tests set up with [`MoneroMiner::OnDemand`].
+    pub async fn generate_monero_blocks(&self, amount: u32) -> Result<()> {
+        self.monero.generate_blocks(amount).await
+    }
+
     pub async fn restart_alice(&mut self) {
         self.alice_handle.abort();
 
@@ -566,6 +607,30 @@ impl TestContext {
         (swap, BobApplicationHandle(join_handle))
     }
 
+    /// A Bitcoin wallet that has not synced yet, like the one of a CLI that
+    /// was just started.
+    pub async fn fresh_bitcoin_wallet(&self) -> Arc<bitcoin::Wallet> {
+        let electrum_rpc_url =
+            Url::parse(&format!("tcp://@localhost:{}", self.electrs_rpc_port)).unwrap();
+
+        let wallet = bitcoin::Wallet::new(
+            bitcoin::Backend::Electrum {
+                rpc_url: electrum_rpc_url,
+            },
+            tempdir().unwrap().path(),
+            Seed::random()
+                .unwrap()
+                .derive_extended_private_key(self.env_config.bitcoin_network)
+                .unwrap(),
+            self.env_config,
+            1,
+        )
+        .await
+        .expect("could not init btc wallet");
+
+        Arc::new(wallet)
+    }
+
     pub async fn stop_and_resume_bob_from_db(
         &mut self,
         join_handle: BobApplicationHandle,
@@ -973,6 +1038,10 @@ pub mod alice_run_until {
 pub mod bob_run_until {
     use swap::protocol::bob::BobState;
 
+    pub fn is_swap_setup_completed(state: &BobState) -> bool {
+        matches!(state, BobState::SwapSetupCompleted(..))
+    }
+
     pub fn is_btc_locked(state: &BobState) -> bool {
         matches!(state, BobState::BtcLocked { .. })
     }
@@ -988,6 +1057,10 @@ pub mod bob_run_until {
     pub fn is_encsig_sent(state: &BobState) -> bool {
         matches!(state, BobState::EncSigSent(..))
     }
+
+    pub fn is_btc_redeemed(state: &BobState) -> bool {
+        matches!(state, BobState::BtcRedeemed(..))
+    }
 }
 
 pub struct SlowCancelConfig;
@@ -1012,6 +1085,17 @@ impl GetConfig for FastCancelConfig {
     }
 }
 
+pub struct SilentBobConfig;
+
+impl GetConfig for SilentBobConfig {
+    fn get_config() -> Config {
+        Config {
+            alice_counterparty_silence_timeout: Some(Duration::from_secs(10)),
+            ..FastPunishConfig::get_config()
+        }
+    }
+}
+
 pub struct FastPunishConfig;
 
 impl GetConfig for FastPunishConfig {